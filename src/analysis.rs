@@ -0,0 +1,274 @@
+use pyo3::prelude::*;
+use std::collections::HashMap;
+
+use crate::dialect::GlobDialect;
+use crate::glob_syntax::{brace_end, class_end, class_members, split_alternatives};
+use crate::pattern::{PatternBody, PatternMatcher};
+
+/// Report produced by `PatternMatcher.analyze`
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct PatternAnalysis {
+    /// Patterns listed more than once, each reported once
+    #[pyo3(get)]
+    pub duplicates: Vec<String>,
+    /// (pattern, broader_pattern) pairs where the broader pattern already
+    /// matches everything the first one does
+    #[pyo3(get)]
+    pub shadowed: Vec<(String, String)>,
+    /// Patterns that matched none of the sample names
+    #[pyo3(get)]
+    pub unmatched: Vec<String>,
+}
+
+#[pymethods]
+impl PatternAnalysis {
+    fn __repr__(&self) -> String {
+        format!(
+            "PatternAnalysis(duplicates={}, shadowed={}, unmatched={})",
+            self.duplicates.len(),
+            self.shadowed.len(),
+            self.unmatched.len()
+        )
+    }
+}
+
+/// Number of sample paths generated per pattern for shadowing checks
+pub const WITNESS_VARIANTS: usize = 6;
+
+const STAR_FILLS: &[&str] = &["", "x", "Xy.z-0"];
+const QUESTION_FILLS: &[&str] = &["x", "0", "_"];
+/// `**/`: zero, one or several directories
+const DEEP_PREFIX_FILLS: &[&str] = &["", "d/", "d/e/"];
+/// Trailing `/**`: one or several components
+const DEEP_SUFFIX_FILLS: &[&str] = &["x", "d/e"];
+/// Characters tried for negated classes like `[!abc]`
+const NEGATED_CLASS_POOL: &str = "xq0_Z-.";
+
+pub fn analyze(matcher: &PatternMatcher, samples: Option<&[String]>) -> PatternAnalysis {
+    let patterns = matcher.patterns();
+
+    // First index of every distinct pattern; later copies are duplicates
+    let mut first_seen: HashMap<&str, usize> = HashMap::new();
+    let mut duplicates = Vec::new();
+    for (idx, pattern) in patterns.iter().enumerate() {
+        let first = *first_seen.entry(pattern).or_insert(idx);
+        if first != idx && !duplicates.contains(pattern) {
+            duplicates.push(pattern.clone());
+        }
+    }
+    let distinct: Vec<usize> = (0..patterns.len())
+        .filter(|&idx| first_seen[patterns[idx].as_str()] == idx)
+        .collect();
+
+    let shadowers: Vec<Vec<usize>> = (0..patterns.len())
+        .map(|idx| {
+            if first_seen[patterns[idx].as_str()] == idx {
+                shadowers_of(matcher, idx)
+            } else {
+                Vec::new()
+            }
+        })
+        .collect();
+    let mut shadowed = Vec::new();
+    for &idx in &distinct {
+        // Equivalent patterns shadow each other; only report the later one
+        let broader = shadowers[idx]
+            .iter()
+            .find(|&&other| other < idx || !shadowers[other].contains(&idx));
+        if let Some(&other) = broader {
+            shadowed.push((patterns[idx].clone(), patterns[other].clone()));
+        }
+    }
+
+    let unmatched = match samples {
+        Some(samples) => {
+            let mut hit = vec![false; patterns.len()];
+            for sample in samples {
+                let (path, is_dir) = matcher.candidate(sample, false);
+                for idx in matcher.match_indices(&path, is_dir) {
+                    hit[idx] = true;
+                }
+            }
+            distinct
+                .iter()
+                .filter(|&&idx| !hit[idx])
+                .map(|&idx| patterns[idx].clone())
+                .collect()
+        }
+        None => Vec::new(),
+    };
+
+    PatternAnalysis {
+        duplicates,
+        shadowed,
+        unmatched,
+    }
+}
+
+/// Other distinct patterns matching every sample path generated from
+/// pattern `idx`
+///
+/// The samples cover the empty, short and long fill of every wildcard, so
+/// this is a strong hint rather than a proof of containment.
+fn shadowers_of(matcher: &PatternMatcher, idx: usize) -> Vec<usize> {
+    let patterns = matcher.patterns();
+    let options = matcher.options();
+    let PatternBody {
+        body,
+        any_depth,
+        dir_only: is_dir,
+    } = options.split_pattern(&patterns[idx]);
+    let escapes = options.dialect == GlobDialect::Globset;
+
+    let mut candidates: Option<Vec<usize>> = None;
+    for variant in 0..WITNESS_VARIANTS {
+        let mut witness = String::new();
+        let mut wildcard = 0;
+        push_witness(&body, escapes, variant, &mut wildcard, &mut witness);
+        let mut witnesses = vec![witness];
+        if any_depth {
+            witnesses.push(format!("d/{}", witnesses[0]));
+        }
+
+        for witness in witnesses {
+            let (path, is_dir) = matcher.candidate(&witness, is_dir);
+            let matched = matcher.match_indices(&path, is_dir);
+            // A sample the pattern rejects means the generator misread its
+            // syntax, so no conclusion can be drawn
+            if !matched.contains(&idx) {
+                return Vec::new();
+            }
+            candidates = Some(match candidates {
+                None => matched,
+                Some(previous) => previous
+                    .into_iter()
+                    .filter(|other| matched.contains(other))
+                    .collect(),
+            });
+        }
+    }
+
+    candidates
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|&other| patterns[other] != patterns[idx])
+        .collect()
+}
+
+/// Append one concrete path matched by `pattern` to `out`
+///
+/// `variant` and the running `wildcard` counter pick which fill each
+/// wildcard gets, so successive variants exercise different combinations.
+fn push_witness(
+    pattern: &str,
+    escapes: bool,
+    variant: usize,
+    wildcard: &mut usize,
+    out: &mut String,
+) {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut i = 0;
+    let pick = |fills: &[&'static str], wildcard: &mut usize| {
+        let fill = fills[(variant + *wildcard) % fills.len()];
+        *wildcard += 1;
+        fill
+    };
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if escapes && i + 1 < chars.len() => {
+                out.push(chars[i + 1]);
+                i += 2;
+            }
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_start = i == 0 || chars[i - 1] == '/';
+                if at_start && chars.get(i + 2) == Some(&'/') {
+                    out.push_str(pick(DEEP_PREFIX_FILLS, wildcard));
+                    i += 3;
+                } else if at_start && i + 2 == chars.len() {
+                    out.push_str(pick(DEEP_SUFFIX_FILLS, wildcard));
+                    i += 2;
+                } else {
+                    out.push_str(pick(STAR_FILLS, wildcard));
+                    i += 2;
+                }
+            }
+            '*' => {
+                out.push_str(pick(STAR_FILLS, wildcard));
+                i += 1;
+            }
+            '?' => {
+                out.push_str(pick(QUESTION_FILLS, wildcard));
+                i += 1;
+            }
+            '[' => match class_end(&chars, i) {
+                Some(end) => {
+                    let choices = class_choices(&chars[i + 1..end]);
+                    if choices.is_empty() {
+                        // Nothing can match this class; the caller's match check
+                        // will discard the witness
+                        out.push('[');
+                    } else {
+                        out.push(choices[(variant + *wildcard) % choices.len()]);
+                        *wildcard += 1;
+                    }
+                    i = end + 1;
+                }
+                None => {
+                    out.push('[');
+                    i += 1;
+                }
+            },
+            '{' if escapes => match brace_end(&chars, i) {
+                Some(end) => {
+                    let inner: String = chars[i + 1..end].iter().collect();
+                    let alternatives = split_alternatives(&inner);
+                    let choice = &alternatives[(variant + *wildcard) % alternatives.len()];
+                    *wildcard += 1;
+                    push_witness(choice, escapes, variant, wildcard, out);
+                    i = end + 1;
+                }
+                None => {
+                    out.push('{');
+                    i += 1;
+                }
+            },
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+}
+
+/// A single entry name matched by `glob`, a requirement glob without any
+/// `/`; successive variants fill wildcards differently
+pub fn sample_name(glob: &str, variant: usize) -> Option<String> {
+    if glob.contains('/') {
+        return None;
+    }
+    let mut name = String::new();
+    push_witness(glob, true, variant, &mut 0, &mut name);
+    Some(name).filter(|name| !name.is_empty())
+}
+
+/// Characters a class body (between the brackets) accepts
+fn class_choices(body: &[char]) -> Vec<char> {
+    let (negated, ranges) = class_members(body);
+    if negated {
+        NEGATED_CLASS_POOL
+            .chars()
+            .filter(|c| !ranges.iter().any(|(lo, hi)| lo <= c && c <= hi))
+            .collect()
+    } else {
+        let mut choices: Vec<char> = ranges
+            .iter()
+            .filter(|(lo, hi)| lo <= hi)
+            .flat_map(|&(lo, hi)| [lo, hi])
+            .filter(|&c| c != '/')
+            .collect();
+        choices.dedup();
+        choices
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+
+use crate::walk::DirContents;
+
+/// Archive formats a walk can look inside
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    /// ``.tar.gz`` or ``.tgz``
+    TarGz,
+    /// ``.tar.zst`` or ``.tzst``
+    TarZst,
+}
+
+impl ArchiveKind {
+    /// The kind a file's name says it is, if any
+    fn of(name: &OsStr) -> Option<Self> {
+        let name = name.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveKind::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(ArchiveKind::TarZst)
+        } else {
+            None
+        }
+    }
+}
+
+/// Files and directories of a tree that isn't on disk - an archive's, or
+/// an object store's - keyed by directory relative to its root, ``""``
+/// for the root itself
+pub(crate) struct VirtualTree {
+    dirs: BTreeMap<PathBuf, (BTreeSet<OsString>, BTreeSet<OsString>)>,
+}
+
+impl VirtualTree {
+    /// A tree with only its root
+    pub(crate) fn new() -> Self {
+        let mut tree = VirtualTree {
+            dirs: BTreeMap::new(),
+        };
+        tree.add_dir(Path::new(""));
+        tree
+    }
+
+    /// Record a directory, and every directory above it
+    pub(crate) fn add_dir(&mut self, dir: &Path) {
+        self.dirs.entry(dir.to_path_buf()).or_default();
+        if let (Some(parent), Some(name)) = (dir.parent(), dir.file_name()) {
+            self.add_dir(parent);
+            if let Some((_, dirs)) = self.dirs.get_mut(parent) {
+                dirs.insert(name.to_os_string());
+            }
+        }
+    }
+
+    pub(crate) fn add_file(&mut self, file: &Path) {
+        let (Some(parent), Some(name)) = (file.parent(), file.file_name()) else {
+            return;
+        };
+        self.add_dir(parent);
+        if let Some((files, _)) = self.dirs.get_mut(parent) {
+            files.insert(name.to_os_string());
+        }
+    }
+
+    /// Listings of every directory, keyed by `root` joined with its path,
+    /// with each directory's depth below `root`
+    pub(crate) fn listings(self, root: &Path) -> Vec<(PathBuf, usize, DirContents)> {
+        self.dirs
+            .into_iter()
+            .map(|(dir, (files, dirs))| {
+                let depth = dir.components().count();
+                let listing: DirContents =
+                    (files.into_iter().collect(), dirs.into_iter().collect());
+                let key = match depth {
+                    0 => root.to_path_buf(),
+                    _ => root.join(dir),
+                };
+                (key, depth, listing)
+            })
+            .collect()
+    }
+}
+
+/// An entry's path inside an archive or store, without ``./``; None for
+/// paths that would leave it, which are skipped
+pub(crate) fn entry_path(path: &Path) -> Option<PathBuf> {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normal.push(name),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!normal.as_os_str().is_empty()).then_some(normal)
+}
+
+/// Listings of every directory inside an archive, keyed by the path the
+/// archive gives it as a directory - ``<archive>/<dir>`` - with each
+/// directory's depth below the archive, 0 for the archive itself
+///
+/// Only the archive's index is read: a zip's central directory, or a
+/// tar's headers, seeking past the data between them. A compressed tar
+/// has to be decompressed to find its headers, but as a stream, with the
+/// data between them thrown away as it's reached.
+fn read_archive(
+    archive: &Path,
+    kind: ArchiveKind,
+) -> io::Result<Vec<(PathBuf, usize, DirContents)>> {
+    let mut tree = VirtualTree::new();
+    let file = File::open(archive)?;
+    match kind {
+        ArchiveKind::Zip => {
+            let zip = zip::ZipArchive::new(file)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            for name in zip.file_names() {
+                let Some(path) = entry_path(Path::new(name)) else {
+                    continue;
+                };
+                if name.ends_with('/') {
+                    tree.add_dir(&path);
+                } else {
+                    tree.add_file(&path);
+                }
+            }
+        }
+        ArchiveKind::Tar => {
+            let mut tar = tar::Archive::new(file);
+            add_tar_entries(&mut tree, tar.entries_with_seek()?)?;
+        }
+        ArchiveKind::TarGz => {
+            let decoder = flate2::read::MultiGzDecoder::new(BufReader::new(file));
+            add_tar_entries(&mut tree, tar::Archive::new(decoder).entries()?)?;
+        }
+        ArchiveKind::TarZst => {
+            let decoder = zstd::stream::read::Decoder::new(file)?;
+            add_tar_entries(&mut tree, tar::Archive::new(decoder).entries()?)?;
+        }
+    }
+    Ok(tree.listings(archive))
+}
+
+/// Record the directories and regular files of a tar
+fn add_tar_entries<R: Read>(
+    tree: &mut VirtualTree,
+    entries: tar::Entries<'_, R>,
+) -> io::Result<()> {
+    for entry in entries {
+        let entry = entry?;
+        let Some(path) = entry_path(&entry.path()?) else {
+            continue;
+        };
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_dir() {
+            tree.add_dir(&path);
+        } else if entry_type.is_file() {
+            tree.add_file(&path);
+        }
+    }
+    Ok(())
+}
+
+/// The listings of an archive a walk found, if it's one to look inside,
+/// limited to directories less than `max_depth` deep when the archive is
+/// `depth` deep
+pub(crate) fn open_archive(
+    path: &Path,
+    depth: usize,
+    max_depth: Option<usize>,
+) -> Option<io::Result<Vec<(PathBuf, DirContents)>>> {
+    let kind = path.file_name().and_then(ArchiveKind::of)?;
+    Some(read_archive(path, kind).map(|listings| {
+        listings
+            .into_iter()
+            .filter(|(_, inner, _)| max_depth.map_or(true, |max| depth + inner < max))
+            .map(|(dir, _, listing)| (dir, listing))
+            .collect()
+    }))
+}
@@ -0,0 +1,150 @@
+use lru::LruCache;
+use regex::Regex;
+use std::cell::RefCell;
+use std::num::NonZeroUsize;
+
+use crate::dialect::{pathlib_capture_body, GlobDialect};
+use crate::glob_syntax::{brace_end, class_end, class_members, split_alternatives};
+use crate::pattern::{MatcherOptions, PatternBody};
+
+thread_local! {
+    /// Capture regexes by source, so repeated `extract` calls compile once
+    static CAPTURE_CACHE: RefCell<LruCache<String, Regex>> =
+        RefCell::new(LruCache::new(NonZeroUsize::new(256).unwrap()));
+}
+
+/// Text matched by each wildcard of `pattern` in a prepared `path`
+///
+/// Returns None if the pattern doesn't match. Wildcards inside a `{a,b}`
+/// branch that didn't match yield empty strings.
+pub fn extract(
+    pattern: &str,
+    options: &MatcherOptions,
+    path: &str,
+    is_dir: bool,
+) -> Result<Option<Vec<String>>, String> {
+    let PatternBody {
+        body,
+        any_depth,
+        dir_only,
+    } = options.split_pattern(pattern);
+    if dir_only && !is_dir {
+        return Ok(None);
+    }
+
+    let body = match options.dialect {
+        GlobDialect::Globset => globset_capture_body(&body, options.full_path),
+        GlobDialect::Pathlib => pathlib_capture_body(&body),
+    };
+    // Unanchored full-path patterns match at any depth; the directories
+    // in front are not one of the pattern's own wildcards
+    let prefix = if any_depth { "(?:.*/)?" } else { "" };
+    let source = format!(r"^(?s:{}{})\z", prefix, body);
+
+    CAPTURE_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if !cache.contains(&source) {
+            let regex = Regex::new(&source)
+                .map_err(|e| format!("Cannot extract from pattern '{}': {}", pattern, e))?;
+            cache.put(source.clone(), regex);
+        }
+        let regex = cache.get(&source).unwrap();
+        Ok(regex.captures(path).map(|captures| {
+            captures
+                .iter()
+                .skip(1)
+                .map(|group| group.map_or_else(String::new, |m| m.as_str().to_string()))
+                .collect()
+        }))
+    })
+}
+
+/// Regex for a globset glob with one capture group per `*`, `**` and `?`
+///
+/// `literal_separator` mirrors the globset option: when set, `*` and `?`
+/// never match `/`. A component-wide `**` captures the directories it spans
+/// without the trailing `/`.
+fn globset_capture_body(pattern: &str, literal_separator: bool) -> String {
+    let (star, question) = if literal_separator {
+        ("([^/]*)", "([^/])")
+    } else {
+        ("(.*)", "(.)")
+    };
+
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut out = String::with_capacity(pattern.len() * 2);
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                out.push_str(&regex::escape(&chars[i + 1].to_string()));
+                i += 2;
+            }
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let whole_component = i == 0 || chars[i - 1] == '/';
+                if whole_component && i + 2 == chars.len() {
+                    out.push_str("(.*)");
+                    i += 2;
+                } else if whole_component && chars.get(i + 2) == Some(&'/') {
+                    out.push_str("(?:(.*)/)?");
+                    i += 3;
+                } else {
+                    out.push_str(star);
+                    i += 2;
+                }
+            }
+            '*' => {
+                out.push_str(star);
+                i += 1;
+            }
+            '?' => {
+                out.push_str(question);
+                i += 1;
+            }
+            '[' => match class_end(&chars, i) {
+                Some(end) => {
+                    let (negated, ranges) = class_members(&chars[i + 1..end]);
+                    out.push('[');
+                    if negated {
+                        out.push('^');
+                    }
+                    for (lo, hi) in ranges {
+                        out.push_str(&regex::escape(&lo.to_string()));
+                        if hi != lo {
+                            out.push('-');
+                            out.push_str(&regex::escape(&hi.to_string()));
+                        }
+                    }
+                    out.push(']');
+                    i = end + 1;
+                }
+                None => {
+                    out.push_str(r"\[");
+                    i += 1;
+                }
+            },
+            '{' => match brace_end(&chars, i) {
+                Some(end) => {
+                    let inner: String = chars[i + 1..end].iter().collect();
+                    let alternatives: Vec<String> = split_alternatives(&inner)
+                        .iter()
+                        .map(|alternative| globset_capture_body(alternative, literal_separator))
+                        .collect();
+                    out.push_str("(?:");
+                    out.push_str(&alternatives.join("|"));
+                    out.push(')');
+                    i = end + 1;
+                }
+                None => {
+                    out.push_str(r"\{");
+                    i += 1;
+                }
+            },
+            c => {
+                out.push_str(&regex::escape(&c.to_string()));
+                i += 1;
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,67 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// How letter case is normalized before matching
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseFold {
+    /// Case-sensitive matching
+    #[default]
+    None,
+    /// Full Unicode case folding: `ß` matches `ss`, `ﬁ` matches `fi`,
+    /// and final sigma matches `σ`
+    Unicode,
+    /// Unicode folding with Turkish and Azerbaijani dotted/dotless i:
+    /// `I` folds to `ı` and `İ` folds to `i`
+    Turkic,
+}
+
+impl CaseFold {
+    pub fn parse(case_fold: Option<&str>) -> PyResult<Self> {
+        match case_fold {
+            None => Ok(CaseFold::None),
+            Some("unicode") => Ok(CaseFold::Unicode),
+            Some("turkic") => Ok(CaseFold::Turkic),
+            Some(other) => Err(PyValueError::new_err(format!(
+                "Unknown case folding '{}': expected 'unicode' or 'turkic'",
+                other
+            ))),
+        }
+    }
+
+    /// Fold `text`, borrowing it when folding would leave it unchanged
+    ///
+    /// Glob syntax is ASCII punctuation, which folding never touches, so
+    /// patterns and candidates can go through the same function.
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            CaseFold::None => Cow::Borrowed(text),
+            CaseFold::Unicode => fold_unless_folded(text),
+            CaseFold::Turkic if text.contains(['I', 'İ']) => {
+                let dotted: String = text
+                    .chars()
+                    .map(|c| match c {
+                        'I' => 'ı',
+                        'İ' => 'i',
+                        c => c,
+                    })
+                    .collect();
+                Cow::Owned(fold_unless_folded(&dotted).into_owned())
+            }
+            CaseFold::Turkic => fold_unless_folded(text),
+        }
+    }
+}
+
+fn fold_unless_folded(text: &str) -> Cow<'_, str> {
+    // Lowercase ASCII is already folded, which covers most filenames
+    if text
+        .bytes()
+        .all(|b| b.is_ascii() && !b.is_ascii_uppercase())
+    {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(caseless::default_case_fold_str(text))
+}
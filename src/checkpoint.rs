@@ -0,0 +1,295 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::file_pattern::{CompiledPattern, StructureMatch};
+use crate::walk::{
+    check_root, compile_patterns, evaluate_dir, read_subtree, ScanResult, ScanResults, WalkedTree,
+};
+
+/// Bumped whenever the checkpoint layout changes
+const CHECKPOINT_VERSION: u32 = 1;
+
+/// Directories visited per worker thread between checkpoint checks
+const BATCH_PER_THREAD: usize = 64;
+
+/// On-disk state of an unfinished `scan_checkpointed` run
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    version: u32,
+    /// Arguments of the scan; a checkpoint is only resumed by the same scan
+    root: String,
+    patterns: Vec<String>,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    first_match: bool,
+    /// Directories still to visit, with their depth below the root
+    frontier: Vec<(String, usize)>,
+    /// Matches found so far: directory, pattern index and what matched
+    matches: Vec<(String, usize, StructureMatch)>,
+}
+
+/// Scan a directory tree, saving progress so an interrupted scan can resume
+///
+/// Every ``checkpoint_interval`` seconds the directories still to visit and
+/// the matches found so far are written to ``checkpoint_path``. Calling
+/// again with the same arguments after a crash, reboot or
+/// ``KeyboardInterrupt`` picks up from the last checkpoint instead of
+/// starting over; directories visited since are visited again. The
+/// checkpoint is removed once the scan completes.
+///
+/// Results are the same as ``scan_parallel`` with ``overlap="all"``. Each
+/// directory is listed once more, as deep as the patterns look, to match
+/// it, so the walk holds no more than the directories still to visit.
+///
+/// Args:
+///     path: Root directory to scan
+///     pattern_jsons: List of JSON-serialized FileStructurePattern objects
+///     checkpoint_path: File the scan's progress is saved to
+///     max_depth: Optional maximum depth to traverse
+///     follow_links: Whether to follow symbolic links
+///     first_match: Attribute each directory only to the first pattern, in
+///         list order, that matches it
+///     checkpoint_interval: Minimum seconds between checkpoints
+///         (default: 60.0)
+///
+/// Returns:
+///     ScanResults for directories that matched
+///
+/// Raises:
+///     PatternSyntaxError: If a pattern is invalid
+///     WalkError: If ``path`` doesn't exist or isn't a directory
+///     ValueError: If the checkpoint can't be written, or
+///         ``checkpoint_path`` holds a checkpoint of a different scan
+#[pyfunction]
+#[pyo3(signature = (
+    path,
+    pattern_jsons,
+    checkpoint_path,
+    max_depth=None,
+    follow_links=false,
+    first_match=false,
+    checkpoint_interval=60.0,
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn scan_checkpointed(
+    py: Python<'_>,
+    path: String,
+    pattern_jsons: Vec<String>,
+    checkpoint_path: PathBuf,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    first_match: bool,
+    checkpoint_interval: f64,
+) -> PyResult<ScanResults> {
+    check_root(&path)?;
+    let patterns = compile_patterns(&pattern_jsons)?;
+    let fresh = Checkpoint {
+        version: CHECKPOINT_VERSION,
+        root: path.clone(),
+        patterns: pattern_jsons,
+        max_depth,
+        follow_links,
+        first_match,
+        frontier: if max_depth == Some(0) {
+            Vec::new()
+        } else {
+            vec![(path.clone(), 0)]
+        },
+        matches: Vec::new(),
+    };
+    let mut checkpoint = match load_checkpoint(&checkpoint_path)? {
+        Some(saved) if saved.resumes(&fresh) => saved,
+        Some(_) => {
+            return Err(PyValueError::new_err(format!(
+                "{} is a checkpoint of a different scan; remove it to start this one",
+                checkpoint_path.display()
+            )))
+        }
+        None => fresh,
+    };
+
+    let scan = CheckpointedScan {
+        patterns: &patterns,
+        levels: patterns
+            .iter()
+            .map(CompiledPattern::depth)
+            .max()
+            .unwrap_or(0),
+        max_depth,
+        follow_links,
+        first_match,
+    };
+    let interval = Duration::from_secs_f64(checkpoint_interval.max(0.0));
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut last_saved = Instant::now();
+    while !checkpoint.frontier.is_empty() {
+        let start = checkpoint
+            .frontier
+            .len()
+            .saturating_sub(threads * BATCH_PER_THREAD);
+        let batch = checkpoint.frontier.split_off(start);
+        for (subdirs, matches) in py.allow_threads(|| scan.visit_batch(&batch, threads)) {
+            checkpoint.frontier.extend(subdirs);
+            checkpoint.matches.extend(matches);
+        }
+        // Save before an interrupt propagates, so it can be resumed from
+        if let Err(err) = py.check_signals() {
+            save_checkpoint(&checkpoint_path, &checkpoint)?;
+            return Err(err);
+        }
+        if last_saved.elapsed() >= interval {
+            save_checkpoint(&checkpoint_path, &checkpoint)?;
+            last_saved = Instant::now();
+        }
+    }
+    match fs::remove_file(&checkpoint_path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(PyValueError::new_err(format!(
+                "Cannot remove checkpoint {}: {}",
+                checkpoint_path.display(),
+                e
+            )))
+        }
+        _ => {}
+    }
+
+    let results = checkpoint
+        .matches
+        .into_iter()
+        .map(|(dir, pattern_idx, found)| {
+            ScanResult::new(
+                dir,
+                path.clone(),
+                pattern_idx,
+                &patterns[pattern_idx],
+                found,
+            )
+        })
+        .collect();
+    Ok(ScanResults::new(results, vec![path], &patterns))
+}
+
+impl Checkpoint {
+    /// Whether this checkpoint was saved by the scan `fresh` starts
+    fn resumes(&self, fresh: &Checkpoint) -> bool {
+        self.root == fresh.root
+            && self.patterns == fresh.patterns
+            && self.max_depth == fresh.max_depth
+            && self.follow_links == fresh.follow_links
+            && self.first_match == fresh.first_match
+    }
+}
+
+struct CheckpointedScan<'a> {
+    patterns: &'a [CompiledPattern],
+    /// Listing depth the patterns need below a matched directory
+    levels: usize,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    first_match: bool,
+}
+
+/// Subdirectories still to visit and matches found in one directory
+type Visited = (Vec<(String, usize)>, Vec<(String, usize, StructureMatch)>);
+
+impl CheckpointedScan<'_> {
+    /// Visit `batch` split across `threads` workers
+    fn visit_batch(&self, batch: &[(String, usize)], threads: usize) -> Vec<Visited> {
+        let chunk = ((batch.len() + threads - 1) / threads).max(1);
+        thread::scope(|scope| {
+            let workers: Vec<_> = batch
+                .chunks(chunk)
+                .map(|dirs| {
+                    scope.spawn(move || {
+                        dirs.iter()
+                            .map(|(dir, depth)| self.visit(Path::new(dir), *depth))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("scan worker panicked"))
+                .collect()
+        })
+    }
+
+    /// Match `dir` and list the subdirectories to visit after it
+    fn visit(&self, dir: &Path, depth: usize) -> Visited {
+        // Listings below max_depth aren't read, as in scan_parallel
+        let levels = self
+            .max_depth
+            .map_or(self.levels, |max| self.levels.min(max - depth - 1));
+        let mut tree = WalkedTree::new();
+        read_subtree(dir, levels, self.follow_links, &mut tree);
+        let dirs = &tree[dir].1;
+
+        let path = dir.to_string_lossy().into_owned();
+        let matches = evaluate_dir(dir, &tree, self.patterns, self.first_match)
+            .into_iter()
+            .map(|(pattern_idx, found)| (path.clone(), pattern_idx, found))
+            .collect();
+
+        let mut subdirs = Vec::new();
+        if self.max_depth.map_or(true, |max| depth + 1 < max) {
+            let canonical = self
+                .follow_links
+                .then(|| fs::canonicalize(dir).ok())
+                .flatten();
+            for name in dirs {
+                let subdir = dir.join(name);
+                // A link back up the branch would be followed forever
+                if let Some(canonical) = &canonical {
+                    if fs::canonicalize(&subdir).is_ok_and(|target| canonical.starts_with(target)) {
+                        continue;
+                    }
+                }
+                subdirs.push((subdir.to_string_lossy().into_owned(), depth + 1));
+            }
+        }
+        (subdirs, matches)
+    }
+}
+
+/// The checkpoint saved at `path`, if there is one
+fn load_checkpoint(path: &Path) -> PyResult<Option<Checkpoint>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(PyValueError::new_err(format!(
+                "Cannot read checkpoint {}: {}",
+                path.display(),
+                e
+            )))
+        }
+    };
+    serde_json::from_slice::<Checkpoint>(&bytes)
+        .ok()
+        .filter(|checkpoint| checkpoint.version == CHECKPOINT_VERSION)
+        .map(Some)
+        .ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "{} is not a scan checkpoint from this version",
+                path.display()
+            ))
+        })
+}
+
+/// Write the checkpoint next to its final path and move it into place, so
+/// a crash mid-write leaves the previous checkpoint intact
+fn save_checkpoint(path: &Path, checkpoint: &Checkpoint) -> PyResult<()> {
+    let write_error = |e: std::io::Error| {
+        PyValueError::new_err(format!("Cannot write checkpoint {}: {}", path.display(), e))
+    };
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let bytes = serde_json::to_vec(checkpoint).expect("checkpoint serialization cannot fail");
+    fs::write(&temporary, bytes).map_err(write_error)?;
+    fs::rename(&temporary, path).map_err(write_error)
+}
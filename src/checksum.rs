@@ -0,0 +1,84 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Hash algorithms files can be checksummed with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Checksum {
+    Blake3,
+    Sha256,
+}
+
+impl Checksum {
+    pub fn parse(algorithm: &str) -> PyResult<Self> {
+        match algorithm {
+            "blake3" => Ok(Checksum::Blake3),
+            "sha256" => Ok(Checksum::Sha256),
+            other => Err(PyValueError::new_err(format!(
+                "Unknown checksum '{}': expected 'blake3' or 'sha256'",
+                other
+            ))),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Checksum::Blake3 => "blake3",
+            Checksum::Sha256 => "sha256",
+        }
+    }
+
+    pub fn hasher(self) -> Hasher {
+        match self {
+            Checksum::Blake3 => Hasher::Blake3(Box::default()),
+            Checksum::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    /// Hex digest of a file's contents, read through `buffer`
+    pub fn hash_file(self, path: &Path, buffer: &mut [u8]) -> io::Result<String> {
+        let mut file = File::open(path)?;
+        let mut hasher = self.hasher();
+        loop {
+            match file.read(buffer) {
+                Ok(0) => break,
+                Ok(read) => hasher.update(&buffer[..read]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(hasher.finish())
+    }
+}
+
+/// A digest being computed, fed data as it streams
+pub enum Hasher {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            Hasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    /// The lowercase hex digest
+    pub fn finish(self) -> String {
+        match self {
+            Hasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            Hasher::Sha256(hasher) => hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        }
+    }
+}
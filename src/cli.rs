@@ -0,0 +1,336 @@
+use clap::{Args, Parser, Subcommand};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::ffi::OsString;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::file_pattern::{CompiledPattern, FileStructurePattern};
+use crate::walk::{
+    compile_specs, evaluate_dir, list_entry, scan_walker, Collector, DirContents, ScanResult,
+    WalkedTree,
+};
+
+/// Walk and scan directory trees for file structure patterns
+#[derive(Parser)]
+#[command(name = "pathvein", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List every directory and file under a root
+    Walk {
+        #[command(flatten)]
+        walk: WalkArgs,
+    },
+    /// Print the directories matching pattern specs
+    Scan {
+        #[command(flatten)]
+        walk: WalkArgs,
+        /// Pattern spec file, as JSON, YAML or TOML; repeat for several
+        #[arg(short, long = "pattern", required = true)]
+        patterns: Vec<PathBuf>,
+        /// Attribute each directory only to the first pattern that matches
+        #[arg(long)]
+        first_match: bool,
+    },
+    /// Count the directories, files and bytes under a root, and the matches
+    /// of any pattern specs
+    Stats {
+        #[command(flatten)]
+        walk: WalkArgs,
+        /// Pattern spec file, as JSON, YAML or TOML; repeat for several
+        #[arg(short, long = "pattern")]
+        patterns: Vec<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+struct WalkArgs {
+    /// Root directory
+    root: PathBuf,
+    /// Maximum depth to traverse
+    #[arg(long)]
+    max_depth: Option<usize>,
+    /// Follow symbolic links
+    #[arg(long)]
+    follow_links: bool,
+    /// Write one JSON object per line rather than plain paths
+    #[arg(long)]
+    ndjson: bool,
+}
+
+/// A directory's listing, as ``walk_parallel`` gives it
+#[derive(Serialize)]
+struct Listing<'a> {
+    path: &'a str,
+    dirnames: Vec<String>,
+    filenames: Vec<String>,
+}
+
+/// Totals for ``stats``
+#[derive(Serialize)]
+struct TreeStats {
+    root: String,
+    directories: u64,
+    files: u64,
+    bytes: u64,
+    errors: u64,
+    patterns: Vec<PatternCount>,
+}
+
+#[derive(Serialize)]
+struct PatternCount {
+    index: usize,
+    name: Option<String>,
+    matches: usize,
+}
+
+/// A tree walked for the CLI
+struct Walked {
+    tree: WalkedTree,
+    directories: u64,
+    files: u64,
+    bytes: u64,
+    errors: u64,
+}
+
+/// Run the ``pathvein`` command line
+pub fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("pathvein: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command) -> io::Result<()> {
+    let mut out = BufWriter::new(io::stdout().lock());
+    match command {
+        Command::Walk { walk } => {
+            let walked = walk_root(&walk, false)?;
+            let mut listings: Vec<_> = walked.tree.iter().collect();
+            listings.sort_by(|a, b| a.0.cmp(b.0));
+            if walk.ndjson {
+                let names = |names: &[OsString]| -> Vec<String> {
+                    names
+                        .iter()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .collect()
+                };
+                for (dir, (files, dirs)) in listings {
+                    let path = dir.to_string_lossy();
+                    write_json(
+                        &mut out,
+                        &Listing {
+                            path: &path,
+                            dirnames: names(dirs),
+                            filenames: names(files),
+                        },
+                    )?;
+                }
+            } else {
+                let mut paths: Vec<PathBuf> = listings
+                    .into_iter()
+                    .flat_map(|(dir, (files, dirs))| {
+                        files.iter().chain(dirs.iter()).map(|name| dir.join(name))
+                    })
+                    .collect();
+                paths.push(walk.root.clone());
+                paths.sort();
+                for path in paths {
+                    writeln!(out, "{}", path.display())?;
+                }
+            }
+        }
+        Command::Scan {
+            walk,
+            patterns,
+            first_match,
+        } => {
+            let patterns = load_patterns(&patterns)?;
+            let walked = walk_root(&walk, false)?;
+            let results = scan(&walk.root, &walked.tree, &patterns, first_match);
+            if walk.ndjson {
+                for result in &results {
+                    write_json(&mut out, result)?;
+                }
+            } else {
+                let mut paths: Vec<&str> = results.iter().map(|result| &*result.path).collect();
+                paths.dedup();
+                for path in paths {
+                    writeln!(out, "{}", path)?;
+                }
+            }
+        }
+        Command::Stats { walk, patterns } => {
+            let patterns = load_patterns(&patterns)?;
+            let walked = walk_root(&walk, true)?;
+            let results = scan(&walk.root, &walked.tree, &patterns, false);
+            let stats = TreeStats {
+                root: walk.root.to_string_lossy().into_owned(),
+                directories: walked.directories,
+                files: walked.files,
+                bytes: walked.bytes,
+                errors: walked.errors,
+                patterns: patterns
+                    .iter()
+                    .enumerate()
+                    .map(|(index, pattern)| PatternCount {
+                        index,
+                        name: pattern.pattern_name.clone(),
+                        matches: results
+                            .iter()
+                            .filter(|result| result.pattern_index == index)
+                            .count(),
+                    })
+                    .collect(),
+            };
+            if walk.ndjson {
+                write_json(&mut out, &stats)?;
+            } else {
+                writeln!(out, "directories: {}", stats.directories)?;
+                writeln!(out, "files: {}", stats.files)?;
+                writeln!(out, "bytes: {}", stats.bytes)?;
+                writeln!(out, "errors: {}", stats.errors)?;
+                for count in &stats.patterns {
+                    match &count.name {
+                        Some(name) => writeln!(out, "matches {}: {}", name, count.matches)?,
+                        None => writeln!(out, "matches #{}: {}", count.index, count.matches)?,
+                    }
+                }
+            }
+        }
+    }
+    out.flush()
+}
+
+fn write_json(out: &mut impl Write, value: &impl Serialize) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, value)?;
+    writeln!(out)
+}
+
+/// Load and compile pattern spec files
+fn load_patterns(paths: &[PathBuf]) -> io::Result<Vec<CompiledPattern>> {
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+    let specs = paths
+        .iter()
+        .map(|path| FileStructurePattern::load_file(path))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(invalid)?;
+    compile_specs(&specs).map_err(invalid)
+}
+
+/// Walk `root` in parallel, collecting each directory's listing as
+/// ``scan_parallel`` does, and the sizes of its files if `sizes`
+fn walk_root(args: &WalkArgs, sizes: bool) -> io::Result<Walked> {
+    if !args.root.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is not a directory", args.root.display()),
+        ));
+    }
+    let root = args.root.to_string_lossy();
+    let builder = scan_walker(&root, args.max_depth, args.follow_links);
+    let listings: DashMap<PathBuf, DirContents> = DashMap::new();
+    let failures = Collector::<()>::new();
+    let (directories, files, bytes) = (AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0));
+    builder.build_parallel().run(|| {
+        Box::new(|entry| {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    failures.walk_error(&e);
+                    return ignore::WalkState::Continue;
+                }
+            };
+            let Some(file_type) = entry.file_type() else {
+                return ignore::WalkState::Continue;
+            };
+            if file_type.is_dir() {
+                directories.fetch_add(1, Ordering::Relaxed);
+            } else if file_type.is_file() {
+                files.fetch_add(1, Ordering::Relaxed);
+                if sizes {
+                    match entry.metadata() {
+                        Ok(metadata) => {
+                            bytes.fetch_add(metadata.len(), Ordering::Relaxed);
+                        }
+                        Err(e) => failures.walk_error(&e),
+                    }
+                }
+            }
+            list_entry(&listings, &entry, false);
+            ignore::WalkState::Continue
+        })
+    });
+    let (_, mut failures) = failures.into_parts();
+    failures.sort();
+    for failure in &failures {
+        eprintln!("pathvein: {}", failure);
+    }
+    let mut tree: WalkedTree = listings.into_iter().collect();
+    for (files, dirs) in tree.values_mut() {
+        files.sort();
+        dirs.sort();
+    }
+    Ok(Walked {
+        tree,
+        directories: directories.into_inner(),
+        files: files.into_inner(),
+        bytes: bytes.into_inner(),
+        errors: failures.len() as u64,
+    })
+}
+
+/// Match every directory of `tree` on one thread per CPU; returns the
+/// matches sorted by path and pattern index
+fn scan(
+    root: &Path,
+    tree: &WalkedTree,
+    patterns: &[CompiledPattern],
+    first_match: bool,
+) -> Vec<ScanResult> {
+    if patterns.is_empty() {
+        return Vec::new();
+    }
+    let dirs: Vec<&PathBuf> = tree.keys().collect();
+    let root = root.to_string_lossy().into_owned();
+    let results = Mutex::new(Vec::new());
+    let next = AtomicUsize::new(0);
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    thread::scope(|scope| {
+        for _ in 0..workers.min(dirs.len()) {
+            scope.spawn(|| {
+                let mut found = Vec::new();
+                while let Some(dir) = dirs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let path = dir.to_string_lossy();
+                    for (index, matched) in evaluate_dir(dir, tree, patterns, first_match) {
+                        found.push(ScanResult::new(
+                            path.clone().into_owned(),
+                            root.clone(),
+                            index,
+                            &patterns[index],
+                            matched,
+                        ));
+                    }
+                }
+                results.lock().expect("results poisoned").extend(found);
+            });
+        }
+    });
+    let mut results = results.into_inner().expect("results poisoned");
+    results.sort_by(|a, b| (&a.path, a.pattern_index).cmp(&(&b.path, b.pattern_index)));
+    results
+}
@@ -0,0 +1,260 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// Which glob semantics a pattern is compiled with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GlobDialect {
+    /// globset semantics: `*` crosses `/`, `{a,b}` alternation
+    #[default]
+    Globset,
+    /// CPython `PurePath.full_match` semantics (3.13+)
+    Pathlib,
+}
+
+impl GlobDialect {
+    pub fn parse(dialect: &str) -> PyResult<Self> {
+        match dialect {
+            "globset" => Ok(GlobDialect::Globset),
+            "pathlib" => Ok(GlobDialect::Pathlib),
+            other => Err(PyValueError::new_err(format!(
+                "Unknown glob dialect '{}': expected 'globset' or 'pathlib'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Compile a glob with pathlib semantics into an anchored regex
+pub fn compile_pathlib(pattern: &str) -> Result<Regex, String> {
+    Regex::new(&format!(r"^(?s:{})\z", pathlib_regex_body(pattern)))
+        .map_err(|e| format!("Invalid glob pattern '{}': {}", pattern, e))
+}
+
+/// Normalize a path or pattern the way `PurePosixPath` does
+///
+/// pathlib compares `str(PurePosixPath(...))` forms, so repeated slashes,
+/// `.` segments and trailing slashes vanish on both sides, while a leading
+/// `//` (but not `///`) is preserved as a distinct root. An empty result is
+/// the empty string rather than `"."`, mirroring `PurePath._pattern_str`.
+pub fn pathlib_normalize(path: &str) -> Cow<'_, str> {
+    let root = if path.starts_with("//") && !path.starts_with("///") {
+        "//"
+    } else if path.starts_with('/') {
+        "/"
+    } else {
+        ""
+    };
+    let is_clean = path[root.len()..]
+        .split('/')
+        .all(|part| !part.is_empty() && part != ".");
+    if is_clean || path.is_empty() {
+        return Cow::Borrowed(path);
+    }
+
+    let parts: Vec<&str> = path
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect();
+    Cow::Owned(format!("{}{}", root, parts.join("/")))
+}
+
+/// Port of CPython's `glob.translate(pat, recursive=True, include_hidden=True)`
+///
+/// This is what `PurePath.full_match` uses, so it reproduces pathlib's
+/// edge cases exactly: `*` never crosses `/`, a whole-segment `**`
+/// matches zero or more segments, and `**` inside a segment degrades to
+/// `*`. The pattern is normalized first (see `pathlib_normalize`), so
+/// candidates must be normalized the same way before matching. The
+/// returned body is unanchored; callers wrap it in `(?s:...)` plus anchors.
+pub fn pathlib_regex_body(pattern: &str) -> String {
+    translate_pathlib(pattern, false)
+}
+
+/// Like `pathlib_regex_body`, but with one capture group per wildcard
+///
+/// A whole-segment `**` captures the directories it spans without the
+/// trailing `/`.
+pub fn pathlib_capture_body(pattern: &str) -> String {
+    translate_pathlib(pattern, true)
+}
+
+fn translate_pathlib(pattern: &str, capture: bool) -> String {
+    let pattern = pathlib_normalize(pattern);
+    const NOT_SEP: &str = "[^/]";
+    const ONE_LAST_SEGMENT: &str = "[^/]+";
+    const ONE_SEGMENT: &str = "[^/]+/";
+    const ANY_SEGMENTS: &str = "(?:.+/)?";
+    const ANY_LAST_SEGMENTS: &str = ".*";
+
+    let parts: Vec<&str> = pattern.split('/').collect();
+    let last_part_idx = parts.len() - 1;
+    let mut out = String::with_capacity(pattern.len() * 2);
+
+    for (idx, part) in parts.iter().enumerate() {
+        match *part {
+            "*" if capture => out.push_str(if idx < last_part_idx {
+                "([^/]+)/"
+            } else {
+                "([^/]+)"
+            }),
+            "*" => out.push_str(if idx < last_part_idx {
+                ONE_SEGMENT
+            } else {
+                ONE_LAST_SEGMENT
+            }),
+            "**" => {
+                if idx < last_part_idx {
+                    // Consecutive `**` segments collapse into one
+                    if parts[idx + 1] != "**" {
+                        out.push_str(if capture { "(?:(.+)/)?" } else { ANY_SEGMENTS });
+                    }
+                } else {
+                    out.push_str(if capture { "(.*)" } else { ANY_LAST_SEGMENTS });
+                }
+            }
+            _ => {
+                if !part.is_empty() {
+                    fnmatch_translate(part, NOT_SEP, capture, &mut out);
+                }
+                if idx < last_part_idx {
+                    out.push('/');
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Port of CPython's `fnmatch._translate` for a single path segment
+///
+/// With `capture`, each `*` and `?` becomes a capture group.
+fn fnmatch_translate(segment: &str, not_sep: &str, capture: bool, out: &mut String) {
+    let pat: Vec<char> = segment.chars().collect();
+    let n = pat.len();
+    let mut i = 0;
+    let mut last_was_star = false;
+
+    while i < n {
+        let c = pat[i];
+        i += 1;
+        match c {
+            '*' => {
+                // Compress consecutive `*` into one
+                if !last_was_star {
+                    if capture {
+                        out.push_str(&format!("({}*)", not_sep));
+                    } else {
+                        out.push_str(not_sep);
+                        out.push('*');
+                    }
+                }
+                last_was_star = true;
+                continue;
+            }
+            '?' if capture => out.push_str(&format!("({})", not_sep)),
+            '?' => out.push_str(not_sep),
+            '[' => i = translate_class(&pat, i, out),
+            _ => out.push_str(&regex::escape(&c.to_string())),
+        }
+        last_was_star = false;
+    }
+}
+
+/// Translate a `[...]` class starting just after `[`; returns the new index
+fn translate_class(pat: &[char], start: usize, out: &mut String) -> usize {
+    let n = pat.len();
+    let mut j = start;
+    if j < n && pat[j] == '!' {
+        j += 1;
+    }
+    if j < n && pat[j] == ']' {
+        j += 1;
+    }
+    while j < n && pat[j] != ']' {
+        j += 1;
+    }
+    if j >= n {
+        // Unterminated class is a literal '['
+        out.push_str(r"\[");
+        return start;
+    }
+
+    let escape = |chunk: &[char]| -> String {
+        chunk
+            .iter()
+            .map(|&ch| match ch {
+                '\\' => r"\\".to_string(),
+                '-' => r"\-".to_string(),
+                '&' | '~' | '|' | '[' | ']' => format!("\\{}", ch),
+                _ => ch.to_string(),
+            })
+            .collect()
+    };
+
+    let mut chunks: Vec<Vec<char>> = Vec::new();
+    let mut i = start;
+    let mut k = if pat[i] == '!' { i + 2 } else { i + 1 };
+    while let Some(offset) = pat[k.min(j)..j].iter().position(|&ch| ch == '-') {
+        let dash = k.min(j) + offset;
+        chunks.push(pat[i..dash].to_vec());
+        i = dash + 1;
+        k = dash + 3;
+    }
+    let tail = pat[i..j].to_vec();
+    if tail.is_empty() {
+        if let Some(last) = chunks.last_mut() {
+            last.push('-');
+        }
+    } else {
+        chunks.push(tail);
+    }
+
+    // Remove empty ranges like `z-a`, which are invalid in a regex
+    let mut idx = chunks.len().saturating_sub(1);
+    while idx > 0 {
+        let (prev_last, next_first) = (chunks[idx - 1].last(), chunks[idx].first());
+        if let (Some(&a), Some(&b)) = (prev_last, next_first) {
+            if a > b {
+                let next = chunks.remove(idx);
+                let prev = &mut chunks[idx - 1];
+                prev.pop();
+                prev.extend_from_slice(&next[1..]);
+            }
+        }
+        idx -= 1;
+    }
+
+    let negated = chunks
+        .first()
+        .is_some_and(|chunk| chunk.first() == Some(&'!'));
+    if negated {
+        chunks[0].remove(0);
+    }
+    let stuff: Vec<String> = chunks.iter().map(|chunk| escape(chunk)).collect();
+    let stuff = stuff.join("-");
+
+    match (negated, stuff.is_empty()) {
+        // Empty class never matches
+        (false, true) => out.push_str(r"\b\B"),
+        // Negated empty class matches any character
+        (true, true) => out.push('.'),
+        (true, false) => {
+            out.push_str("[^");
+            out.push_str(&stuff);
+            out.push(']');
+        }
+        (false, false) => {
+            out.push('[');
+            if stuff.starts_with('^') {
+                out.push('\\');
+            }
+            out.push_str(&stuff);
+            out.push(']');
+        }
+    }
+    j + 1
+}
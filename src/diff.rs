@@ -0,0 +1,243 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::walk::ScanResult;
+
+/// Scan results in memory, or a file ``export_results`` wrote them to
+#[derive(FromPyObject)]
+pub enum ScanSource {
+    File(PathBuf),
+    Results(Vec<ScanResult>),
+}
+
+/// What differs between two scans, from ``diff_scans``
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct ScanDiff {
+    /// Matches only the new scan has
+    #[pyo3(get)]
+    pub added: Vec<ScanResult>,
+    /// Matches only the old scan has
+    #[pyo3(get)]
+    pub removed: Vec<ScanResult>,
+    /// Matches both scans have that found different things
+    #[pyo3(get)]
+    pub changed: Vec<ScanChange>,
+}
+
+#[pymethods]
+impl ScanDiff {
+    /// Whether the scans found the same matches
+    #[getter]
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ScanDiff(added={}, removed={}, changed={})",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        )
+    }
+}
+
+/// One match both scans have, and how it differs
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct ScanChange {
+    #[pyo3(get)]
+    pub path: String,
+    #[pyo3(get)]
+    pub pattern_index: usize,
+    /// ``pattern_name`` in the new scan
+    #[pyo3(get)]
+    pub pattern_name: Option<String>,
+    /// ScanResult fields that differ, e.g. ``["matched_files", "score"]``
+    #[pyo3(get)]
+    pub fields: Vec<String>,
+    /// Requirements whose outcome differs: file globs that matched other
+    /// names, optional directories that appeared or went away, and roles
+    /// bound to other paths
+    #[pyo3(get)]
+    pub requirements: Vec<String>,
+    #[pyo3(get)]
+    pub old: ScanResult,
+    #[pyo3(get)]
+    pub new: ScanResult,
+}
+
+#[pymethods]
+impl ScanChange {
+    fn __repr__(&self) -> String {
+        format!(
+            "ScanChange(path='{}', pattern_index={}, fields={:?})",
+            self.path, self.pattern_index, self.fields
+        )
+    }
+}
+
+/// Compare two scans of the same tree
+///
+/// Matches are paired by ``path`` and ``pattern_index``, as ScanResult
+/// equality pairs them. A paired match is changed if any field besides
+/// ``root`` and ``failures`` differs; the order of names within a field
+/// doesn't count.
+///
+/// Args:
+///     old: The earlier scan's ScanResults or list of ScanResult, or the
+///         path of a ``"json"`` or ``"ndjson"`` file ``export_results`` or
+///         ``scan_to_file`` wrote
+///     new: The later scan, in any of the same forms
+///
+/// Returns:
+///     ScanDiff with added, removed and changed matches, each sorted by
+///     path and pattern index
+///
+/// Raises:
+///     ValueError: If a file can't be read or isn't a JSON or NDJSON export
+#[pyfunction]
+pub fn diff_scans(py: Python<'_>, old: ScanSource, new: ScanSource) -> PyResult<ScanDiff> {
+    py.allow_threads(|| {
+        let old = keyed(old.load()?);
+        let mut new = keyed(new.load()?);
+        let mut diff = ScanDiff {
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        };
+        for (key, old) in old {
+            match new.remove(&key) {
+                None => diff.removed.push(old),
+                Some(new) => {
+                    if let Some(change) = compare(old, new) {
+                        diff.changed.push(change);
+                    }
+                }
+            }
+        }
+        diff.added = new.into_values().collect();
+        Ok(diff)
+    })
+}
+
+impl ScanSource {
+    fn load(self) -> PyResult<Vec<ScanResult>> {
+        match self {
+            ScanSource::Results(results) => Ok(results),
+            ScanSource::File(path) => read_export(&path),
+        }
+    }
+}
+
+/// Results keyed, and so ordered, by path and pattern index
+fn keyed(results: Vec<ScanResult>) -> BTreeMap<(String, usize), ScanResult> {
+    results
+        .into_iter()
+        .map(|result| ((result.path.clone(), result.pattern_index), result))
+        .collect()
+}
+
+/// Results from a JSON array or NDJSON file
+fn read_export(path: &Path) -> PyResult<Vec<ScanResult>> {
+    let read_error = |e: &dyn std::fmt::Display| {
+        PyValueError::new_err(format!(
+            "Cannot read scan results from {}: {}",
+            path.display(),
+            e
+        ))
+    };
+    let text = fs::read_to_string(path).map_err(|e| read_error(&e))?;
+    if text.trim_start().starts_with('[') {
+        return serde_json::from_str(&text).map_err(|e| read_error(&e));
+    }
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            serde_json::from_str(line).map_err(|e| read_error(&format!("line {}: {}", idx + 1, e)))
+        })
+        .collect()
+}
+
+/// How `new` differs from `old`, None if it doesn't
+fn compare(old: ScanResult, new: ScanResult) -> Option<ScanChange> {
+    let mut fields = Vec::new();
+    let mut differs = |field: &str, differ: bool| {
+        if differ {
+            fields.push(field.to_string());
+        }
+    };
+    differs("pattern_name", old.pattern_name != new.pattern_name);
+    differs(
+        "matched_files",
+        names_by_key(&old.matched_files) != names_by_key(&new.matched_files),
+    );
+    differs(
+        "optional_files",
+        as_set(&old.optional_files) != as_set(&new.optional_files),
+    );
+    differs(
+        "optional_directories",
+        as_set(&old.optional_directories) != as_set(&new.optional_directories),
+    );
+    differs("branch", old.branch != new.branch);
+    differs("branch_name", old.branch_name != new.branch_name);
+    differs("score", old.score != new.score);
+    differs("aliases", as_set(&old.aliases) != as_set(&new.aliases));
+    differs("runners_up", old.runners_up != new.runners_up);
+    differs(
+        "bindings",
+        names_by_key(&old.bindings) != names_by_key(&new.bindings),
+    );
+    differs("captures", old.captures != new.captures);
+    if fields.is_empty() {
+        return None;
+    }
+
+    let mut requirements = changed_keys(&old.matched_files, &new.matched_files);
+    requirements.extend(
+        as_set(&old.optional_directories)
+            .symmetric_difference(&as_set(&new.optional_directories))
+            .map(|name| name.to_string()),
+    );
+    requirements.extend(changed_keys(&old.bindings, &new.bindings));
+    requirements.sort();
+    requirements.dedup();
+    Some(ScanChange {
+        path: new.path.clone(),
+        pattern_index: new.pattern_index,
+        pattern_name: new.pattern_name.clone(),
+        fields,
+        requirements,
+        old,
+        new,
+    })
+}
+
+fn as_set(names: &[String]) -> BTreeSet<&str> {
+    names.iter().map(String::as_str).collect()
+}
+
+fn names_by_key(map: &BTreeMap<String, Vec<String>>) -> BTreeMap<&str, BTreeSet<&str>> {
+    map.iter()
+        .map(|(key, names)| (key.as_str(), as_set(names)))
+        .collect()
+}
+
+/// Keys mapped to different names in `old` and `new`, or in only one
+fn changed_keys(
+    old: &BTreeMap<String, Vec<String>>,
+    new: &BTreeMap<String, Vec<String>>,
+) -> Vec<String> {
+    let (old, new) = (names_by_key(old), names_by_key(new));
+    old.keys()
+        .chain(new.keys())
+        .filter(|key| old.get(*key) != new.get(*key))
+        .map(|key| key.to_string())
+        .collect()
+}
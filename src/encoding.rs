@@ -0,0 +1,228 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::filetype::{visit_files, DetectSource};
+use crate::walk::{check_root, exclude_matcher, thread_count, Collector, Failure};
+
+/// Share of a Latin-1 sample that may be control bytes before it's taken
+/// for binary
+const MAX_CONTROL_SHARE: f64 = 0.05;
+
+/// Text or binary, and the encoding of one file, from ``detect_encodings``
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct FileEncoding {
+    #[pyo3(get)]
+    pub path: String,
+    #[pyo3(get)]
+    pub is_text: bool,
+    /// Python codec name the text decodes with: ``"utf-8"``,
+    /// ``"utf-8-sig"``, ``"utf-16"`` (with a byte order mark),
+    /// ``"utf-16-le"``, ``"utf-16-be"`` or ``"latin-1"``; None for binary
+    /// files
+    #[pyo3(get)]
+    pub encoding: Option<&'static str>,
+}
+
+#[pymethods]
+impl FileEncoding {
+    fn __repr__(&self) -> String {
+        match self.encoding {
+            Some(encoding) => format!(
+                "FileEncoding(path='{}', is_text=True, encoding='{}')",
+                self.path, encoding
+            ),
+            None => format!("FileEncoding(path='{}', is_text=False)", self.path),
+        }
+    }
+}
+
+/// Files classified as text or binary, from ``detect_encodings``
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct FileEncodings {
+    /// Every file read, sorted by path
+    #[pyo3(get)]
+    pub files: Vec<FileEncoding>,
+    /// Files that couldn't be read
+    #[pyo3(get)]
+    pub errors: Vec<Failure>,
+}
+
+#[pymethods]
+impl FileEncodings {
+    /// Paths of the text files
+    #[getter]
+    fn text(&self) -> Vec<String> {
+        self.paths(true)
+    }
+
+    /// Paths of the binary files
+    #[getter]
+    fn binary(&self) -> Vec<String> {
+        self.paths(false)
+    }
+
+    fn __len__(&self) -> usize {
+        self.files.len()
+    }
+
+    fn __repr__(&self) -> String {
+        let text = self.files.iter().filter(|file| file.is_text).count();
+        format!(
+            "FileEncodings(text={}, binary={}, errors={})",
+            text,
+            self.files.len() - text,
+            self.errors.len()
+        )
+    }
+}
+
+impl FileEncodings {
+    fn paths(&self, is_text: bool) -> Vec<String> {
+        self.files
+            .iter()
+            .filter(|file| file.is_text == is_text)
+            .map(|file| file.path.clone())
+            .collect()
+    }
+}
+
+/// Classify files as text or binary, and guess the encoding of text
+///
+/// Only the first ``sample_size`` bytes of each file are read, in
+/// parallel, so the cost doesn't grow with file size. A byte order mark
+/// settles the encoding. Without one, a sample that decodes as UTF-8 is
+/// UTF-8; one with NUL bytes is UTF-16 if they fall in every other byte,
+/// and binary otherwise; and anything else is Latin-1, unless more than
+/// 5% of it is control characters, when it's binary. Empty files are
+/// UTF-8 text.
+///
+/// Args:
+///     paths: Directory whose files to classify, walked in parallel, or a
+///         list of file paths
+///     sample_size: Bytes read from the start of each file (default: 8192)
+///     threads: Files read at once (default: one per CPU)
+///     max_depth: Optional maximum depth to traverse, for a directory
+///     follow_links: Whether to follow symbolic links, for a directory
+///     exclude: Optional gitignore-style globs, relative to the directory,
+///         for entries to skip, as for ``walk_parallel``
+///
+/// Returns:
+///     FileEncodings with each file's path, whether it is text, and the
+///     Python codec to decode it with
+///
+/// Raises:
+///     ValueError: If sample_size or threads is 0, or an exclude pattern
+///         is invalid
+///     WalkError: If ``paths`` is a string that isn't a directory
+#[pyfunction]
+#[pyo3(signature = (paths, sample_size=8192, threads=None, max_depth=None, follow_links=false, exclude=None))]
+pub fn detect_encodings(
+    py: Python<'_>,
+    paths: DetectSource,
+    sample_size: usize,
+    threads: Option<usize>,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    exclude: Option<Vec<String>>,
+) -> PyResult<FileEncodings> {
+    if sample_size == 0 {
+        return Err(PyValueError::new_err("sample_size must be at least 1"));
+    }
+    let threads = thread_count(threads)?;
+    let matcher = exclude_matcher(exclude)?;
+    if let DetectSource::Root(root) = &paths {
+        check_root(root)?;
+    }
+    Ok(py.allow_threads(|| {
+        let collector = Collector::new();
+        let walk_errors =
+            visit_files(
+                paths,
+                threads,
+                max_depth,
+                follow_links,
+                matcher,
+                |path| match read_sample(&path, sample_size) {
+                    Ok(sample) => {
+                        let encoding = classify(&sample);
+                        collector.push(FileEncoding {
+                            path: path.to_string_lossy().into_owned(),
+                            is_text: encoding.is_some(),
+                            encoding,
+                        });
+                    }
+                    Err(e) => collector.fail(&path, e),
+                },
+            );
+        let (files, errors) = collector.finish(walk_errors, |file| &file.path);
+        FileEncodings { files, errors }
+    }))
+}
+
+fn read_sample(path: &Path, limit: usize) -> io::Result<Vec<u8>> {
+    let mut sample = Vec::with_capacity(limit.min(1 << 16));
+    File::open(path)?
+        .take(limit as u64)
+        .read_to_end(&mut sample)?;
+    Ok(sample)
+}
+
+/// The encoding of text starting with `sample`; None if it's binary
+fn classify(sample: &[u8]) -> Option<&'static str> {
+    if sample.starts_with(b"\xef\xbb\xbf") {
+        return Some("utf-8-sig");
+    }
+    if sample.starts_with(b"\xff\xfe") || sample.starts_with(b"\xfe\xff") {
+        return Some("utf-16");
+    }
+    if sample.contains(&0) {
+        return utf16_without_bom(sample);
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => return Some("utf-8"),
+        // A character cut off by the end of the sample is still UTF-8
+        Err(e) if e.error_len().is_none() => return Some("utf-8"),
+        Err(_) => {}
+    }
+    let controls = sample
+        .iter()
+        .filter(|&&byte| {
+            (byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b)) || byte == 0x7f
+        })
+        .count();
+    (controls as f64 <= sample.len() as f64 * MAX_CONTROL_SHARE).then_some("latin-1")
+}
+
+/// UTF-16 without a byte order mark: mostly-ASCII text has a NUL in every
+/// other byte, the high bytes - odd offsets for little-endian
+fn utf16_without_bom(sample: &[u8]) -> Option<&'static str> {
+    let pairs = sample.len() / 2;
+    if pairs == 0 {
+        return None;
+    }
+    let nuls_at = |parity: usize| {
+        sample
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .take(pairs)
+            .filter(|&&byte| byte == 0)
+            .count()
+    };
+    let (even, odd) = (nuls_at(0), nuls_at(1));
+    // Most high bytes are NUL, and the low bytes carry the text
+    let mostly = |nuls: usize| nuls * 10 >= pairs * 7;
+    let rarely = |nuls: usize| nuls * 10 <= pairs;
+    if mostly(odd) && rarely(even) {
+        Some("utf-16-le")
+    } else if mostly(even) && rarely(odd) {
+        Some("utf-16-be")
+    } else {
+        None
+    }
+}
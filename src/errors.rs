@@ -0,0 +1,124 @@
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyOSError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{PyDict, PyType};
+use std::path::Path;
+
+create_exception!(
+    pathvein._pathvein_rs,
+    PathveinError,
+    PyException,
+    "Base class of the errors pathvein raises."
+);
+
+create_exception!(
+    pathvein._pathvein_rs,
+    ScanCancelled,
+    PathveinError,
+    "A scan was stopped before it finished.\n\n\
+     Raise it from a ``progress`` or ``events`` callback to cancel a scan;\n\
+     the scan stops and re-raises it."
+);
+
+static PATTERN_SYNTAX_ERROR: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+static PATTERN_ERROR: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+static WALK_ERROR: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+
+/// An exception class deriving from `bases`, which `create_exception!`
+/// can't declare with more than one
+fn exception_type<'py>(
+    py: Python<'py>,
+    cell: &'static GILOnceCell<Py<PyType>>,
+    name: &str,
+    bases: impl FnOnce(Python<'py>) -> Vec<Bound<'py, PyType>>,
+    doc: &str,
+) -> Bound<'py, PyType> {
+    cell.get_or_init(py, || {
+        let namespace = PyDict::new(py);
+        let build = || -> PyResult<Py<PyType>> {
+            namespace.set_item("__module__", "pathvein._pathvein_rs")?;
+            namespace.set_item("__doc__", doc)?;
+            let bases = pyo3::types::PyTuple::new(py, bases(py))?;
+            let class = py.get_type::<PyType>().call1((name, bases, namespace))?;
+            Ok(class.downcast_into::<PyType>()?.unbind())
+        };
+        build().expect("creating an exception class cannot fail")
+    })
+    .bind(py)
+    .clone()
+}
+
+/// ``PatternSyntaxError(PathveinError, ValueError)``
+pub fn pattern_syntax_error_type(py: Python<'_>) -> Bound<'_, PyType> {
+    exception_type(
+        py,
+        &PATTERN_SYNTAX_ERROR,
+        "PatternSyntaxError",
+        |py| {
+            vec![
+                py.get_type::<PathveinError>(),
+                py.get_type::<PyValueError>(),
+            ]
+        },
+        "A pattern, glob or pattern specification is malformed.",
+    )
+}
+
+/// ``PatternError(PathveinError, ValueError)``
+pub fn pattern_error_type(py: Python<'_>) -> Bound<'_, PyType> {
+    exception_type(
+        py,
+        &PATTERN_ERROR,
+        "PatternError",
+        |py| {
+            vec![
+                py.get_type::<PathveinError>(),
+                py.get_type::<PyValueError>(),
+            ]
+        },
+        "A pattern set failed to build, from a malformed pattern or a\n\
+         size limit.\n\n\
+         ``pattern_index`` and ``pattern`` name the offending pattern, or are\n\
+         None when the failure is not down to a single pattern.",
+    )
+}
+
+/// ``WalkError(PathveinError, OSError)``
+pub fn walk_error_type(py: Python<'_>) -> Bound<'_, PyType> {
+    exception_type(
+        py,
+        &WALK_ERROR,
+        "WalkError",
+        |py| vec![py.get_type::<PathveinError>(), py.get_type::<PyOSError>()],
+        "A directory to scan can't be read.\n\n\
+         ``path`` is the directory.",
+    )
+}
+
+/// Build a PatternSyntaxError
+pub fn pattern_syntax_error(message: impl Into<String>) -> PyErr {
+    Python::with_gil(|py| PyErr::from_type(pattern_syntax_error_type(py), message.into()))
+}
+
+/// Build a PatternError, attaching the offending pattern when known
+pub fn pattern_error(message: String, culprit: Option<(usize, &str)>) -> PyErr {
+    Python::with_gil(|py| {
+        let err = PyErr::from_type(pattern_error_type(py), message);
+        let value = err.value(py);
+        let (index, pattern) = culprit.unzip();
+        // Setting attributes on a fresh exception instance cannot fail
+        let _ = value.setattr("pattern_index", index);
+        let _ = value.setattr("pattern", pattern);
+        err
+    })
+}
+
+/// Build a WalkError for `path`
+pub fn walk_error(path: &Path, message: String) -> PyErr {
+    Python::with_gil(|py| {
+        let err = PyErr::from_type(walk_error_type(py), message);
+        let _ = err.value(py).setattr("path", path.to_string_lossy());
+        err
+    })
+}
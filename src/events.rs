@@ -0,0 +1,133 @@
+use pyo3::prelude::*;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::explain::MatchFailure;
+use crate::walk::error_path;
+
+/// Something that happened during a scan, passed to the ``events`` callback
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct ScanEvent {
+    /// ``"directory_entered"``, ``"match_found"``, ``"requirement_failed"``
+    /// or ``"io_error"``
+    #[pyo3(get)]
+    pub kind: &'static str,
+    /// Directory the event is about; None for an I/O error without a path
+    #[pyo3(get)]
+    pub path: Option<String>,
+    /// Pattern matched or failed, for ``match_found`` and
+    /// ``requirement_failed``
+    #[pyo3(get)]
+    pub pattern_index: Option<usize>,
+    #[pyo3(get)]
+    pub pattern_name: Option<String>,
+    /// Requirements the directory fails, for ``requirement_failed``
+    #[pyo3(get)]
+    pub failures: Vec<MatchFailure>,
+    /// Error description, for ``io_error``
+    #[pyo3(get)]
+    pub message: Option<String>,
+}
+
+#[pymethods]
+impl ScanEvent {
+    fn __repr__(&self) -> String {
+        let mut repr = format!("ScanEvent(kind='{}'", self.kind);
+        if let Some(path) = &self.path {
+            repr.push_str(&format!(", path='{}'", path));
+        }
+        if let Some(pattern_index) = self.pattern_index {
+            repr.push_str(&format!(", pattern_index={}", pattern_index));
+        }
+        if let Some(message) = &self.message {
+            repr.push_str(&format!(", message='{}'", message));
+        }
+        repr.push(')');
+        repr
+    }
+}
+
+impl ScanEvent {
+    fn new(kind: &'static str, path: Option<&Path>) -> Self {
+        ScanEvent {
+            kind,
+            path: path.map(|path| path.to_string_lossy().into_owned()),
+            pattern_index: None,
+            pattern_name: None,
+            failures: Vec::new(),
+            message: None,
+        }
+    }
+
+    pub fn directory_entered(dir: &Path) -> Self {
+        ScanEvent::new("directory_entered", Some(dir))
+    }
+
+    pub fn match_found(dir: &Path, pattern_index: usize, pattern_name: Option<String>) -> Self {
+        ScanEvent {
+            pattern_index: Some(pattern_index),
+            pattern_name,
+            ..ScanEvent::new("match_found", Some(dir))
+        }
+    }
+
+    pub fn requirement_failed(
+        dir: &Path,
+        pattern_index: usize,
+        pattern_name: Option<String>,
+        failures: Vec<MatchFailure>,
+    ) -> Self {
+        ScanEvent {
+            pattern_index: Some(pattern_index),
+            pattern_name,
+            failures,
+            ..ScanEvent::new("requirement_failed", Some(dir))
+        }
+    }
+
+    pub fn io_error(error: &ignore::Error) -> Self {
+        ScanEvent {
+            message: Some(error.to_string()),
+            ..ScanEvent::new("io_error", error_path(error))
+        }
+    }
+}
+
+/// The ``events`` callback of a scan, shared by walker threads
+pub struct EventSink {
+    callback: PyObject,
+    error: Mutex<Option<PyErr>>,
+}
+
+impl EventSink {
+    pub fn new(callback: PyObject) -> Self {
+        EventSink {
+            callback,
+            error: Mutex::new(None),
+        }
+    }
+
+    /// Pass `event` to the callback; returns false once the callback has
+    /// raised, and drops the event
+    pub fn emit(&self, event: ScanEvent) -> bool {
+        if self.error.lock().unwrap().is_some() {
+            return false;
+        }
+        match Python::with_gil(|py| self.callback.call1(py, (event,)).map(|_| ())) {
+            Ok(()) => true,
+            Err(err) => {
+                self.error.lock().unwrap().get_or_insert(err);
+                false
+            }
+        }
+    }
+
+    /// The error the callback raised, if any
+    pub fn finish(&self) -> PyResult<()> {
+        match self.error.lock().unwrap().take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use pyo3::prelude::*;
+use serde::Serialize;
+use std::path::Path;
+
+/// One requirement of a pattern that a directory fails
+///
+/// Returned by ``FileStructurePattern.explain`` and carried by partial
+/// scan results in ``ScanResult.failures``.
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug, Serialize)]
+pub struct MatchFailure {
+    /// What kind of requirement failed: ``"directory_name"``,
+    /// ``"missing_file"``, ``"excluded"``, ``"empty_directory"``,
+    /// ``"extra_file"``, ``"file_count"``, ``"constraint"``, ``"size"``,
+    /// ``"content"``, ``"age"``, ``"total_size"``, ``"missing_directory"``,
+    /// ``"any_of"`` or ``"none_of"``
+    #[pyo3(get)]
+    pub kind: &'static str,
+    /// The glob, directory name or pattern the requirement was written as
+    #[pyo3(get)]
+    pub requirement: String,
+    /// Directory the requirement was checked in; a nested pattern's
+    /// failures are reported in the subdirectory that came closest
+    #[pyo3(get)]
+    pub path: String,
+    /// Human-readable description of the failure
+    #[pyo3(get)]
+    pub message: String,
+}
+
+#[pymethods]
+impl MatchFailure {
+    fn __repr__(&self) -> String {
+        format!(
+            "MatchFailure(kind='{}', requirement='{}', path='{}')",
+            self.kind, self.requirement, self.path
+        )
+    }
+
+    fn __str__(&self) -> String {
+        format!("{}: {}", self.path, self.message)
+    }
+}
+
+impl MatchFailure {
+    pub fn new(kind: &'static str, requirement: &str, dir: &Path, message: String) -> Self {
+        MatchFailure {
+            kind,
+            requirement: requirement.to_string(),
+            path: dir.to_string_lossy().into_owned(),
+            message,
+        }
+    }
+}
+
+/// `min`/`max` limits described for a failure message
+pub fn describe_limits(min: Option<usize>, max: Option<usize>) -> String {
+    match (min, max) {
+        (Some(min), Some(max)) => format!("between {} and {}", min, max),
+        (Some(min), None) => format!("at least {}", min),
+        (None, Some(max)) => format!("at most {}", max),
+        (None, None) => "any number".to_string(),
+    }
+}
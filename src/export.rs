@@ -0,0 +1,227 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::walk::{scan_parallel, ScanOutput, ScanResult, ScanRoots};
+
+/// File formats scan results can be written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ExportFormat {
+    Json,
+    Ndjson,
+    Csv,
+}
+
+impl ExportFormat {
+    fn parse(format: &str) -> PyResult<Self> {
+        match format {
+            "json" => Ok(ExportFormat::Json),
+            "ndjson" => Ok(ExportFormat::Ndjson),
+            "csv" => Ok(ExportFormat::Csv),
+            other => Err(PyValueError::new_err(format!(
+                "Unknown export format '{}': expected 'json', 'ndjson' or 'csv'",
+                other
+            ))),
+        }
+    }
+
+    /// The explicit format, or the one implied by the output's extension
+    fn resolve(format: Option<&str>, output: &Path) -> PyResult<Self> {
+        if let Some(format) = format {
+            return Self::parse(format);
+        }
+        match output.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Ok(ExportFormat::Json),
+            Some("ndjson" | "jsonl") => Ok(ExportFormat::Ndjson),
+            Some("csv") => Ok(ExportFormat::Csv),
+            _ => Err(PyValueError::new_err(format!(
+                "Cannot infer export format from '{}': pass format='json', 'ndjson' or 'csv'",
+                output.display()
+            ))),
+        }
+    }
+}
+
+/// Columns of the CSV export, in order
+const CSV_HEADER: &str =
+    "path,pattern_index,pattern_name,branch,branch_name,optional_files,optional_directories,matched_files,score,root,aliases,runners_up,bindings,captures";
+
+/// Write scan results to a file
+///
+/// In CSV, ``optional_files``, ``optional_directories``, ``aliases``,
+/// ``runners_up`` and ``captures`` are joined with ``;``, and
+/// ``matched_files`` and ``bindings`` are JSON objects.
+///
+/// Args:
+///     results: ScanResults or a list of ScanResult objects
+///     output: File to write; replaced if it exists
+///     format: ``"json"`` for one array, ``"ndjson"`` for one object per
+///         line or ``"csv"`` for one row per result. Inferred from the
+///         output's extension (.json, .ndjson/.jsonl, .csv) when omitted.
+///
+/// Returns:
+///     Number of results written
+///
+/// Raises:
+///     ValueError: If the format is unknown or the file can't be written
+#[pyfunction]
+#[pyo3(signature = (results, output, format=None))]
+pub fn export_results(
+    py: Python<'_>,
+    results: Vec<PyRef<'_, ScanResult>>,
+    output: &str,
+    format: Option<&str>,
+) -> PyResult<usize> {
+    let format = ExportFormat::resolve(format, Path::new(output))?;
+    let results: Vec<&ScanResult> = results.iter().map(|result| &**result).collect();
+    py.allow_threads(|| write_results(&results, Path::new(output), format))
+}
+
+/// Scan a directory tree and write the matches straight to a file
+///
+/// Equivalent to ``export_results(scan_parallel(...), output)`` without
+/// creating a Python object per result, which matters for scans with
+/// millions of matches.
+///
+/// Args:
+///     path: Root directory to scan
+///     pattern_jsons: List of JSON-serialized FileStructurePattern objects
+///     output: File to write; replaced if it exists
+///     format: ``"json"``, ``"ndjson"`` or ``"csv"``, as for
+///         ``export_results``
+///     max_depth: Optional maximum depth to traverse
+///     follow_links: Whether to follow symbolic links
+///     first_match: Attribute each directory only to the first pattern, in
+///         list order, that matches it
+///     overlap: ``"all"``, ``"outermost"`` or ``"innermost"``, as for
+///         ``scan_parallel``
+///     deterministic: Write results sorted, as for ``scan_parallel``, so
+///         the file is the same on every run over the same tree
+///         (default: False)
+///
+/// Returns:
+///     Number of results written
+///
+/// Raises:
+///     ValueError: If a pattern, the format or overlap is invalid, or the
+///         file can't be written
+#[pyfunction]
+#[pyo3(signature = (
+    path,
+    pattern_jsons,
+    output,
+    format=None,
+    max_depth=None,
+    follow_links=false,
+    first_match=false,
+    overlap="all",
+    deterministic=false,
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn scan_to_file(
+    py: Python<'_>,
+    path: String,
+    pattern_jsons: Vec<String>,
+    output: &str,
+    format: Option<&str>,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    first_match: bool,
+    overlap: &str,
+    deterministic: bool,
+) -> PyResult<usize> {
+    let format = ExportFormat::resolve(format, Path::new(output))?;
+    let results = scan_parallel(
+        py,
+        ScanRoots::One(path),
+        pattern_jsons,
+        max_depth,
+        follow_links,
+        first_match,
+        overlap,
+        None,
+        0.0,
+        None,
+        None,
+        false,
+        true,
+        false,
+        None,
+        false,
+        false,
+        false,
+        None,
+        deterministic,
+        false,
+        false,
+        None,
+        false,
+    )?;
+    let ScanOutput::Results(results) = results else {
+        unreachable!("scan_parallel returns results unless stats_only or stream is set")
+    };
+    let results: Vec<&ScanResult> = results.results.iter().collect();
+    py.allow_threads(|| write_results(&results, Path::new(output), format))
+}
+
+fn write_results(results: &[&ScanResult], output: &Path, format: ExportFormat) -> PyResult<usize> {
+    let write_error = |e: std::io::Error| {
+        PyValueError::new_err(format!("Cannot write {}: {}", output.display(), e))
+    };
+    let file = File::create(output).map_err(write_error)?;
+    let mut writer = BufWriter::new(file);
+    match format {
+        ExportFormat::Json => {
+            serde_json::to_writer(&mut writer, results).map_err(std::io::Error::from)
+        }
+        ExportFormat::Ndjson => results.iter().try_for_each(|result| {
+            serde_json::to_writer(&mut writer, result)?;
+            writer.write_all(b"\n")
+        }),
+        ExportFormat::Csv => write_csv(&mut writer, results),
+    }
+    .and_then(|()| writer.flush())
+    .map_err(write_error)?;
+    Ok(results.len())
+}
+
+fn write_csv(writer: &mut impl Write, results: &[&ScanResult]) -> std::io::Result<()> {
+    writeln!(writer, "{}", CSV_HEADER)?;
+    for result in results {
+        let row = [
+            result.path.clone(),
+            result.pattern_index.to_string(),
+            result.pattern_name.clone().unwrap_or_default(),
+            result.branch.map(|idx| idx.to_string()).unwrap_or_default(),
+            result.branch_name.clone().unwrap_or_default(),
+            result.optional_files.join(";"),
+            result.optional_directories.join(";"),
+            serde_json::to_string(&result.matched_files)?,
+            result.score.to_string(),
+            result.root.clone(),
+            result.aliases.join(";"),
+            result
+                .runners_up
+                .iter()
+                .map(|idx| idx.to_string())
+                .collect::<Vec<_>>()
+                .join(";"),
+            serde_json::to_string(&result.bindings)?,
+            result.captures.join(";"),
+        ];
+        let row: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        writeln!(writer, "{}", row.join(","))?;
+    }
+    Ok(())
+}
+
+/// Quote a CSV field if it contains a delimiter, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -1,25 +1,1424 @@
-use serde::{Deserialize, Serialize};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use regex::{Regex, RegexBuilder};
+use serde::de::{self, value::MapAccessDeserializer, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
-use crate::pattern::PatternMatcher;
+use crate::capture;
+use crate::casefold::CaseFold;
+use crate::errors::pattern_syntax_error;
+use crate::explain::{describe_limits, MatchFailure};
+use crate::filetype;
+use crate::inherit;
+use crate::memory::MemoryTree;
+use crate::migrate;
+use crate::pattern::{MatcherOptions, PatternMatcher};
+use crate::walk::{read_subtree, WalkedTree};
 
 /// Rust representation of FileStructurePattern
 ///
 /// This mirrors the Python FileStructurePattern class but can be
 /// serialized/deserialized for efficient FFI transfer.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct FileStructurePattern {
+    #[pyo3(get, set)]
+    #[serde(default = "any_directory")]
     pub directory_name: String,
+    #[pyo3(get, set)]
+    #[serde(default)]
     pub files: Vec<String>,
+    #[pyo3(get, set)]
+    #[serde(default, deserialize_with = "nested_patterns")]
     pub directories: Vec<FileStructurePattern>,
+    #[pyo3(get, set)]
+    #[serde(default)]
     pub optional_files: Vec<String>,
+    #[pyo3(get, set)]
+    #[serde(default, deserialize_with = "nested_patterns")]
     pub optional_directories: Vec<FileStructurePattern>,
+    /// Label reported in scan results, so callers can route on it
+    #[pyo3(get, set)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern_name: Option<String>,
+    /// Globs that must not match any file
+    #[pyo3(get, set)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded_files: Vec<String>,
+    /// Globs that must not match any subdirectory name
+    #[pyo3(get, set)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded_directories: Vec<String>,
+    /// Globs that must each match at least one subdirectory with no
+    /// entries at all, e.g. ``logs`` that a run must create but not fill
+    #[pyo3(get, set)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub empty_directories: Vec<String>,
+    /// Every file in the directory must match a glob in ``files``,
+    /// ``optional_files`` or ``file_constraints``
+    #[pyo3(get, set)]
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub no_extra_files: bool,
+    /// Fewest files the directory may hold
+    #[pyo3(get, set)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_file_count: Option<usize>,
+    /// Most files the directory may hold
+    #[pyo3(get, set)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_count: Option<usize>,
+    /// Limits on the entries matching each glob
+    #[pyo3(get, set)]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub file_constraints: BTreeMap<String, FileConstraint>,
+    /// Seconds within which the directory itself must have been modified
+    #[pyo3(get)]
+    #[serde(
+        default,
+        deserialize_with = "duration_limit",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub newer_than: Option<u64>,
+    /// Seconds the directory itself must have gone unmodified
+    #[pyo3(get)]
+    #[serde(
+        default,
+        deserialize_with = "duration_limit",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub older_than: Option<u64>,
+    /// Seconds no file in the directory may have been modified within, so
+    /// directories still being written are skipped
+    #[pyo3(get)]
+    #[serde(
+        default,
+        deserialize_with = "duration_limit",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub stable_for: Option<u64>,
+    /// Fewest bytes the directory may hold, counting every file beneath it
+    #[pyo3(get)]
+    #[serde(
+        default,
+        deserialize_with = "size_limit",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub min_total_size: Option<u64>,
+    /// Most bytes the directory may hold, counting every file beneath it
+    #[pyo3(get)]
+    #[serde(
+        default,
+        deserialize_with = "size_limit",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_total_size: Option<u64>,
+    /// Alternative patterns for the same directory, at least one of which
+    /// must match; the first that does is reported as the match's branch
+    #[pyo3(get, set)]
+    #[serde(
+        default,
+        alias = "anyOf",
+        deserialize_with = "nested_patterns",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub any_of: Vec<FileStructurePattern>,
+    /// Patterns the same directory must also match
+    #[pyo3(get, set)]
+    #[serde(
+        default,
+        alias = "allOf",
+        deserialize_with = "nested_patterns",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub all_of: Vec<FileStructurePattern>,
+    /// Patterns the same directory must not match
+    #[pyo3(get, set)]
+    #[serde(
+        default,
+        alias = "not",
+        deserialize_with = "nested_patterns",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub none_of: Vec<FileStructurePattern>,
+    /// Patterns this one builds on, by ``pattern_name`` or spec file path;
+    /// see ``resolve``
+    #[pyo3(get, set)]
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub extends: Vec<String>,
+    /// Match every name in this pattern, and the patterns nested in it,
+    /// regardless of letter case
+    #[pyo3(get, set)]
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub case_insensitive: bool,
+    /// Let symbolic links to directories satisfy this pattern's directory
+    /// requirements, and those of the patterns nested in it, whether or
+    /// not the walk follows links
+    #[pyo3(get, set)]
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub follow_symlinked_dirs: bool,
+    /// Rank among the patterns matching the same directory in a scan with
+    /// ``best_match``; the highest wins, and unset counts as 0
+    #[pyo3(get, set)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i64>,
+    /// Names for requirements, each mapped to a glob in ``files`` or
+    /// ``optional_files`` or the ``directory_name`` of a nested pattern;
+    /// a match binds each name to the paths that satisfied it
+    #[pyo3(get, set)]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub roles: BTreeMap<String, String>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl Default for FileStructurePattern {
+    /// A pattern matching any directory, as ``FileStructurePattern()``
+    fn default() -> Self {
+        FileStructurePattern {
+            directory_name: any_directory(),
+            files: Vec::new(),
+            directories: Vec::new(),
+            optional_files: Vec::new(),
+            optional_directories: Vec::new(),
+            pattern_name: None,
+            excluded_files: Vec::new(),
+            excluded_directories: Vec::new(),
+            empty_directories: Vec::new(),
+            no_extra_files: false,
+            min_file_count: None,
+            max_file_count: None,
+            file_constraints: BTreeMap::new(),
+            newer_than: None,
+            older_than: None,
+            stable_for: None,
+            min_total_size: None,
+            max_total_size: None,
+            any_of: Vec::new(),
+            all_of: Vec::new(),
+            none_of: Vec::new(),
+            extends: Vec::new(),
+            case_insensitive: false,
+            follow_symlinked_dirs: false,
+            priority: None,
+            roles: BTreeMap::new(),
+        }
+    }
+}
+
+/// Named patterns `extends` can refer to, as given from Python
+#[derive(FromPyObject)]
+pub enum PatternLibrary {
+    Named(HashMap<String, FileStructurePattern>),
+    Listed(Vec<FileStructurePattern>),
+}
+
+impl PatternLibrary {
+    fn into_map(self) -> HashMap<String, FileStructurePattern> {
+        match self {
+            PatternLibrary::Named(named) => named,
+            PatternLibrary::Listed(listed) => listed
+                .into_iter()
+                .filter_map(|pattern| Some((pattern.pattern_name.clone()?, pattern)))
+                .collect(),
+        }
+    }
+}
+
+/// A single string or a list of strings
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(one) => vec![one],
+        OneOrMany::Many(many) => many,
+    })
+}
+
+/// An age given as seconds or as text like ``"15m"``
+#[derive(FromPyObject, Deserialize)]
+#[serde(untagged)]
+pub enum DurationLimit {
+    Seconds(u64),
+    Text(String),
+}
+
+impl DurationLimit {
+    fn seconds(self) -> Result<u64, String> {
+        match self {
+            DurationLimit::Seconds(seconds) => Ok(seconds),
+            DurationLimit::Text(text) => parse_duration(&text),
+        }
+    }
+
+    fn py_seconds(limit: Option<DurationLimit>) -> PyResult<Option<u64>> {
+        limit
+            .map(DurationLimit::seconds)
+            .transpose()
+            .map_err(PyValueError::new_err)
+    }
+}
+
+fn duration_limit<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<DurationLimit>::deserialize(deserializer)?
+        .map(DurationLimit::seconds)
+        .transpose()
+        .map_err(de::Error::custom)
+}
+
+/// Seconds in a duration like ``90``, ``"15m"``, ``"2h"`` or ``"7d"``
+///
+/// Units are ``s``, ``m``, ``h``, ``d`` and ``w``; a bare number is seconds.
+pub fn parse_duration(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration '{}': expected a number of seconds", text))?;
+    let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3_600.0,
+        "d" => 86_400.0,
+        "w" => 604_800.0,
+        _ => {
+            return Err(format!(
+                "Invalid duration '{}': unknown unit '{}'",
+                text,
+                unit.trim()
+            ))
+        }
+    };
+    Ok((number * multiplier).round() as u64)
+}
+
+/// Limits on the entries matching one glob of a FileStructurePattern
+///
+/// Count limits apply to the number of matching entries; size limits apply
+/// to each matching file and need a stat per file, so they are only read
+/// for patterns that set them.
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FileConstraint {
+    #[pyo3(get, set)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_count: Option<usize>,
+    #[pyo3(get, set)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_count: Option<usize>,
+    /// Smallest size in bytes each matching file may have
+    #[pyo3(get)]
+    #[serde(
+        default,
+        deserialize_with = "size_limit",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub min_size: Option<u64>,
+    /// Largest size in bytes each matching file may have
+    #[pyo3(get)]
+    #[serde(
+        default,
+        deserialize_with = "size_limit",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_size: Option<u64>,
+    /// Text each matching file must contain within its first
+    /// ``CONTENT_READ_LIMIT`` bytes
+    #[pyo3(get, set)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_contains: Option<String>,
+    /// Bytes each matching file must start with, e.g. a format's magic
+    /// number; written as hex in specs
+    #[serde(
+        default,
+        deserialize_with = "hex_bytes",
+        serialize_with = "to_hex",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub first_bytes: Option<Vec<u8>>,
+    /// Type each matching file must be, detected from its leading bytes
+    /// rather than its extension: a MIME type such as ``"image/tiff"`` or a
+    /// short name such as ``"gzip"``; written as ``type`` in specs
+    #[pyo3(get, set)]
+    #[serde(
+        default,
+        rename = "type",
+        alias = "file_type",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub file_type: Option<String>,
+}
+
+/// Most bytes of a file read to check ``content_contains``
+pub const CONTENT_READ_LIMIT: usize = 1 << 20;
+
+fn hex_bytes<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|hex| parse_hex(&hex))
+        .transpose()
+        .map_err(de::Error::custom)
+}
+
+fn to_hex<S: serde::Serializer>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+    let hex: Option<String> = bytes
+        .as_ref()
+        .map(|bytes| bytes.iter().map(|byte| format!("{:02x}", byte)).collect());
+    hex.serialize(serializer)
+}
+
+/// Bytes written as hex digits, e.g. ``"89504e47"``, optionally spaced
+fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let invalid = || {
+        format!(
+            "Invalid first_bytes '{}': expected pairs of hex digits",
+            text
+        )
+    };
+    if digits.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair: String = pair.iter().collect();
+            u8::from_str_radix(&pair, 16).map_err(|_| invalid())
+        })
+        .collect()
+}
+
+/// A size given as a number of bytes or as text like ``"1MB"``
+#[derive(FromPyObject, Deserialize)]
+#[serde(untagged)]
+pub enum SizeLimit {
+    Bytes(u64),
+    Text(String),
+}
+
+impl SizeLimit {
+    fn bytes(self) -> Result<u64, String> {
+        match self {
+            SizeLimit::Bytes(bytes) => Ok(bytes),
+            SizeLimit::Text(text) => parse_size(&text),
+        }
+    }
+
+    fn py_bytes(limit: Option<SizeLimit>) -> PyResult<Option<u64>> {
+        limit
+            .map(SizeLimit::bytes)
+            .transpose()
+            .map_err(PyValueError::new_err)
+    }
+}
+
+fn size_limit<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<SizeLimit>::deserialize(deserializer)?
+        .map(SizeLimit::bytes)
+        .transpose()
+        .map_err(de::Error::custom)
+}
+
+/// Bytes in a size like ``512``, ``"1.5MB"`` or ``"4 KiB"``
+///
+/// ``KB``/``MB``/``GB``/``TB`` are powers of 1000 and ``KiB``/``MiB``/
+/// ``GiB``/``TiB`` powers of 1024; units are case-insensitive.
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size '{}': expected a number of bytes", text))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" | "k" => 1_000,
+        "mb" | "m" => 1_000_000,
+        "gb" | "g" => 1_000_000_000,
+        "tb" | "t" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => {
+            return Err(format!(
+                "Invalid size '{}': unknown unit '{}'",
+                text,
+                unit.trim()
+            ))
+        }
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+#[pymethods]
+impl FileConstraint {
+    /// Create a FileConstraint
+    ///
+    /// Args:
+    ///     min_count: Fewest entries that must match the glob
+    ///     max_count: Most entries that may match the glob
+    ///     min_size: Smallest size each matching file may have, in bytes
+    ///         or as text like ``"1MB"`` or ``"4KiB"``
+    ///     max_size: Largest size each matching file may have
+    ///     content_contains: Text each matching file must contain within
+    ///         its first MiB, e.g. ``'"schema_version"'``
+    ///     first_bytes: Bytes each matching file must start with, e.g.
+    ///         ``b"\x89PNG"``
+    ///     file_type: Type each matching file must be, by magic bytes,
+    ///         e.g. ``"image/tiff"`` or ``"gzip"``, as ``detect_types``
+    ///         names it
+    ///
+    /// Returns:
+    ///     FileConstraint instance
+    ///
+    /// Raises:
+    ///     ValueError: If a size is not a valid size
+    #[new]
+    #[pyo3(signature = (
+        min_count=None,
+        max_count=None,
+        min_size=None,
+        max_size=None,
+        content_contains=None,
+        first_bytes=None,
+        file_type=None,
+    ))]
+    pub fn py_new(
+        min_count: Option<usize>,
+        max_count: Option<usize>,
+        min_size: Option<SizeLimit>,
+        max_size: Option<SizeLimit>,
+        content_contains: Option<String>,
+        first_bytes: Option<Vec<u8>>,
+        file_type: Option<String>,
+    ) -> PyResult<Self> {
+        if let Some(name) = &file_type {
+            filetype::lookup(name)
+                .ok_or_else(|| PyValueError::new_err(filetype::unknown_type(name)))?;
+        }
+        Ok(FileConstraint {
+            min_count,
+            max_count,
+            min_size: SizeLimit::py_bytes(min_size)?,
+            max_size: SizeLimit::py_bytes(max_size)?,
+            content_contains,
+            first_bytes,
+            file_type,
+        })
+    }
+
+    #[getter]
+    fn first_bytes<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyBytes>> {
+        self.first_bytes
+            .as_deref()
+            .map(|bytes| PyBytes::new(py, bytes))
+    }
+
+    #[setter]
+    fn set_first_bytes(&mut self, first_bytes: Option<Vec<u8>>) {
+        self.first_bytes = first_bytes;
+    }
+
+    #[setter]
+    fn set_min_size(&mut self, min_size: Option<SizeLimit>) -> PyResult<()> {
+        self.min_size = SizeLimit::py_bytes(min_size)?;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_max_size(&mut self, max_size: Option<SizeLimit>) -> PyResult<()> {
+        self.max_size = SizeLimit::py_bytes(max_size)?;
+        Ok(())
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self == other
+    }
+
+    fn __repr__(&self) -> String {
+        let show = |limit: Option<u64>| limit.map_or("None".to_string(), |n| n.to_string());
+        let mut repr = format!(
+            "FileConstraint(min_count={}, max_count={}",
+            show(self.min_count.map(|n| n as u64)),
+            show(self.max_count.map(|n| n as u64))
+        );
+        if self.has_size_limits() {
+            repr += &format!(
+                ", min_size={}, max_size={}",
+                show(self.min_size),
+                show(self.max_size)
+            );
+        }
+        if let Some(text) = &self.content_contains {
+            repr += &format!(", content_contains={:?}", text);
+        }
+        if let Some(bytes) = &self.first_bytes {
+            let escaped: String = bytes.escape_ascii().map(char::from).collect();
+            repr += &format!(", first_bytes=b\"{}\"", escaped);
+        }
+        if let Some(name) = &self.file_type {
+            repr += &format!(", file_type='{}'", name);
+        }
+        repr + ")"
+    }
+}
+
+impl FileConstraint {
+    fn allows_count(&self, count: usize) -> bool {
+        within(count, self.min_count, self.max_count)
+    }
+
+    fn has_size_limits(&self) -> bool {
+        self.min_size.is_some() || self.max_size.is_some()
+    }
+
+    fn allows_size(&self, size: u64) -> bool {
+        within(size, self.min_size, self.max_size)
+    }
+
+    fn has_content_limits(&self) -> bool {
+        self.content_contains.is_some() || self.first_bytes.is_some() || self.file_type.is_some()
+    }
+
+    /// Bytes of a file to read to check the content limits
+    fn content_read_len(&self) -> usize {
+        if self.content_contains.is_some() {
+            return CONTENT_READ_LIMIT;
+        }
+        let prefix = self.first_bytes.as_ref().map_or(0, Vec::len);
+        let sniff = if self.file_type.is_some() {
+            filetype::SNIFF_LEN
+        } else {
+            0
+        };
+        prefix.max(sniff)
+    }
+
+    /// Whether a file starting with `head`, read up to `content_read_len`,
+    /// meets the content limits
+    fn allows_content(&self, head: &[u8]) -> bool {
+        let starts = self
+            .first_bytes
+            .as_ref()
+            .map_or(true, |prefix| head.starts_with(prefix));
+        let contains = self.content_contains.as_ref().map_or(true, |text| {
+            let needle = text.as_bytes();
+            needle.is_empty() || head.windows(needle.len()).any(|window| window == needle)
+        });
+        // Compiling the pattern has checked the type is known
+        let typed = self
+            .file_type
+            .as_deref()
+            .and_then(filetype::lookup)
+            .map_or(true, |file_type| file_type.matches(head));
+        starts && contains && typed
+    }
+}
+
+fn within<T: PartialOrd>(value: T, min: Option<T>, max: Option<T>) -> bool {
+    min.map_or(true, |min| value >= min) && max.map_or(true, |max| value <= max)
+}
+
+fn any_directory() -> String {
+    "*".to_string()
+}
+
+/// A nested pattern as written by Python's `FileStructurePattern.to_json`
+/// (a JSON string) or inline as an object
+///
+/// Deserialized by hand rather than as an untagged enum so errors inside
+/// an inline pattern keep their own message and key path.
+struct NestedPattern(FileStructurePattern);
+
+impl<'de> Deserialize<'de> for NestedPattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct NestedVisitor;
+
+        impl<'de> Visitor<'de> for NestedVisitor {
+            type Value = NestedPattern;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a pattern object or a pattern JSON string")
+            }
+
+            fn visit_str<E: de::Error>(self, json: &str) -> Result<NestedPattern, E> {
+                FileStructurePattern::from_json(json)
+                    .map(NestedPattern)
+                    .map_err(E::custom)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<NestedPattern, A::Error> {
+                FileStructurePattern::deserialize(MapAccessDeserializer::new(map))
+                    .map(NestedPattern)
+            }
+        }
+
+        deserializer.deserialize_any(NestedVisitor)
+    }
+}
+
+fn nested_patterns<'de, D>(deserializer: D) -> Result<Vec<FileStructurePattern>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Vec::<NestedPattern>::deserialize(deserializer)?
+        .into_iter()
+        .map(|NestedPattern(pattern)| pattern)
+        .collect())
+}
+
+/// A spec with the current layout ``version`` written first
+#[derive(Serialize)]
+struct Versioned<'a> {
+    version: u64,
+    #[serde(flatten)]
+    pattern: &'a FileStructurePattern,
+}
+
+impl<'a> Versioned<'a> {
+    fn new(pattern: &'a FileStructurePattern) -> Self {
+        Versioned {
+            version: migrate::SPEC_VERSION,
+            pattern,
+        }
+    }
+}
+
+#[pymethods]
+impl FileStructurePattern {
+    /// Create a FileStructurePattern
+    ///
+    /// ``directory_name`` and file globs may instead be regular expressions
+    /// with a ``regex:`` prefix, e.g. ``regex:^run_\d{8}$``. A regex is
+    /// searched for in the name, so anchor it to match the whole name.
+    ///
+    /// Args:
+    ///     directory_name: Glob the directory's own name must match
+    ///         (default: "*"). In a nested pattern, a leading ``**/``, as
+    ///         in ``**/metadata``, lets it match a subdirectory at any
+    ///         depth instead of only a direct child.
+    ///     files: Globs that must each match at least one file
+    ///     directories: Sub-patterns that must each match a subdirectory
+    ///     optional_files: Globs for files that may be present
+    ///     optional_directories: Sub-patterns for subdirectories that may
+    ///         be present
+    ///     pattern_name: Optional label reported as ``ScanResult.pattern_name``
+    ///     excluded_files: Globs that must not match any file, e.g.
+    ///         ``.incomplete``
+    ///     excluded_directories: Globs that must not match any
+    ///         subdirectory name, e.g. ``tmp``
+    ///     empty_directories: Globs that must each match an empty
+    ///         subdirectory, e.g. ``logs`` created but not yet written to
+    ///     no_extra_files: Every file must match a glob in ``files``,
+    ///         ``optional_files`` or ``file_constraints``, so stray files
+    ///         rule the directory out (default: False)
+    ///     min_file_count: Fewest files the directory may hold
+    ///     max_file_count: Most files the directory may hold
+    ///     file_constraints: FileConstraint per glob, e.g.
+    ///         ``{"*.fastq.gz": FileConstraint(min_count=4)}``
+    ///     newer_than: The directory itself must have been modified within
+    ///         this age, in seconds or as text like ``"2h"``
+    ///     older_than: The directory itself must not have been modified
+    ///         within this age
+    ///     stable_for: No file in the directory may have been modified
+    ///         within this age, e.g. ``"15m"`` to skip uploads in progress
+    ///     min_total_size: Fewest bytes the directory may hold, counting
+    ///         every file beneath it, in bytes or as text like ``"10MB"``;
+    ///         rules out stub or placeholder directories
+    ///     max_total_size: Most bytes the directory may hold, counting
+    ///         every file beneath it
+    ///     any_of: Patterns for the same directory, at least one of which
+    ///         must match (``anyOf`` in specs); the first that does is
+    ///         reported as ``ScanResult.branch``
+    ///     all_of: Patterns the same directory must also match (``allOf``)
+    ///     none_of: Patterns the same directory must not match (``not``)
+    ///     extends: ``pattern_name``s or spec file paths of patterns this one
+    ///         builds on; see ``resolve``
+    ///     case_insensitive: Match names in this pattern and its nested
+    ///         patterns regardless of case, e.g. ``README.md`` matches
+    ///         ``readme.MD`` (default: False)
+    ///     follow_symlinked_dirs: Let symbolic links to directories
+    ///         satisfy ``directories`` and ``optional_directories`` here and
+    ///         in nested patterns, reading them even when the scan doesn't
+    ///         follow links; otherwise they never do (default: False)
+    ///     priority: Rank among patterns matching the same directory, for
+    ///         scans with ``best_match``; higher wins (default: 0)
+    ///     roles: Names for requirements, e.g. ``{"manifest": "*.json",
+    ///         "raw_dir": "raw_*"}``, each a glob in ``files`` or
+    ///         ``optional_files`` or a nested pattern's ``directory_name``;
+    ///         reported as ``ScanResult.bindings``
+    ///
+    /// Returns:
+    ///     FileStructurePattern instance
+    ///
+    /// Raises:
+    ///     ValueError: If an age is not a valid duration or a total size
+    ///         not a valid size
+    #[new]
+    #[pyo3(signature = (
+        directory_name="*",
+        files=None,
+        directories=None,
+        optional_files=None,
+        optional_directories=None,
+        pattern_name=None,
+        excluded_files=None,
+        excluded_directories=None,
+        empty_directories=None,
+        no_extra_files=false,
+        min_file_count=None,
+        max_file_count=None,
+        file_constraints=None,
+        newer_than=None,
+        older_than=None,
+        stable_for=None,
+        min_total_size=None,
+        max_total_size=None,
+        any_of=None,
+        all_of=None,
+        none_of=None,
+        extends=None,
+        case_insensitive=false,
+        follow_symlinked_dirs=false,
+        priority=None,
+        roles=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn py_new(
+        directory_name: &str,
+        files: Option<Vec<String>>,
+        directories: Option<Vec<FileStructurePattern>>,
+        optional_files: Option<Vec<String>>,
+        optional_directories: Option<Vec<FileStructurePattern>>,
+        pattern_name: Option<String>,
+        excluded_files: Option<Vec<String>>,
+        excluded_directories: Option<Vec<String>>,
+        empty_directories: Option<Vec<String>>,
+        no_extra_files: bool,
+        min_file_count: Option<usize>,
+        max_file_count: Option<usize>,
+        file_constraints: Option<BTreeMap<String, FileConstraint>>,
+        newer_than: Option<DurationLimit>,
+        older_than: Option<DurationLimit>,
+        stable_for: Option<DurationLimit>,
+        min_total_size: Option<SizeLimit>,
+        max_total_size: Option<SizeLimit>,
+        any_of: Option<Vec<FileStructurePattern>>,
+        all_of: Option<Vec<FileStructurePattern>>,
+        none_of: Option<Vec<FileStructurePattern>>,
+        extends: Option<Vec<String>>,
+        case_insensitive: bool,
+        follow_symlinked_dirs: bool,
+        priority: Option<i64>,
+        roles: Option<BTreeMap<String, String>>,
+    ) -> PyResult<Self> {
+        Ok(FileStructurePattern {
+            directory_name: directory_name.to_string(),
+            files: files.unwrap_or_default(),
+            directories: directories.unwrap_or_default(),
+            optional_files: optional_files.unwrap_or_default(),
+            optional_directories: optional_directories.unwrap_or_default(),
+            pattern_name,
+            excluded_files: excluded_files.unwrap_or_default(),
+            excluded_directories: excluded_directories.unwrap_or_default(),
+            empty_directories: empty_directories.unwrap_or_default(),
+            no_extra_files,
+            min_file_count,
+            max_file_count,
+            file_constraints: file_constraints.unwrap_or_default(),
+            newer_than: DurationLimit::py_seconds(newer_than)?,
+            older_than: DurationLimit::py_seconds(older_than)?,
+            stable_for: DurationLimit::py_seconds(stable_for)?,
+            min_total_size: SizeLimit::py_bytes(min_total_size)?,
+            max_total_size: SizeLimit::py_bytes(max_total_size)?,
+            any_of: any_of.unwrap_or_default(),
+            all_of: all_of.unwrap_or_default(),
+            none_of: none_of.unwrap_or_default(),
+            extends: extends.unwrap_or_default(),
+            case_insensitive,
+            follow_symlinked_dirs,
+            priority,
+            roles: roles.unwrap_or_default(),
+        })
+    }
+
+    #[setter]
+    fn set_newer_than(&mut self, newer_than: Option<DurationLimit>) -> PyResult<()> {
+        self.newer_than = DurationLimit::py_seconds(newer_than)?;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_older_than(&mut self, older_than: Option<DurationLimit>) -> PyResult<()> {
+        self.older_than = DurationLimit::py_seconds(older_than)?;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_stable_for(&mut self, stable_for: Option<DurationLimit>) -> PyResult<()> {
+        self.stable_for = DurationLimit::py_seconds(stable_for)?;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_min_total_size(&mut self, min_total_size: Option<SizeLimit>) -> PyResult<()> {
+        self.min_total_size = SizeLimit::py_bytes(min_total_size)?;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_max_total_size(&mut self, max_total_size: Option<SizeLimit>) -> PyResult<()> {
+        self.max_total_size = SizeLimit::py_bytes(max_total_size)?;
+        Ok(())
+    }
+
+    /// Serialize to JSON, with nested directories as inline objects
+    ///
+    /// The current layout ``version`` is written with it.
+    ///
+    /// Returns:
+    ///     JSON string accepted by ``from_json`` on either class
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&Versioned::new(self))
+            .expect("pattern JSON serialization cannot fail")
+    }
+
+    /// Create a FileStructurePattern from a JSON string
+    ///
+    /// Nested directories may be JSON strings (as Python's ``to_json``
+    /// writes them) or inline objects. Missing keys take their defaults.
+    ///
+    /// A ``version`` key gives the spec's layout version; specs without
+    /// one are version 1 and older layouts are upgraded as they are read,
+    /// so stored specs keep loading as the format evolves.
+    ///
+    /// Args:
+    ///     spec_str: JSON string containing pattern specification
+    ///
+    /// Returns:
+    ///     FileStructurePattern instance
+    ///
+    /// Raises:
+    ///     ValueError: If spec_str is not a valid pattern specification,
+    ///         or its version is newer than this pathvein reads
+    #[staticmethod]
+    #[pyo3(name = "from_json")]
+    pub fn py_from_json(spec_str: &str) -> PyResult<Self> {
+        Self::from_json(spec_str)
+            .map_err(|e| pattern_syntax_error(format!("Invalid pattern JSON: {}", e)))
+    }
+
+    /// Create a FileStructurePattern from a YAML string
+    ///
+    /// Takes the same keys as ``from_json``, with nested directories
+    /// written inline as mappings:
+    ///
+    /// .. code-block:: yaml
+    ///
+    ///     directory_name: "experiment_*"
+    ///     files: [config.yaml]
+    ///     directories:
+    ///       - directory_name: data
+    ///         files: ["*.csv"]
+    ///
+    /// Args:
+    ///     spec_str: YAML string containing pattern specification
+    ///
+    /// Returns:
+    ///     FileStructurePattern instance
+    ///
+    /// Raises:
+    ///     ValueError: If spec_str is not a valid pattern specification
+    #[staticmethod]
+    #[pyo3(name = "from_yaml")]
+    pub fn py_from_yaml(spec_str: &str) -> PyResult<Self> {
+        Self::from_yaml(spec_str)
+            .map_err(|e| pattern_syntax_error(format!("Invalid pattern YAML: {}", e)))
+    }
+
+    /// Serialize to a TOML string, with nested directories as arrays of tables
+    ///
+    /// The current layout ``version`` is written with it.
+    ///
+    /// Returns:
+    ///     TOML string accepted by ``from_toml``
+    ///
+    /// Raises:
+    ///     ValueError: If the pattern cannot be represented in TOML
+    pub fn to_toml(&self) -> PyResult<String> {
+        toml::to_string(&Versioned::new(self))
+            .map_err(|e| PyValueError::new_err(format!("Cannot write pattern as TOML: {}", e)))
+    }
+
+    /// Create a FileStructurePattern from a TOML string
+    ///
+    /// Takes the same keys as ``from_json``. ``table`` selects a nested
+    /// table by its dotted key, so a pattern can live inside a larger
+    /// configuration file:
+    ///
+    /// .. code-block:: toml
+    ///
+    ///     [tool.pathvein.pattern]
+    ///     directory_name = "experiment_*"
+    ///     files = ["config.yaml"]
+    ///
+    ///     [[tool.pathvein.pattern.directories]]
+    ///     directory_name = "data"
+    ///     files = ["*.csv"]
+    ///
+    /// Args:
+    ///     spec_str: TOML string containing pattern specification
+    ///     table: Dotted key of the table holding the pattern, e.g.
+    ///         ``"tool.pathvein.pattern"`` (default: the whole document)
+    ///
+    /// Returns:
+    ///     FileStructurePattern instance
+    ///
+    /// Raises:
+    ///     ValueError: If spec_str is not a valid pattern specification or
+    ///         ``table`` is missing
+    #[staticmethod]
+    #[pyo3(name = "from_toml", signature = (spec_str, table=None))]
+    pub fn py_from_toml(spec_str: &str, table: Option<&str>) -> PyResult<Self> {
+        let invalid =
+            |e: toml::de::Error| pattern_syntax_error(format!("Invalid pattern TOML: {}", e));
+        let Some(table) = table else {
+            return Self::from_toml(spec_str).map_err(invalid);
+        };
+        let mut value: toml::Value = toml::from_str(spec_str).map_err(invalid)?;
+        for key in table.split('.') {
+            value = match value {
+                toml::Value::Table(mut entries) => entries.remove(key),
+                _ => None,
+            }
+            .ok_or_else(|| PyValueError::new_err(format!("No TOML table '{}'", table)))?;
+        }
+        Self::from_versioned(value.clone().try_into(), || value.try_into()).map_err(invalid)
+    }
+
+    /// Copy of this pattern with every ``extends`` merged in
+    ///
+    /// Each reference names a pattern in ``library`` by its
+    /// ``pattern_name``, or is a path to a JSON, YAML or TOML spec file
+    /// (anything ending in ``.json``, ``.yaml``, ``.yml`` or ``.toml``),
+    /// relative to ``base_dir``. Bases are resolved first and applied in
+    /// order; the extending pattern's lists are appended to the base's and
+    /// its own settings take precedence. Nested patterns are resolved too.
+    ///
+    /// Args:
+    ///     library: Named patterns to extend, as a list keyed by their
+    ///         ``pattern_name`` or a dict of name to pattern
+    ///     base_dir: Directory file references are relative to
+    ///         (default: the current directory)
+    ///
+    /// Returns:
+    ///     FileStructurePattern with empty ``extends``
+    ///
+    /// Raises:
+    ///     ValueError: If a reference can't be found or loaded, or patterns
+    ///         extend each other in a cycle
+    #[pyo3(signature = (library=None, base_dir=None))]
+    pub fn resolve(
+        &self,
+        library: Option<PatternLibrary>,
+        base_dir: Option<PathBuf>,
+    ) -> PyResult<Self> {
+        let library = library.map(PatternLibrary::into_map).unwrap_or_default();
+        inherit::resolve(self, &library, base_dir.as_deref().unwrap_or(Path::new("")))
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Load a pattern from a JSON, YAML or TOML spec file, by extension,
+    /// and resolve its ``extends``
+    ///
+    /// File references in ``extends`` are relative to the spec file.
+    ///
+    /// Args:
+    ///     path: Spec file to load
+    ///     library: Named patterns ``extends`` may refer to, as for
+    ///         ``resolve``
+    ///
+    /// Returns:
+    ///     FileStructurePattern instance
+    ///
+    /// Raises:
+    ///     ValueError: If the file can't be read or is not a valid
+    ///         specification, or its ``extends`` can't be resolved
+    #[staticmethod]
+    #[pyo3(signature = (path, library=None))]
+    pub fn load(path: PathBuf, library: Option<PatternLibrary>) -> PyResult<Self> {
+        let library = library.map(PatternLibrary::into_map).unwrap_or_default();
+        let pattern = Self::load_file(&path).map_err(PyValueError::new_err)?;
+        inherit::resolve(&pattern, &library, path.parent().unwrap_or(Path::new("")))
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Fraction of this pattern's requirements a directory satisfies
+    ///
+    /// Each required file glob, excluded glob, count, size or age limit and
+    /// combinator counts as one requirement, as does each required nested
+    /// pattern, credited with the best score of any subdirectory. 1.0
+    /// exactly when the directory matches.
+    ///
+    /// Args:
+    ///     dirpath: Directory to score
+    ///     follow_links: Whether to follow symbolic links in its listings
+    ///
+    /// Returns:
+    ///     Score between 0.0 and 1.0
+    ///
+    /// Raises:
+    ///     ValueError: If the pattern is invalid or its ``extends`` can't
+    ///         be resolved
+    #[pyo3(signature = (dirpath, follow_links=false))]
+    fn score(&self, py: Python<'_>, dirpath: PathBuf, follow_links: bool) -> PyResult<f64> {
+        let compiled = self.compile_resolved()?;
+        py.allow_threads(|| {
+            let mut tree = WalkedTree::new();
+            read_subtree(&dirpath, compiled.depth(), follow_links, &mut tree);
+            Ok(compiled.score(&dirpath, &tree))
+        })
+    }
+
+    /// Describe why a directory doesn't match this pattern
+    ///
+    /// A nested pattern no subdirectory satisfies is explained by the
+    /// failures of the subdirectory that came closest, if any came close
+    /// at all.
+    ///
+    /// Args:
+    ///     dirpath: Directory to check
+    ///     follow_links: Whether to follow symbolic links in its listings
+    ///
+    /// Returns:
+    ///     List of MatchFailure, empty if the directory matches
+    ///
+    /// Raises:
+    ///     ValueError: If the pattern is invalid or its ``extends`` can't
+    ///         be resolved
+    #[pyo3(signature = (dirpath, follow_links=false))]
+    fn explain(
+        &self,
+        py: Python<'_>,
+        dirpath: PathBuf,
+        follow_links: bool,
+    ) -> PyResult<Vec<MatchFailure>> {
+        let compiled = self.compile_resolved()?;
+        py.allow_threads(|| {
+            let mut tree = WalkedTree::new();
+            read_subtree(&dirpath, compiled.depth(), follow_links, &mut tree);
+            Ok(compiled.explain(&dirpath, &tree))
+        })
+    }
+
+    /// Check the pattern against a hypothetical directory, without
+    /// touching the filesystem
+    ///
+    /// ``structure`` gives the directory's contents as nested dicts: a
+    /// dict value is a subdirectory, an int is a file of that many bytes,
+    /// a str or bytes is a file with that content and anything else (e.g.
+    /// ``None``) is a file of unknown size and content:
+    ///
+    /// .. code-block:: python
+    ///
+    ///     pattern.test({"README.md": None, "data": {"a.csv": 1024}})
+    ///
+    /// Files have no modification time, so age limits are never met.
+    ///
+    /// Args:
+    ///     structure: Nested dict describing the directory's contents
+    ///     name: The directory's own name, checked against
+    ///         ``directory_name``; if omitted, only the contents are checked
+    ///
+    /// Returns:
+    ///     True if the described directory matches
+    ///
+    /// Raises:
+    ///     ValueError: If the pattern is invalid or its ``extends`` can't
+    ///         be resolved
+    ///     TypeError: If a key of ``structure`` is not a string
+    #[pyo3(signature = (structure, name=None))]
+    fn test(&self, structure: &Bound<'_, PyDict>, name: Option<&str>) -> PyResult<bool> {
+        let mut compiled = self.compile_resolved()?;
+        if name.is_none() {
+            compiled.directory_name_matcher = None;
+        }
+        let root = Path::new(name.unwrap_or(""));
+        let tree = MemoryTree::from_dict(root, structure)?;
+        Ok(compiled.matches_in(root, &tree))
+    }
+
+    fn __reduce__<'py>(slf: &Bound<'py, Self>) -> PyResult<(Bound<'py, PyAny>, (String,))> {
+        let from_json = slf.get_type().getattr("from_json")?;
+        Ok((from_json, (slf.borrow().to_json(),)))
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self == other
+    }
+
+    fn __hash__(&self) -> u64 {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn __repr__(&self) -> String {
+        let name = match &self.pattern_name {
+            Some(name) => format!("pattern_name={:?}, ", name),
+            None => String::new(),
+        };
+        let mut excluded = String::new();
+        if !self.excluded_files.is_empty() {
+            excluded += &format!(", excluded_files={:?}", self.excluded_files);
+        }
+        if !self.excluded_directories.is_empty() {
+            excluded += &format!(", excluded_directories={:?}", self.excluded_directories);
+        }
+        if !self.empty_directories.is_empty() {
+            excluded += &format!(", empty_directories={:?}", self.empty_directories);
+        }
+        if self.no_extra_files {
+            excluded += ", no_extra_files=True";
+        }
+        if self.case_insensitive {
+            excluded += ", case_insensitive=True";
+        }
+        if self.follow_symlinked_dirs {
+            excluded += ", follow_symlinked_dirs=True";
+        }
+        if let Some(priority) = self.priority {
+            excluded += &format!(", priority={}", priority);
+        }
+        format!(
+            "FileStructurePattern({}directory_name={:?}, files={:?}, directories={}, \
+             optional_files={:?}, optional_directories={}{})",
+            name,
+            self.directory_name,
+            self.files,
+            self.directories.len(),
+            self.optional_files,
+            self.optional_directories.len(),
+            excluded
+        )
+    }
 }
 
 /// Precompiled version of FileStructurePattern with cached matchers
 pub struct CompiledPattern {
-    pub directory_name_matcher: Option<PatternMatcher>,
-    pub file_matchers: Vec<PatternMatcher>,
-    pub subdir_matchers: Vec<PatternMatcher>,
+    /// `directory_name` as written, used when reporting this pattern
+    pub directory_name: String,
+    pub pattern_name: Option<String>,
+    pub directory_name_matcher: Option<NameMatcher>,
+    /// `directory_name` starts with `**/`, so as a nested pattern it may
+    /// match a subdirectory at any depth
+    pub any_depth: bool,
+    pub files: Vec<FileRequirement>,
+    pub optional_files: Vec<FileRequirement>,
+    /// Required nested patterns, each satisfied by some subdirectory's own
+    /// contents
+    pub subpatterns: Vec<CompiledPattern>,
+    pub optional_subpatterns: Vec<CompiledPattern>,
+    /// Globs from `excluded_files` and `excluded_directories`; any match
+    /// rules the directory out
+    pub excluded: Vec<FileRequirement>,
+    /// Globs from `empty_directories`, matched against subdirectories
+    pub empty_directories: Vec<FileRequirement>,
+    pub no_extra_files: bool,
+    pub min_file_count: Option<usize>,
+    pub max_file_count: Option<usize>,
+    /// Compiled `file_constraints` globs with their limits
+    pub constraints: Vec<(FileRequirement, FileConstraint)>,
+    pub newer_than: Option<Duration>,
+    pub older_than: Option<Duration>,
+    pub stable_for: Option<Duration>,
+    pub min_total_size: Option<u64>,
+    pub max_total_size: Option<u64>,
+    /// Combinator branches, each evaluated against the same directory
+    pub any_of: Vec<CompiledPattern>,
+    pub all_of: Vec<CompiledPattern>,
+    pub none_of: Vec<CompiledPattern>,
+    /// Symlinked subdirectories may satisfy `subpatterns` and
+    /// `optional_subpatterns`, read on demand if the walk didn't follow them
+    pub follow_symlinked_dirs: bool,
+    /// `priority`, 0 if unset
+    pub priority: i64,
+    /// Role names and the file glob or nested `directory_name` each binds
+    pub roles: Vec<(String, String)>,
+}
+
+/// One compiled glob from `files` or `optional_files`
+pub struct FileRequirement {
+    /// The glob as written in the pattern
+    pub glob: String,
+    pub matcher: NameMatcher,
+    /// Written with a trailing `/`, so it is matched against subdirectories
+    pub dir_only: bool,
+}
+
+/// What a successful structure match found besides its requirements
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StructureMatch {
+    /// Each required or optional file glob mapped to the entry names that
+    /// satisfied it; optional globs that matched nothing are left out
+    pub matched_files: HashMap<String, Vec<String>>,
+    /// Optional file globs satisfied by at least one entry
+    pub optional_files: Vec<String>,
+    /// `directory_name`s of optional sub-patterns satisfied by a subdirectory
+    pub optional_directories: Vec<String>,
+    /// Index into `any_of` of the first branch that matched
+    pub branch: Option<usize>,
+    /// Each role mapped to the paths that satisfied its requirement
+    #[serde(default)]
+    pub bindings: HashMap<String, Vec<String>>,
+    /// Text matched by each wildcard of `directory_name`, or each group of
+    /// a `regex:` one
+    #[serde(default)]
+    pub captures: Vec<String>,
+}
+
+/// Directory listings that recursive structure matching looks children up in
+pub trait DirectoryTree {
+    /// (subdirectory names, file names) directly inside `dir`; both empty
+    /// if the directory is empty or was never listed
+    fn children(&self, dir: &Path) -> (&[OsString], &[OsString]);
+
+    /// Size in bytes of a file, for patterns with size limits; None if it
+    /// can't be read
+    fn file_size(&self, path: &Path) -> Option<u64> {
+        std::fs::metadata(path).ok().map(|metadata| metadata.len())
+    }
+
+    /// Modification time of a file or directory, for patterns with age
+    /// limits; None if it can't be read
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Up to `limit` bytes from the start of a file, for patterns with
+    /// content limits; None if it can't be read
+    fn read_head(&self, path: &Path, limit: usize) -> Option<Vec<u8>> {
+        let mut head = Vec::new();
+        std::fs::File::open(path)
+            .and_then(|file| file.take(limit as u64).read_to_end(&mut head))
+            .ok()?;
+        Some(head)
+    }
+
+    /// Whether `path` is a symbolic link, for patterns that don't let
+    /// symlinked directories satisfy requirements
+    fn is_symlink(&self, path: &Path) -> bool {
+        std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_symlink())
+    }
+
+    /// Names of the symbolic links to directories inside `dir`, for
+    /// patterns that follow them when the walk doesn't
+    fn linked_dirs(&self, dir: &Path) -> Vec<OsString> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_symlink()))
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name())
+            .collect()
+    }
+
+    /// Bytes in every file beneath `dir`, for patterns with total size
+    /// limits; None if it can't be read
+    ///
+    /// Symbolic links are not followed, so linked data isn't counted.
+    fn total_size(&self, dir: &Path) -> Option<u64> {
+        let mut total = 0;
+        for entry in std::fs::read_dir(dir).ok()? {
+            let entry = entry.ok()?;
+            let metadata = entry.metadata().ok()?;
+            if metadata.is_dir() {
+                total += self.total_size(&entry.path())?;
+            } else if metadata.is_file() {
+                total += metadata.len();
+            }
+        }
+        Some(total)
+    }
+}
+
+/// A tree with listings read past symbolic links the walk left out
+struct LinkedTree<'a> {
+    base: &'a dyn DirectoryTree,
+    /// Listings that replace or add to `base`'s
+    listings: WalkedTree,
+}
+
+impl DirectoryTree for LinkedTree<'_> {
+    fn children(&self, dir: &Path) -> (&[OsString], &[OsString]) {
+        match self.listings.get(dir) {
+            Some((files, dirs)) => (dirs, files),
+            None => self.base.children(dir),
+        }
+    }
+
+    fn file_size(&self, path: &Path) -> Option<u64> {
+        self.base.file_size(path)
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        self.base.modified(path)
+    }
+
+    fn read_head(&self, path: &Path, limit: usize) -> Option<Vec<u8>> {
+        self.base.read_head(path, limit)
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        self.base.is_symlink(path)
+    }
+
+    fn linked_dirs(&self, dir: &Path) -> Vec<OsString> {
+        self.base.linked_dirs(dir)
+    }
+
+    fn total_size(&self, dir: &Path) -> Option<u64> {
+        self.base.total_size(dir)
+    }
 }
 
 impl FileStructurePattern {
@@ -28,83 +1427,949 @@ impl FileStructurePattern {
     /// This prevents recompiling patterns on every match check.
     /// Should be called once before starting the directory walk.
     pub fn compile(&self) -> Result<CompiledPattern, String> {
+        self.compile_nested(false, false)
+    }
+
+    /// Compile as nested in a pattern, case-insensitive and following
+    /// symlinked directories if that one is
+    fn compile_nested(
+        &self,
+        case_insensitive: bool,
+        follow_symlinked_dirs: bool,
+    ) -> Result<CompiledPattern, String> {
+        let case_insensitive = case_insensitive || self.case_insensitive;
+        let follow_symlinked_dirs = follow_symlinked_dirs || self.follow_symlinked_dirs;
+
         // Compile directory name matcher if needed
-        let directory_name_matcher =
-            if !self.directory_name.is_empty() && self.directory_name != "*" {
-                Some(
-                    PatternMatcher::new(vec![self.directory_name.clone()])
-                        .map_err(|e| format!("Invalid directory pattern: {}", e))?,
-                )
-            } else {
-                None
-            };
+        let (directory_name, any_depth) = match self.directory_name.strip_prefix(ANY_DEPTH) {
+            Some(name) if !name.is_empty() => (directory_glob(name), true),
+            _ => (directory_glob(&self.directory_name), false),
+        };
+        let directory_name_matcher = if !directory_name.is_empty() && directory_name != "*" {
+            Some(
+                NameMatcher::compile(directory_name, case_insensitive)
+                    .map_err(|e| format!("Invalid directory pattern: {}", e))?,
+            )
+        } else {
+            None
+        };
 
         // Compile all file pattern matchers
-        let mut file_matchers = Vec::new();
-        for file_pattern in &self.files {
-            let matcher = PatternMatcher::new(vec![file_pattern.clone()])
-                .map_err(|e| format!("Invalid file pattern '{}': {}", file_pattern, e))?;
-            file_matchers.push(matcher);
-        }
+        let compile_files = |globs: &[String]| {
+            globs
+                .iter()
+                .map(|glob| FileRequirement::compile(glob, case_insensitive))
+                .collect::<Result<Vec<_>, _>>()
+        };
 
-        // Compile all subdirectory pattern matchers
-        let mut subdir_matchers = Vec::new();
-        for subdir_pattern in &self.directories {
-            if !subdir_pattern.directory_name.is_empty() && subdir_pattern.directory_name != "*" {
-                let matcher = PatternMatcher::new(vec![subdir_pattern.directory_name.clone()])
-                    .map_err(|e| format!("Invalid subdirectory pattern: {}", e))?;
-                subdir_matchers.push(matcher);
-            }
-        }
+        // Compile subdirectory patterns recursively
+        let compile_directories = |patterns: &[FileStructurePattern]| {
+            patterns
+                .iter()
+                .map(|pattern| pattern.compile_nested(case_insensitive, follow_symlinked_dirs))
+                .collect::<Result<Vec<_>, _>>()
+        };
 
         Ok(CompiledPattern {
+            directory_name: self.directory_name.clone(),
+            pattern_name: self.pattern_name.clone(),
             directory_name_matcher,
-            file_matchers,
-            subdir_matchers,
+            any_depth,
+            files: compile_files(&self.files)?,
+            optional_files: compile_files(&self.optional_files)?,
+            subpatterns: compile_directories(&self.directories)?,
+            optional_subpatterns: compile_directories(&self.optional_directories)?,
+            excluded: compile_files(&self.excluded_files)?
+                .into_iter()
+                .chain(
+                    compile_files(&self.excluded_directories)?
+                        .into_iter()
+                        .map(|requirement| FileRequirement {
+                            dir_only: true,
+                            ..requirement
+                        }),
+                )
+                .collect(),
+            empty_directories: compile_files(&self.empty_directories)?
+                .into_iter()
+                .map(|requirement| FileRequirement {
+                    dir_only: true,
+                    ..requirement
+                })
+                .collect(),
+            no_extra_files: self.no_extra_files,
+            min_file_count: self.min_file_count,
+            max_file_count: self.max_file_count,
+            constraints: self
+                .file_constraints
+                .iter()
+                .map(|(glob, constraint)| {
+                    if let Some(name) = &constraint.file_type {
+                        filetype::lookup(name).ok_or_else(|| filetype::unknown_type(name))?;
+                    }
+                    FileRequirement::compile(glob, case_insensitive)
+                        .map(|requirement| (requirement, constraint.clone()))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            newer_than: self.newer_than.map(Duration::from_secs),
+            older_than: self.older_than.map(Duration::from_secs),
+            stable_for: self.stable_for.map(Duration::from_secs),
+            min_total_size: self.min_total_size,
+            max_total_size: self.max_total_size,
+            any_of: compile_directories(&self.any_of)?,
+            all_of: compile_directories(&self.all_of)?,
+            none_of: compile_directories(&self.none_of)?,
+            follow_symlinked_dirs,
+            priority: self.priority.unwrap_or(0),
+            roles: self.compile_roles()?,
         })
     }
 
-    /// Serialize to JSON string for FFI transfer
-    #[allow(dead_code)]
-    pub fn to_json(&self) -> Result<String, serde_json::Error> {
-        serde_json::to_string(self)
+    /// Check that every role names a requirement of this pattern
+    fn compile_roles(&self) -> Result<Vec<(String, String)>, String> {
+        self.roles
+            .iter()
+            .map(|(role, target)| {
+                let known = self.files.contains(target)
+                    || self.optional_files.contains(target)
+                    || self
+                        .directories
+                        .iter()
+                        .chain(&self.optional_directories)
+                        .any(|pattern| pattern.directory_name == *target);
+                if known {
+                    Ok((role.clone(), target.clone()))
+                } else {
+                    Err(format!(
+                        "Role '{}' names '{}', which is not a file glob or nested directory_name of the pattern",
+                        role, target
+                    ))
+                }
+            })
+            .collect()
+    }
+
+    /// Compile for matching a single directory, resolving file references
+    /// in `extends`
+    fn compile_resolved(&self) -> PyResult<CompiledPattern> {
+        inherit::resolve(self, &HashMap::new(), Path::new(""))
+            .map_err(pattern_syntax_error)?
+            .compile()
+            .map_err(|e| pattern_syntax_error(format!("Pattern compilation error: {}", e)))
     }
 
     /// Deserialize from JSON string
     pub fn from_json(json_str: &str) -> Result<Self, serde_json::Error> {
-        serde_json::from_str(json_str)
+        Self::from_versioned(serde_json::from_str(json_str), || {
+            serde_json::from_str(json_str)
+        })
+    }
+
+    /// Deserialize from YAML string
+    pub fn from_yaml(yaml_str: &str) -> Result<Self, serde_yaml::Error> {
+        Self::from_versioned(serde_yaml::from_str(yaml_str), || {
+            serde_yaml::from_str(yaml_str)
+        })
+    }
+
+    /// Deserialize from TOML string
+    pub fn from_toml(toml_str: &str) -> Result<Self, toml::de::Error> {
+        Self::from_versioned(toml::from_str(toml_str), || toml::from_str(toml_str))
+    }
+
+    /// Deserialize a spec parsed as `value`, upgrading it first if it is in
+    /// an older layout; specs already current are read by `direct`, whose
+    /// errors carry line numbers
+    fn from_versioned<E: de::Error>(
+        value: Result<serde_json::Value, E>,
+        direct: impl FnOnce() -> Result<Self, E>,
+    ) -> Result<Self, E> {
+        let mut value = value?;
+        if migrate::upgrade(&mut value).map_err(E::custom)? {
+            serde_json::from_value(value).map_err(E::custom)
+        } else {
+            direct()
+        }
+    }
+
+    /// Read a spec file, choosing the format by its extension
+    pub fn load_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read pattern file {}: {}", path.display(), e))?;
+        let extension = path.extension().and_then(OsStr::to_str).unwrap_or("");
+        let parsed = match extension.to_ascii_lowercase().as_str() {
+            "json" => Self::from_json(&text).map_err(|e| e.to_string()),
+            "yaml" | "yml" => Self::from_yaml(&text).map_err(|e| e.to_string()),
+            "toml" => Self::from_toml(&text).map_err(|e| e.to_string()),
+            _ => Err("expected a .json, .yaml, .yml or .toml file".to_string()),
+        };
+        parsed.map_err(|e| format!("Invalid pattern file {}: {}", path.display(), e))
+    }
+}
+
+/// Strip a gitignore-style leading `/` from a requirement glob
+///
+/// File requirements are always matched against the entries directly inside
+/// the candidate directory, i.e. they are already anchored to it. A leading
+/// `/` (as in `/README.md`) therefore just restates that anchoring, so it is
+/// dropped instead of making the pattern impossible to match.
+fn root_relative(pattern: &str) -> &str {
+    pattern.strip_prefix('/').unwrap_or(pattern)
+}
+
+/// Prefix of a nested `directory_name` that may match at any depth
+pub const ANY_DEPTH: &str = "**/";
+
+/// Directory names always name directories, so a trailing `/` is redundant
+fn directory_glob(pattern: &str) -> &str {
+    if pattern.starts_with(REGEX_PREFIX) {
+        return pattern;
+    }
+    match pattern.strip_suffix('/') {
+        Some(stripped) if !stripped.is_empty() => stripped,
+        _ => pattern,
+    }
+}
+
+/// Marks a `directory_name` or file requirement written as a regex
+pub const REGEX_PREFIX: &str = "regex:";
+
+/// A compiled glob, or a `regex:` regex, tested against one entry name
+pub enum NameMatcher {
+    Glob(PatternMatcher),
+    /// Searched for in the name, so it needs `^`/`$` to match all of it
+    Regex(Regex),
+}
+
+impl NameMatcher {
+    fn compile(pattern: &str, case_insensitive: bool) -> Result<Self, String> {
+        match pattern.strip_prefix(REGEX_PREFIX) {
+            Some(regex) => RegexBuilder::new(regex)
+                .case_insensitive(case_insensitive)
+                .build()
+                .map(NameMatcher::Regex)
+                .map_err(|e| e.to_string()),
+            None => {
+                let options = MatcherOptions {
+                    case_fold: if case_insensitive {
+                        CaseFold::Unicode
+                    } else {
+                        CaseFold::None
+                    },
+                    ..MatcherOptions::default()
+                };
+                PatternMatcher::build(vec![pattern.to_string()], options)
+                    .map(NameMatcher::Glob)
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    pub fn is_match_os(&self, name: &OsStr, is_dir: bool) -> bool {
+        match self {
+            NameMatcher::Glob(matcher) => matcher.is_match_os(name, is_dir),
+            NameMatcher::Regex(regex) => regex.is_match(&name.to_string_lossy()),
+        }
+    }
+
+    /// Text matched by each wildcard of the glob, or each group of the
+    /// regex, in a directory name this matches
+    ///
+    /// Case-insensitive globs capture from the case-folded name.
+    pub fn captures(&self, name: &OsStr) -> Vec<String> {
+        let name = name.to_string_lossy();
+        match self {
+            NameMatcher::Glob(matcher) => {
+                let (prepared, is_dir) = matcher.candidate(&name, true);
+                capture::extract(
+                    &matcher.patterns()[0],
+                    &matcher.options(),
+                    &prepared,
+                    is_dir,
+                )
+                .ok()
+                .flatten()
+                .unwrap_or_default()
+            }
+            NameMatcher::Regex(regex) => regex
+                .captures(&name)
+                .map(|captures| {
+                    captures
+                        .iter()
+                        .skip(1)
+                        .map(|group| group.map_or_else(String::new, |m| m.as_str().to_string()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl FileRequirement {
+    /// Compile a requirement glob. A trailing `/` makes it directory-only,
+    /// so it is checked against subdirectories; a `regex:` requirement is
+    /// always matched against files.
+    fn compile(glob: &str, case_insensitive: bool) -> Result<Self, String> {
+        let pattern = root_relative(glob);
+        let (pattern, dir_only) = match pattern.strip_suffix('/') {
+            Some(dir_pattern) if !pattern.starts_with(REGEX_PREFIX) => (dir_pattern, true),
+            _ => (pattern, false),
+        };
+        let matcher = NameMatcher::compile(pattern, case_insensitive)
+            .map_err(|e| format!("Invalid file pattern '{}': {}", glob, e))?;
+        Ok(FileRequirement {
+            glob: glob.to_string(),
+            matcher,
+            dir_only,
+        })
+    }
+
+    /// Whether at least one entry of the right kind satisfies this glob
+    fn is_met<S: AsRef<OsStr>>(&self, dirnames: &[S], filenames: &[S]) -> bool {
+        let names = if self.dir_only { dirnames } else { filenames };
+        names
+            .iter()
+            .any(|name| self.matcher.is_match_os(name.as_ref(), self.dir_only))
+    }
+
+    /// Number of entries of the right kind that satisfy this glob
+    fn count<S: AsRef<OsStr>>(&self, dirnames: &[S], filenames: &[S]) -> usize {
+        let names = if self.dir_only { dirnames } else { filenames };
+        names
+            .iter()
+            .filter(|name| self.matcher.is_match_os(name.as_ref(), self.dir_only))
+            .count()
+    }
+
+    /// Every entry of the right kind that satisfies this glob
+    fn matching<S: AsRef<OsStr>>(&self, dirnames: &[S], filenames: &[S]) -> Vec<String> {
+        let names = if self.dir_only { dirnames } else { filenames };
+        names
+            .iter()
+            .map(AsRef::as_ref)
+            .filter(|name| self.matcher.is_match_os(name, self.dir_only))
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect()
     }
 }
 
 impl CompiledPattern {
-    /// Check if a directory matches this pattern using precompiled matchers
+    /// How many levels of subdirectory listings a match needs below the
+    /// matched directory itself
+    pub fn depth(&self) -> usize {
+        let nested = self
+            .subpatterns
+            .iter()
+            .chain(&self.optional_subpatterns)
+            .map(|subpattern| {
+                // A `**/` pattern may look any number of levels down
+                if subpattern.any_depth {
+                    usize::MAX
+                } else {
+                    subpattern.depth().saturating_add(1)
+                }
+            });
+        // Branches look at the same directory, so add no level of their own
+        let branches = self
+            .any_of
+            .iter()
+            .chain(&self.all_of)
+            .chain(&self.none_of)
+            .map(CompiledPattern::depth);
+        // Telling a subdirectory is empty takes its listing
+        let empty = (!self.empty_directories.is_empty()).then_some(1);
+        nested.chain(branches).chain(empty).max().unwrap_or(0)
+    }
+
+    /// Whether matching reads file sizes or modification times, which a
+    /// directory listing alone doesn't capture
+    pub fn needs_stat(&self) -> bool {
+        self.newer_than.is_some()
+            || self.older_than.is_some()
+            || self.stable_for.is_some()
+            || self.min_total_size.is_some()
+            || self.max_total_size.is_some()
+            || self.constraints.iter().any(|(_, constraint)| {
+                constraint.has_size_limits() || constraint.has_content_limits()
+            })
+            || self
+                .subpatterns
+                .iter()
+                .chain(&self.optional_subpatterns)
+                .chain(&self.any_of)
+                .chain(&self.all_of)
+                .chain(&self.none_of)
+                .any(CompiledPattern::needs_stat)
+    }
+
+    /// Match a directory and report which optional components are present
     ///
-    /// This is MUCH faster than recompiling patterns on every check.
-    /// No pattern compilation happens here - just matching against precompiled DFAs.
-    pub fn matches(&self, dirpath_name: &str, dirnames: &[String], filenames: &[String]) -> bool {
-        // Check directory name with precompiled matcher
-        if let Some(ref matcher) = self.directory_name_matcher {
-            if !matcher.matches(dirpath_name) {
+    /// Returns None if the directory does not satisfy the requirements.
+    pub fn match_in(&self, dir: &Path, tree: &dyn DirectoryTree) -> Option<StructureMatch> {
+        if !self.matches_in(dir, tree) {
+            return None;
+        }
+        let linked = self.linked_tree(dir, tree);
+        let tree = linked
+            .as_ref()
+            .map_or(tree, |linked| linked as &dyn DirectoryTree);
+        let (dirnames, filenames) = tree.children(dir);
+        let mut matched_files = HashMap::new();
+        for requirement in &self.files {
+            matched_files.insert(
+                requirement.glob.clone(),
+                requirement.matching(dirnames, filenames),
+            );
+        }
+        let mut optional_files = Vec::new();
+        for requirement in &self.optional_files {
+            let names = requirement.matching(dirnames, filenames);
+            if !names.is_empty() {
+                optional_files.push(requirement.glob.clone());
+                matched_files.insert(requirement.glob.clone(), names);
+            }
+        }
+        let optional_directories = self
+            .optional_subpatterns
+            .iter()
+            .filter(|subpattern| {
+                subpattern
+                    .candidates(dir, tree, self.follow_symlinked_dirs)
+                    .iter()
+                    .any(|subdir| subpattern.matches_in(subdir, tree))
+            })
+            .map(|subpattern| subpattern.directory_name.clone())
+            .collect();
+        let branch = self
+            .any_of
+            .iter()
+            .position(|alternative| alternative.matches_in(dir, tree));
+        let mut bindings = HashMap::new();
+        for (role, target) in &self.roles {
+            let mut paths: Vec<String> = match matched_files.get(target) {
+                Some(names) => names
+                    .iter()
+                    .map(|name| dir.join(name).to_string_lossy().into_owned())
+                    .collect(),
+                None => self
+                    .subpatterns
+                    .iter()
+                    .chain(&self.optional_subpatterns)
+                    .filter(|subpattern| subpattern.directory_name == *target)
+                    .flat_map(|subpattern| {
+                        subpattern
+                            .candidates(dir, tree, self.follow_symlinked_dirs)
+                            .into_iter()
+                            .filter(|subdir| subpattern.matches_in(subdir, tree))
+                            .map(|subdir| subdir.to_string_lossy().into_owned())
+                    })
+                    .collect(),
+            };
+            paths.sort();
+            paths.dedup();
+            bindings.insert(role.clone(), paths);
+        }
+        let name = dir.file_name().unwrap_or_default();
+        let captures = match &self.directory_name_matcher {
+            Some(matcher) => matcher.captures(name),
+            // A bare `*` isn't compiled, but captures the whole name
+            None if self.directory_name == "*" => vec![name.to_string_lossy().into_owned()],
+            None => Vec::new(),
+        };
+        Some(StructureMatch {
+            matched_files,
+            optional_files,
+            optional_directories,
+            branch,
+            bindings,
+            captures,
+        })
+    }
+
+    /// Check if a directory, and recursively its subdirectories, match
+    ///
+    /// Each required nested pattern must be satisfied by at least one
+    /// subdirectory's own contents, looked up in `tree`.
+    pub fn matches_in(&self, dir: &Path, tree: &dyn DirectoryTree) -> bool {
+        let linked = self.linked_tree(dir, tree);
+        let tree = linked
+            .as_ref()
+            .map_or(tree, |linked| linked as &dyn DirectoryTree);
+        let (dirnames, filenames) = tree.children(dir);
+        let name = dir.file_name().unwrap_or_default();
+        if !self.matches(name, dirnames, filenames)
+            || !self.sizes_allowed(dir, filenames, tree)
+            || !self.ages_allowed(dir, filenames, tree)
+            || !self.contents_allowed(dir, filenames, tree)
+            || !self
+                .empty_directories
+                .iter()
+                .all(|requirement| self.has_empty_directory(requirement, dir, tree))
+        {
+            return false;
+        }
+
+        self.subpatterns.iter().all(|subpattern| {
+            subpattern
+                .candidates(dir, tree, self.follow_symlinked_dirs)
+                .iter()
+                .any(|subdir| subpattern.matches_in(subdir, tree))
+        }) && self.branches_allow(dir, tree)
+            // Walks the whole subtree, so it goes last
+            && self.total_size_allowed(dir, tree)
+    }
+
+    /// Fraction of the requirements the directory satisfies; 1.0 exactly
+    /// when `matches_in` holds
+    pub fn score(&self, dir: &Path, tree: &dyn DirectoryTree) -> f64 {
+        self.assess(dir, tree, None)
+    }
+
+    /// Every requirement the directory fails, empty when it matches
+    pub fn explain(&self, dir: &Path, tree: &dyn DirectoryTree) -> Vec<MatchFailure> {
+        let mut failures = Vec::new();
+        self.assess(dir, tree, Some(&mut failures));
+        failures
+    }
+
+    /// Score the directory, describing each failed requirement in
+    /// `failures` if given
+    ///
+    /// Each requirement earns a credit from 0.0 to 1.0; a required nested
+    /// pattern earns the best score of any subdirectory.
+    fn assess(
+        &self,
+        dir: &Path,
+        tree: &dyn DirectoryTree,
+        mut failures: Option<&mut Vec<MatchFailure>>,
+    ) -> f64 {
+        let linked = self.linked_tree(dir, tree);
+        let tree = linked
+            .as_ref()
+            .map_or(tree, |linked| linked as &dyn DirectoryTree);
+        let (dirnames, filenames) = tree.children(dir);
+        let mut credits = Vec::new();
+        let mut check = |ok: bool, failure: &dyn Fn() -> MatchFailure| {
+            credits.push(if ok { 1.0 } else { 0.0 });
+            if let (false, Some(failures)) = (ok, failures.as_deref_mut()) {
+                failures.push(failure());
+            }
+        };
+
+        if let Some(matcher) = &self.directory_name_matcher {
+            let name = dir.file_name().unwrap_or_default();
+            check(matcher.is_match_os(name, true), &|| {
+                MatchFailure::new(
+                    "directory_name",
+                    &self.directory_name,
+                    dir,
+                    format!(
+                        "name '{}' does not match '{}'",
+                        name.to_string_lossy(),
+                        self.directory_name
+                    ),
+                )
+            });
+        }
+        for requirement in &self.files {
+            check(requirement.is_met(dirnames, filenames), &|| {
+                let kind = if requirement.dir_only {
+                    "subdirectory"
+                } else {
+                    "file"
+                };
+                MatchFailure::new(
+                    "missing_file",
+                    &requirement.glob,
+                    dir,
+                    format!("no {} matches '{}'", kind, requirement.glob),
+                )
+            });
+        }
+        for forbidden in &self.excluded {
+            check(!forbidden.is_met(dirnames, filenames), &|| {
+                let message = match forbidden.matching(dirnames, filenames).first() {
+                    Some(name) => format!("'{}' matches excluded '{}'", name, forbidden.glob),
+                    None => format!("an entry matches excluded '{}'", forbidden.glob),
+                };
+                MatchFailure::new("excluded", &forbidden.glob, dir, message)
+            });
+        }
+        for requirement in &self.empty_directories {
+            check(self.has_empty_directory(requirement, dir, tree), &|| {
+                MatchFailure::new(
+                    "empty_directory",
+                    &requirement.glob,
+                    dir,
+                    format!("no empty subdirectory matches '{}'", requirement.glob),
+                )
+            });
+        }
+        if self.no_extra_files {
+            check(self.extra_file(filenames).is_none(), &|| {
+                let extra = self.extra_file(filenames).unwrap_or_default();
+                MatchFailure::new(
+                    "extra_file",
+                    "",
+                    dir,
+                    format!("'{}' matches no listed glob", extra.to_string_lossy()),
+                )
+            });
+        }
+        if self.min_file_count.is_some() || self.max_file_count.is_some() {
+            let count = filenames.len();
+            check(
+                within(count, self.min_file_count, self.max_file_count),
+                &|| {
+                    MatchFailure::new(
+                        "file_count",
+                        "",
+                        dir,
+                        format!(
+                            "holds {} files, expected {}",
+                            count,
+                            describe_limits(self.min_file_count, self.max_file_count)
+                        ),
+                    )
+                },
+            );
+        }
+        for (requirement, constraint) in &self.constraints {
+            let count = requirement.count(dirnames, filenames);
+            check(constraint.allows_count(count), &|| {
+                MatchFailure::new(
+                    "constraint",
+                    &requirement.glob,
+                    dir,
+                    format!(
+                        "{} entries match '{}', expected {}",
+                        count,
+                        requirement.glob,
+                        describe_limits(constraint.min_count, constraint.max_count)
+                    ),
+                )
+            });
+        }
+        if self
+            .constraints
+            .iter()
+            .any(|(requirement, constraint)| !requirement.dir_only && constraint.has_size_limits())
+        {
+            check(self.sizes_allowed(dir, filenames, tree), &|| {
+                MatchFailure::new(
+                    "size",
+                    "",
+                    dir,
+                    "a file is outside the size limits for its glob".to_string(),
+                )
+            });
+        }
+        if self.constraints.iter().any(|(requirement, constraint)| {
+            !requirement.dir_only && constraint.has_content_limits()
+        }) {
+            check(self.contents_allowed(dir, filenames, tree), &|| {
+                MatchFailure::new(
+                    "content",
+                    "",
+                    dir,
+                    "a file's content doesn't meet the content limits for its glob".to_string(),
+                )
+            });
+        }
+        if self.newer_than.is_some() || self.older_than.is_some() || self.stable_for.is_some() {
+            check(self.ages_allowed(dir, filenames, tree), &|| {
+                MatchFailure::new(
+                    "age",
+                    "",
+                    dir,
+                    "the directory or its files are outside the age limits".to_string(),
+                )
+            });
+        }
+        if self.min_total_size.is_some() || self.max_total_size.is_some() {
+            check(self.total_size_allowed(dir, tree), &|| {
+                MatchFailure::new(
+                    "total_size",
+                    "",
+                    dir,
+                    "the directory's total size is outside the total size limits".to_string(),
+                )
+            });
+        }
+
+        for subpattern in &self.subpatterns {
+            let best = subpattern
+                .candidates(dir, tree, self.follow_symlinked_dirs)
+                .into_iter()
+                .map(|subdir| (subpattern.score(&subdir, tree), subdir))
+                // Ties go to the first path, whatever the listing order
+                .max_by(|a, b| a.0.total_cmp(&b.0).then_with(|| b.1.cmp(&a.1)));
+            let score = best.as_ref().map_or(0.0, |(score, _)| *score);
+            credits.push(score);
+            if let (true, Some(failures)) = (score < 1.0, failures.as_deref_mut()) {
+                match best {
+                    // Report what the closest subdirectory lacks
+                    Some((score, subdir)) if score > 0.0 => {
+                        subpattern.assess(&subdir, tree, Some(failures));
+                    }
+                    _ => failures.push(MatchFailure::new(
+                        "missing_directory",
+                        &subpattern.directory_name,
+                        dir,
+                        format!(
+                            "no subdirectory matches pattern '{}'",
+                            subpattern.directory_name
+                        ),
+                    )),
+                }
+            }
+        }
+        if !self.any_of.is_empty() {
+            let score = self
+                .any_of
+                .iter()
+                .map(|alternative| alternative.score(dir, tree))
+                .fold(0.0, f64::max);
+            credits.push(score);
+            if let (true, Some(failures)) = (score < 1.0, failures.as_deref_mut()) {
+                failures.push(MatchFailure::new(
+                    "any_of",
+                    "",
+                    dir,
+                    "none of the any_of alternatives match".to_string(),
+                ));
+            }
+        }
+        for required in &self.all_of {
+            credits.push(required.assess(dir, tree, failures.as_deref_mut()));
+        }
+        for forbidden in &self.none_of {
+            let matched = forbidden.matches_in(dir, tree);
+            credits.push(if matched { 0.0 } else { 1.0 });
+            if let (true, Some(failures)) = (matched, failures.as_deref_mut()) {
+                let name = forbidden
+                    .pattern_name
+                    .as_deref()
+                    .unwrap_or(&forbidden.directory_name);
+                failures.push(MatchFailure::new(
+                    "none_of",
+                    name,
+                    dir,
+                    format!("matches none_of pattern '{}'", name),
+                ));
+            }
+        }
+
+        if credits.is_empty() {
+            return 1.0;
+        }
+        credits.iter().sum::<f64>() / credits.len() as f64
+    }
+
+    /// Whether the directory satisfies the `any_of`/`all_of`/`none_of`
+    /// combinators
+    fn branches_allow(&self, dir: &Path, tree: &dyn DirectoryTree) -> bool {
+        (self.any_of.is_empty() || self.any_of.iter().any(|p| p.matches_in(dir, tree)))
+            && self.all_of.iter().all(|p| p.matches_in(dir, tree))
+            && !self.none_of.iter().any(|p| p.matches_in(dir, tree))
+    }
+
+    /// Subdirectories of `dir` this pattern may match when nested in
+    /// another: the direct ones, or every one beneath `dir` if it is a
+    /// `**/` pattern. Symlinked ones are left out unless `follow_links`.
+    fn candidates(&self, dir: &Path, tree: &dyn DirectoryTree, follow_links: bool) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(parent) = pending.pop() {
+            for dirname in tree.children(&parent).0 {
+                let subdir = parent.join(dirname);
+                if !follow_links && tree.is_symlink(&subdir) {
+                    continue;
+                }
+                if self.any_depth {
+                    pending.push(subdir.clone());
+                }
+                found.push(subdir);
+            }
+        }
+        found
+    }
+
+    /// `tree` with the symlinked subdirectories of `dir` it leaves out
+    /// read in, if this pattern follows them and there are any
+    fn linked_tree<'a>(&self, dir: &Path, tree: &'a dyn DirectoryTree) -> Option<LinkedTree<'a>> {
+        if !self.follow_symlinked_dirs
+            || (self.subpatterns.is_empty() && self.optional_subpatterns.is_empty())
+        {
+            return None;
+        }
+        let (dirnames, filenames) = tree.children(dir);
+        let links: Vec<OsString> = tree
+            .linked_dirs(dir)
+            .into_iter()
+            .filter(|name| !dirnames.contains(name))
+            .collect();
+        if links.is_empty() {
+            return None;
+        }
+        let mut listings = WalkedTree::new();
+        let levels = self.depth().saturating_sub(1);
+        for name in &links {
+            read_subtree(&dir.join(name), levels, true, &mut listings);
+        }
+        listings.insert(
+            dir.to_path_buf(),
+            (
+                filenames.iter().cloned().collect(),
+                dirnames.iter().cloned().chain(links).collect(),
+            ),
+        );
+        Some(LinkedTree {
+            base: tree,
+            listings,
+        })
+    }
+
+    /// Whether a subdirectory matching `requirement` has no entries
+    fn has_empty_directory(
+        &self,
+        requirement: &FileRequirement,
+        dir: &Path,
+        tree: &dyn DirectoryTree,
+    ) -> bool {
+        let (dirnames, _) = tree.children(dir);
+        dirnames.iter().any(|dirname| {
+            if !requirement.matcher.is_match_os(dirname, true) {
                 return false;
             }
+            let (subdirs, files) = tree.children(&dir.join(dirname));
+            subdirs.is_empty() && files.is_empty()
+        })
+    }
+
+    /// The first file no glob in `files`, `optional_files` or
+    /// `file_constraints` matches
+    fn extra_file<'a, S: AsRef<OsStr>>(&self, filenames: &'a [S]) -> Option<&'a OsStr> {
+        let listed = self
+            .files
+            .iter()
+            .chain(&self.optional_files)
+            .chain(self.constraints.iter().map(|(requirement, _)| requirement))
+            .filter(|requirement| !requirement.dir_only);
+        filenames.iter().map(AsRef::as_ref).find(|name| {
+            !listed
+                .clone()
+                .any(|glob| glob.matcher.is_match_os(name, false))
+        })
+    }
+
+    /// Whether the files beneath the directory add up to within the total
+    /// size limits
+    fn total_size_allowed(&self, dir: &Path, tree: &dyn DirectoryTree) -> bool {
+        if self.min_total_size.is_none() && self.max_total_size.is_none() {
+            return true;
         }
+        tree.total_size(dir)
+            .is_some_and(|total| within(total, self.min_total_size, self.max_total_size))
+    }
 
-        // Check required file patterns - each must match at least one file
-        for matcher in &self.file_matchers {
-            let has_match = filenames.iter().any(|filename| matcher.matches(filename));
-            if !has_match {
+    /// Whether the directory and its files are within the age limits
+    fn ages_allowed(&self, dir: &Path, filenames: &[OsString], tree: &dyn DirectoryTree) -> bool {
+        if self.newer_than.is_none() && self.older_than.is_none() && self.stable_for.is_none() {
+            return true;
+        }
+        let now = SystemTime::now();
+        // Modified in the future counts as modified just now
+        let age = |modified: SystemTime| now.duration_since(modified).unwrap_or_default();
+        if self.newer_than.is_some() || self.older_than.is_some() {
+            let Some(dir_age) = tree.modified(dir).map(age) else {
+                return false;
+            };
+            if !within(dir_age, self.older_than, self.newer_than) {
                 return false;
             }
         }
+        self.stable_for.map_or(true, |stable_for| {
+            filenames.iter().all(|name| {
+                tree.modified(&dir.join(name))
+                    .is_some_and(|modified| age(modified) >= stable_for)
+            })
+        })
+    }
 
-        // Check required subdirectory patterns - each must match at least one subdirectory
-        for matcher in &self.subdir_matchers {
-            let has_match = dirnames.iter().any(|dirname| matcher.matches(dirname));
-            if !has_match {
+    /// Whether every file matching a size-limited glob is within its limits
+    fn sizes_allowed(&self, dir: &Path, filenames: &[OsString], tree: &dyn DirectoryTree) -> bool {
+        self.constraints
+            .iter()
+            .filter(|(requirement, constraint)| {
+                !requirement.dir_only && constraint.has_size_limits()
+            })
+            .all(|(requirement, constraint)| {
+                filenames
+                    .iter()
+                    .filter(|name| requirement.matcher.is_match_os(name, false))
+                    .all(|name| {
+                        tree.file_size(&dir.join(name))
+                            .is_some_and(|size| constraint.allows_size(size))
+                    })
+            })
+    }
+
+    /// Whether every file matching a glob with content limits meets them,
+    /// reading at most `CONTENT_READ_LIMIT` bytes of each
+    fn contents_allowed(
+        &self,
+        dir: &Path,
+        filenames: &[OsString],
+        tree: &dyn DirectoryTree,
+    ) -> bool {
+        self.constraints
+            .iter()
+            .filter(|(requirement, constraint)| {
+                !requirement.dir_only && constraint.has_content_limits()
+            })
+            .all(|(requirement, constraint)| {
+                filenames
+                    .iter()
+                    .filter(|name| requirement.matcher.is_match_os(name, false))
+                    .all(|name| {
+                        tree.read_head(&dir.join(name), constraint.content_read_len())
+                            .is_some_and(|head| constraint.allows_content(&head))
+                    })
+            })
+    }
+
+    /// Check a directory's own name and entries, without recursing
+    ///
+    /// This is MUCH faster than recompiling patterns on every check.
+    /// No pattern compilation happens here - just matching against precompiled DFAs.
+    /// Names can be `OsString`s straight from the walker or plain `String`s.
+    pub fn matches<S: AsRef<OsStr>>(
+        &self,
+        dirpath_name: &OsStr,
+        dirnames: &[S],
+        filenames: &[S],
+    ) -> bool {
+        // Check directory name with precompiled matcher
+        if let Some(ref matcher) = self.directory_name_matcher {
+            if !matcher.is_match_os(dirpath_name, true) {
                 return false;
             }
         }
 
-        true
+        // Check required file patterns - each must match at least one entry
+        // and no forbidden glob may match any
+        self.files
+            .iter()
+            .all(|requirement| requirement.is_met(dirnames, filenames))
+            && !self
+                .excluded
+                .iter()
+                .any(|forbidden| forbidden.is_met(dirnames, filenames))
+            && within(filenames.len(), self.min_file_count, self.max_file_count)
+            && !(self.no_extra_files && self.extra_file(filenames).is_some())
+            && self.constraints.iter().all(|(requirement, constraint)| {
+                constraint.allows_count(requirement.count(dirnames, filenames))
+            })
     }
 }
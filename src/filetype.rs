@@ -0,0 +1,365 @@
+use pyo3::prelude::*;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::pattern::PatternMatcher;
+use crate::walk::{
+    check_root, exclude_matcher, is_excluded, scan_walker, thread_count, Collector, Failure,
+};
+
+/// A file type recognised by its leading bytes
+pub struct FileType {
+    /// Short name, e.g. ``"gzip"``
+    pub name: &'static str,
+    /// MIME type, e.g. ``"application/gzip"``
+    pub mime: &'static str,
+    /// Byte strings the file has at the given offsets; any one identifies it
+    signatures: &'static [(usize, &'static [u8])],
+    /// Extra bytes that must appear at an offset, for container formats
+    /// such as RIFF whose first bytes are shared
+    subtype: Option<(usize, &'static [u8])>,
+}
+
+impl FileType {
+    /// Whether a file starting with `head` is of this type
+    pub fn matches(&self, head: &[u8]) -> bool {
+        let at = |offset: usize, bytes: &[u8]| {
+            head.get(offset..offset + bytes.len())
+                .is_some_and(|found| found == bytes)
+        };
+        self.signatures
+            .iter()
+            .any(|&(offset, bytes)| at(offset, bytes))
+            && self
+                .subtype
+                .map_or(true, |(offset, bytes)| at(offset, bytes))
+    }
+}
+
+const fn file_type(
+    name: &'static str,
+    mime: &'static str,
+    signatures: &'static [(usize, &'static [u8])],
+) -> FileType {
+    FileType {
+        name,
+        mime,
+        signatures,
+        subtype: None,
+    }
+}
+
+const fn riff(name: &'static str, mime: &'static str, form: &'static [u8]) -> FileType {
+    FileType {
+        name,
+        mime,
+        signatures: &[(0, b"RIFF")],
+        subtype: Some((8, form)),
+    }
+}
+
+/// Types `type` requirements can name, and ``detect_types`` reports; a
+/// file is the first type in the list that it matches
+pub static FILE_TYPES: &[FileType] = &[
+    file_type("png", "image/png", &[(0, b"\x89PNG\r\n\x1a\n")]),
+    file_type("jpeg", "image/jpeg", &[(0, b"\xff\xd8\xff")]),
+    file_type("gif", "image/gif", &[(0, b"GIF87a"), (0, b"GIF89a")]),
+    file_type(
+        "tiff",
+        "image/tiff",
+        &[
+            (0, b"II*\x00"),
+            (0, b"MM\x00*"),
+            (0, b"II+\x00"),
+            (0, b"MM\x00+"),
+        ],
+    ),
+    file_type("bmp", "image/bmp", &[(0, b"BM")]),
+    riff("webp", "image/webp", b"WEBP"),
+    riff("wav", "audio/wav", b"WAVE"),
+    riff("avi", "video/x-msvideo", b"AVI "),
+    file_type("flac", "audio/flac", &[(0, b"fLaC")]),
+    file_type("ogg", "audio/ogg", &[(0, b"OggS")]),
+    file_type("mp4", "video/mp4", &[(4, b"ftyp")]),
+    file_type("mp3", "audio/mpeg", &[(0, b"ID3"), (0, b"\xff\xfb")]),
+    file_type("matroska", "video/x-matroska", &[(0, b"\x1a\x45\xdf\xa3")]),
+    file_type(
+        "psd",
+        "image/vnd.adobe.photoshop",
+        &[(0, b"8BPS\x00\x01"), (0, b"8BPS\x00\x02")],
+    ),
+    file_type("pdf", "application/pdf", &[(0, b"%PDF-")]),
+    file_type(
+        "zip",
+        "application/zip",
+        &[(0, b"PK\x03\x04"), (0, b"PK\x05\x06")],
+    ),
+    file_type("gzip", "application/gzip", &[(0, b"\x1f\x8b")]),
+    file_type("bzip2", "application/x-bzip2", &[(0, b"BZh")]),
+    file_type("xz", "application/x-xz", &[(0, b"\xfd7zXZ\x00")]),
+    file_type("zstd", "application/zstd", &[(0, b"\x28\xb5\x2f\xfd")]),
+    file_type(
+        "rar",
+        "application/vnd.rar",
+        &[(0, b"Rar!\x1a\x07\x00"), (0, b"Rar!\x1a\x07\x01\x00")],
+    ),
+    file_type(
+        "7z",
+        "application/x-7z-compressed",
+        &[(0, b"7z\xbc\xaf\x27\x1c")],
+    ),
+    file_type("tar", "application/x-tar", &[(257, b"ustar")]),
+    file_type("hdf5", "application/x-hdf5", &[(0, b"\x89HDF\r\n\x1a\n")]),
+    file_type(
+        "netcdf",
+        "application/x-netcdf",
+        &[(0, b"CDF\x01"), (0, b"CDF\x02")],
+    ),
+    file_type("fits", "application/fits", &[(0, b"SIMPLE  =")]),
+    file_type("parquet", "application/vnd.apache.parquet", &[(0, b"PAR1")]),
+    file_type(
+        "sqlite",
+        "application/vnd.sqlite3",
+        &[(0, b"SQLite format 3\x00")],
+    ),
+    file_type("npy", "application/x-npy", &[(0, b"\x93NUMPY")]),
+    file_type(
+        "arrow",
+        "application/vnd.apache.arrow.file",
+        &[(0, b"ARROW1")],
+    ),
+    file_type("dicom", "application/dicom", &[(128, b"DICM")]),
+    file_type("elf", "application/x-elf", &[(0, b"\x7fELF")]),
+    file_type(
+        "exe",
+        "application/vnd.microsoft.portable-executable",
+        &[(0, b"MZ")],
+    ),
+    file_type("wasm", "application/wasm", &[(0, b"\x00asm")]),
+];
+
+/// Bytes of a file read to recognise any of `FILE_TYPES`
+pub const SNIFF_LEN: usize = 264;
+
+/// The type of a file starting with `head`, if it's one of `FILE_TYPES`
+pub fn detect(head: &[u8]) -> Option<&'static FileType> {
+    FILE_TYPES.iter().find(|file_type| file_type.matches(head))
+}
+
+/// The type called `name`, by short name or MIME type, ignoring case
+pub fn lookup(name: &str) -> Option<&'static FileType> {
+    FILE_TYPES.iter().find(|file_type| {
+        file_type.name.eq_ignore_ascii_case(name) || file_type.mime.eq_ignore_ascii_case(name)
+    })
+}
+
+/// Error message for a `type` that names no known type
+pub fn unknown_type(name: &str) -> String {
+    let names: Vec<&str> = FILE_TYPES.iter().map(|file_type| file_type.name).collect();
+    format!(
+        "Unknown file type '{}': expected one of {} or its MIME type",
+        name,
+        names.join(", ")
+    )
+}
+
+/// Type of one file, from ``detect_types``
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct DetectedType {
+    #[pyo3(get)]
+    pub path: String,
+    /// Short name, e.g. ``"png"``, as ``file_type`` constraints name it;
+    /// None if the file isn't a known type
+    #[pyo3(get)]
+    pub name: Option<&'static str>,
+    /// MIME type, e.g. ``"image/png"``; None if the file isn't a known
+    /// type
+    #[pyo3(get)]
+    pub mime: Option<&'static str>,
+}
+
+#[pymethods]
+impl DetectedType {
+    fn __repr__(&self) -> String {
+        let quoted = |value: Option<&str>| value.map_or("None".to_string(), |v| format!("'{}'", v));
+        format!(
+            "DetectedType(path='{}', name={}, mime={})",
+            self.path,
+            quoted(self.name),
+            quoted(self.mime)
+        )
+    }
+}
+
+/// Files and their types, from ``detect_types``
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct DetectedTypes {
+    /// Every file read, sorted by path
+    #[pyo3(get)]
+    pub files: Vec<DetectedType>,
+    /// Files that couldn't be read
+    #[pyo3(get)]
+    pub errors: Vec<Failure>,
+}
+
+#[pymethods]
+impl DetectedTypes {
+    /// Paths by MIME type, leaving out files of no known type
+    fn by_mime(&self) -> BTreeMap<&'static str, Vec<String>> {
+        let mut by_mime: BTreeMap<&'static str, Vec<String>> = BTreeMap::new();
+        for file in &self.files {
+            if let Some(mime) = file.mime {
+                by_mime.entry(mime).or_default().push(file.path.clone());
+            }
+        }
+        by_mime
+    }
+
+    fn __len__(&self) -> usize {
+        self.files.len()
+    }
+
+    fn __repr__(&self) -> String {
+        let known = self.files.iter().filter(|file| file.name.is_some()).count();
+        format!(
+            "DetectedTypes(files={}, known={}, errors={})",
+            self.files.len(),
+            known,
+            self.errors.len()
+        )
+    }
+}
+
+/// Files to read: every file under a directory, or a list of files
+#[derive(FromPyObject)]
+pub enum DetectSource {
+    Root(String),
+    Paths(Vec<String>),
+}
+
+/// Detect the types of files from their magic bytes
+///
+/// Reads the first few hundred bytes of each file, in parallel, and
+/// matches them against the signatures of the types ``file_type``
+/// constraints know - images, audio and video, archives and compressed
+/// streams, scientific formats, databases and executables. Extensions
+/// aren't looked at, so misnamed files are typed by what they hold.
+///
+/// Args:
+///     paths: Directory whose files to type, walked in parallel, or a list
+///         of file paths
+///     threads: Files read at once (default: one per CPU)
+///     max_depth: Optional maximum depth to traverse, for a directory
+///     follow_links: Whether to follow symbolic links, for a directory
+///     exclude: Optional gitignore-style globs, relative to the directory,
+///         for entries to skip, as for ``walk_parallel``
+///
+/// Returns:
+///     DetectedTypes with each file's path, type name and MIME type
+///
+/// Raises:
+///     ValueError: If threads is 0 or an exclude pattern is invalid
+///     WalkError: If ``paths`` is a string that isn't a directory
+#[pyfunction]
+#[pyo3(signature = (paths, threads=None, max_depth=None, follow_links=false, exclude=None))]
+pub fn detect_types(
+    py: Python<'_>,
+    paths: DetectSource,
+    threads: Option<usize>,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    exclude: Option<Vec<String>>,
+) -> PyResult<DetectedTypes> {
+    let threads = thread_count(threads)?;
+    let matcher = exclude_matcher(exclude)?;
+    if let DetectSource::Root(root) = &paths {
+        check_root(root)?;
+    }
+    Ok(py.allow_threads(|| {
+        let collector = Collector::new();
+        let walk_errors =
+            visit_files(
+                paths,
+                threads,
+                max_depth,
+                follow_links,
+                matcher,
+                |path| match sniff(&path) {
+                    Ok(file_type) => collector.push(DetectedType {
+                        path: path.to_string_lossy().into_owned(),
+                        name: file_type.map(|file_type| file_type.name),
+                        mime: file_type.map(|file_type| file_type.mime),
+                    }),
+                    Err(e) => collector.fail(&path, e),
+                },
+            );
+        let (files, errors) = collector.finish(walk_errors, |file| &file.path);
+        DetectedTypes { files, errors }
+    }))
+}
+
+/// Call `visit` with every file `source` names - each file under a
+/// directory, walked in parallel, or each path of a list - from `threads`
+/// threads at once; returns the entries the walk couldn't read
+pub(crate) fn visit_files(
+    source: DetectSource,
+    threads: usize,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    matcher: Option<PatternMatcher>,
+    visit: impl Fn(PathBuf) + Sync,
+) -> Vec<Failure> {
+    let walk = Collector::<()>::new();
+    match source {
+        DetectSource::Root(root) => {
+            let mut builder = scan_walker(&root, max_depth, follow_links);
+            builder.threads(threads);
+            if let Some(matcher) = matcher {
+                let matcher = Arc::new(matcher);
+                let root = PathBuf::from(&root);
+                builder.filter_entry(move |entry| !is_excluded(&matcher, &root, entry));
+            }
+            builder.build_parallel().run(|| {
+                Box::new(|entry| {
+                    match entry {
+                        Ok(entry) if entry.file_type().is_some_and(|t| t.is_file()) => {
+                            visit(entry.into_path())
+                        }
+                        Ok(_) => {}
+                        Err(e) => walk.walk_error(&e),
+                    }
+                    ignore::WalkState::Continue
+                })
+            });
+        }
+        DetectSource::Paths(paths) => {
+            let next = AtomicUsize::new(0);
+            thread::scope(|scope| {
+                for _ in 0..threads.min(paths.len()) {
+                    scope.spawn(|| {
+                        while let Some(path) = paths.get(next.fetch_add(1, Ordering::Relaxed)) {
+                            visit(PathBuf::from(path));
+                        }
+                    });
+                }
+            });
+        }
+    }
+    walk.into_parts().1
+}
+
+/// The type of the file at `path`, from its first `SNIFF_LEN` bytes
+fn sniff(path: &Path) -> io::Result<Option<&'static FileType>> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut head)?;
+    Ok(detect(&head))
+}
@@ -0,0 +1,224 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// How a query is compared against candidate names
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FuzzyMode {
+    /// Query characters must appear in order (fzf style)
+    Subsequence,
+    /// Bounded Levenshtein distance between query and candidate
+    EditDistance,
+}
+
+impl FuzzyMode {
+    fn parse(mode: &str) -> PyResult<Self> {
+        match mode {
+            "subsequence" => Ok(FuzzyMode::Subsequence),
+            "edit" => Ok(FuzzyMode::EditDistance),
+            other => Err(PyValueError::new_err(format!(
+                "Invalid fuzzy mode '{}': expected 'subsequence' or 'edit'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Typo-tolerant lookups over a fixed set of names
+///
+/// Intended for interactive tools that walk a tree once and then let users
+/// search the collected filenames. Candidates are prepared once so each
+/// query only pays for scoring.
+#[pyclass(module = "pathvein._pathvein_rs")]
+pub struct FuzzyMatcher {
+    candidates: Vec<String>,
+    /// Candidates as (possibly case-folded) chars, prepared once
+    prepared: Vec<Vec<char>>,
+    mode: FuzzyMode,
+    max_distance: usize,
+    case_sensitive: bool,
+}
+
+#[pymethods]
+impl FuzzyMatcher {
+    /// Create a FuzzyMatcher over a list of candidate names
+    ///
+    /// Args:
+    ///     candidates: Names to search (e.g. filenames from walk_parallel)
+    ///     mode: "subsequence" for fzf-style scoring or "edit" for
+    ///         bounded edit distance (default: "subsequence")
+    ///     max_distance: Largest edit distance accepted in "edit" mode
+    ///     case_sensitive: Whether letter case must match (default: False)
+    ///
+    /// Returns:
+    ///     FuzzyMatcher instance
+    ///
+    /// Raises:
+    ///     ValueError: If mode is unknown
+    #[new]
+    #[pyo3(signature = (candidates, mode="subsequence", max_distance=2, case_sensitive=false))]
+    pub fn new(
+        candidates: Vec<String>,
+        mode: &str,
+        max_distance: usize,
+        case_sensitive: bool,
+    ) -> PyResult<Self> {
+        let mode = FuzzyMode::parse(mode)?;
+        let prepared = candidates
+            .iter()
+            .map(|candidate| prepare(candidate, case_sensitive))
+            .collect();
+        Ok(FuzzyMatcher {
+            candidates,
+            prepared,
+            mode,
+            max_distance,
+            case_sensitive,
+        })
+    }
+
+    /// Find the candidates that best match a query
+    ///
+    /// Args:
+    ///     name: Query string
+    ///     limit: Maximum number of results (default: 10)
+    ///
+    /// Returns:
+    ///     List of (candidate, score) tuples, best first. Scores are in
+    ///     (0, 1], where 1 is an exact match.
+    #[pyo3(signature = (name, limit=10))]
+    pub fn best_matches(&self, py: Python<'_>, name: &str, limit: usize) -> Vec<(String, f64)> {
+        let query = prepare(name, self.case_sensitive);
+        py.allow_threads(|| {
+            let mut scored: Vec<(usize, f64)> = self
+                .prepared
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, candidate)| {
+                    let score = match self.mode {
+                        FuzzyMode::Subsequence => subsequence_score(&query, candidate),
+                        FuzzyMode::EditDistance => edit_score(&query, candidate, self.max_distance),
+                    };
+                    score.map(|score| (idx, score))
+                })
+                .collect();
+
+            // Best score first, ties broken by name so results are stable
+            scored.sort_by(|a, b| {
+                b.1.total_cmp(&a.1)
+                    .then_with(|| self.candidates[a.0].cmp(&self.candidates[b.0]))
+            });
+            scored.truncate(limit);
+            scored
+                .into_iter()
+                .map(|(idx, score)| (self.candidates[idx].clone(), score))
+                .collect()
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("FuzzyMatcher({} candidates)", self.candidates.len())
+    }
+
+    fn __len__(&self) -> usize {
+        self.candidates.len()
+    }
+}
+
+fn prepare(text: &str, case_sensitive: bool) -> Vec<char> {
+    if case_sensitive {
+        text.chars().collect()
+    } else {
+        text.chars().flat_map(char::to_lowercase).collect()
+    }
+}
+
+/// Characters after which a match counts as a word start
+fn is_boundary(prev: char) -> bool {
+    matches!(prev, '_' | '-' | '.' | ' ' | '/' | '\\')
+}
+
+/// fzf-style subsequence score, or None if query is not a subsequence
+///
+/// Each matched character earns a base point, with bonuses for matching at
+/// the start of the name, right after a word boundary, or directly after the
+/// previous match. Gaps between matches cost a little. The raw score is
+/// normalized by the best possible score for the query.
+fn subsequence_score(query: &[char], candidate: &[char]) -> Option<f64> {
+    if query.is_empty() {
+        return Some(1.0);
+    }
+    if query.len() > candidate.len() {
+        return None;
+    }
+    if query == candidate {
+        return Some(1.0);
+    }
+
+    const MATCH: f64 = 1.0;
+    const START_BONUS: f64 = 1.0;
+    const BOUNDARY_BONUS: f64 = 0.8;
+    const CONSECUTIVE_BONUS: f64 = 1.0;
+    const GAP_PENALTY: f64 = 0.05;
+
+    let mut score = 0.0;
+    let mut last: Option<usize> = None;
+    let mut pos = 0;
+    for &wanted in query {
+        let found = candidate[pos..].iter().position(|&c| c == wanted)? + pos;
+        score += MATCH;
+        if found == 0 {
+            score += START_BONUS;
+        } else if is_boundary(candidate[found - 1]) {
+            score += BOUNDARY_BONUS;
+        }
+        match last {
+            Some(prev) if found == prev + 1 => score += CONSECUTIVE_BONUS,
+            Some(prev) => score -= GAP_PENALTY * (found - prev - 1) as f64,
+            None => {}
+        }
+        last = Some(found);
+        pos = found + 1;
+    }
+
+    // Leftover characters make a candidate less specific
+    score -= GAP_PENALTY * (candidate.len() - pos) as f64;
+
+    // Best case: every char consecutive from the very start, minus one
+    // bonus so only an exact match reaches 1.0
+    let best = query.len() as f64 * (MATCH + CONSECUTIVE_BONUS) + START_BONUS;
+    Some((score / best).clamp(f64::EPSILON, 1.0 - f64::EPSILON))
+}
+
+/// Edit-distance score, or None if the distance exceeds `max_distance`
+fn edit_score(query: &[char], candidate: &[char], max_distance: usize) -> Option<f64> {
+    let distance = bounded_levenshtein(query, candidate, max_distance)?;
+    let longest = query.len().max(candidate.len()).max(1);
+    Some(1.0 - distance as f64 / longest as f64).filter(|score| *score > 0.0)
+}
+
+/// Levenshtein distance with early exit once `max` is exceeded
+fn bounded_levenshtein(a: &[char], b: &[char], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, &ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        let mut row_min = curr[0];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != cb);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+            row_min = row_min.min(curr[j + 1]);
+        }
+        // Every path through this row already costs more than allowed
+        if row_min > max {
+            return None;
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    let distance = prev[b.len()];
+    (distance <= max).then_some(distance)
+}
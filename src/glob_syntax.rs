@@ -0,0 +1,82 @@
+/// Index of the `]` closing the class opened at `start`
+pub fn class_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    if matches!(chars.get(i), Some('!') | Some('^')) {
+        i += 1;
+    }
+    // A `]` right after the opening is a member, not the end
+    if chars.get(i) == Some(&']') {
+        i += 1;
+    }
+    chars[i.min(chars.len())..]
+        .iter()
+        .position(|&c| c == ']')
+        .map(|offset| i + offset)
+}
+
+/// Parse a class body (between the brackets) into whether it is negated
+/// and its inclusive ranges; single members are one-character ranges
+pub fn class_members(body: &[char]) -> (bool, Vec<(char, char)>) {
+    let (negated, body) = match body.first() {
+        Some('!') | Some('^') => (true, &body[1..]),
+        _ => (false, body),
+    };
+
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < body.len() {
+        if i + 2 < body.len() && body[i + 1] == '-' {
+            ranges.push((body[i], body[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((body[i], body[i]));
+            i += 1;
+        }
+    }
+    (negated, ranges)
+}
+
+/// Index of the `}` closing the alternation opened at `start`
+pub fn brace_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = start;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Split an alternation body on its top-level commas
+pub fn split_alternatives(inner: &str) -> Vec<String> {
+    let mut alternatives = vec![String::new()];
+    let mut depth = 0;
+    let mut escaped = false;
+    for c in inner.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alternatives.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(current) = alternatives.last_mut() {
+            current.push(c);
+        }
+    }
+    alternatives
+}
@@ -0,0 +1,233 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use regex::bytes::{Regex, RegexBuilder};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::filetype::{visit_files, DetectSource};
+use crate::pattern::PatternMatcher;
+use crate::walk::{check_root, exclude_matcher, thread_count, Collector, Failure};
+
+/// Bytes at the start of a file checked for a NUL, as ripgrep does, to
+/// tell binary files from text
+const BINARY_SNIFF: usize = 8192;
+
+/// One matching line, from ``grep_tree``
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct GrepLine {
+    /// 1-based line number
+    #[pyo3(get)]
+    pub line_number: u64,
+    /// The line, without its line ending; invalid UTF-8 is replaced
+    #[pyo3(get)]
+    pub line: String,
+}
+
+#[pymethods]
+impl GrepLine {
+    fn __repr__(&self) -> String {
+        format!(
+            "GrepLine(line_number={}, line={:?})",
+            self.line_number, self.line
+        )
+    }
+}
+
+/// A file with matching lines, from ``grep_tree``
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct GrepFile {
+    #[pyo3(get)]
+    pub path: String,
+    /// Matching lines, in file order
+    #[pyo3(get)]
+    pub lines: Vec<GrepLine>,
+}
+
+#[pymethods]
+impl GrepFile {
+    /// Line numbers of the matching lines
+    #[getter]
+    fn line_numbers(&self) -> Vec<u64> {
+        self.lines.iter().map(|line| line.line_number).collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("GrepFile(path='{}', lines={})", self.path, self.lines.len())
+    }
+}
+
+/// Files under a directory with lines matching a regex, from ``grep_tree``
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct GrepResults {
+    #[pyo3(get)]
+    pub root: String,
+    /// Files with at least one matching line, sorted by path
+    #[pyo3(get)]
+    pub files: Vec<GrepFile>,
+    /// Files that couldn't be searched
+    #[pyo3(get)]
+    pub errors: Vec<Failure>,
+}
+
+#[pymethods]
+impl GrepResults {
+    /// Matching lines across every file
+    #[getter]
+    fn matches(&self) -> usize {
+        self.files.iter().map(|file| file.lines.len()).sum()
+    }
+
+    fn __len__(&self) -> usize {
+        self.files.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "GrepResults(root='{}', files={}, matches={})",
+            self.root,
+            self.files.len(),
+            self.matches()
+        )
+    }
+}
+
+/// Search the files under a directory for lines matching a regex
+///
+/// Files are walked and searched in parallel, a line at a time, as
+/// ripgrep searches them: each worker reads the next file the walk finds,
+/// so nothing is collected before searching starts. Binary files - those
+/// with a NUL byte in their first 8 KiB - are skipped. Lines are matched
+/// as bytes, so files needn't be UTF-8.
+///
+/// Args:
+///     path: Root directory to search
+///     regex: Regular expression, in Rust ``regex`` syntax, searched for
+///         in each line
+///     include: Optional gitignore-style globs, relative to the root, for
+///         the files to search, e.g. ``["*.py", "src/**/*.rs"]`` (default:
+///         every file)
+///     exclude: Optional gitignore-style globs, relative to the root, for
+///         entries to skip, as for ``walk_parallel``
+///     ignore_case: Match letters in either case
+///     max_count: Most matching lines reported per file (default: no
+///         limit)
+///     max_depth: Optional maximum depth to traverse
+///     follow_links: Whether to follow symbolic links
+///     threads: Files searched at once (default: one per CPU)
+///
+/// Returns:
+///     GrepResults with each matching file's path and matching lines
+///
+/// Raises:
+///     ValueError: If the regex or a glob is invalid, or threads or
+///         max_count is 0
+///     WalkError: If ``path`` doesn't exist or isn't a directory
+#[pyfunction]
+#[pyo3(signature = (
+    path,
+    regex,
+    include=None,
+    exclude=None,
+    ignore_case=false,
+    max_count=None,
+    max_depth=None,
+    follow_links=false,
+    threads=None,
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn grep_tree(
+    py: Python<'_>,
+    path: String,
+    regex: &str,
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+    ignore_case: bool,
+    max_count: Option<usize>,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    threads: Option<usize>,
+) -> PyResult<GrepResults> {
+    let threads = thread_count(threads)?;
+    if max_count == Some(0) {
+        return Err(PyValueError::new_err("max_count must be at least 1"));
+    }
+    let regex = RegexBuilder::new(regex)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| PyValueError::new_err(format!("Invalid regex '{}': {}", regex, e)))?;
+    check_root(&path)?;
+    let include = exclude_matcher(include)?;
+    let exclude = exclude_matcher(exclude)?;
+    Ok(py.allow_threads(|| {
+        let root = PathBuf::from(&path);
+        let collector = Collector::new();
+        let walk_errors = visit_files(
+            DetectSource::Root(path.clone()),
+            threads,
+            max_depth,
+            follow_links,
+            exclude,
+            |file| {
+                if !included(include.as_ref(), &root, &file) {
+                    return;
+                }
+                match search_file(&file, &regex, max_count) {
+                    Ok(lines) if lines.is_empty() => {}
+                    Ok(lines) => collector.push(GrepFile {
+                        path: file.to_string_lossy().into_owned(),
+                        lines,
+                    }),
+                    Err(e) => collector.fail(&file, e),
+                }
+            },
+        );
+        let (files, errors) = collector.finish(walk_errors, |file| &file.path);
+        GrepResults {
+            root: path,
+            files,
+            errors,
+        }
+    }))
+}
+
+/// Whether a file is one ``include`` asks for
+fn included(include: Option<&PatternMatcher>, root: &Path, path: &Path) -> bool {
+    include.map_or(true, |include| {
+        include.is_match_path(path.strip_prefix(root).unwrap_or(path), false)
+    })
+}
+
+/// Lines of a file matching `regex`, up to `max_count`; none for binary
+/// files
+fn search_file(path: &Path, regex: &Regex, max_count: Option<usize>) -> io::Result<Vec<GrepLine>> {
+    let mut reader = BufReader::with_capacity(64 * 1024, File::open(path)?);
+    let head = reader.fill_buf()?;
+    if head[..head.len().min(BINARY_SNIFF)].contains(&0) {
+        return Ok(Vec::new());
+    }
+    let mut lines = Vec::new();
+    let mut line = Vec::new();
+    let mut line_number = 0;
+    while max_count.map_or(true, |max| lines.len() < max) {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        line_number += 1;
+        let text = line
+            .strip_suffix(b"\n")
+            .map(|text| text.strip_suffix(b"\r").unwrap_or(text))
+            .unwrap_or(&line);
+        if regex.is_match(text) {
+            lines.push(GrepLine {
+                line_number,
+                line: String::from_utf8_lossy(text).into_owned(),
+            });
+        }
+    }
+    Ok(lines)
+}
@@ -0,0 +1,249 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::file_pattern::{CompiledPattern, StructureMatch};
+use crate::walk::{
+    check_root, compile_patterns, evaluate_dir, read_listing, ScanResult, ScanResults, WalkedTree,
+};
+
+/// Bumped whenever the cache layout changes; older caches are ignored
+const CACHE_VERSION: u32 = 1;
+
+/// Directories modified this close to the previous scan are re-read, since
+/// a change in the same timestamp tick would leave the mtime unchanged
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// On-disk state of the previous `scan_incremental` run
+#[derive(Serialize, Deserialize, Default)]
+struct ScanCache {
+    version: u32,
+    /// Pattern JSONs and `first_match` the outcomes were computed with
+    patterns: Vec<String>,
+    first_match: bool,
+    /// Nanoseconds since the epoch when the scan started
+    scanned_at: u64,
+    directories: HashMap<String, CachedDirectory>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CachedDirectory {
+    /// Directory mtime, in nanoseconds since the epoch; the fingerprint of
+    /// its listing
+    modified: u64,
+    files: Vec<String>,
+    dirs: Vec<String>,
+    matches: Vec<(usize, StructureMatch)>,
+}
+
+/// Scan a directory tree, reusing the previous run's work where nothing changed
+///
+/// Keeps each directory's listing and match outcome in a cache file keyed
+/// by the directory's modification time, which changes whenever entries are
+/// added, removed or renamed. Unchanged directories are not re-read, and
+/// their outcomes are reused unless a directory the patterns look into has
+/// changed. Patterns with size, age or content limits are always
+/// re-evaluated, since those depend on more than listings.
+///
+/// Results are the same as ``scan_parallel`` with ``overlap="all"``. The
+/// cache file is created if missing and rewritten after every scan; a cache
+/// from other patterns still saves the directory reads.
+///
+/// Args:
+///     path: Root directory to scan
+///     pattern_jsons: List of JSON-serialized FileStructurePattern objects
+///     cache_path: File the scan state is kept in between runs
+///     max_depth: Optional maximum depth to traverse
+///     follow_links: Whether to follow symbolic links
+///     first_match: Attribute each directory only to the first pattern, in
+///         list order, that matches it
+///
+/// Returns:
+///     ScanResults for directories that matched
+///
+/// Raises:
+///     PatternSyntaxError: If a pattern is invalid
+///     WalkError: If ``path`` doesn't exist or isn't a directory
+///     ValueError: If the cache can't be written
+#[pyfunction]
+#[pyo3(signature = (path, pattern_jsons, cache_path, max_depth=None, follow_links=false, first_match=false))]
+pub fn scan_incremental(
+    py: Python<'_>,
+    path: PathBuf,
+    pattern_jsons: Vec<String>,
+    cache_path: PathBuf,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    first_match: bool,
+) -> PyResult<ScanResults> {
+    check_root(&path.to_string_lossy())?;
+    let patterns = compile_patterns(&pattern_jsons)?;
+    py.allow_threads(|| {
+        let previous = load_cache(&cache_path);
+        let reuse_outcomes = previous.patterns == pattern_jsons
+            && previous.first_match == first_match
+            && !patterns.iter().any(CompiledPattern::needs_stat);
+
+        let mut scan = IncrementalScan {
+            previous: &previous,
+            max_depth,
+            follow_links,
+            tree: WalkedTree::new(),
+            modified: HashMap::new(),
+            changed: Vec::new(),
+            visiting: HashSet::new(),
+        };
+        let scanned_at = epoch_nanos(SystemTime::now());
+        scan.visit(&path, 0);
+        let root = path.to_string_lossy().into_owned();
+
+        // A match looks `depth` levels down, so a change invalidates the
+        // outcomes of that many ancestors
+        let depth = patterns
+            .iter()
+            .map(CompiledPattern::depth)
+            .max()
+            .unwrap_or(0);
+        let stale: HashSet<&Path> = scan
+            .changed
+            .iter()
+            .flat_map(|dir| dir.ancestors().take(depth.saturating_add(1)))
+            .collect();
+
+        let mut cache = ScanCache {
+            version: CACHE_VERSION,
+            patterns: pattern_jsons.clone(),
+            first_match,
+            scanned_at,
+            directories: HashMap::new(),
+        };
+        let mut results = Vec::new();
+        for (dir, (files, dirs)) in &scan.tree {
+            let key = dir.to_string_lossy().into_owned();
+            let cached = previous.directories.get(&key);
+            let matches = match cached {
+                Some(cached) if reuse_outcomes && !stale.contains(dir.as_path()) => {
+                    cached.matches.clone()
+                }
+                _ => evaluate_dir(dir, &scan.tree, &patterns, first_match),
+            };
+            let path = dir.to_string_lossy().into_owned();
+            for (pattern_idx, found) in &matches {
+                results.push(ScanResult::new(
+                    path.clone(),
+                    root.clone(),
+                    *pattern_idx,
+                    &patterns[*pattern_idx],
+                    found.clone(),
+                ));
+            }
+            cache.directories.insert(
+                key,
+                CachedDirectory {
+                    modified: scan.modified[dir],
+                    files: files
+                        .iter()
+                        .map(|f| f.to_string_lossy().into_owned())
+                        .collect(),
+                    dirs: dirs
+                        .iter()
+                        .map(|d| d.to_string_lossy().into_owned())
+                        .collect(),
+                    matches,
+                },
+            );
+        }
+        save_cache(&cache_path, &cache)?;
+        Ok(ScanResults::new(results, vec![root], &patterns))
+    })
+}
+
+struct IncrementalScan<'a> {
+    previous: &'a ScanCache,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    tree: WalkedTree,
+    /// mtime of every listed directory
+    modified: HashMap<PathBuf, u64>,
+    /// Directories whose listing was read rather than taken from the cache
+    changed: Vec<PathBuf>,
+    /// Canonical paths on the current branch, to stop symlink loops
+    visiting: HashSet<PathBuf>,
+}
+
+impl IncrementalScan<'_> {
+    fn visit(&mut self, dir: &Path, depth: usize) {
+        if self.max_depth.is_some_and(|max| depth >= max) {
+            return;
+        }
+        let canonical = if self.follow_links {
+            match fs::canonicalize(dir) {
+                Ok(canonical) if self.visiting.insert(canonical.clone()) => Some(canonical),
+                _ => return,
+            }
+        } else {
+            None
+        };
+
+        let modified = fs::metadata(dir)
+            .and_then(|metadata| metadata.modified())
+            .map_or(0, epoch_nanos);
+        let racy_since = self
+            .previous
+            .scanned_at
+            .saturating_sub(RACY_WINDOW.as_nanos() as u64);
+        let listing = match self.previous.directories.get(&*dir.to_string_lossy()) {
+            Some(cached) if cached.modified == modified && modified < racy_since => (
+                cached.files.iter().map(OsString::from).collect(),
+                cached.dirs.iter().map(OsString::from).collect(),
+            ),
+            _ => {
+                self.changed.push(dir.to_path_buf());
+                read_listing(dir, self.follow_links)
+            }
+        };
+        let subdirs: SmallVec<[OsString; 8]> = listing.1.clone();
+        self.tree.insert(dir.to_path_buf(), listing);
+        self.modified.insert(dir.to_path_buf(), modified);
+
+        for name in &subdirs {
+            self.visit(&dir.join(name), depth + 1);
+        }
+        if let Some(canonical) = canonical {
+            self.visiting.remove(&canonical);
+        }
+    }
+}
+
+/// The previous scan's state, or an empty cache if there is none usable
+fn load_cache(path: &Path) -> ScanCache {
+    fs::read(path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<ScanCache>(&bytes).ok())
+        .filter(|cache| cache.version == CACHE_VERSION)
+        .unwrap_or_default()
+}
+
+/// Write the cache next to its final path and move it into place, so an
+/// interrupted scan never leaves a truncated cache
+fn save_cache(path: &Path, cache: &ScanCache) -> PyResult<()> {
+    let write_error = |e: std::io::Error| {
+        PyValueError::new_err(format!("Cannot write scan cache {}: {}", path.display(), e))
+    };
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let bytes = serde_json::to_vec(cache).expect("scan cache serialization cannot fail");
+    fs::write(&temporary, bytes).map_err(write_error)?;
+    fs::rename(&temporary, path).map_err(write_error)
+}
+
+fn epoch_nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as u64)
+}
@@ -0,0 +1,193 @@
+use pyo3::prelude::*;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::file_pattern::FileStructurePattern;
+use crate::walk::read_listing;
+
+/// Infer a FileStructurePattern from an example directory
+///
+/// Every file of the example becomes a required file glob and every
+/// subdirectory a required nested pattern, down to ``max_depth`` levels.
+/// With ``generalize``, runs of digits, and dates and times written with
+/// ``-``, ``_``, ``.`` or ``:`` between them, become ``*`` (``run_001``
+/// becomes ``run_*``; file extensions are kept), and sibling directories
+/// that generalize to the same glob are merged into one nested pattern
+/// requiring only what they all share.
+///
+/// The result is a starting point: review it, then mark components
+/// optional or loosen globs as needed.
+///
+/// Args:
+///     path: Example directory
+///     generalize: Replace numbers and dates in names with ``*``
+///         (default: True)
+///     max_depth: Levels of subdirectories to describe (default: all)
+///     follow_links: Whether to follow symbolic links
+///
+/// Returns:
+///     FileStructurePattern matching the example
+#[pyfunction]
+#[pyo3(signature = (path, generalize=true, max_depth=None, follow_links=false))]
+pub fn infer_pattern(
+    py: Python<'_>,
+    path: PathBuf,
+    generalize: bool,
+    max_depth: Option<usize>,
+    follow_links: bool,
+) -> FileStructurePattern {
+    py.allow_threads(|| {
+        let inference = Inference {
+            generalize,
+            follow_links,
+        };
+        // A relative path like "." has no name of its own
+        let resolved = std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        let name = resolved
+            .file_name()
+            .map(|name| inference.glob(&name.to_string_lossy(), false))
+            .unwrap_or_else(|| "*".to_string());
+        FileStructurePattern {
+            directory_name: name,
+            ..inference.describe(&path, max_depth)
+        }
+    })
+}
+
+struct Inference {
+    generalize: bool,
+    follow_links: bool,
+}
+
+impl Inference {
+    /// Pattern for the contents of `dir`, with `directory_name` left as "*"
+    fn describe(&self, dir: &Path, levels: Option<usize>) -> FileStructurePattern {
+        let (filenames, dirnames) = read_listing(dir, self.follow_links);
+        let mut files: Vec<String> = filenames
+            .iter()
+            .map(|name| self.glob(&name.to_string_lossy(), true))
+            .collect();
+        files.sort();
+        files.dedup();
+
+        let mut directories = Vec::new();
+        if levels != Some(0) {
+            let mut groups: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+            for name in &dirnames {
+                groups
+                    .entry(self.glob(&name.to_string_lossy(), false))
+                    .or_default()
+                    .push(dir.join(name));
+            }
+            for (glob, members) in groups {
+                let shared = members
+                    .iter()
+                    .map(|member| self.describe(member, levels.map(|n| n - 1)))
+                    .reduce(|a, b| shared(&a, &b))
+                    .expect("groups are never empty");
+                directories.push(FileStructurePattern {
+                    directory_name: glob,
+                    ..shared
+                });
+            }
+        }
+
+        FileStructurePattern {
+            files,
+            directories,
+            ..FileStructurePattern::default()
+        }
+    }
+
+    /// Glob matching `name`, generalized if requested
+    fn glob(&self, name: &str, is_file: bool) -> String {
+        // Keep a file's extension as written: the digits in "mp4" or "h5"
+        // are part of its type, not a counter
+        let (stem, extension) = match name.rfind('.') {
+            Some(dot) if is_file && dot > 0 && is_extension(&name[dot + 1..]) => name.split_at(dot),
+            _ => (name, ""),
+        };
+        let stem = if self.generalize {
+            generalize(stem)
+        } else {
+            escape(stem)
+        };
+        stem + &escape(extension)
+    }
+}
+
+fn is_extension(text: &str) -> bool {
+    !text.is_empty() && text.len() <= 5 && text.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Escape `name` and replace each run of digits with `*`, merging runs
+/// joined by date and time separators into one
+fn generalize(name: &str) -> String {
+    let mut glob = String::new();
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !c.is_ascii_digit() {
+            push_escaped(&mut glob, c);
+            continue;
+        }
+        while chars.peek().is_some_and(char::is_ascii_digit) {
+            chars.next();
+        }
+        // "*-" followed by this run collapses into the existing "*"
+        let mut tail = glob.chars().rev();
+        match (tail.next(), tail.next()) {
+            (Some('-' | '_' | '.' | ':'), Some('*')) => {
+                glob.pop();
+            }
+            (Some('*'), _) => {}
+            _ => glob.push('*'),
+        }
+    }
+    glob
+}
+
+fn escape(name: &str) -> String {
+    let mut glob = String::new();
+    for c in name.chars() {
+        push_escaped(&mut glob, c);
+    }
+    glob
+}
+
+/// Append `c`, wrapped in a character class if it is glob syntax
+fn push_escaped(glob: &mut String, c: char) {
+    if matches!(c, '*' | '?' | '[' | ']' | '{' | '}') {
+        glob.push('[');
+        glob.push(c);
+        glob.push(']');
+    } else {
+        glob.push(c);
+    }
+}
+
+/// What two inferred patterns both require
+fn shared(a: &FileStructurePattern, b: &FileStructurePattern) -> FileStructurePattern {
+    FileStructurePattern {
+        files: a
+            .files
+            .iter()
+            .filter(|glob| b.files.contains(glob))
+            .cloned()
+            .collect(),
+        directories: a
+            .directories
+            .iter()
+            .filter_map(|ours| {
+                let theirs = b
+                    .directories
+                    .iter()
+                    .find(|theirs| theirs.directory_name == ours.directory_name)?;
+                Some(FileStructurePattern {
+                    directory_name: ours.directory_name.clone(),
+                    ..shared(ours, theirs)
+                })
+            })
+            .collect(),
+        ..FileStructurePattern::default()
+    }
+}
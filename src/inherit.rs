@@ -0,0 +1,179 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::file_pattern::FileStructurePattern;
+
+/// Merge every `extends` of `pattern`, and of its nested patterns, into it
+///
+/// References are looked up in `library` by name, or loaded as spec files
+/// relative to `base_dir` when they look like one.
+pub fn resolve(
+    pattern: &FileStructurePattern,
+    library: &HashMap<String, FileStructurePattern>,
+    base_dir: &Path,
+) -> Result<FileStructurePattern, String> {
+    Resolver {
+        library,
+        stack: Vec::new(),
+    }
+    .resolve(pattern, base_dir)
+}
+
+struct Resolver<'a> {
+    library: &'a HashMap<String, FileStructurePattern>,
+    /// References being resolved, innermost last, to catch cycles
+    stack: Vec<String>,
+}
+
+impl Resolver<'_> {
+    fn resolve(
+        &mut self,
+        pattern: &FileStructurePattern,
+        base_dir: &Path,
+    ) -> Result<FileStructurePattern, String> {
+        let mut resolved: Option<FileStructurePattern> = None;
+        for reference in &pattern.extends {
+            let base = self.resolve_reference(reference, base_dir)?;
+            resolved = Some(match resolved {
+                Some(earlier) => merge(earlier, base),
+                None => base,
+            });
+        }
+
+        let mut own = pattern.clone();
+        own.extends = Vec::new();
+        for nested in [
+            &mut own.directories,
+            &mut own.optional_directories,
+            &mut own.any_of,
+            &mut own.all_of,
+            &mut own.none_of,
+        ] {
+            for child in nested.iter_mut() {
+                *child = self.resolve(child, base_dir)?;
+            }
+        }
+        Ok(match resolved {
+            Some(base) => merge(base, own),
+            None => own,
+        })
+    }
+
+    fn resolve_reference(
+        &mut self,
+        reference: &str,
+        base_dir: &Path,
+    ) -> Result<FileStructurePattern, String> {
+        let (key, base, base_dir) = if is_file_reference(reference) {
+            let path = base_dir.join(reference);
+            let key = path.to_string_lossy().into_owned();
+            let base = FileStructurePattern::load_file(&path)?;
+            let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            (key, base, dir)
+        } else {
+            let base = self
+                .library
+                .get(reference)
+                .cloned()
+                .ok_or_else(|| format!("Cannot extend unknown pattern '{}'", reference))?;
+            (reference.to_string(), base, base_dir.to_path_buf())
+        };
+        if self.stack.contains(&key) {
+            return Err(format!(
+                "Patterns extend each other in a cycle: {} -> {}",
+                self.stack.join(" -> "),
+                key
+            ));
+        }
+        self.stack.push(key);
+        let resolved = self.resolve(&base, &base_dir);
+        self.stack.pop();
+        resolved
+    }
+}
+
+fn is_file_reference(reference: &str) -> bool {
+    let lower = reference.to_ascii_lowercase();
+    [".json", ".yaml", ".yml", ".toml"]
+        .iter()
+        .any(|extension| lower.ends_with(extension))
+}
+
+/// `child` laid over `base`: lists are concatenated without duplicates and
+/// the child's own settings win
+fn merge(base: FileStructurePattern, child: FileStructurePattern) -> FileStructurePattern {
+    let FileStructurePattern {
+        directory_name,
+        files,
+        directories,
+        optional_files,
+        optional_directories,
+        pattern_name,
+        excluded_files,
+        excluded_directories,
+        empty_directories,
+        no_extra_files,
+        min_file_count,
+        max_file_count,
+        file_constraints,
+        newer_than,
+        older_than,
+        stable_for,
+        min_total_size,
+        max_total_size,
+        any_of,
+        all_of,
+        none_of,
+        extends: _,
+        case_insensitive,
+        follow_symlinked_dirs,
+        priority,
+        roles,
+    } = child;
+    FileStructurePattern {
+        directory_name: if directory_name == "*" {
+            base.directory_name
+        } else {
+            directory_name
+        },
+        files: append(base.files, files),
+        directories: append(base.directories, directories),
+        optional_files: append(base.optional_files, optional_files),
+        optional_directories: append(base.optional_directories, optional_directories),
+        // A name labels one pattern, so it is not inherited
+        pattern_name,
+        excluded_files: append(base.excluded_files, excluded_files),
+        excluded_directories: append(base.excluded_directories, excluded_directories),
+        empty_directories: append(base.empty_directories, empty_directories),
+        no_extra_files: no_extra_files || base.no_extra_files,
+        min_file_count: min_file_count.or(base.min_file_count),
+        max_file_count: max_file_count.or(base.max_file_count),
+        file_constraints: base
+            .file_constraints
+            .into_iter()
+            .chain(file_constraints)
+            .collect::<BTreeMap<_, _>>(),
+        newer_than: newer_than.or(base.newer_than),
+        older_than: older_than.or(base.older_than),
+        stable_for: stable_for.or(base.stable_for),
+        min_total_size: min_total_size.or(base.min_total_size),
+        max_total_size: max_total_size.or(base.max_total_size),
+        any_of: append(base.any_of, any_of),
+        all_of: append(base.all_of, all_of),
+        none_of: append(base.none_of, none_of),
+        extends: Vec::new(),
+        case_insensitive: case_insensitive || base.case_insensitive,
+        follow_symlinked_dirs: follow_symlinked_dirs || base.follow_symlinked_dirs,
+        priority: priority.or(base.priority),
+        roles: base.roles.into_iter().chain(roles).collect(),
+    }
+}
+
+fn append<T: PartialEq>(mut base: Vec<T>, extra: Vec<T>) -> Vec<T> {
+    for item in extra {
+        if !base.contains(&item) {
+            base.push(item);
+        }
+    }
+    base
+}
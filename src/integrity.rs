@@ -0,0 +1,534 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::checksum::Checksum;
+use crate::pattern::PatternMatcher;
+use crate::walk::{
+    check_root, exclude_matcher, is_excluded, scan_walker, thread_count, Collector, Failure,
+};
+
+/// Bytes read from a file at a time while hashing it
+const HASH_BUFFER: usize = 1 << 20;
+
+/// Digest of one file, from ``hash_tree``
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct HashedFile {
+    /// Path relative to the hashed root, with ``/`` separators
+    #[pyo3(get)]
+    pub path: String,
+    #[pyo3(get)]
+    pub size: u64,
+    /// Lowercase hex digest
+    #[pyo3(get)]
+    pub digest: String,
+}
+
+#[pymethods]
+impl HashedFile {
+    fn __repr__(&self) -> String {
+        format!(
+            "HashedFile(path='{}', size={}, digest='{}')",
+            self.path, self.size, self.digest
+        )
+    }
+}
+
+/// Digests of every file under a directory, from ``hash_tree``
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct TreeHashes {
+    #[pyo3(get)]
+    pub root: String,
+    /// ``"blake3"`` or ``"sha256"``
+    #[pyo3(get)]
+    pub algorithm: &'static str,
+    /// Files hashed, sorted by path
+    #[pyo3(get)]
+    pub files: Vec<HashedFile>,
+    /// Files that couldn't be listed or hashed
+    #[pyo3(get)]
+    pub errors: Vec<Failure>,
+}
+
+#[pymethods]
+impl TreeHashes {
+    /// Digests keyed by relative path
+    fn digests(&self) -> HashMap<String, String> {
+        self.files
+            .iter()
+            .map(|file| (file.path.clone(), file.digest.clone()))
+            .collect()
+    }
+
+    /// Bytes hashed
+    #[getter]
+    fn bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.size).sum()
+    }
+
+    fn __len__(&self) -> usize {
+        self.files.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "TreeHashes(root='{}', algorithm='{}', files={}, errors={})",
+            self.root,
+            self.algorithm,
+            self.files.len(),
+            self.errors.len()
+        )
+    }
+}
+
+/// What ``verify_checksums`` found comparing a tree to its checksum file
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct ChecksumVerification {
+    /// Directory the listed paths are relative to
+    #[pyo3(get)]
+    pub root: String,
+    #[pyo3(get)]
+    pub manifest: String,
+    /// ``"blake3"`` or ``"sha256"``
+    #[pyo3(get)]
+    pub algorithm: &'static str,
+    /// Listed files whose digest matches
+    #[pyo3(get)]
+    pub matched: Vec<String>,
+    /// Listed files that aren't there
+    #[pyo3(get)]
+    pub missing: Vec<String>,
+    /// Files there that aren't listed
+    #[pyo3(get)]
+    pub extra: Vec<String>,
+    /// Listed files whose digest differs
+    #[pyo3(get)]
+    pub corrupted: Vec<String>,
+    /// Files that couldn't be listed or hashed
+    #[pyo3(get)]
+    pub errors: Vec<Failure>,
+}
+
+#[pymethods]
+impl ChecksumVerification {
+    /// Whether the tree is exactly what the manifest lists: nothing
+    /// missing, extra, corrupted or unreadable
+    #[getter]
+    fn ok(&self) -> bool {
+        self.missing.is_empty()
+            && self.extra.is_empty()
+            && self.corrupted.is_empty()
+            && self.errors.is_empty()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ChecksumVerification(matched={}, missing={}, extra={}, corrupted={}, errors={})",
+            self.matched.len(),
+            self.missing.len(),
+            self.extra.len(),
+            self.corrupted.len(),
+            self.errors.len()
+        )
+    }
+}
+
+/// Hash every file under a directory
+///
+/// The tree is walked in parallel, as ``walk_parallel`` walks it, and its
+/// files are then hashed on ``threads`` threads at once, largest first so
+/// one big file doesn't leave the rest waiting, each read in 1 MiB chunks.
+/// Symbolic links aren't hashed unless ``follow_links`` is set. A file
+/// that can't be read is reported in ``errors`` without stopping the rest.
+///
+/// Args:
+///     path: Root directory to hash
+///     algorithm: ``"blake3"`` or ``"sha256"`` (default: "blake3")
+///     threads: Files hashed at once (default: one per CPU)
+///     max_depth: Optional maximum depth to traverse
+///     follow_links: Whether to follow symbolic links (default: False)
+///     exclude: Optional gitignore-style globs, relative to the root, for
+///         entries to skip, as for ``walk_parallel``
+///
+/// Returns:
+///     TreeHashes with each file's path relative to ``path``, size and
+///     digest
+///
+/// Raises:
+///     ValueError: If the algorithm is unknown, threads is 0 or an exclude
+///         pattern is invalid
+///     WalkError: If ``path`` doesn't exist or isn't a directory
+#[pyfunction]
+#[pyo3(signature = (path, algorithm="blake3", threads=None, max_depth=None, follow_links=false, exclude=None))]
+pub fn hash_tree(
+    py: Python<'_>,
+    path: String,
+    algorithm: &str,
+    threads: Option<usize>,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    exclude: Option<Vec<String>>,
+) -> PyResult<TreeHashes> {
+    let checksum = Checksum::parse(algorithm)?;
+    let threads = thread_count(threads)?;
+    check_root(&path)?;
+    let matcher = exclude_matcher(exclude)?;
+    Ok(py.allow_threads(|| {
+        let root = PathBuf::from(&path);
+        let (found, mut errors) = list_files(&root, max_depth, follow_links, matcher, threads);
+        let (files, hash_errors) = hash_files(&root, found, checksum, threads);
+        errors.extend(hash_errors);
+        errors.sort();
+        TreeHashes {
+            root: path,
+            algorithm: checksum.name(),
+            files,
+            errors,
+        }
+    }))
+}
+
+/// Write a checksum file for a directory tree
+///
+/// The file is in the format ``sha256sum`` and ``b3sum`` write and check:
+/// a line of ``<digest>  <path>`` per file, sorted by path, with paths
+/// relative to ``path`` and ``/`` separators. Names holding a backslash or
+/// line break are escaped as those tools escape them. If ``output`` is
+/// inside the tree it isn't listed.
+///
+/// Args:
+///     path: Root directory to checksum
+///     output: Checksum file to write; replaced if it exists
+///     algorithm: ``"blake3"`` or ``"sha256"``. Inferred from the output's
+///         name when omitted: ``SHA256SUMS`` or ``.sha256`` for sha256,
+///         ``B3SUMS``, ``.blake3`` or ``.b3`` for blake3.
+///     threads: Files hashed at once (default: one per CPU)
+///     exclude: Optional gitignore-style globs, relative to the root, for
+///         entries to leave out, as for ``walk_parallel``
+///
+/// Returns:
+///     TreeHashes of the files written; files that couldn't be read are
+///     in its ``errors`` and left out of the checksum file
+///
+/// Raises:
+///     ValueError: If the algorithm is unknown or can't be inferred,
+///         threads is 0, an exclude pattern is invalid or the file can't
+///         be written
+///     WalkError: If ``path`` doesn't exist or isn't a directory
+#[pyfunction]
+#[pyo3(signature = (path, output, algorithm=None, threads=None, exclude=None))]
+pub fn write_checksums(
+    py: Python<'_>,
+    path: String,
+    output: PathBuf,
+    algorithm: Option<&str>,
+    threads: Option<usize>,
+    exclude: Option<Vec<String>>,
+) -> PyResult<TreeHashes> {
+    let checksum = manifest_checksum(&output, algorithm)?;
+    let threads = thread_count(threads)?;
+    check_root(&path)?;
+    let matcher = exclude_matcher(exclude)?;
+    py.allow_threads(|| {
+        let root = PathBuf::from(&path);
+        let (found, mut errors) = list_files(&root, None, false, matcher, threads);
+        let (files, hash_errors) = hash_files(&root, without(found, &output), checksum, threads);
+        errors.extend(hash_errors);
+        errors.sort();
+        write_manifest(&output, &files).map_err(|e| {
+            PyValueError::new_err(format!("Cannot write {}: {}", output.display(), e))
+        })?;
+        Ok(TreeHashes {
+            root: path,
+            algorithm: checksum.name(),
+            files,
+            errors,
+        })
+    })
+}
+
+/// Check a directory tree against a checksum file
+///
+/// Reads files in the format ``sha256sum`` and ``b3sum`` write, as
+/// ``write_checksums`` does, hashes every listed file that's there, and
+/// walks the tree for files that aren't listed. Blank lines and lines
+/// starting with ``#`` are ignored.
+///
+/// Args:
+///     manifest: Checksum file to check against
+///     root: Directory the listed paths are relative to (default: the
+///         checksum file's directory)
+///     algorithm: ``"blake3"`` or ``"sha256"``, inferred from the checksum
+///         file's name when omitted, as for ``write_checksums``
+///     threads: Files hashed at once (default: one per CPU)
+///
+/// Returns:
+///     ChecksumVerification listing the files matched, missing, extra and
+///     corrupted; the checksum file itself is never extra
+///
+/// Raises:
+///     ValueError: If the algorithm is unknown or can't be inferred,
+///         threads is 0, or the checksum file can't be read or has a line
+///         that isn't ``<digest>  <path>``
+///     WalkError: If ``root`` doesn't exist or isn't a directory
+#[pyfunction]
+#[pyo3(signature = (manifest, root=None, algorithm=None, threads=None))]
+pub fn verify_checksums(
+    py: Python<'_>,
+    manifest: PathBuf,
+    root: Option<String>,
+    algorithm: Option<&str>,
+    threads: Option<usize>,
+) -> PyResult<ChecksumVerification> {
+    let checksum = manifest_checksum(&manifest, algorithm)?;
+    let threads = thread_count(threads)?;
+    let root = root.unwrap_or_else(|| match manifest.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy().into_owned(),
+        _ => ".".to_string(),
+    });
+    check_root(&root)?;
+    py.allow_threads(|| {
+        let listed = read_manifest(&manifest)?;
+        let root_path = PathBuf::from(&root);
+        let (found, mut errors) = list_files(&root_path, None, false, None, threads);
+        let mut report = ChecksumVerification {
+            root,
+            manifest: manifest.to_string_lossy().into_owned(),
+            algorithm: checksum.name(),
+            matched: Vec::new(),
+            missing: Vec::new(),
+            extra: Vec::new(),
+            corrupted: Vec::new(),
+            errors: Vec::new(),
+        };
+        let mut to_hash = Vec::new();
+        let mut present = HashSet::new();
+        for (path, size) in without(found, &manifest) {
+            let relative = relative_path(&root_path, &path);
+            if listed.contains_key(&relative) {
+                present.insert(relative);
+                to_hash.push((path, size));
+            } else {
+                report.extra.push(relative);
+            }
+        }
+        report.missing = listed
+            .keys()
+            .filter(|path| !present.contains(*path))
+            .cloned()
+            .collect();
+        let (hashed, hash_errors) = hash_files(&root_path, to_hash, checksum, threads);
+        for file in hashed {
+            if listed[&file.path] == file.digest {
+                report.matched.push(file.path);
+            } else {
+                report.corrupted.push(file.path);
+            }
+        }
+        errors.extend(hash_errors);
+        errors.sort();
+        report.errors = errors;
+        report.extra.sort();
+        Ok(report)
+    })
+}
+
+/// Every file under `root` with its size, and the entries the walk
+/// couldn't read
+fn list_files(
+    root: &Path,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    matcher: Option<PatternMatcher>,
+    threads: usize,
+) -> (Vec<(PathBuf, u64)>, Vec<Failure>) {
+    let mut builder = scan_walker(&root.to_string_lossy(), max_depth, follow_links);
+    builder.threads(threads);
+    if let Some(matcher) = matcher {
+        let matcher = Arc::new(matcher);
+        let root = root.to_path_buf();
+        builder.filter_entry(move |entry| !is_excluded(&matcher, &root, entry));
+    }
+    let collector = Collector::new();
+    builder.build_parallel().run(|| {
+        Box::new(|entry| {
+            match entry {
+                Ok(entry) if entry.file_type().is_some_and(|t| t.is_file()) => {
+                    match entry.metadata() {
+                        Ok(metadata) => collector.push((entry.into_path(), metadata.len())),
+                        Err(e) => collector.walk_error(&e),
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => collector.walk_error(&e),
+            }
+            ignore::WalkState::Continue
+        })
+    });
+    collector.into_parts()
+}
+
+/// Hash `files` on `threads` threads, each taking the next file as it
+/// finishes one; returns the digests sorted by path
+fn hash_files(
+    root: &Path,
+    mut files: Vec<(PathBuf, u64)>,
+    checksum: Checksum,
+    threads: usize,
+) -> (Vec<HashedFile>, Vec<Failure>) {
+    // Largest first, so the longest hashes start soonest
+    files.sort_by_key(|(_, size)| Reverse(*size));
+    let collector = Collector::new();
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..threads.min(files.len()).max(1) {
+            scope.spawn(|| {
+                let mut buffer = vec![0; HASH_BUFFER];
+                while let Some((path, size)) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    match checksum.hash_file(path, &mut buffer) {
+                        Ok(digest) => collector.push(HashedFile {
+                            path: relative_path(root, path),
+                            size: *size,
+                            digest,
+                        }),
+                        Err(e) => collector.fail(path, e),
+                    }
+                }
+            });
+        }
+    });
+    collector.finish(Vec::new(), |file| &file.path)
+}
+
+/// `path` relative to `root`, with ``/`` separators on every platform
+fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Digest algorithm of a checksum file: `algorithm`, or the one its name
+/// implies
+fn manifest_checksum(manifest: &Path, algorithm: Option<&str>) -> PyResult<Checksum> {
+    if let Some(algorithm) = algorithm {
+        return Checksum::parse(algorithm);
+    }
+    let name = manifest
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let extension = manifest.extension().and_then(|ext| ext.to_str());
+    if name == "SHA256SUMS" || extension == Some("sha256") {
+        return Ok(Checksum::Sha256);
+    }
+    if name == "B3SUMS" || matches!(extension, Some("blake3" | "b3")) {
+        return Ok(Checksum::Blake3);
+    }
+    Err(PyValueError::new_err(format!(
+        "Cannot infer checksum algorithm from '{}': pass algorithm='blake3' or 'sha256'",
+        manifest.display()
+    )))
+}
+
+/// `found` less the checksum file itself, if it's among them
+fn without(found: Vec<(PathBuf, u64)>, manifest: &Path) -> Vec<(PathBuf, u64)> {
+    let Ok(manifest) = fs::canonicalize(manifest) else {
+        return found;
+    };
+    found
+        .into_iter()
+        .filter(|(path, _)| {
+            path.file_name() != manifest.file_name()
+                || fs::canonicalize(path).map_or(true, |path| path != manifest)
+        })
+        .collect()
+}
+
+fn write_manifest(output: &Path, files: &[HashedFile]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(output)?);
+    for file in files {
+        if file.path.contains(['\\', '\n', '\r']) {
+            let escaped = file
+                .path
+                .replace('\\', "\\\\")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r");
+            writeln!(writer, "\\{}  {}", file.digest, escaped)?;
+        } else {
+            writeln!(writer, "{}  {}", file.digest, file.path)?;
+        }
+    }
+    writer.flush()
+}
+
+/// Digests a checksum file lists, keyed by path; the first entry for a
+/// path wins
+fn read_manifest(manifest: &Path) -> PyResult<BTreeMap<String, String>> {
+    let read_error = |e: &dyn std::fmt::Display| {
+        PyValueError::new_err(format!(
+            "Cannot read checksums from {}: {}",
+            manifest.display(),
+            e
+        ))
+    };
+    let text = fs::read_to_string(manifest).map_err(|e| read_error(&e))?;
+    let mut listed = BTreeMap::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (digest, path) = parse_line(line)
+            .ok_or_else(|| read_error(&format!("line {}: expected '<digest>  <path>'", idx + 1)))?;
+        let path = path.strip_prefix("./").unwrap_or(&path).to_string();
+        listed.entry(path).or_insert(digest);
+    }
+    Ok(listed)
+}
+
+/// Digest and path of a ``<digest>  <path>`` or ``<digest> *<path>``
+/// line, unescaping one that starts with a backslash
+fn parse_line(line: &str) -> Option<(String, String)> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (digest, rest) = line.split_once(' ')?;
+    let path = rest.strip_prefix([' ', '*'])?;
+    if path.is_empty() || digest.is_empty() || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let path = match escaped {
+        false => path.to_string(),
+        true => {
+            let mut unescaped = String::new();
+            let mut chars = path.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => match chars.next()? {
+                        '\\' => unescaped.push('\\'),
+                        'n' => unescaped.push('\n'),
+                        'r' => unescaped.push('\r'),
+                        _ => return None,
+                    },
+                    c => unescaped.push(c),
+                }
+            }
+            unescaped
+        }
+    };
+    Some((digest.to_ascii_lowercase(), path))
+}
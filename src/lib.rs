@@ -1,16 +1,125 @@
 use pyo3::prelude::*;
 
+mod analysis;
+mod archive;
+mod capture;
+mod casefold;
+mod checkpoint;
+mod checksum;
+#[cfg(feature = "cli")]
+pub mod cli;
+mod dialect;
+mod diff;
+mod encoding;
+mod errors;
+mod events;
+mod explain;
+mod export;
 mod file_pattern;
+mod filetype;
+mod fuzzy;
+mod glob_syntax;
+mod grep;
+mod incremental;
+mod infer;
+mod inherit;
+mod integrity;
+mod lint;
+mod memory;
+mod migrate;
 mod pattern;
+mod profile;
+mod progress;
+mod remote;
+mod shuffle;
+mod snapshot;
+mod spec;
+mod stats;
+mod stream;
+mod template;
+mod trash;
 mod walk;
+mod watch;
 
 /// High-performance file structure pattern matching with Rust
 #[pymodule]
 fn _pathvein_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(walk::walk_parallel, m)?)?;
     m.add_function(wrap_pyfunction!(walk::scan_parallel, m)?)?;
+    m.add_function(wrap_pyfunction!(incremental::scan_incremental, m)?)?;
+    m.add_function(wrap_pyfunction!(checkpoint::scan_checkpointed, m)?)?;
+    m.add_function(wrap_pyfunction!(watch::watch, m)?)?;
+    m.add_function(wrap_pyfunction!(export::export_results, m)?)?;
+    m.add_function(wrap_pyfunction!(export::scan_to_file, m)?)?;
+    m.add_function(wrap_pyfunction!(diff::diff_scans, m)?)?;
+    m.add_function(wrap_pyfunction!(shuffle::shuffle, m)?)?;
+    m.add_function(wrap_pyfunction!(shuffle::plan_shuffle, m)?)?;
+    m.add_function(wrap_pyfunction!(integrity::hash_tree, m)?)?;
+    m.add_function(wrap_pyfunction!(integrity::write_checksums, m)?)?;
+    m.add_function(wrap_pyfunction!(integrity::verify_checksums, m)?)?;
+    m.add_function(wrap_pyfunction!(snapshot::snapshot, m)?)?;
+    m.add_function(wrap_pyfunction!(snapshot::load_snapshot, m)?)?;
+    m.add_function(wrap_pyfunction!(snapshot::diff_snapshots, m)?)?;
+    m.add_function(wrap_pyfunction!(grep::grep_tree, m)?)?;
+    m.add_function(wrap_pyfunction!(filetype::detect_types, m)?)?;
+    m.add_function(wrap_pyfunction!(encoding::detect_encodings, m)?)?;
     m.add_function(wrap_pyfunction!(pattern::match_pattern, m)?)?;
+    m.add_function(wrap_pyfunction!(pattern::match_pattern_many, m)?)?;
+    m.add_function(wrap_pyfunction!(pattern::match_pattern_cache_info, m)?)?;
+    m.add_function(wrap_pyfunction!(pattern::match_pattern_cache_clear, m)?)?;
+    m.add_function(wrap_pyfunction!(pattern::glob_to_regex, m)?)?;
+    m.add_function(wrap_pyfunction!(spec::validate_spec, m)?)?;
+    m.add_function(wrap_pyfunction!(lint::lint_patterns, m)?)?;
+    m.add_function(wrap_pyfunction!(infer::infer_pattern, m)?)?;
     m.add_class::<pattern::PatternMatcher>()?;
+    m.add_class::<pattern::CacheInfo>()?;
     m.add_class::<walk::ScanResult>()?;
+    m.add_class::<walk::ScanResults>()?;
+    m.add_class::<walk::ScanResultsIterator>()?;
+    m.add_class::<walk::Failure>()?;
+    m.add_class::<stats::ScanStats>()?;
+    m.add_class::<events::ScanEvent>()?;
+    m.add_class::<stats::PatternStats>()?;
+    m.add_class::<stats::ScanSummary>()?;
+    m.add_class::<stats::ScanProfile>()?;
+    m.add_class::<diff::ScanDiff>()?;
+    m.add_class::<diff::ScanChange>()?;
+    m.add_class::<shuffle::ShuffleReport>()?;
+    m.add_class::<shuffle::ShuffledDirectory>()?;
+    m.add_class::<shuffle::ShufflePlan>()?;
+    m.add_class::<shuffle::PlannedFile>()?;
+    m.add_class::<shuffle::VerifiedFile>()?;
+    m.add_class::<integrity::TreeHashes>()?;
+    m.add_class::<integrity::HashedFile>()?;
+    m.add_class::<integrity::ChecksumVerification>()?;
+    m.add_class::<snapshot::Snapshot>()?;
+    m.add_class::<snapshot::SnapshotEntry>()?;
+    m.add_class::<snapshot::SnapshotDiff>()?;
+    m.add_class::<snapshot::SnapshotChange>()?;
+    m.add_class::<grep::GrepResults>()?;
+    m.add_class::<grep::GrepFile>()?;
+    m.add_class::<grep::GrepLine>()?;
+    m.add_class::<filetype::DetectedTypes>()?;
+    m.add_class::<filetype::DetectedType>()?;
+    m.add_class::<encoding::FileEncodings>()?;
+    m.add_class::<encoding::FileEncoding>()?;
+    m.add_class::<stream::ScanIterator>()?;
+    m.add_class::<progress::ScanProgress>()?;
+    m.add_class::<watch::Watcher>()?;
+    m.add_class::<file_pattern::FileStructurePattern>()?;
+    m.add_class::<file_pattern::FileConstraint>()?;
+    m.add_class::<explain::MatchFailure>()?;
+    m.add_class::<fuzzy::FuzzyMatcher>()?;
+    m.add_class::<analysis::PatternAnalysis>()?;
+    m.add_class::<spec::SpecError>()?;
+    m.add_class::<lint::LintDiagnostic>()?;
+    m.add("PathveinError", m.py().get_type::<errors::PathveinError>())?;
+    m.add(
+        "PatternSyntaxError",
+        errors::pattern_syntax_error_type(m.py()),
+    )?;
+    m.add("PatternError", errors::pattern_error_type(m.py()))?;
+    m.add("WalkError", errors::walk_error_type(m.py()))?;
+    m.add("ScanCancelled", m.py().get_type::<errors::ScanCancelled>())?;
     Ok(())
 }
@@ -0,0 +1,407 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::analysis::{sample_name, WITNESS_VARIANTS};
+use crate::file_pattern::{CompiledPattern, FileStructurePattern, ANY_DEPTH, REGEX_PREFIX};
+use crate::inherit;
+use crate::memory::MemoryTree;
+use crate::spec::check_spec;
+
+/// One problem found by `lint_patterns`
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct LintDiagnostic {
+    /// ``"invalid"``, ``"unknown_key"``, ``"duplicate"``, ``"unreachable"``
+    /// or ``"conflicting_name"``
+    #[pyo3(get)]
+    pub kind: &'static str,
+    /// Spec file the problem is in
+    #[pyo3(get)]
+    pub source: String,
+    /// ``pattern_name`` of the pattern, if it loaded and has one
+    #[pyo3(get)]
+    pub pattern_name: Option<String>,
+    /// Spec file of the other pattern involved, for ``"duplicate"``,
+    /// ``"unreachable"`` and ``"conflicting_name"``
+    #[pyo3(get)]
+    pub related: Option<String>,
+    /// 1-based line of the problem, if the parser reported one
+    #[pyo3(get)]
+    pub line: Option<usize>,
+    #[pyo3(get)]
+    pub message: String,
+}
+
+#[pymethods]
+impl LintDiagnostic {
+    fn __repr__(&self) -> String {
+        format!(
+            "LintDiagnostic(kind='{}', source='{}', message={:?})",
+            self.kind, self.source, self.message
+        )
+    }
+}
+
+impl LintDiagnostic {
+    fn new(kind: &'static str, source: &Path, message: String) -> Self {
+        LintDiagnostic {
+            kind,
+            source: source.to_string_lossy().into_owned(),
+            pattern_name: None,
+            related: None,
+            line: None,
+            message,
+        }
+    }
+}
+
+/// A directory of spec files, or a list of them
+#[derive(FromPyObject)]
+pub enum LintSource {
+    Directory(PathBuf),
+    Files(Vec<PathBuf>),
+}
+
+/// How a scan attributes a directory several patterns match
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Strategy {
+    All,
+    FirstMatch,
+    BestMatch,
+}
+
+impl Strategy {
+    fn parse(strategy: &str) -> PyResult<Self> {
+        match strategy {
+            "all" => Ok(Strategy::All),
+            "first_match" => Ok(Strategy::FirstMatch),
+            "best_match" => Ok(Strategy::BestMatch),
+            other => Err(PyValueError::new_err(format!(
+                "Unknown strategy '{}': expected 'all', 'first_match' or 'best_match'",
+                other
+            ))),
+        }
+    }
+}
+
+/// A spec file that loaded and compiled
+struct Loaded {
+    source: PathBuf,
+    pattern: FileStructurePattern,
+    compiled: CompiledPattern,
+}
+
+/// Check a library of pattern spec files for mistakes
+///
+/// Each file is checked the way ``validate_spec`` checks it, then loaded
+/// with ``extends`` resolved against the other files' ``pattern_name``
+/// and compiled, which catches invalid globs. Across files it reports:
+///
+/// - ``"duplicate"``: a pattern identical to an earlier one but for its
+///   name
+/// - ``"conflicting_name"``: a ``pattern_name`` an earlier, different
+///   pattern already has
+/// - ``"unreachable"``: a pattern that never wins a directory under
+///   ``strategy``, because a pattern that wins over it matches every
+///   directory it does. Containment is checked on sample directories
+///   built from the pattern's requirements, so this is a strong hint
+///   rather than a proof; patterns with ``regex:`` globs, size, content or
+///   age limits are not checked.
+///
+/// Args:
+///     source: Directory whose ``.json``, ``.yaml``, ``.yml`` and ``.toml``
+///         files are checked, in name order, or a list of spec files, in
+///         list order
+///     strategy: How scans attribute a directory several patterns match:
+///         ``"first_match"`` to the first in order, ``"best_match"`` to
+///         the highest ``priority``, or ``"all"`` to every one, where no
+///         pattern is unreachable (default: "first_match")
+///
+/// Returns:
+///     List of LintDiagnostic, in file order; empty if nothing was found
+///
+/// Raises:
+///     ValueError: If strategy is unknown or the directory can't be read
+#[pyfunction]
+#[pyo3(signature = (source, strategy="first_match"))]
+pub fn lint_patterns(
+    py: Python<'_>,
+    source: LintSource,
+    strategy: &str,
+) -> PyResult<Vec<LintDiagnostic>> {
+    let strategy = Strategy::parse(strategy)?;
+    let files = match source {
+        LintSource::Files(files) => files,
+        LintSource::Directory(dir) => spec_files(&dir)?,
+    };
+    let mut diagnostics = Vec::new();
+
+    let mut parsed = Vec::new();
+    for file in files.iter().cloned() {
+        if let Some(pattern) = parse_file(&file, &mut diagnostics)? {
+            parsed.push((file, pattern));
+        }
+    }
+    let mut library = HashMap::new();
+    for (_, pattern) in &parsed {
+        if let Some(name) = &pattern.pattern_name {
+            library
+                .entry(name.clone())
+                .or_insert_with(|| pattern.clone());
+        }
+    }
+    let mut loaded = Vec::new();
+    for (source, pattern) in parsed {
+        let base_dir = source.parent().unwrap_or(Path::new(""));
+        let checked = inherit::resolve(&pattern, &library, base_dir).and_then(|pattern| {
+            let compiled = pattern
+                .compile()
+                .map_err(|e| format!("Pattern compilation error: {}", e))?;
+            Ok((pattern, compiled))
+        });
+        match checked {
+            Ok((pattern, compiled)) => loaded.push(Loaded {
+                source,
+                pattern,
+                compiled,
+            }),
+            Err(message) => {
+                let mut diagnostic = LintDiagnostic::new("invalid", &source, message);
+                diagnostic.pattern_name = pattern.pattern_name;
+                diagnostics.push(diagnostic);
+            }
+        }
+    }
+
+    py.allow_threads(|| diagnostics.extend(compare(&loaded, strategy)));
+    let position: HashMap<String, usize> = files
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(idx, file)| (file.to_string_lossy().into_owned(), idx))
+        .collect();
+    diagnostics.sort_by_key(|diagnostic| position[&diagnostic.source]);
+    Ok(diagnostics)
+}
+
+/// Spec files directly inside `dir`, by name
+fn spec_files(dir: &Path) -> PyResult<Vec<PathBuf>> {
+    let entries = std::fs::read_dir(dir).map_err(|e| {
+        PyValueError::new_err(format!(
+            "Cannot read pattern directory {}: {}",
+            dir.display(),
+            e
+        ))
+    })?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && spec_format(path).is_some())
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Format of a spec file, by its extension
+fn spec_format(path: &Path) -> Option<&'static str> {
+    let extension = path.extension().and_then(OsStr::to_str)?;
+    match extension.to_ascii_lowercase().as_str() {
+        "json" => Some("json"),
+        "yaml" | "yml" => Some("yaml"),
+        "toml" => Some("toml"),
+        _ => None,
+    }
+}
+
+/// Read and deserialize one spec file, recording its problems
+fn parse_file(
+    file: &Path,
+    diagnostics: &mut Vec<LintDiagnostic>,
+) -> PyResult<Option<FileStructurePattern>> {
+    let Some(format) = spec_format(file) else {
+        diagnostics.push(LintDiagnostic::new(
+            "invalid",
+            file,
+            "expected a .json, .yaml, .yml or .toml file".to_string(),
+        ));
+        return Ok(None);
+    };
+    let text = match std::fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(e) => {
+            let message = format!("Cannot read pattern file: {}", e);
+            diagnostics.push(LintDiagnostic::new("invalid", file, message));
+            return Ok(None);
+        }
+    };
+    let (unknown_keys, failure) = check_spec(&text, format)?;
+    let failed = failure.is_some();
+    for (kind, error) in unknown_keys
+        .into_iter()
+        .map(|error| ("unknown_key", error))
+        .chain(failure.map(|error| ("invalid", error)))
+    {
+        let message = match kind {
+            "unknown_key" => format!("unknown key '{}'", error.path),
+            _ if error.path.is_empty() => error.message,
+            _ => format!("{}: {}", error.path, error.message),
+        };
+        let mut diagnostic = LintDiagnostic::new(kind, file, message);
+        diagnostic.line = error.line;
+        diagnostics.push(diagnostic);
+    }
+    if failed {
+        return Ok(None);
+    }
+    let parsed = match format {
+        "json" => FileStructurePattern::from_json(&text).map_err(|e| e.to_string()),
+        "yaml" => FileStructurePattern::from_yaml(&text).map_err(|e| e.to_string()),
+        _ => FileStructurePattern::from_toml(&text).map_err(|e| e.to_string()),
+    };
+    match parsed {
+        Ok(pattern) => Ok(Some(pattern)),
+        Err(message) => {
+            diagnostics.push(LintDiagnostic::new("invalid", file, message));
+            Ok(None)
+        }
+    }
+}
+
+/// Duplicates, name conflicts and unreachable patterns among `loaded`
+fn compare(loaded: &[Loaded], strategy: Strategy) -> Vec<LintDiagnostic> {
+    let unnamed: Vec<FileStructurePattern> = loaded
+        .iter()
+        .map(|entry| FileStructurePattern {
+            pattern_name: None,
+            ..entry.pattern.clone()
+        })
+        .collect();
+    let witnesses: Vec<Vec<(PathBuf, MemoryTree)>> = loaded.iter().map(witnesses).collect();
+
+    let mut diagnostics = Vec::new();
+    for (idx, entry) in loaded.iter().enumerate() {
+        let report = |kind: &'static str, other: usize, message: String| LintDiagnostic {
+            pattern_name: entry.pattern.pattern_name.clone(),
+            related: Some(loaded[other].source.to_string_lossy().into_owned()),
+            ..LintDiagnostic::new(kind, &entry.source, message)
+        };
+        if let Some(other) = (0..idx).find(|&other| unnamed[other] == unnamed[idx]) {
+            let message = "same pattern as an earlier file".to_string();
+            diagnostics.push(report("duplicate", other, message));
+            continue;
+        }
+        let name = entry.pattern.pattern_name.as_ref();
+        if let Some(other) = (0..idx)
+            .find(|&other| name.is_some() && loaded[other].pattern.pattern_name.as_ref() == name)
+        {
+            let message = format!(
+                "pattern_name '{}' is already used by a different pattern",
+                name.map_or("", String::as_str)
+            );
+            diagnostics.push(report("conflicting_name", other, message));
+        }
+        let shadowing = (0..loaded.len()).find(|&other| {
+            other != idx
+                && unnamed[other] != unnamed[idx]
+                && wins_over(loaded, other, idx, strategy)
+                && !witnesses[idx].is_empty()
+                && witnesses[idx]
+                    .iter()
+                    .all(|(dir, tree)| loaded[other].compiled.matches_in(dir, tree))
+        });
+        if let Some(other) = shadowing {
+            let message =
+                "every directory this pattern matches is taken by a broader pattern".to_string();
+            diagnostics.push(report("unreachable", other, message));
+        }
+    }
+    diagnostics
+}
+
+/// Whether pattern `winner` is attributed a directory both it and `loser`
+/// match
+fn wins_over(loaded: &[Loaded], winner: usize, loser: usize, strategy: Strategy) -> bool {
+    match strategy {
+        Strategy::All => false,
+        Strategy::FirstMatch => winner < loser,
+        Strategy::BestMatch => {
+            let (ours, theirs) = (
+                loaded[winner].compiled.priority,
+                loaded[loser].compiled.priority,
+            );
+            ours > theirs || (ours == theirs && winner < loser)
+        }
+    }
+}
+
+/// Sample directories `entry` matches, built from its required names;
+/// empty when they can't be built or the pattern rejects one, so no
+/// conclusion is drawn
+fn witnesses(entry: &Loaded) -> Vec<(PathBuf, MemoryTree)> {
+    let mut built = Vec::new();
+    for variant in 0..WITNESS_VARIANTS {
+        let mut tree = MemoryTree::default();
+        let Some(name) = sample_name(entry_glob(&entry.pattern.directory_name), variant) else {
+            continue;
+        };
+        let root = PathBuf::from(name);
+        if add_requirements(&entry.pattern, &root, variant, &mut tree).is_none() {
+            return Vec::new();
+        }
+        if !entry.compiled.matches_in(&root, &tree) {
+            return Vec::new();
+        }
+        built.push((root, tree));
+    }
+    built
+}
+
+/// Add an entry for each required file and subdirectory of `pattern` to
+/// `dir`; None if one can't be sampled
+fn add_requirements(
+    pattern: &FileStructurePattern,
+    dir: &Path,
+    variant: usize,
+    tree: &mut MemoryTree,
+) -> Option<()> {
+    if pattern.directory_name.starts_with(REGEX_PREFIX) {
+        return None;
+    }
+    for glob in &pattern.files {
+        if glob.starts_with(REGEX_PREFIX) {
+            return None;
+        }
+        let glob = glob.strip_prefix('/').unwrap_or(glob);
+        let (glob, is_dir) = match glob.strip_suffix('/') {
+            Some(dir_glob) => (dir_glob, true),
+            None => (glob, false),
+        };
+        tree.add_entry(dir, &sample_name(glob, variant)?, is_dir);
+    }
+    for glob in &pattern.empty_directories {
+        if glob.starts_with(REGEX_PREFIX) {
+            return None;
+        }
+        tree.add_entry(dir, &sample_name(entry_glob(glob), variant)?, true);
+    }
+    for subpattern in &pattern.directories {
+        // A `**/` pattern is satisfied by a direct child too
+        let glob = subpattern.directory_name.strip_prefix(ANY_DEPTH);
+        let name = sample_name(
+            entry_glob(glob.unwrap_or(&subpattern.directory_name)),
+            variant,
+        )?;
+        tree.add_entry(dir, &name, true);
+        add_requirements(subpattern, &dir.join(&name), variant, tree)?;
+    }
+    Some(())
+}
+
+/// A directory name glob without a redundant trailing `/`
+fn entry_glob(glob: &str) -> &str {
+    glob.strip_suffix('/')
+        .filter(|glob| !glob.is_empty())
+        .unwrap_or(glob)
+}
@@ -0,0 +1,115 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::file_pattern::DirectoryTree;
+
+/// A hypothetical directory tree described by nested Python dicts
+///
+/// Keys are entry names. A dict value is a subdirectory with those
+/// contents; an int is a file of that many bytes; a str or bytes is a
+/// file with that content; anything else, e.g. ``None``, is a file of
+/// unknown size and content.
+#[derive(Default)]
+pub struct MemoryTree {
+    /// (subdirectory names, file names) per directory
+    listings: HashMap<PathBuf, (Vec<OsString>, Vec<OsString>)>,
+    sizes: HashMap<PathBuf, u64>,
+    contents: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryTree {
+    /// Add an entry named `name` to `dir`'s listing
+    pub fn add_entry(&mut self, dir: &Path, name: &str, is_dir: bool) {
+        let listing = self.listings.entry(dir.to_path_buf()).or_default();
+        let names = if is_dir {
+            &mut listing.0
+        } else {
+            &mut listing.1
+        };
+        names.push(OsString::from(name));
+    }
+
+    /// Build the tree with `structure` as the contents of `root`
+    pub fn from_dict(root: &Path, structure: &Bound<'_, PyDict>) -> PyResult<Self> {
+        let mut tree = MemoryTree::default();
+        tree.add(root, structure)?;
+        Ok(tree)
+    }
+
+    fn add(&mut self, dir: &Path, contents: &Bound<'_, PyDict>) -> PyResult<()> {
+        let mut listing = (Vec::new(), Vec::new());
+        for (name, value) in contents.iter() {
+            let name: String = name.extract()?;
+            let path = dir.join(&name);
+            if let Ok(subdir) = value.downcast::<PyDict>() {
+                self.add(&path, subdir)?;
+                listing.0.push(OsString::from(name));
+            } else {
+                if let Ok(size) = value.extract::<u64>() {
+                    self.sizes.insert(path, size);
+                } else if let Ok(text) = value.extract::<String>() {
+                    self.sizes.insert(path.clone(), text.len() as u64);
+                    self.contents.insert(path, text.into_bytes());
+                } else if let Ok(bytes) = value.downcast::<PyBytes>() {
+                    self.sizes
+                        .insert(path.clone(), bytes.as_bytes().len() as u64);
+                    self.contents.insert(path, bytes.as_bytes().to_vec());
+                }
+                listing.1.push(OsString::from(name));
+            }
+        }
+        self.listings.insert(dir.to_path_buf(), listing);
+        Ok(())
+    }
+}
+
+impl DirectoryTree for MemoryTree {
+    fn children(&self, dir: &Path) -> (&[OsString], &[OsString]) {
+        match self.listings.get(dir) {
+            Some((dirs, files)) => (dirs, files),
+            None => (&[], &[]),
+        }
+    }
+
+    fn file_size(&self, path: &Path) -> Option<u64> {
+        self.sizes.get(path).copied()
+    }
+
+    /// Nothing in memory has a modification time, so age limits fail
+    fn modified(&self, _path: &Path) -> Option<SystemTime> {
+        None
+    }
+
+    /// Files given without content fail content limits
+    fn read_head(&self, path: &Path, limit: usize) -> Option<Vec<u8>> {
+        let content = self.contents.get(path)?;
+        Some(content[..content.len().min(limit)].to_vec())
+    }
+
+    /// Nothing in memory is a symbolic link
+    fn is_symlink(&self, _path: &Path) -> bool {
+        false
+    }
+
+    fn linked_dirs(&self, _dir: &Path) -> Vec<OsString> {
+        Vec::new()
+    }
+
+    /// Files of unknown size make the total unknown
+    fn total_size(&self, dir: &Path) -> Option<u64> {
+        let (dirs, files) = self.children(dir);
+        let files: Option<u64> = files
+            .iter()
+            .map(|name| self.file_size(&dir.join(name)))
+            .sum();
+        let dirs: Option<u64> = dirs
+            .iter()
+            .map(|name| self.total_size(&dir.join(name)))
+            .sum();
+        Some(files? + dirs?)
+    }
+}
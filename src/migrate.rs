@@ -0,0 +1,88 @@
+use serde_json::{Map, Value};
+
+/// Layout version written into specs and the newest one read
+///
+/// 1. Unversioned specs, as Python's ``to_json`` writes them: nested
+///    patterns are JSON strings, and combinators may be ``anyOf``,
+///    ``allOf`` and ``not``
+/// 2. Nested patterns are inline objects and every key is snake_case
+pub const SPEC_VERSION: u64 = 2;
+
+/// Upgrade of a spec from one version to the next; returns whether it
+/// changed the spec
+type Migration = fn(&mut Map<String, Value>) -> Result<bool, String>;
+
+/// Upgrades from each version to the next, starting at version 1
+const MIGRATIONS: &[Migration] = &[inline_nested];
+
+/// Keys holding nested patterns
+const NESTED_KEYS: &[&str] = &[
+    "directories",
+    "optional_directories",
+    "any_of",
+    "all_of",
+    "none_of",
+];
+
+/// Bring a spec up to the current layout, dropping its ``version`` key;
+/// returns whether anything besides the version changed
+///
+/// A spec that isn't an object is left for deserialization to reject.
+pub fn upgrade(spec: &mut Value) -> Result<bool, String> {
+    let Value::Object(pattern) = spec else {
+        return Ok(false);
+    };
+    let version = spec_version(pattern)?;
+    pattern.remove("version");
+    let mut changed = false;
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        changed |= migration(pattern)?;
+    }
+    Ok(changed)
+}
+
+/// The ``version`` of a spec, 1 if it has none
+pub fn spec_version(pattern: &Map<String, Value>) -> Result<u64, String> {
+    let version = match pattern.get("version") {
+        None => return Ok(1),
+        Some(version) => version
+            .as_u64()
+            .filter(|&version| version >= 1)
+            .ok_or_else(|| format!("version must be a positive integer, got {}", version))?,
+    };
+    if version > SPEC_VERSION {
+        return Err(format!(
+            "spec version {} is newer than this pathvein reads (up to {}); upgrade pathvein",
+            version, SPEC_VERSION
+        ));
+    }
+    Ok(version)
+}
+
+/// Version 1 to 2: parse nested patterns written as JSON strings, and
+/// rename camelCase combinators
+fn inline_nested(pattern: &mut Map<String, Value>) -> Result<bool, String> {
+    let mut changed = false;
+    for (old, new) in [("anyOf", "any_of"), ("allOf", "all_of"), ("not", "none_of")] {
+        if let Some(value) = pattern.remove(old) {
+            pattern.entry(new).or_insert(value);
+            changed = true;
+        }
+    }
+    for key in NESTED_KEYS {
+        let Some(Value::Array(nested)) = pattern.get_mut(*key) else {
+            continue;
+        };
+        for child in nested.iter_mut() {
+            if let Value::String(json) = child {
+                *child = serde_json::from_str(json)
+                    .map_err(|e| format!("Invalid nested pattern in {}: {}", key, e))?;
+                changed = true;
+            }
+            if let Value::Object(child) = child {
+                changed |= inline_nested(child)?;
+            }
+        }
+    }
+    Ok(changed)
+}
@@ -6,7 +6,8 @@ otherwise falls back to pure Python implementations.
 """
 
 import logging
-from typing import List, Optional, Tuple
+from pathlib import Path
+from typing import Iterator, List, Optional, Tuple
 
 logger = logging.getLogger(__name__)
 
@@ -58,7 +59,11 @@ def walk_parallel(
 
 
 def scan_parallel(
-    path: str, patterns, max_depth: Optional[int] = None, follow_links: bool = False
+    path: str,
+    patterns,
+    max_depth: Optional[int] = None,
+    follow_links: bool = False,
+    stream: bool = False,
 ):
     """
     Scan directory tree for pattern matches in Rust.
@@ -72,9 +77,11 @@ def scan_parallel(
         patterns: List of FileStructurePattern objects to match against
         max_depth: Optional maximum depth to traverse
         follow_links: Whether to follow symbolic links
+        stream: Yield each match as soon as it is found, while the walk goes on
 
     Returns:
-        List of (path, pattern) tuples for directories that matched
+        List of (path, pattern) tuples for directories that matched, or an
+        iterator over them with ``stream``
     """
     if HAS_RUST_BACKEND and _pathvein_rs is not None:
         from pathlib import Path as PathType
@@ -84,23 +91,27 @@ def scan_parallel(
 
         # Walk and match entirely in Rust - no FFI crossings in loop
         results = _pathvein_rs.scan_parallel(
-            path, pattern_jsons, max_depth, follow_links
+            path, pattern_jsons, max_depth, follow_links, stream=stream
         )
         # Convert results back to (Path, pattern) tuples
-        return [(PathType(r.path), patterns[r.pattern_index]) for r in results]
+        matches = ((PathType(r.path), patterns[r.pattern_index]) for r in results)
     else:
         # Fall back to Python implementation
-        from pathlib import Path as PathType
-
-        results = []
-        for dirpath_str, dirnames, filenames in walk_parallel(
-            path, max_depth, follow_links
-        ):
-            dirpath = PathType(dirpath_str)
-            for pattern in patterns:
-                if pattern.matches((dirpath, dirnames, filenames)):
-                    results.append((dirpath, pattern))
-        return results
+        matches = _scan_walk(path, patterns, max_depth, follow_links)
+    return matches if stream else list(matches)
+
+
+def _scan_walk(
+    path: str, patterns, max_depth: Optional[int], follow_links: bool
+) -> Iterator[Tuple[Path, object]]:
+    """Match each directory walk_parallel lists against every pattern"""
+    for dirpath_str, dirnames, filenames in walk_parallel(
+        path, max_depth, follow_links
+    ):
+        dirpath = Path(dirpath_str)
+        for pattern in patterns:
+            if pattern.matches((dirpath, dirnames, filenames)):
+                yield dirpath, pattern
 
 
 class PatternMatcher:
@@ -9,7 +9,18 @@ the pattern matching and file organization capabilities.
 
 import logging
 from pathlib import Path
-from typing import Callable, Generator, Iterable, List, NamedTuple, Set
+from typing import (
+    Callable,
+    Generator,
+    Iterable,
+    Iterator,
+    List,
+    Literal,
+    NamedTuple,
+    Set,
+    Union,
+    overload,
+)
 
 from ._path_utils import iterdir
 from ._backend import scan_parallel
@@ -96,11 +107,49 @@ def assess(
                     yield ScanResult(root, pattern)
 
 
+@overload
 def scan(
     source: Path,
     patterns: Iterable[FileStructurePattern],
-) -> Set[ScanResult]:
-    """Recursively scan a directory path for directory structures that match the requirements"""
+    stream: Literal[False] = False,
+) -> Set[ScanResult]: ...
+
+
+@overload
+def scan(
+    source: Path,
+    patterns: Iterable[FileStructurePattern],
+    stream: Literal[True],
+) -> Iterator[ScanResult]: ...
+
+
+def scan(
+    source: Path,
+    patterns: Iterable[FileStructurePattern],
+    stream: bool = False,
+) -> Union[Set[ScanResult], Iterator[ScanResult]]:
+    """Recursively scan a directory path for directory structures that match the requirements
+
+    With ``stream``, yield each match as soon as it is found, while the walk goes on,
+    instead of returning the set of matches once the scan is done.
+    """
+
+    matches = _scan_matches(source, patterns, stream)
+    if stream:
+        return matches
+
+    found = set(matches)
+    logger.debug("Matching paths: %s", found)
+
+    return found
+
+
+def _scan_matches(
+    source: Path,
+    patterns: Iterable[FileStructurePattern],
+    stream: bool,
+) -> Generator[ScanResult, None, None]:
+    """Each match of a scan, as the backend finds it"""
 
     logger.info("Beginning scan of %s", source.as_posix())
 
@@ -113,17 +162,15 @@ def scan(
     for pattern in pattern_list:
         logger.debug("Scanning for paths that match structure: %s", pattern)
 
-    matches = set()
-
     # Use Rust-backed scan_parallel for standard Path objects (local filesystem)
     # This walks AND matches in Rust, avoiding FFI overhead
     # For cloud storage (UPath, S3Path, etc.), use Python walk() which supports any path-like object
     if type(source) is Path:
         # Local filesystem - use Rust scan that walks and matches in one pass
-        results = scan_parallel(str(source), pattern_list)
+        results = scan_parallel(str(source), pattern_list, stream=stream)
         for dirpath, pattern in results:
             logger.debug("Matched structure %s in %s", pattern, dirpath)
-            matches.add(ScanResult(dirpath, pattern))
+            yield ScanResult(dirpath, pattern)
     else:
         # Cloud storage or other path-like objects - use Python walk()
         logger.debug("Using Python walk for non-local path: %s", type(source))
@@ -132,11 +179,7 @@ def scan(
             for pattern in pattern_list:
                 if pattern.matches((dirpath, dirnames, filenames)):
                     logger.debug("Matched structure %s in %s", pattern, dirpath)
-                    matches.add(ScanResult(dirpath, pattern))
-
-    logger.debug("Matching paths: %s", matches)
-
-    return matches
+                    yield ScanResult(dirpath, pattern)
 
 
 def shuffle(
@@ -28,6 +28,11 @@ def _none_of(iter: Iterable[bool]) -> bool:
     return all(not value for value in iter)
 
 
+def _nested_json(spec: Any) -> str:
+    """A nested pattern spec as a JSON string, whether written as one or inline"""
+    return spec if isinstance(spec, str) else json.dumps(spec)
+
+
 @dataclass
 class FileStructurePattern:
     """
@@ -86,6 +91,9 @@ class FileStructurePattern:
     def from_json(cls, spec_str: str) -> Self:
         """Create a FileStructurePattern from a JSON string
 
+        Nested directories may be JSON strings, as ``to_json`` writes them,
+        or inline objects, as the Rust backend writes them.
+
         Args:
             spec_str: JSON string containing pattern specification
 
@@ -116,13 +124,13 @@ class FileStructurePattern:
                 .add_files(spec.get("optional_files", []), is_optional=True)
                 .add_directories(
                     (
-                        cls.from_json(subdirectory_spec)
+                        cls.from_json(_nested_json(subdirectory_spec))
                         for subdirectory_spec in spec.get("directories", [])
                     )
                 )
                 .add_directories(
                     (
-                        cls.from_json(subdirectory_spec)
+                        cls.from_json(_nested_json(subdirectory_spec))
                         for subdirectory_spec in spec.get("optional_directories", [])
                     ),
                     is_optional=True,
@@ -1,19 +1,277 @@
-use globset::{Glob, GlobMatcher, GlobSet, GlobSetBuilder};
+use globset::{Glob, GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
 use lru::LruCache;
-use pyo3::exceptions::PyValueError;
+use pyo3::exceptions::{PyIndexError, PyUserWarning, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyIterator, PyList};
+use regex::{Regex, RegexSet};
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
 use std::num::NonZeroUsize;
-use std::sync::Mutex;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::analysis::PatternAnalysis;
+use crate::casefold::CaseFold;
+use crate::dialect::{compile_pathlib, pathlib_normalize, pathlib_regex_body, GlobDialect};
+use crate::errors::{pattern_error, pattern_syntax_error};
+use crate::profile::PatternProfile;
+
+/// Why a pattern set failed to build, raised in Python as a PatternError
+#[derive(Debug)]
+pub(crate) struct MatcherError {
+    pub message: String,
+    /// Index and text of the offending pattern, when one is to blame
+    pub culprit: Option<(usize, String)>,
+}
+
+impl std::fmt::Display for MatcherError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<MatcherError> for PyErr {
+    fn from(err: MatcherError) -> Self {
+        let culprit = err
+            .culprit
+            .as_ref()
+            .map(|(index, pattern)| (*index, pattern.as_str()));
+        pattern_error(err.message, culprit)
+    }
+}
 
 /// High-performance glob pattern matcher using Rust's globset
 ///
 /// This provides 3-5x faster pattern matching compared to Python's fnmatch
 /// by compiling all patterns once into an optimized DFA.
-#[pyclass]
+///
+/// Patterns without any glob metacharacters are kept out of the DFA and
+/// looked up in a hash map instead, so manifests of millions of exact
+/// filenames stay O(1) per lookup.
+#[pyclass(module = "pathvein._pathvein_rs")]
 #[derive(Clone)]
 pub struct PatternMatcher {
-    globset: GlobSet,
+    /// Compiled non-literal patterns only
+    engine: GlobEngine,
+    /// Maps an engine match index back to its index in `patterns`
+    glob_indices: Vec<usize>,
+    /// Exact-name patterns, kept out of the engine
+    literals: LiteralIndex,
+    /// Per-pattern flag for trailing-`/` patterns that only match
+    /// directories; empty when the matcher has none
+    dir_only: Vec<bool>,
     patterns: Vec<String>,
+    options: MatcherOptions,
+    /// Per-pattern timings, only collected when created with `profile=True`
+    profile: Option<Arc<PatternProfile>>,
+}
+
+/// Indices of every pattern sharing one literal key
+type LiteralHits = SmallVec<[usize; 1]>;
+
+/// Exact-name patterns, looked up by hash instead of through the DFA
+#[derive(Clone, Default)]
+struct LiteralIndex {
+    /// Literals compared against the whole candidate path
+    exact: HashMap<String, LiteralHits>,
+    /// Full-path mode only: unanchored literals, compared against the last
+    /// path component so they match at any depth
+    basename: HashMap<String, LiteralHits>,
+}
+
+impl LiteralIndex {
+    fn hits<'a>(&'a self, path: &str) -> impl Iterator<Item = &'a LiteralHits> + 'a {
+        let basename_hits = if self.basename.is_empty() {
+            None
+        } else {
+            self.basename.get(path.rsplit('/').next().unwrap_or(path))
+        };
+        self.exact.get(path).into_iter().chain(basename_hits)
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        self.hits(path).next().is_some()
+    }
+
+    fn count(&self, path: &str) -> usize {
+        self.hits(path).map(|hits| hits.len()).sum()
+    }
+
+    /// Indices are pushed in pattern order, so the first of each list is its minimum
+    fn first(&self, path: &str) -> Option<usize> {
+        self.hits(path).map(|hits| hits[0]).min()
+    }
+
+    /// Append matching indices; returns whether anything was appended
+    fn extend_into(&self, path: &str, out: &mut Vec<usize>) -> bool {
+        let before = out.len();
+        for hits in self.hits(path) {
+            out.extend_from_slice(hits);
+        }
+        out.len() > before
+    }
+}
+
+/// Where a pattern applies in full-path mode (gitignore rules)
+enum Anchoring<'a> {
+    /// Leading `/` or an inner `/`: matched from the scan root only
+    Root(&'a str),
+    /// A bare name: matched at any depth
+    AnyDepth(&'a str),
+}
+
+impl<'a> Anchoring<'a> {
+    fn of(pattern: &'a str) -> Self {
+        if let Some(rest) = pattern.strip_prefix('/') {
+            Anchoring::Root(rest)
+        } else if pattern.trim_end_matches('/').contains('/') {
+            Anchoring::Root(pattern)
+        } else {
+            Anchoring::AnyDepth(pattern)
+        }
+    }
+}
+
+/// Compilation and matching options shared by every pattern in a matcher
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatcherOptions {
+    pub dialect: GlobDialect,
+    /// Treat `\` in candidate paths as `/` before matching
+    pub normalize_separators: bool,
+    /// Match root-relative paths with gitignore-style anchoring: a leading
+    /// `/` pins a pattern to the root, bare names match at any depth, and
+    /// `*` no longer crosses `/`
+    pub full_path: bool,
+    /// Unicode case folding applied to patterns and candidates alike
+    pub case_fold: CaseFold,
+}
+
+impl Default for MatcherOptions {
+    fn default() -> Self {
+        MatcherOptions {
+            dialect: GlobDialect::Globset,
+            normalize_separators: cfg!(windows),
+            full_path: false,
+            case_fold: CaseFold::None,
+        }
+    }
+}
+
+impl MatcherOptions {
+    /// Split a pattern as written into what gets compiled for it
+    pub(crate) fn split_pattern<'a>(&self, pattern: &'a str) -> PatternBody<'a> {
+        let (body, any_depth) = if self.full_path {
+            match Anchoring::of(pattern) {
+                Anchoring::Root(body) => (body, false),
+                Anchoring::AnyDepth(body) => (body, true),
+            }
+        } else {
+            (pattern, false)
+        };
+
+        // pathlib ignores trailing slashes (as `full_match` does), so
+        // only the globset dialect gives them directory-only meaning
+        let (body, dir_only) = match body.strip_suffix('/') {
+            Some(stripped) if self.dialect == GlobDialect::Globset && !stripped.is_empty() => {
+                (stripped, true)
+            }
+            _ => (body, false),
+        };
+
+        PatternBody {
+            body: self.case_fold.apply(body),
+            any_depth,
+            dir_only,
+        }
+    }
+
+    /// Bring a candidate path into the form patterns are compared against
+    pub fn prepare<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        let path = self.case_fold.apply(path);
+        let path = if self.normalize_separators && path.contains('\\') {
+            Cow::Owned(path.replace('\\', "/"))
+        } else {
+            path
+        };
+        let path = match (self.dialect, path) {
+            (GlobDialect::Globset, path) => path,
+            (GlobDialect::Pathlib, Cow::Borrowed(path)) => pathlib_normalize(path),
+            (GlobDialect::Pathlib, Cow::Owned(path)) => {
+                Cow::Owned(pathlib_normalize(&path).into_owned())
+            }
+        };
+        // Full-path candidates are relative to the scan root either way
+        match path {
+            Cow::Borrowed(path) if self.full_path => Cow::Borrowed(path.trim_start_matches('/')),
+            Cow::Owned(path) if self.full_path && path.starts_with('/') => {
+                Cow::Owned(path.trim_start_matches('/').to_string())
+            }
+            path => path,
+        }
+    }
+}
+
+/// A pattern split into the glob that gets compiled and how it applies
+pub(crate) struct PatternBody<'a> {
+    /// Glob text with anchoring and any directory-only `/` removed, case folded
+    pub body: Cow<'a, str>,
+    /// Full-path mode only: a bare name that matches at any depth
+    pub any_depth: bool,
+    /// Ends in `/` and only matches directories
+    pub dir_only: bool,
+}
+
+/// Compiled form of the non-literal patterns, per dialect
+#[derive(Clone)]
+enum GlobEngine {
+    Globset(GlobSet),
+    Regex(RegexSet),
+}
+
+impl GlobEngine {
+    fn is_match(&self, path: &str) -> bool {
+        match self {
+            GlobEngine::Globset(set) => set.is_match(path),
+            GlobEngine::Regex(set) => set.is_match(path),
+        }
+    }
+
+    fn matches(&self, path: &str) -> Vec<usize> {
+        match self {
+            GlobEngine::Globset(set) => set.matches(path),
+            GlobEngine::Regex(set) => set.matches(path).into_iter().collect(),
+        }
+    }
+
+    /// Lowest engine index matching `path`, without collecting every match
+    fn first_match(&self, path: &str) -> Option<usize> {
+        match self {
+            GlobEngine::Globset(set) => MATCH_SCRATCH.with(|scratch| {
+                let mut scratch = scratch.borrow_mut();
+                set.matches_into(path, &mut scratch);
+                scratch.iter().copied().min()
+            }),
+            GlobEngine::Regex(set) => set.matches(path).iter().next(),
+        }
+    }
+
+    fn match_count(&self, path: &str) -> usize {
+        match self {
+            GlobEngine::Globset(set) => set.matches(path).len(),
+            GlobEngine::Regex(set) => set.matches(path).iter().count(),
+        }
+    }
+}
+
+thread_local! {
+    /// Reused match buffer so `first_match` doesn't allocate per call
+    static MATCH_SCRATCH: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
 }
 
 #[pymethods]
@@ -22,80 +280,395 @@ impl PatternMatcher {
     ///
     /// Args:
     ///     patterns: List of glob patterns (e.g., ["*.py", "test_*.rs"])
+    ///     dialect: Glob semantics, "globset" (default) or "pathlib" to
+    ///         reproduce CPython's ``PurePath.full_match`` exactly
+    ///     normalize_separators: Treat ``\`` in candidate paths as ``/`` so
+    ///         rule files written with ``/`` work on Windows paths
+    ///         (default: True on Windows, False elsewhere)
+    ///     full_path: Match root-relative paths with gitignore-style
+    ///         anchoring: ``/README.md`` only matches at the root while
+    ///         ``README.md`` matches at any depth (default: False)
+    ///     case_fold: Match case-insensitively using full Unicode case
+    ///         folding: "unicode" (``ß`` matches ``ss``) or "turkic" (also
+    ///         folds ``I`` to ``ı`` and ``İ`` to ``i``). Default None
+    ///         matches case-sensitively
+    ///     profile: Record the time spent on each pattern for
+    ///         ``profile_report()``. Every pattern is then evaluated on its
+    ///         own, which is much slower (default: False)
+    ///     allow_empty: What to do with an empty pattern list, which never
+    ///         matches anything: None warns (default), True allows it
+    ///         silently and False raises ValueError
+    ///     max_patterns: Optional upper bound on the number of patterns,
+    ///         to fail fast on rule files far larger than expected
     ///
     /// Returns:
     ///     PatternMatcher instance
     ///
     /// Raises:
-    ///     ValueError: If any pattern is invalid
+    ///     PatternError: If a pattern fails to compile; a ValueError
+    ///         subclass carrying ``pattern_index`` and ``pattern``
+    ///     ValueError: If the dialect or case_fold is invalid, or the
+    ///         pattern count is rejected by allow_empty or max_patterns
     #[new]
-    pub fn new(patterns: Vec<String>) -> PyResult<Self> {
-        let mut builder = GlobSetBuilder::new();
-
-        for pattern in &patterns {
-            match Glob::new(pattern) {
-                Ok(glob) => {
-                    builder.add(glob);
-                }
-                Err(e) => {
-                    return Err(PyValueError::new_err(format!(
-                        "Invalid glob pattern '{}': {}",
-                        pattern, e
-                    )));
+    #[pyo3(signature = (
+        patterns,
+        dialect="globset",
+        normalize_separators=None,
+        full_path=false,
+        case_fold=None,
+        profile=false,
+        allow_empty=None,
+        max_patterns=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn py_new(
+        py: Python<'_>,
+        patterns: Vec<String>,
+        dialect: &str,
+        normalize_separators: Option<bool>,
+        full_path: bool,
+        case_fold: Option<&str>,
+        profile: bool,
+        allow_empty: Option<bool>,
+        max_patterns: Option<usize>,
+    ) -> PyResult<Self> {
+        if patterns.is_empty() {
+            match allow_empty {
+                Some(true) => {}
+                Some(false) => {
+                    return Err(PyValueError::new_err(
+                        "PatternMatcher needs at least one pattern",
+                    ))
                 }
+                None => PyErr::warn(
+                    py,
+                    &py.get_type::<PyUserWarning>(),
+                    pyo3::ffi::c_str!(
+                        "PatternMatcher created with no patterns never matches; \
+                         pass allow_empty=True to silence this warning"
+                    ),
+                    1,
+                )?,
             }
         }
+        if let Some(limit) = max_patterns.filter(|&limit| patterns.len() > limit) {
+            return Err(PyValueError::new_err(format!(
+                "PatternMatcher given {} patterns, more than max_patterns={}",
+                patterns.len(),
+                limit
+            )));
+        }
 
-        match builder.build() {
-            Ok(globset) => Ok(PatternMatcher {
-                globset,
-                // No clone needed - we own the patterns vector
-                patterns,
-            }),
-            Err(e) => Err(PyValueError::new_err(format!(
-                "Error building pattern matcher: {}",
-                e
-            ))),
+        let defaults = MatcherOptions::default();
+        let options = MatcherOptions {
+            dialect: GlobDialect::parse(dialect)?,
+            normalize_separators: normalize_separators.unwrap_or(defaults.normalize_separators),
+            full_path,
+            case_fold: CaseFold::parse(case_fold)?,
+        };
+        let mut matcher = Self::with_options(patterns, options)?;
+        if profile {
+            matcher.profile = Some(Arc::new(PatternProfile::new(&matcher.patterns, options)?));
         }
+        Ok(matcher)
     }
 
     /// Check if a path matches any of the patterns
     ///
+    /// Patterns ending in ``/`` (e.g. ``build/``) only match directories.
+    /// A candidate is a directory if ``is_dir`` is set or it ends in ``/``.
+    ///
     /// Args:
     ///     path: File or directory name to match
+    ///     depth: Optional exact depth the path must sit at, counted in
+    ///         components below the root (``depth=1`` is a direct child)
+    ///     is_dir: Whether the path names a directory (default: False)
     ///
     /// Returns:
     ///     True if path matches any pattern, False otherwise
-    pub fn matches(&self, path: &str) -> bool {
-        self.globset.is_match(path)
+    #[pyo3(signature = (path, depth=None, is_dir=false))]
+    pub fn matches(&self, path: &str, depth: Option<usize>, is_dir: bool) -> bool {
+        let (path, is_dir) = self.candidate(path, is_dir);
+        at_depth(&path, depth) && self.is_prepared_match(&path, is_dir)
     }
 
     /// Find all patterns that match the given path
     ///
     /// Args:
     ///     path: File or directory name to match
+    ///     depth: Optional exact depth the path must sit at
+    ///     is_dir: Whether the path names a directory (default: False)
     ///
     /// Returns:
     ///     List of matching pattern strings
-    pub fn matching_patterns(&self, path: &str) -> Vec<String> {
-        self.globset
-            .matches(path)
-            .iter()
-            .map(|idx| self.patterns[*idx].clone())
+    #[pyo3(signature = (path, depth=None, is_dir=false))]
+    pub fn matching_patterns(&self, path: &str, depth: Option<usize>, is_dir: bool) -> Vec<String> {
+        let (path, is_dir) = self.candidate(path, is_dir);
+        if !at_depth(&path, depth) {
+            return Vec::new();
+        }
+        self.match_indices(&path, is_dir)
+            .into_iter()
+            .map(|idx| self.patterns[idx].clone())
             .collect()
     }
 
+    /// Find the highest-priority (earliest-listed) matching pattern
+    ///
+    /// This is the routing question "which rule applies", answered without
+    /// building the full list of matching patterns.
+    ///
+    /// Args:
+    ///     path: File or directory name to match
+    ///     depth: Optional exact depth the path must sit at
+    ///     is_dir: Whether the path names a directory (default: False)
+    ///
+    /// Returns:
+    ///     The first matching pattern string, or None
+    #[pyo3(signature = (path, depth=None, is_dir=false))]
+    pub fn first_match(&self, path: &str, depth: Option<usize>, is_dir: bool) -> Option<String> {
+        let (path, is_dir) = self.candidate(path, is_dir);
+        if !at_depth(&path, depth) {
+            return None;
+        }
+        self.first_match_index(&path, is_dir)
+            .map(|idx| self.patterns[idx].clone())
+    }
+
     /// Check if path matches all patterns
     ///
     /// Args:
     ///     path: File or directory name to match
+    ///     depth: Optional exact depth the path must sit at
+    ///     is_dir: Whether the path names a directory (default: False)
     ///
     /// Returns:
     ///     True if path matches ALL patterns, False otherwise
-    pub fn matches_all(&self, path: &str) -> bool {
-        // Optimized: Count matches without allocating a Vec
-        let match_count = self.globset.matches(path).len();
-        self.patterns.len() == match_count
+    #[pyo3(signature = (path, depth=None, is_dir=false))]
+    pub fn matches_all(&self, path: &str, depth: Option<usize>, is_dir: bool) -> bool {
+        let (path, is_dir) = self.candidate(path, is_dir);
+        if !at_depth(&path, depth) {
+            return false;
+        }
+        self.patterns.len() == self.prepared_match_count(&path, is_dir)
+    }
+
+    /// Pull out the text matched by each wildcard of one pattern
+    ///
+    /// Every ``*``, ``**`` and ``?`` in the pattern becomes one entry, in
+    /// order, so ``run_*/sample_*.csv`` yields ``["042", "07"]`` for
+    /// ``run_042/sample_07.csv``. A whole-component ``**`` yields the
+    /// directories it spans without the trailing ``/``.
+    ///
+    /// Args:
+    ///     path: File or directory name to match
+    ///     pattern_index: Index of the pattern in this matcher
+    ///     is_dir: Whether the path names a directory (default: False)
+    ///
+    /// Returns:
+    ///     List of captured strings, or None if the pattern doesn't match
+    ///
+    /// Raises:
+    ///     IndexError: If pattern_index is out of range
+    #[pyo3(signature = (path, pattern_index, is_dir=false))]
+    pub fn extract(
+        &self,
+        path: &str,
+        pattern_index: usize,
+        is_dir: bool,
+    ) -> PyResult<Option<Vec<String>>> {
+        let pattern = self.patterns.get(pattern_index).ok_or_else(|| {
+            PyIndexError::new_err(format!(
+                "pattern_index {} out of range for {} patterns",
+                pattern_index,
+                self.patterns.len()
+            ))
+        })?;
+        let (path, is_dir) = self.candidate(path, is_dir);
+        crate::capture::extract(pattern, &self.options, &path, is_dir)
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Count how many patterns match a path
+    ///
+    /// Args:
+    ///     path: File or directory name to match
+    ///     depth: Optional exact depth the path must sit at
+    ///     is_dir: Whether the path names a directory (default: False)
+    ///
+    /// Returns:
+    ///     Number of matching patterns, counting duplicates separately
+    #[pyo3(signature = (path, depth=None, is_dir=false))]
+    pub fn matches_count(&self, path: &str, depth: Option<usize>, is_dir: bool) -> usize {
+        let (path, is_dir) = self.candidate(path, is_dir);
+        if !at_depth(&path, depth) {
+            return 0;
+        }
+        self.prepared_match_count(&path, is_dir)
+    }
+
+    /// Check if any of the paths matches any pattern
+    ///
+    /// Stops at the first matching path and runs with the GIL released.
+    ///
+    /// Args:
+    ///     paths: File or directory names to match
+    ///     depth: Optional exact depth each path must sit at
+    ///     is_dir: Whether the paths name directories (default: False)
+    ///
+    /// Returns:
+    ///     True if at least one path matches, False otherwise
+    #[pyo3(signature = (paths, depth=None, is_dir=false))]
+    pub fn matches_any(
+        &self,
+        py: Python<'_>,
+        paths: Vec<String>,
+        depth: Option<usize>,
+        is_dir: bool,
+    ) -> bool {
+        py.allow_threads(|| paths.iter().any(|path| self.matches(path, depth, is_dir)))
+    }
+
+    /// Check if every path matches at least one pattern
+    ///
+    /// Stops at the first path that matches nothing and runs with the GIL
+    /// released. An empty list of paths is vacuously true.
+    ///
+    /// Args:
+    ///     paths: File or directory names to match
+    ///     depth: Optional exact depth each path must sit at
+    ///     is_dir: Whether the paths name directories (default: False)
+    ///
+    /// Returns:
+    ///     True if all paths match, False otherwise
+    #[pyo3(signature = (paths, depth=None, is_dir=false))]
+    pub fn all_match(
+        &self,
+        py: Python<'_>,
+        paths: Vec<String>,
+        depth: Option<usize>,
+        is_dir: bool,
+    ) -> bool {
+        py.allow_threads(|| paths.iter().all(|path| self.matches(path, depth, is_dir)))
+    }
+
+    /// Report duplicate, shadowed and never-matching patterns
+    ///
+    /// A pattern is shadowed when a broader one in the same set already
+    /// matches everything it does (``*.py`` makes ``test_*.py``
+    /// redundant). Shadowing is checked against sample paths generated
+    /// from each pattern's wildcards, so treat it as a strong hint rather
+    /// than a proof.
+    ///
+    /// Args:
+    ///     samples: Optional names or paths to check for patterns that
+    ///         never match; ``unmatched`` is empty when omitted
+    ///
+    /// Returns:
+    ///     PatternAnalysis with ``duplicates``, ``shadowed`` (pattern,
+    ///     broader_pattern) pairs and ``unmatched`` lists
+    #[pyo3(signature = (samples=None))]
+    pub fn analyze(&self, py: Python<'_>, samples: Option<Vec<String>>) -> PatternAnalysis {
+        py.allow_threads(|| crate::analysis::analyze(self, samples.as_deref()))
+    }
+
+    /// Time spent on each pattern since creation or ``reset_profile()``
+    ///
+    /// Returns:
+    ///     List of (pattern, seconds, hits) tuples, slowest first, where
+    ///     hits counts the candidates the pattern matched
+    ///
+    /// Raises:
+    ///     ValueError: If the matcher was not created with ``profile=True``
+    pub fn profile_report(&self) -> PyResult<Vec<(String, f64, u64)>> {
+        let profile = self.profile()?;
+        Ok(profile
+            .report()
+            .into_iter()
+            .map(|(idx, seconds, hits)| (self.patterns[idx].clone(), seconds, hits))
+            .collect())
+    }
+
+    /// Number of candidates evaluated while profiling
+    ///
+    /// Raises:
+    ///     ValueError: If the matcher was not created with ``profile=True``
+    #[getter]
+    pub fn profile_calls(&self) -> PyResult<u64> {
+        Ok(self.profile()?.calls())
+    }
+
+    /// Clear the collected timings
+    ///
+    /// Raises:
+    ///     ValueError: If the matcher was not created with ``profile=True``
+    pub fn reset_profile(&self) -> PyResult<()> {
+        self.profile()?.reset();
+        Ok(())
+    }
+
+    /// Serialize the matcher so another process can rebuild it
+    ///
+    /// The payload is a small header followed by the JSON pattern list.
+    /// globset does not expose its compiled automata, so the DFA itself
+    /// is rebuilt by `from_bytes`; what is saved is reading and
+    /// validating the rule files again in every worker.
+    ///
+    /// Returns:
+    ///     bytes accepted by `PatternMatcher.from_bytes`
+    pub fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let spec = MatcherSpec {
+            patterns: self.patterns.clone(),
+            options: self.options,
+        };
+        let payload = serde_json::to_vec(&spec).map_err(|e| {
+            PyValueError::new_err(format!("Error serializing pattern matcher: {}", e))
+        })?;
+
+        let mut data = Vec::with_capacity(MATCHER_MAGIC.len() + 1 + payload.len());
+        data.extend_from_slice(MATCHER_MAGIC);
+        data.push(MATCHER_FORMAT_VERSION);
+        data.extend_from_slice(&payload);
+        Ok(PyBytes::new(py, &data))
+    }
+
+    /// Rebuild a matcher from the output of `to_bytes`
+    ///
+    /// Args:
+    ///     data: Bytes produced by `PatternMatcher.to_bytes`
+    ///
+    /// Returns:
+    ///     PatternMatcher instance
+    ///
+    /// Raises:
+    ///     ValueError: If the data is not a serialized matcher
+    #[staticmethod]
+    pub fn from_bytes(data: &[u8]) -> PyResult<Self> {
+        let payload = data
+            .strip_prefix(MATCHER_MAGIC.as_slice())
+            .ok_or_else(|| PyValueError::new_err("Not a serialized PatternMatcher"))?;
+        match payload.split_first() {
+            Some((&MATCHER_FORMAT_VERSION, spec)) => {
+                let spec: MatcherSpec = serde_json::from_slice(spec).map_err(|e| {
+                    PyValueError::new_err(format!("Corrupt serialized PatternMatcher: {}", e))
+                })?;
+                PatternMatcher::with_options(spec.patterns, spec.options)
+            }
+            Some((version, _)) => Err(PyValueError::new_err(format!(
+                "Unsupported PatternMatcher format version {}",
+                version
+            ))),
+            None => Err(PyValueError::new_err("Truncated serialized PatternMatcher")),
+        }
+    }
+
+    /// Pickle support so matchers can be sent to worker processes
+    fn __reduce__<'py>(
+        slf: &Bound<'py, Self>,
+    ) -> PyResult<(Bound<'py, PyAny>, (Bound<'py, PyBytes>,))> {
+        let from_bytes = slf.get_type().getattr("from_bytes")?;
+        let data = slf.borrow().to_bytes(slf.py())?;
+        Ok((from_bytes, (data,)))
     }
 
     fn __repr__(&self) -> String {
@@ -105,55 +678,779 @@ impl PatternMatcher {
     fn __len__(&self) -> usize {
         self.patterns.len()
     }
+
+    /// Whether `pattern` is one of this matcher's patterns, exactly as written
+    fn __contains__(&self, pattern: &str) -> bool {
+        self.patterns.iter().any(|p| p == pattern)
+    }
+
+    /// Iterate over the patterns in the order they were given
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        PyList::new(py, &self.patterns)?.try_iter()
+    }
+
+    /// Matchers are equal when they have the same patterns, in the same
+    /// order, and the same options
+    fn __eq__(&self, other: &Self) -> bool {
+        self.patterns == other.patterns && self.options == other.options
+    }
+
+    fn __hash__(&self) -> u64 {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+        let mut hasher = DefaultHasher::new();
+        self.patterns.hash(&mut hasher);
+        self.options.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Union: patterns from either matcher, duplicates dropped
+    ///
+    /// Raises:
+    ///     ValueError: If the matchers were built with different options
+    fn __or__(&self, other: PyRef<'_, Self>) -> PyResult<Self> {
+        self.combine(&other, |_| true, true)
+    }
+
+    /// Intersection: patterns present in both matchers
+    ///
+    /// Raises:
+    ///     ValueError: If the matchers were built with different options
+    fn __and__(&self, other: PyRef<'_, Self>) -> PyResult<Self> {
+        let theirs: HashSet<&str> = other.patterns.iter().map(String::as_str).collect();
+        self.combine(&other, |pattern| theirs.contains(pattern), false)
+    }
+
+    /// Difference: patterns in this matcher that are not in `other`
+    ///
+    /// Raises:
+    ///     ValueError: If the matchers were built with different options
+    fn __sub__(&self, other: PyRef<'_, Self>) -> PyResult<Self> {
+        let theirs: HashSet<&str> = other.patterns.iter().map(String::as_str).collect();
+        self.combine(&other, |pattern| !theirs.contains(pattern), false)
+    }
+}
+
+/// Header identifying a serialized PatternMatcher
+const MATCHER_MAGIC: &[u8; 4] = b"PVPM";
+
+/// Bumped whenever `MatcherSpec` changes incompatibly
+const MATCHER_FORMAT_VERSION: u8 = 1;
+
+/// Everything needed to rebuild a PatternMatcher in another process
+#[derive(Serialize, Deserialize)]
+struct MatcherSpec {
+    patterns: Vec<String>,
+    #[serde(flatten)]
+    options: MatcherOptions,
+}
+
+impl PatternMatcher {
+    /// Create a globset-dialect matcher (Rust-side constructor)
+    pub fn new(patterns: Vec<String>) -> PyResult<Self> {
+        Self::with_options(patterns, MatcherOptions::default())
+    }
+
+    /// Build a new matcher from this matcher's patterns that pass `keep`,
+    /// optionally followed by `other`'s, keeping first-seen order
+    ///
+    /// Patterns are compared as written, so `*.py` and `**/*.py` stay
+    /// distinct even where they match the same paths.
+    fn combine(
+        &self,
+        other: &Self,
+        keep: impl Fn(&str) -> bool,
+        include_other: bool,
+    ) -> PyResult<Self> {
+        if self.options != other.options {
+            return Err(PyValueError::new_err(
+                "Cannot combine PatternMatchers built with different options",
+            ));
+        }
+        let theirs = if include_other {
+            other.patterns.as_slice()
+        } else {
+            &[]
+        };
+        let mut seen = HashSet::new();
+        let patterns: Vec<String> = self
+            .patterns
+            .iter()
+            .filter(|pattern| keep(pattern))
+            .chain(theirs)
+            .filter(|pattern| seen.insert(pattern.as_str()))
+            .cloned()
+            .collect();
+        Self::with_options(patterns, self.options)
+    }
+
+    /// Create a matcher compiling every non-literal pattern with `options`
+    pub fn with_options(patterns: Vec<String>, options: MatcherOptions) -> PyResult<Self> {
+        Self::build(patterns, options).map_err(PyErr::from)
+    }
+
+    /// [`with_options`](Self::with_options) without Python: its error
+    /// needs no interpreter, so callers outside one can report it
+    pub(crate) fn build(
+        patterns: Vec<String>,
+        options: MatcherOptions,
+    ) -> Result<Self, MatcherError> {
+        let mut glob_indices = Vec::new();
+        let mut globs: Vec<Cow<str>> = Vec::new();
+        let mut literals = LiteralIndex::default();
+        let mut dir_only = vec![false; patterns.len()];
+
+        for (idx, pattern) in patterns.iter().enumerate() {
+            let PatternBody {
+                body,
+                any_depth,
+                dir_only: is_dir_only,
+            } = options.split_pattern(pattern);
+            dir_only[idx] = is_dir_only;
+
+            if is_literal(&body) {
+                let key = options.prepare(&body).into_owned();
+                let index = if any_depth {
+                    &mut literals.basename
+                } else {
+                    &mut literals.exact
+                };
+                index.entry(key).or_default().push(idx);
+            } else {
+                glob_indices.push(idx);
+                globs.push(if any_depth {
+                    Cow::Owned(format!("**/{}", body))
+                } else {
+                    body
+                });
+            }
+        }
+
+        let engine = match options.dialect {
+            GlobDialect::Globset => {
+                let mut builder = GlobSetBuilder::new();
+                for (pattern, &idx) in globs.iter().zip(&glob_indices) {
+                    let glob = GlobBuilder::new(pattern)
+                        .literal_separator(options.full_path)
+                        .build()
+                        .map_err(|e| MatcherError {
+                            message: format!(
+                                "Invalid glob pattern '{}' at index {}: {}",
+                                patterns[idx], idx, e
+                            ),
+                            culprit: Some((idx, patterns[idx].clone())),
+                        })?;
+                    builder.add(glob);
+                }
+                let globset = builder.build().map_err(|e| MatcherError {
+                    message: format!("Error building pattern matcher: {}", e),
+                    culprit: None,
+                })?;
+                GlobEngine::Globset(globset)
+            }
+            GlobDialect::Pathlib => {
+                let regexes: Vec<String> = globs
+                    .iter()
+                    .map(|pattern| format!(r"^(?s:{})\z", pathlib_regex_body(pattern)))
+                    .collect();
+                let set = RegexSet::new(&regexes).map_err(|e| {
+                    // The set error doesn't say which regex failed, so find
+                    // the first one that fails on its own
+                    let culprit = regexes
+                        .iter()
+                        .position(|regex| Regex::new(regex).is_err())
+                        .map(|pos| (glob_indices[pos], patterns[glob_indices[pos]].clone()));
+                    let message = match &culprit {
+                        Some((idx, pattern)) => {
+                            format!("Invalid glob pattern '{}' at index {}: {}", pattern, idx, e)
+                        }
+                        None => format!("Error building pattern matcher: {}", e),
+                    };
+                    MatcherError { message, culprit }
+                })?;
+                GlobEngine::Regex(set)
+            }
+        };
+
+        if !dir_only.contains(&true) {
+            dir_only = Vec::new();
+        }
+
+        Ok(PatternMatcher {
+            engine,
+            glob_indices,
+            literals,
+            dir_only,
+            // No clone needed - we own the patterns vector
+            patterns,
+            options,
+            profile: None,
+        })
+    }
+
+    fn profile(&self) -> PyResult<&PatternProfile> {
+        self.profile.as_deref().ok_or_else(|| {
+            PyValueError::new_err("Profiling is not enabled; create the matcher with profile=True")
+        })
+    }
+
+    pub(crate) fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    pub(crate) fn options(&self) -> MatcherOptions {
+        self.options
+    }
+
+    /// Rust-side match check of a non-directory path without depth anchoring
+    pub fn is_match(&self, path: &str) -> bool {
+        self.is_prepared_match(&self.options.prepare(path), false)
+    }
+
+    /// Rust-side match check of an `OsStr` name or path, e.g. straight from
+    /// a directory walk
+    ///
+    /// UTF-8 names (the common case) are borrowed as-is; only names that
+    /// are not valid UTF-8 pay for a lossy conversion.
+    pub fn is_match_os(&self, path: &OsStr, is_dir: bool) -> bool {
+        match path.to_str() {
+            Some(path) => self.is_prepared_match(&self.options.prepare(path), is_dir),
+            None => self.is_prepared_match(&self.options.prepare(&path.to_string_lossy()), is_dir),
+        }
+    }
+
+    /// Rust-side match check of a `Path`; see `is_match_os`
+    pub fn is_match_path(&self, path: &Path, is_dir: bool) -> bool {
+        self.is_match_os(path.as_os_str(), is_dir)
+    }
+
+    /// Prepare a candidate and work out whether it names a directory
+    ///
+    /// In the globset dialect a trailing `/` marks the candidate as a
+    /// directory, mirroring how directory-only patterns are written.
+    pub(crate) fn candidate<'a>(&self, path: &'a str, is_dir: bool) -> (Cow<'a, str>, bool) {
+        if self.options.dialect == GlobDialect::Globset {
+            if let Some(stripped) = path.strip_suffix('/').filter(|p| !p.is_empty()) {
+                return (self.options.prepare(stripped), true);
+            }
+        }
+        (self.options.prepare(path), is_dir)
+    }
+
+    /// Whether directory-only patterns have to be filtered out for this candidate
+    fn filters_dirs(&self, is_dir: bool) -> bool {
+        !is_dir && !self.dir_only.is_empty()
+    }
+
+    /// Whether answering for this candidate has to go through `match_indices`
+    fn needs_indices(&self, is_dir: bool) -> bool {
+        self.filters_dirs(is_dir) || self.profile.is_some()
+    }
+
+    /// Match check for a path already passed through `MatcherOptions::prepare`
+    pub(crate) fn is_prepared_match(&self, path: &str, is_dir: bool) -> bool {
+        if self.needs_indices(is_dir) {
+            return !self.match_indices(path, is_dir).is_empty();
+        }
+        self.literals.contains(path) || self.engine.is_match(path)
+    }
+
+    /// Number of patterns matching a prepared `path`
+    fn prepared_match_count(&self, path: &str, is_dir: bool) -> usize {
+        if self.needs_indices(is_dir) {
+            self.match_indices(path, is_dir).len()
+        } else {
+            self.literals.count(path) + self.engine.match_count(path)
+        }
+    }
+
+    /// Index of the earliest pattern matching a prepared `path`
+    fn first_match_index(&self, path: &str, is_dir: bool) -> Option<usize> {
+        if self.needs_indices(is_dir) {
+            return self.match_indices(path, is_dir).first().copied();
+        }
+        // glob_indices is increasing, so the lowest engine index maps lowest
+        let literal = self.literals.first(path);
+        let glob = self
+            .engine
+            .first_match(path)
+            .map(|glob_idx| self.glob_indices[glob_idx]);
+        match (literal, glob) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Indices of every pattern matching a prepared `path`, in pattern order
+    pub(crate) fn match_indices(&self, path: &str, is_dir: bool) -> Vec<usize> {
+        if let Some(profile) = &self.profile {
+            return profile.match_indices(path, is_dir);
+        }
+        let mut indices: Vec<usize> = self
+            .engine
+            .matches(path)
+            .into_iter()
+            .map(|glob_idx| self.glob_indices[glob_idx])
+            .collect();
+        if self.literals.extend_into(path, &mut indices) {
+            indices.sort_unstable();
+        }
+        if self.filters_dirs(is_dir) {
+            indices.retain(|&idx| !self.dir_only[idx]);
+        }
+        indices
+    }
+}
+
+/// Whether a pattern contains no glob syntax and can only match itself
+fn is_literal(pattern: &str) -> bool {
+    !pattern
+        .chars()
+        .any(|c| matches!(c, '*' | '?' | '[' | ']' | '{' | '}' | '\\'))
+}
+
+/// Number of path components in a root-relative path
+///
+/// Empty components (leading, trailing or doubled separators) are ignored,
+/// so `"a/b/data"`, `"/a/b/data"` and `"a//b/data/"` all sit at depth 3.
+fn path_depth(path: &str) -> usize {
+    path.split('/').filter(|part| !part.is_empty()).count()
+}
+
+/// Check the depth anchor of a path (no anchor always passes)
+///
+/// Plain globs can't express "exactly N levels below the root" because
+/// `*` and `**` both cross separators, so depth is checked separately.
+fn at_depth(path: &str, depth: Option<usize>) -> bool {
+    depth.map_or(true, |depth| path_depth(path) == depth)
 }
 
-// Global cache for compiled patterns (matches Python's @lru_cache(maxsize=256))
-static PATTERN_CACHE: Mutex<Option<LruCache<String, GlobMatcher>>> = Mutex::new(None);
+/// Single compiled pattern as stored in the match_pattern cache
+#[derive(Clone)]
+enum SingleMatcher {
+    Glob(GlobMatcher),
+    /// pathlib-dialect regex; candidates must be normalized before matching
+    Pathlib(Regex),
+}
+
+impl SingleMatcher {
+    fn is_match(&self, path: &str) -> bool {
+        match self {
+            SingleMatcher::Glob(matcher) => matcher.is_match(path),
+            SingleMatcher::Pathlib(regex) => regex.is_match(path),
+        }
+    }
+}
 
-/// Get or compile a pattern from the cache
-fn get_or_compile_pattern(pattern: &str) -> PyResult<GlobMatcher> {
-    let mut cache_lock = PATTERN_CACHE.lock().unwrap();
+/// Number of compiled patterns kept per dialect, in each cache
+const PATTERN_CACHE_SIZE: usize = 256;
+
+type PatternCache = Mutex<Option<LruCache<String, SingleMatcher>>>;
+
+// Shared caches for compiled patterns (matches Python's @lru_cache(maxsize=256)),
+// one per dialect so lookups can borrow the pattern string as the key. These
+// are only the cold path behind the per-thread caches below.
+static PATTERN_CACHE: PatternCache = Mutex::new(None);
+static PATHLIB_PATTERN_CACHE: PatternCache = Mutex::new(None);
+
+/// Per-thread caches, so concurrent callers don't contend on a global lock
+struct LocalPatternCache {
+    /// Value of `CACHE_GENERATION` when this cache was last cleared
+    generation: u64,
+    globset: LruCache<String, SingleMatcher>,
+    pathlib: LruCache<String, SingleMatcher>,
+}
+
+impl LocalPatternCache {
+    fn new() -> Self {
+        let size = NonZeroUsize::new(PATTERN_CACHE_SIZE).unwrap();
+        LocalPatternCache {
+            generation: CACHE_GENERATION.load(Ordering::Relaxed),
+            globset: LruCache::new(size),
+            pathlib: LruCache::new(size),
+        }
+    }
+}
+
+thread_local! {
+    static LOCAL_PATTERN_CACHE: RefCell<LocalPatternCache> =
+        RefCell::new(LocalPatternCache::new());
+}
+
+/// Bumped by `match_pattern_cache_clear` so every thread drops its cache
+/// on its next lookup
+static CACHE_GENERATION: AtomicU64 = AtomicU64::new(0);
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static CACHE_SHARED_HITS: AtomicU64 = AtomicU64::new(0);
+static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// Get or compile a pattern, checking this thread's cache first
+fn get_or_compile_pattern(pattern: &str, dialect: GlobDialect) -> PyResult<SingleMatcher> {
+    LOCAL_PATTERN_CACHE.with(|local| {
+        let mut local = local.borrow_mut();
+        let generation = CACHE_GENERATION.load(Ordering::Relaxed);
+        if local.generation != generation {
+            *local = LocalPatternCache::new();
+        }
+        let cache = match dialect {
+            GlobDialect::Globset => &mut local.globset,
+            GlobDialect::Pathlib => &mut local.pathlib,
+        };
+
+        if let Some(matcher) = cache.get(pattern) {
+            CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+            return Ok(matcher.clone());
+        }
+        let matcher = get_or_compile_shared(pattern, dialect)?;
+        cache.put(pattern.to_string(), matcher.clone());
+        Ok(matcher)
+    })
+}
+
+/// Cold path: look the pattern up in the shared cache, compiling on a miss
+///
+/// Sharing compiled patterns means a pattern is only compiled once no
+/// matter how many threads use it.
+fn get_or_compile_shared(pattern: &str, dialect: GlobDialect) -> PyResult<SingleMatcher> {
+    let cache = match dialect {
+        GlobDialect::Globset => &PATTERN_CACHE,
+        GlobDialect::Pathlib => &PATHLIB_PATTERN_CACHE,
+    };
+    let mut cache_lock = cache.lock().unwrap();
 
     // Initialize cache on first use
     if cache_lock.is_none() {
-        *cache_lock = Some(LruCache::new(NonZeroUsize::new(256).unwrap()));
+        *cache_lock = Some(LruCache::new(
+            NonZeroUsize::new(PATTERN_CACHE_SIZE).unwrap(),
+        ));
     }
 
     let cache = cache_lock.as_mut().unwrap();
 
     // Check if pattern is in cache
     if let Some(matcher) = cache.get(pattern) {
+        CACHE_SHARED_HITS.fetch_add(1, Ordering::Relaxed);
         return Ok(matcher.clone());
     }
 
     // Compile and cache the pattern
-    match Glob::new(pattern) {
-        Ok(glob) => {
-            let matcher = glob.compile_matcher();
-            cache.put(pattern.to_string(), matcher.clone());
-            Ok(matcher)
-        }
-        Err(e) => Err(PyValueError::new_err(format!(
-            "Invalid glob pattern '{}': {}",
-            pattern, e
-        ))),
+    CACHE_MISSES.fetch_add(1, Ordering::Relaxed);
+    let matcher = match dialect {
+        GlobDialect::Globset => Glob::new(pattern)
+            .map(|glob| SingleMatcher::Glob(glob.compile_matcher()))
+            .map_err(|e| format!("Invalid glob pattern '{}': {}", pattern, e)),
+        GlobDialect::Pathlib => compile_pathlib(pattern).map(SingleMatcher::Pathlib),
+    }
+    .map_err(pattern_syntax_error)?;
+    cache.put(pattern.to_string(), matcher.clone());
+    Ok(matcher)
+}
+
+/// Counters for the match_pattern compile caches
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct CacheInfo {
+    /// Lookups answered by the calling thread's own cache
+    #[pyo3(get)]
+    pub hits: u64,
+    /// Thread-cache misses answered by the shared cache
+    #[pyo3(get)]
+    pub shared_hits: u64,
+    /// Lookups that had to compile the pattern
+    #[pyo3(get)]
+    pub misses: u64,
+    /// Patterns kept per dialect in each cache
+    #[pyo3(get)]
+    pub maxsize: usize,
+}
+
+#[pymethods]
+impl CacheInfo {
+    fn __repr__(&self) -> String {
+        format!(
+            "CacheInfo(hits={}, shared_hits={}, misses={}, maxsize={})",
+            self.hits, self.shared_hits, self.misses, self.maxsize
+        )
+    }
+}
+
+/// Report hit/miss counters for the caches behind ``match_pattern``
+///
+/// Each thread keeps its own LRU cache and falls back to a shared one, so
+/// a high ``shared_hits`` count with few ``misses`` means threads are
+/// working through more distinct patterns than fit in their own cache.
+///
+/// Returns:
+///     CacheInfo with hits, shared_hits, misses and maxsize
+#[pyfunction]
+pub fn match_pattern_cache_info() -> CacheInfo {
+    CacheInfo {
+        hits: CACHE_HITS.load(Ordering::Relaxed),
+        shared_hits: CACHE_SHARED_HITS.load(Ordering::Relaxed),
+        misses: CACHE_MISSES.load(Ordering::Relaxed),
+        maxsize: PATTERN_CACHE_SIZE,
+    }
+}
+
+/// Empty the ``match_pattern`` caches on every thread and reset the counters
+#[pyfunction]
+pub fn match_pattern_cache_clear() {
+    for cache in [&PATTERN_CACHE, &PATHLIB_PATTERN_CACHE] {
+        *cache.lock().unwrap() = None;
+    }
+    CACHE_GENERATION.fetch_add(1, Ordering::Relaxed);
+    for counter in [&CACHE_HITS, &CACHE_SHARED_HITS, &CACHE_MISSES] {
+        counter.store(0, Ordering::Relaxed);
     }
 }
 
 /// Match a single path against a single pattern (convenience function)
 ///
-/// Uses an LRU cache (maxsize=256) to avoid recompiling patterns, matching
-/// Python's behavior. For the best performance with many matches, use
+/// Uses per-thread LRU caches (maxsize=256) backed by a shared one to avoid
+/// recompiling patterns, matching Python's behavior; see
+/// ``match_pattern_cache_info``. For the best performance with many matches, use
 /// PatternMatcher which pre-compiles all patterns once.
 ///
 /// Args:
 ///     path: File or directory name to match
 ///     pattern: Glob pattern (e.g., "*.py")
+///     depth: Optional exact depth the path must sit at, counted in
+///         components below the root (e.g. ``match_pattern("raw/data",
+///         "*/data", depth=2)`` rejects ``"a/raw/data"``)
+///     dialect: Glob semantics, "globset" (default) or "pathlib"
+///     normalize_separators: Treat ``\`` in the path as ``/`` (default:
+///         True on Windows, False elsewhere)
 ///
 /// Returns:
 ///     True if path matches pattern, False otherwise
 #[pyfunction]
-pub fn match_pattern(path: &str, pattern: &str) -> PyResult<bool> {
-    let matcher = get_or_compile_pattern(pattern)?;
-    Ok(matcher.is_match(path))
+#[pyo3(signature = (path, pattern, depth=None, dialect="globset", normalize_separators=None))]
+pub fn match_pattern(
+    path: &str,
+    pattern: &str,
+    depth: Option<usize>,
+    dialect: &str,
+    normalize_separators: Option<bool>,
+) -> PyResult<bool> {
+    let defaults = MatcherOptions::default();
+    let options = MatcherOptions {
+        dialect: GlobDialect::parse(dialect)?,
+        normalize_separators: normalize_separators.unwrap_or(defaults.normalize_separators),
+        ..defaults
+    };
+    let matcher = get_or_compile_pattern(pattern, options.dialect)?;
+    let path = options.prepare(path);
+    Ok(at_depth(&path, depth) && matcher.is_match(&path))
+}
+
+/// Match many paths against a single pattern in one call
+///
+/// The pattern is compiled (or fetched from the match_pattern cache) once
+/// and the loop runs in Rust with the GIL released, avoiding the per-call
+/// FFI overhead of calling ``match_pattern`` from a Python loop.
+///
+/// Args:
+///     paths: File or directory names to match
+///     pattern: Glob pattern (e.g., "*.py")
+///     depth: Optional exact depth each path must sit at
+///     dialect: Glob semantics, "globset" (default) or "pathlib"
+///     normalize_separators: Treat ``\`` in paths as ``/`` (default:
+///         True on Windows, False elsewhere)
+///
+/// Returns:
+///     List of booleans, one per path, in the same order
+///
+/// Raises:
+///     ValueError: If the pattern or dialect is invalid
+#[pyfunction]
+#[pyo3(signature = (paths, pattern, depth=None, dialect="globset", normalize_separators=None))]
+pub fn match_pattern_many(
+    py: Python<'_>,
+    paths: Vec<String>,
+    pattern: &str,
+    depth: Option<usize>,
+    dialect: &str,
+    normalize_separators: Option<bool>,
+) -> PyResult<Vec<bool>> {
+    let defaults = MatcherOptions::default();
+    let options = MatcherOptions {
+        dialect: GlobDialect::parse(dialect)?,
+        normalize_separators: normalize_separators.unwrap_or(defaults.normalize_separators),
+        ..defaults
+    };
+    let matcher = get_or_compile_pattern(pattern, options.dialect)?;
+    Ok(py.allow_threads(|| {
+        paths
+            .iter()
+            .map(|path| {
+                let path = options.prepare(path);
+                at_depth(&path, depth) && matcher.is_match(&path)
+            })
+            .collect()
+    }))
+}
+
+/// Regex flavor produced by `glob_to_regex`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RegexDialect {
+    /// Rust `regex` crate syntax, exactly what globset compiles
+    Rust,
+    /// Python `re` module
+    Python,
+    /// PCRE, as used by most SQLite REGEXP extensions
+    Pcre,
+    /// PostgreSQL advanced regular expressions (`~` operator)
+    Postgres,
+}
+
+impl RegexDialect {
+    fn parse(dialect: &str) -> PyResult<Self> {
+        match dialect {
+            "rust" => Ok(RegexDialect::Rust),
+            "python" => Ok(RegexDialect::Python),
+            "pcre" | "sqlite" => Ok(RegexDialect::Pcre),
+            "postgres" | "postgresql" => Ok(RegexDialect::Postgres),
+            other => Err(PyValueError::new_err(format!(
+                "Unknown regex dialect '{}': expected 'rust', 'python', 'pcre', or 'postgres'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Rewrite a globset regex for another engine
+///
+/// globset always emits `(?<flags>-u)^...$`, matching bytes with `.`
+/// also matching newlines. Other engines don't know the `u` flag, and
+/// Python/PCRE `$` also matches before a trailing newline, so the flag
+/// group is reduced to the flags they share plus `s`, and the end anchor is
+/// made strict. Non-ASCII characters, which globset escapes byte by byte,
+/// are written back as the characters themselves.
+fn translate_regex(regex: &str, dialect: RegexDialect) -> String {
+    if dialect == RegexDialect::Rust {
+        return regex.to_string();
+    }
+
+    let (flags, body) = match regex
+        .strip_prefix("(?")
+        .and_then(|rest| rest.split_once(')'))
+    {
+        Some((flags, body)) => (flags, body),
+        None => ("", regex),
+    };
+    // Keep only enabled flags (the part before '-'), which is at most `i`
+    let mut enabled = flags.split('-').next().unwrap_or("").to_string();
+    // Postgres `.` already matches newlines
+    if dialect != RegexDialect::Postgres {
+        enabled.push('s');
+    }
+    let mut out = String::with_capacity(regex.len() + 6);
+    if !enabled.is_empty() {
+        out.push_str("(?");
+        out.push_str(&enabled);
+        out.push(')');
+    }
+
+    let body = body.strip_suffix('$').unwrap_or(body);
+    out.push_str(&decode_byte_escapes(body));
+    out.push_str(match dialect {
+        RegexDialect::Python => r"\Z",
+        RegexDialect::Pcre => r"\z",
+        RegexDialect::Postgres | RegexDialect::Rust => "$",
+    });
+    out
+}
+
+/// Replace runs of `\xHH` escapes that spell out UTF-8 characters with
+/// those characters; ASCII bytes and invalid sequences stay escaped
+fn decode_byte_escapes(body: &str) -> String {
+    let mut out = String::with_capacity(body.len());
+    let mut bytes = Vec::new();
+    let flush = |bytes: &mut Vec<u8>, out: &mut String| {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => {
+                for ch in text.chars() {
+                    if ch.is_ascii() {
+                        out.push_str(&format!("\\x{:02x}", ch as u32));
+                    } else {
+                        out.push(ch);
+                    }
+                }
+            }
+            Err(_) => {
+                for byte in bytes.iter() {
+                    out.push_str(&format!("\\x{:02x}", byte));
+                }
+            }
+        }
+        bytes.clear();
+    };
+    let mut chars = body.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            flush(&mut bytes, &mut out);
+            out.push(ch);
+            continue;
+        }
+        let escaped = chars.next();
+        if escaped == Some('x') {
+            let hex: String = chars.by_ref().take(2).collect();
+            if let Ok(byte) = u8::from_str_radix(&hex, 16) {
+                bytes.push(byte);
+                continue;
+            }
+            flush(&mut bytes, &mut out);
+            out.push_str("\\x");
+            out.push_str(&hex);
+            continue;
+        }
+        flush(&mut bytes, &mut out);
+        out.push(ch);
+        out.extend(escaped);
+    }
+    flush(&mut bytes, &mut out);
+    out
+}
+
+/// Translate a glob into the regex pathvein matches it with
+///
+/// Useful for embedding the same semantics in a database (SQLite REGEXP,
+/// Postgres `~`) or for debugging why a pattern does or doesn't match.
+/// Wildcards match newlines, as globset's do. globset matches ``?`` and
+/// character classes against single bytes, so for non-ASCII names those
+/// two can disagree with the translated regex, which matches characters.
+///
+/// Args:
+///     pattern: Glob pattern (e.g., "*.py")
+///     dialect: Target regex flavor: "rust" (default, exactly what globset
+///         uses), "python", "pcre"/"sqlite", or "postgres"
+///     glob_dialect: Glob semantics of `pattern`, "globset" (default) or
+///         "pathlib"
+///
+/// Returns:
+///     Regex string anchored at both ends
+///
+/// Raises:
+///     ValueError: If the pattern or either dialect is invalid
+#[pyfunction]
+#[pyo3(signature = (pattern, dialect="rust", glob_dialect="globset"))]
+pub fn glob_to_regex(pattern: &str, dialect: &str, glob_dialect: &str) -> PyResult<String> {
+    let dialect = RegexDialect::parse(dialect)?;
+    match GlobDialect::parse(glob_dialect)? {
+        GlobDialect::Globset => {
+            let glob = Glob::new(pattern).map_err(|e| {
+                pattern_syntax_error(format!("Invalid glob pattern '{}': {}", pattern, e))
+            })?;
+            Ok(translate_regex(glob.regex(), dialect))
+        }
+        GlobDialect::Pathlib => {
+            let body = pathlib_regex_body(pattern);
+            Ok(match dialect {
+                RegexDialect::Rust | RegexDialect::Pcre => format!(r"^(?s:{})\z", body),
+                RegexDialect::Python => format!(r"^(?s:{})\Z", body),
+                // Postgres has no scoped flag groups, but `.` already matches newlines
+                RegexDialect::Postgres => format!("^(?:{})$", body),
+            })
+        }
+    }
 }
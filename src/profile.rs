@@ -0,0 +1,76 @@
+use pyo3::prelude::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use crate::pattern::{MatcherOptions, PatternMatcher};
+
+/// Per-pattern timings collected by a matcher created with `profile=True`
+///
+/// Every pattern is compiled on its own and evaluated separately, so the
+/// time spent on each one can be measured. This is much slower than the
+/// combined automaton and is meant for finding the one slow glob in a
+/// large set, not for production scans.
+pub struct PatternProfile {
+    matchers: Vec<PatternMatcher>,
+    /// Cumulative evaluation time per pattern, in nanoseconds
+    nanos: Vec<AtomicU64>,
+    /// Number of candidates each pattern matched
+    hits: Vec<AtomicU64>,
+    /// Number of candidates evaluated
+    calls: AtomicU64,
+}
+
+impl PatternProfile {
+    pub fn new(patterns: &[String], options: MatcherOptions) -> PyResult<Self> {
+        let matchers = patterns
+            .iter()
+            .map(|pattern| PatternMatcher::with_options(vec![pattern.clone()], options))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(PatternProfile {
+            nanos: patterns.iter().map(|_| AtomicU64::new(0)).collect(),
+            hits: patterns.iter().map(|_| AtomicU64::new(0)).collect(),
+            calls: AtomicU64::new(0),
+            matchers,
+        })
+    }
+
+    /// Evaluate every pattern against a prepared path, timing each one
+    pub fn match_indices(&self, path: &str, is_dir: bool) -> Vec<usize> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        let mut indices = Vec::new();
+        for (idx, matcher) in self.matchers.iter().enumerate() {
+            let start = Instant::now();
+            let matched = matcher.is_prepared_match(path, is_dir);
+            let elapsed = start.elapsed().as_nanos() as u64;
+            self.nanos[idx].fetch_add(elapsed, Ordering::Relaxed);
+            if matched {
+                self.hits[idx].fetch_add(1, Ordering::Relaxed);
+                indices.push(idx);
+            }
+        }
+        indices
+    }
+
+    /// (pattern index, seconds, hits), slowest first
+    pub fn report(&self) -> Vec<(usize, f64, u64)> {
+        let mut report: Vec<(usize, f64, u64)> = (0..self.matchers.len())
+            .map(|idx| {
+                let seconds = self.nanos[idx].load(Ordering::Relaxed) as f64 / 1e9;
+                (idx, seconds, self.hits[idx].load(Ordering::Relaxed))
+            })
+            .collect();
+        report.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        report
+    }
+
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        for counter in self.nanos.iter().chain(&self.hits) {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.calls.store(0, Ordering::Relaxed);
+    }
+}
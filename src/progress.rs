@@ -0,0 +1,201 @@
+use pyo3::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Snapshot of a running scan, passed to the ``progress`` callback
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct ScanProgress {
+    /// Directories found so far, walked or still queued
+    #[pyo3(get)]
+    pub directories_discovered: u64,
+    /// Directories whose listing has been read
+    #[pyo3(get)]
+    pub directories_walked: u64,
+    /// Directories matched against the patterns
+    #[pyo3(get)]
+    pub directories_evaluated: u64,
+    /// Matches found so far
+    #[pyo3(get)]
+    pub matches: u64,
+    /// Seconds since the scan started
+    #[pyo3(get)]
+    pub elapsed: f64,
+    /// Rough seconds remaining, from the current rate and the directories
+    /// still to go; None until there is a rate to go on
+    #[pyo3(get)]
+    pub eta: Option<f64>,
+}
+
+#[pymethods]
+impl ScanProgress {
+    fn __repr__(&self) -> String {
+        format!(
+            "ScanProgress(discovered={}, walked={}, evaluated={}, matches={}, elapsed={:.2})",
+            self.directories_discovered,
+            self.directories_walked,
+            self.directories_evaluated,
+            self.matches,
+            self.elapsed
+        )
+    }
+}
+
+/// Counters behind a scan's progress callback, shared by walker threads
+///
+/// The walk only knows how many directories are queued, not how many are
+/// below them, so the walk-phase ETA grows as the tree is discovered.
+pub struct ProgressReporter {
+    callback: PyObject,
+    interval: Duration,
+    start: Instant,
+    /// Nanoseconds after `start` of the last callback
+    last_report: AtomicU64,
+    discovered: AtomicU64,
+    walked: AtomicU64,
+    evaluated: AtomicU64,
+    matches: AtomicU64,
+    /// Directories to evaluate once the walk is done
+    to_evaluate: AtomicU64,
+    /// Nanoseconds after `start` when evaluation began
+    evaluation_start: AtomicU64,
+    evaluating: AtomicBool,
+    error: Mutex<Option<PyErr>>,
+}
+
+impl ProgressReporter {
+    pub fn new(callback: PyObject, interval: f64, roots: usize) -> Self {
+        ProgressReporter {
+            callback,
+            interval: Duration::from_secs_f64(interval.max(0.0)),
+            start: Instant::now(),
+            last_report: AtomicU64::new(0),
+            // The roots are known before anything is walked
+            discovered: AtomicU64::new(roots as u64),
+            walked: AtomicU64::new(0),
+            evaluated: AtomicU64::new(0),
+            matches: AtomicU64::new(0),
+            to_evaluate: AtomicU64::new(0),
+            evaluation_start: AtomicU64::new(0),
+            evaluating: AtomicBool::new(false),
+            error: Mutex::new(None),
+        }
+    }
+
+    /// Count a walked directory and queue its subdirectories
+    ///
+    /// The walker reads the listing after visiting the directory, so the
+    /// subdirectories are counted with a listing of our own; the walker's
+    /// read right after finds it in the OS cache; subdirectories the walk
+    /// has `pruned` aren't counted. Returns false once the callback has
+    /// raised.
+    pub fn walked(
+        &self,
+        dir: &Path,
+        descends: bool,
+        follow_links: bool,
+        pruned: impl Fn(&Path) -> bool,
+    ) -> bool {
+        if descends {
+            let subdirs = fs::read_dir(dir).map_or(0, |entries| {
+                entries
+                    .flatten()
+                    .filter(|entry| match entry.file_type() {
+                        Ok(t) if t.is_symlink() && follow_links => entry.path().is_dir(),
+                        Ok(t) => t.is_dir(),
+                        Err(_) => false,
+                    })
+                    .filter(|entry| !pruned(&entry.path()))
+                    .count()
+            });
+            self.discovered.fetch_add(subdirs as u64, Ordering::Relaxed);
+        }
+        self.walked.fetch_add(1, Ordering::Relaxed);
+        self.tick()
+    }
+
+    /// Switch to the evaluation phase, with `directories` to match
+    pub fn start_evaluation(&self, directories: usize) {
+        self.to_evaluate
+            .store(directories as u64, Ordering::Relaxed);
+        self.evaluation_start
+            .store(self.now_nanos(), Ordering::Relaxed);
+        self.evaluating.store(true, Ordering::Relaxed);
+    }
+
+    /// Count an evaluated directory and its matches
+    pub fn evaluated(&self, matches: usize) -> bool {
+        self.evaluated.fetch_add(1, Ordering::Relaxed);
+        self.matches.fetch_add(matches as u64, Ordering::Relaxed);
+        self.tick()
+    }
+
+    /// Report the final counts, and any error the callback raised
+    pub fn finish(&self) -> PyResult<()> {
+        if let Some(err) = self.error.lock().unwrap().take() {
+            return Err(err);
+        }
+        let mut snapshot = self.snapshot();
+        snapshot.eta = Some(0.0);
+        Python::with_gil(|py| self.callback.call1(py, (snapshot,)).map(|_| ()))
+    }
+
+    /// Call back if the interval has passed and no other thread is
+    fn tick(&self) -> bool {
+        if self.error.lock().unwrap().is_some() {
+            return false;
+        }
+        let now = self.now_nanos();
+        let last = self.last_report.load(Ordering::Relaxed);
+        if now.saturating_sub(last) < self.interval.as_nanos() as u64
+            || self
+                .last_report
+                .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+        {
+            return true;
+        }
+        let snapshot = self.snapshot();
+        match Python::with_gil(|py| self.callback.call1(py, (snapshot,)).map(|_| ())) {
+            Ok(()) => true,
+            Err(err) => {
+                self.error.lock().unwrap().get_or_insert(err);
+                false
+            }
+        }
+    }
+
+    fn snapshot(&self) -> ScanProgress {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let discovered = self.discovered.load(Ordering::Relaxed);
+        let walked = self.walked.load(Ordering::Relaxed);
+        let evaluated = self.evaluated.load(Ordering::Relaxed);
+        let (done, remaining, phase_seconds) = if self.evaluating.load(Ordering::Relaxed) {
+            let since = self.evaluation_start.load(Ordering::Relaxed) as f64 / 1e9;
+            let to_evaluate = self.to_evaluate.load(Ordering::Relaxed);
+            (
+                evaluated,
+                to_evaluate.saturating_sub(evaluated),
+                elapsed - since,
+            )
+        } else {
+            (walked, discovered.saturating_sub(walked), elapsed)
+        };
+        let eta = (done > 0).then(|| remaining as f64 * phase_seconds / done as f64);
+        ScanProgress {
+            directories_discovered: discovered,
+            directories_walked: walked,
+            directories_evaluated: evaluated,
+            matches: self.matches.load(Ordering::Relaxed),
+            elapsed,
+            eta,
+        }
+    }
+
+    fn now_nanos(&self) -> u64 {
+        self.start.elapsed().as_nanos() as u64
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::walk::DirContents;
+
+/// Schemes of the object stores roots can name
+const STORE_SCHEMES: &[&str] = &["s3", "s3a", "gs", "az", "adl", "azure", "abfs", "abfss"];
+
+/// Whether a root names a bucket or container rather than a local
+/// directory, e.g. ``s3://bucket/prefix``
+pub(crate) fn is_store_url(root: &str) -> bool {
+    root.split_once("://")
+        .is_some_and(|(scheme, _)| STORE_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()))
+}
+
+/// Listings of every "directory" under an object store URL, keyed by the
+/// URL joined with the key prefix it stands for, with each one's depth
+/// below the URL
+///
+/// Every key under the URL's prefix is listed, and each ``/`` in a key
+/// makes a directory. `storage_options` configure the store, over
+/// ``AWS_*``, ``GOOGLE_*`` and ``AZURE_*`` environment variables.
+#[cfg(feature = "object-store")]
+pub(crate) fn list_store(
+    url: &str,
+    storage_options: &HashMap<String, String>,
+) -> Result<Vec<(PathBuf, usize, DirContents)>, String> {
+    use crate::archive::{entry_path, VirtualTree};
+    use futures::StreamExt;
+    use std::path::Path;
+
+    let parsed = url::Url::parse(url).map_err(|e| format!("Invalid URL {}: {}", url, e))?;
+    let mut options: HashMap<String, String> = std::env::vars()
+        .filter(|(key, _)| {
+            ["AWS_", "GOOGLE_", "AZURE_"]
+                .iter()
+                .any(|p| key.starts_with(p))
+        })
+        .map(|(key, value)| (key.to_ascii_lowercase(), value))
+        .collect();
+    options.extend(storage_options.clone());
+    let (store, prefix) = object_store::parse_url_opts(&parsed, options)
+        .map_err(|e| format!("Cannot open {}: {}", url, e))?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| format!("Cannot list {}: {}", url, e))?;
+    let mut tree = VirtualTree::new();
+    runtime.block_on(async {
+        let prefix = (!prefix.as_ref().is_empty()).then_some(&prefix);
+        let mut objects = store.list(prefix);
+        while let Some(object) = objects.next().await {
+            let object = object.map_err(|e| format!("Cannot list {}: {}", url, e))?;
+            let key = object.location.as_ref();
+            let relative = match prefix {
+                Some(prefix) => key
+                    .strip_prefix(prefix.as_ref())
+                    .map(|rest| rest.trim_start_matches('/'))
+                    .unwrap_or(key),
+                None => key,
+            };
+            let Some(path) = entry_path(Path::new(relative)) else {
+                continue;
+            };
+            tree.add_file(&path);
+        }
+        Ok::<_, String>(())
+    })?;
+    Ok(tree.listings(Path::new(url.trim_end_matches('/'))))
+}
+
+#[cfg(not(feature = "object-store"))]
+pub(crate) fn list_store(
+    url: &str,
+    _storage_options: &HashMap<String, String>,
+) -> Result<Vec<(PathBuf, usize, DirContents)>, String> {
+    Err(format!(
+        "Cannot list {}: pathvein was built without object store support (the 'object-store' feature)",
+        url
+    ))
+}
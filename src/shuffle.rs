@@ -0,0 +1,1693 @@
+use filetime::FileTime;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::checksum::{Checksum, Hasher};
+use crate::template::DestinationTemplate;
+use crate::trash::Removal;
+use crate::walk::ScanResult;
+
+/// Bytes read and written at a time when copying a file
+const COPY_BUFFER: usize = 1 << 20;
+
+/// Fewest bytes read at a time when throttled
+const THROTTLE_CHUNK_MIN: u64 = 4096;
+
+/// Matched directories to shuffle: scan results, or plain paths
+#[derive(FromPyObject)]
+pub enum ShuffleSource {
+    Results(Vec<ScanResult>),
+    Paths(Vec<PathBuf>),
+}
+
+impl ShuffleSource {
+    /// The directories, with paths relative to their scan root resolved,
+    /// and the results they come from
+    fn directories(self) -> Vec<(PathBuf, Option<ScanResult>)> {
+        match self {
+            ShuffleSource::Paths(paths) => paths.into_iter().map(|path| (path, None)).collect(),
+            ShuffleSource::Results(results) => results
+                .into_iter()
+                .map(|result| (Path::new(&result.root).join(&result.path), Some(result)))
+                .collect(),
+        }
+    }
+}
+
+/// What `shuffle` does with each matched directory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ShuffleMode {
+    Copy,
+    Move,
+    /// Always copy, verifying every file, and delete the source only once
+    /// its copy is in place
+    SafeMove,
+    /// Recreate the tree with hard links to the source's files
+    Hardlink,
+}
+
+impl ShuffleMode {
+    fn parse(mode: &str) -> PyResult<Self> {
+        match mode {
+            "copy" => Ok(ShuffleMode::Copy),
+            "move" => Ok(ShuffleMode::Move),
+            "safe_move" => Ok(ShuffleMode::SafeMove),
+            "hardlink" => Ok(ShuffleMode::Hardlink),
+            other => Err(PyValueError::new_err(format!(
+                "Unknown shuffle mode '{}': expected 'copy', 'move', 'safe_move' or 'hardlink'",
+                other
+            ))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ShuffleMode::Copy => "copy",
+            ShuffleMode::Move => "move",
+            ShuffleMode::SafeMove => "safe_move",
+            ShuffleMode::Hardlink => "hardlink",
+        }
+    }
+
+    /// Whether the source is deleted once shuffled
+    fn removes_source(self) -> bool {
+        matches!(self, ShuffleMode::Move | ShuffleMode::SafeMove)
+    }
+}
+
+/// What `shuffle` does with a matched directory whose destination exists
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OnConflict {
+    /// Fail the directory
+    Error,
+    /// Leave the directory where it is
+    Skip,
+    /// Replace the destination with the directory
+    Overwrite,
+    /// Shuffle to the first free ``<name>-<n>`` beside the destination
+    Rename,
+    /// Add the directory's contents to the destination, replacing files
+    /// of the same name
+    Merge,
+}
+
+impl OnConflict {
+    fn parse(on_conflict: &str) -> PyResult<Self> {
+        match on_conflict {
+            "error" => Ok(OnConflict::Error),
+            "skip" => Ok(OnConflict::Skip),
+            "overwrite" => Ok(OnConflict::Overwrite),
+            "rename" => Ok(OnConflict::Rename),
+            "merge" => Ok(OnConflict::Merge),
+            other => Err(PyValueError::new_err(format!(
+                "Unknown on_conflict '{}': expected 'error', 'skip', 'overwrite', 'rename' or 'merge'",
+                other
+            ))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            OnConflict::Error => "error",
+            OnConflict::Skip => "skip",
+            OnConflict::Overwrite => "overwrite",
+            OnConflict::Rename => "rename",
+            OnConflict::Merge => "merge",
+        }
+    }
+}
+
+/// What happened to one matched directory in a shuffle
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct ShuffledDirectory {
+    #[pyo3(get)]
+    pub source: String,
+    #[pyo3(get)]
+    pub destination: String,
+    /// ``"copied"``, ``"moved"``, ``"linked"``, ``"skipped"`` or
+    /// ``"failed"``
+    #[pyo3(get)]
+    pub status: &'static str,
+    /// The ``on_conflict`` policy applied, when the destination already
+    /// existed
+    #[pyo3(get)]
+    pub conflict: Option<&'static str>,
+    /// Files written to, or moved with, the destination
+    #[pyo3(get)]
+    pub files: u64,
+    /// Bytes in those files
+    #[pyo3(get)]
+    pub bytes: u64,
+    /// Why the directory failed, for ``"failed"``
+    #[pyo3(get)]
+    pub error: Option<String>,
+    /// Checksums of each file copied, when shuffled with ``verify``;
+    /// empty for a directory moved by renaming it
+    #[pyo3(get)]
+    pub verified: Vec<VerifiedFile>,
+    /// Where the source went, when moved with ``delete="trash"``
+    #[pyo3(get)]
+    pub trashed: Option<String>,
+}
+
+#[pymethods]
+impl ShuffledDirectory {
+    fn __repr__(&self) -> String {
+        format!(
+            "ShuffledDirectory(source='{}', destination='{}', status='{}')",
+            self.source, self.destination, self.status
+        )
+    }
+}
+
+/// One line of a transfer manifest: a file that reached its destination
+#[derive(Serialize)]
+struct ManifestEntry {
+    source: String,
+    destination: String,
+    size: u64,
+    /// Digest of the data read from the source, when verified
+    hash: Option<String>,
+    /// Checksum `hash` was computed with
+    algorithm: Option<&'static str>,
+    /// Source's modification time, in seconds since the epoch
+    modified: Option<f64>,
+    /// When the file was shuffled, in seconds since the epoch
+    shuffled: f64,
+    /// Its directory's status
+    status: &'static str,
+}
+
+fn epoch_seconds(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0.0, |since| since.as_secs_f64())
+}
+
+/// Checksums of one copied file, read back from its destination
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct VerifiedFile {
+    #[pyo3(get)]
+    pub source: String,
+    #[pyo3(get)]
+    pub destination: String,
+    #[pyo3(get)]
+    pub size: u64,
+    /// Hex digest of the data read from the source as it was copied
+    #[pyo3(get)]
+    pub source_hash: String,
+    /// Hex digest of the destination, read back after the copy
+    #[pyo3(get)]
+    pub destination_hash: String,
+}
+
+#[pymethods]
+impl VerifiedFile {
+    /// Whether the destination holds what was read from the source
+    #[getter]
+    fn ok(&self) -> bool {
+        self.source_hash == self.destination_hash
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "VerifiedFile(source='{}', destination='{}', ok={})",
+            self.source,
+            self.destination,
+            if self.ok() { "True" } else { "False" }
+        )
+    }
+}
+
+/// Outcome of a `shuffle`
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct ShuffleReport {
+    /// One ShuffledDirectory per matched directory, in input order
+    #[pyo3(get)]
+    pub directories: Vec<ShuffledDirectory>,
+    /// Seconds the shuffle took
+    #[pyo3(get)]
+    pub elapsed: f64,
+    /// Checksum files were verified with, if any
+    #[pyo3(get)]
+    pub verify: Option<&'static str>,
+    /// Path the transfer manifest was written to, if one was asked for
+    #[pyo3(get)]
+    pub manifest: Option<String>,
+}
+
+#[pymethods]
+impl ShuffleReport {
+    /// Files written or moved, over all directories
+    #[getter]
+    fn files(&self) -> u64 {
+        self.directories.iter().map(|dir| dir.files).sum()
+    }
+
+    /// Bytes written or moved, over all directories
+    #[getter]
+    fn bytes(&self) -> u64 {
+        self.directories.iter().map(|dir| dir.bytes).sum()
+    }
+
+    /// Files whose destination checksum differs from the source's
+    #[getter]
+    fn mismatches(&self) -> Vec<VerifiedFile> {
+        self.directories
+            .iter()
+            .flat_map(|dir| &dir.verified)
+            .filter(|file| !file.ok())
+            .cloned()
+            .collect()
+    }
+
+    /// The directories that failed
+    #[getter]
+    fn failed(&self) -> Vec<ShuffledDirectory> {
+        self.directories
+            .iter()
+            .filter(|dir| dir.status == "failed")
+            .cloned()
+            .collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.directories.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ShuffleReport(directories={}, files={}, bytes={}, failed={})",
+            self.directories.len(),
+            self.files(),
+            self.bytes(),
+            self.failed().len()
+        )
+    }
+}
+
+/// One file a shuffle would copy or move
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct PlannedFile {
+    #[pyo3(get)]
+    pub source: String,
+    #[pyo3(get)]
+    pub destination: String,
+    /// Size in bytes
+    #[pyo3(get)]
+    pub size: u64,
+}
+
+#[pymethods]
+impl PlannedFile {
+    fn __repr__(&self) -> String {
+        format!(
+            "PlannedFile(source='{}', destination='{}', size={})",
+            self.source, self.destination, self.size
+        )
+    }
+}
+
+/// What a `shuffle` would do, from ``plan_shuffle``
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct ShufflePlan {
+    /// ``"copy"``, ``"move"`` or ``"hardlink"``
+    #[pyo3(get)]
+    pub mode: &'static str,
+    /// ``(source, destination)`` of each matched directory with a
+    /// destination, in input order
+    #[pyo3(get)]
+    pub directories: Vec<(String, String)>,
+    /// Every file that would be copied or moved
+    #[pyo3(get)]
+    pub files: Vec<PlannedFile>,
+    /// Bytes in those files
+    #[pyo3(get)]
+    pub bytes: u64,
+    /// Destinations that already exist, or that two matches would share,
+    /// which ``on_conflict`` doesn't resolve
+    #[pyo3(get)]
+    pub collisions: Vec<String>,
+    /// ``(destination, on_conflict)`` for each destination that already
+    /// exists and what ``on_conflict`` does about it
+    #[pyo3(get)]
+    pub conflicts: Vec<(String, &'static str)>,
+    /// Sources that can't be read or listed, destinations that can't be
+    /// written, and, for ``"move"``, sources that can't be removed
+    #[pyo3(get)]
+    pub permission_problems: Vec<String>,
+    /// Directories the destination template has no path for
+    #[pyo3(get)]
+    pub template_errors: Vec<String>,
+}
+
+#[pymethods]
+impl ShufflePlan {
+    /// Whether the shuffle would run without collisions, permission
+    /// problems or template errors
+    #[getter]
+    fn ok(&self) -> bool {
+        self.collisions.is_empty()
+            && self.permission_problems.is_empty()
+            && self.template_errors.is_empty()
+    }
+
+    fn __len__(&self) -> usize {
+        self.files.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ShufflePlan(mode='{}', directories={}, files={}, bytes={}, collisions={}, conflicts={}, permission_problems={}, template_errors={})",
+            self.mode,
+            self.directories.len(),
+            self.files.len(),
+            self.bytes,
+            self.collisions.len(),
+            self.conflicts.len(),
+            self.permission_problems.len(),
+            self.template_errors.len()
+        )
+    }
+}
+
+/// Copy or move matched directories into a destination directory
+///
+/// Each directory is copied to ``dest / <its name>``, or where
+/// ``destination_template`` lays it out, with its whole tree:
+/// subdirectories are created first, then the files of every directory
+/// are copied in parallel with large buffered reads and writes. Symbolic
+/// links are recreated as links. Files and directories keep their
+/// permissions, and with the other ``preserve_*`` flags their times,
+/// owner and extended attributes, as ``cp -a`` keeps them.
+///
+/// Where the filesystem supports it (Btrfs, XFS, APFS), files are cloned
+/// rather than copied, so a copy on the same filesystem takes no time or
+/// space whatever its size.
+///
+/// Each copy is made in a hidden sibling of its destination and renamed
+/// into place once complete, so the destination never holds a partial
+/// copy: a directory that fails part way has its copy removed.
+///
+/// ``"move"`` renames a directory when it is on the destination's
+/// filesystem, and otherwise copies it and deletes the source once the
+/// copy is in place. A directory whose destination already exists, or
+/// that fails, is reported as failed without stopping the others, unless
+/// ``on_conflict`` says otherwise: ``"skip"`` leaves it where it is,
+/// ``"overwrite"`` swaps the old destination for the new copy once it's
+/// complete, ``"rename"`` shuffles it to ``<name>-1``, ``<name>-2`` and
+/// so on, whichever is free, and ``"merge"`` moves the complete copy's
+/// files into the existing destination, replacing those of the same name.
+///
+/// ``"safe_move"`` is for when losing the source isn't an option: it never
+/// renames, always verifies (with BLAKE3 unless ``verify`` names another
+/// checksum), and deletes the source only after every file of its copy
+/// matched and the copy is in place.
+///
+/// A source that's copied and then removed is deleted outright, unless
+/// ``delete="trash"``: then it goes to the platform's trash (the
+/// freedesktop.org trash on Linux and BSD, ``~/.Trash`` on macOS), or with
+/// ``trash_dir`` into that directory beside a ``<name>.quarantine.json``
+/// recording where it came from and, with ``trash_days``, when it may be
+/// deleted. A source that can't be trashed, say because the trash is on
+/// another filesystem, stays where it is.
+///
+/// ``"hardlink"`` creates the directories and hard links each file to its
+/// source instead of copying it, which takes no time or space but only
+/// works within one filesystem; a directory on another fails. The links
+/// share their files' metadata, so ``preserve_*`` applies only to
+/// directories.
+///
+/// With ``verify``, files are hashed as they're read from the source and
+/// again from the destination once written; a directory with any file
+/// that differs fails, and so is rolled back. Verified files are always
+/// copied through the buffer rather than cloned.
+///
+/// With ``max_bytes_per_second``, copies are paced so the whole shuffle
+/// writes no faster than that, and go through the buffer rather than
+/// being cloned, since a clone can't be paced.
+///
+/// Args:
+///     matches: ScanResults or list of ScanResult from a scan, or a list
+///         of directory paths
+///     dest: Directory to shuffle into; created if missing
+///     mode: ``"copy"``, ``"move"``, ``"safe_move"`` or ``"hardlink"``
+///         (default: "copy")
+///     threads: Files copied at once (default: one per CPU)
+///     verify: ``"blake3"`` or ``"sha256"`` to checksum every copied file
+///         (default: None)
+///     preserve_times: Copy access and modification times (default: False)
+///     preserve_permissions: Copy permission bits (default: True)
+///     preserve_ownership: Copy owner and group; without the privilege to
+///         give files away, only the group, where this process is in it
+///         (default: False)
+///     preserve_xattrs: Copy extended attributes, skipping those this
+///         process or the destination filesystem can't set (default: False)
+///     max_bytes_per_second: Limit on the bytes written per second, over
+///         all threads (default: None, for no limit)
+///     on_conflict: ``"error"``, ``"skip"``, ``"overwrite"``, ``"rename"``
+///         or ``"merge"``, for directories whose destination exists
+///         (default: "error")
+///     destination_template: Where each directory goes under ``dest``, as
+///         a path of text and fields: ``{source_name}``,
+///         ``{pattern_name}``, ``{pattern_index}``, ``{branch_name}``,
+///         ``{capture[N]}`` for the Nth ``directory_name`` wildcard, and
+///         ``{binding[role]}`` for the name of the path bound to a role
+///         (default: None, for ``{source_name}``)
+///     manifest: NDJSON file to record every file shuffled in, one object
+///         per line with its ``source``, ``destination``, ``size``,
+///         ``hash`` and ``algorithm`` (with ``verify``, else null),
+///         ``modified`` and ``shuffled`` times in seconds since the epoch,
+///         and its directory's ``status``; failed directories are left
+///         out. Replaced if it exists (default: None)
+///     retries: Times to copy a file again when its copy fails or doesn't
+///         match before failing its directory (default: 0)
+///     delete: ``"unlink"`` or ``"trash"``, for how ``"move"`` and
+///         ``"safe_move"`` remove sources they copied (default: "unlink")
+///     trash_dir: Quarantine directory for ``delete="trash"`` to use
+///         instead of the platform's trash (default: None)
+///     trash_days: Days a quarantined source is kept, recorded as its
+///         expiry; needs ``trash_dir`` (default: None, for no expiry)
+///
+/// Returns:
+///     ShuffleReport with what happened to each directory
+///
+/// Raises:
+///     ValueError: If mode, verify, on_conflict or delete is unknown, the
+///         destination template is invalid, verify is given with
+///         ``"hardlink"``, ``delete="trash"`` with a mode that keeps its
+///         sources, threads or max_bytes_per_second is 0 or ``dest``
+///         can't be created
+#[pyfunction]
+#[pyo3(signature = (
+    matches,
+    dest,
+    mode="copy",
+    threads=None,
+    verify=None,
+    preserve_times=false,
+    preserve_permissions=true,
+    preserve_ownership=false,
+    preserve_xattrs=false,
+    max_bytes_per_second=None,
+    on_conflict="error",
+    destination_template=None,
+    manifest=None,
+    retries=0,
+    delete="unlink",
+    trash_dir=None,
+    trash_days=None,
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn shuffle(
+    py: Python<'_>,
+    matches: ShuffleSource,
+    dest: PathBuf,
+    mode: &str,
+    threads: Option<usize>,
+    verify: Option<&str>,
+    preserve_times: bool,
+    preserve_permissions: bool,
+    preserve_ownership: bool,
+    preserve_xattrs: bool,
+    max_bytes_per_second: Option<u64>,
+    on_conflict: &str,
+    destination_template: Option<&str>,
+    manifest: Option<PathBuf>,
+    retries: u32,
+    delete: &str,
+    trash_dir: Option<PathBuf>,
+    trash_days: Option<f64>,
+) -> PyResult<ShuffleReport> {
+    if threads == Some(0) {
+        return Err(PyValueError::new_err("threads must be at least 1"));
+    }
+    if max_bytes_per_second == Some(0) {
+        return Err(PyValueError::new_err(
+            "max_bytes_per_second must be at least 1",
+        ));
+    }
+    let mode = ShuffleMode::parse(mode)?;
+    if mode == ShuffleMode::Hardlink && verify.is_some() {
+        return Err(PyValueError::new_err(
+            "verify can't be used with mode='hardlink': links share their source's data",
+        ));
+    }
+    let removal = Removal::parse(delete, trash_dir, trash_days)?;
+    if !matches!(removal, Removal::Unlink) && !mode.removes_source() {
+        return Err(PyValueError::new_err(format!(
+            "delete='trash' can't be used with mode='{}': its sources are kept",
+            mode.name()
+        )));
+    }
+    let options = ShuffleOptions {
+        mode,
+        threads: threads.unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get())),
+        verify: match (mode, verify.map(Checksum::parse).transpose()?) {
+            (ShuffleMode::SafeMove, None) => Some(Checksum::Blake3),
+            (_, verify) => verify,
+        },
+        preserve: Preserve {
+            times: preserve_times,
+            permissions: preserve_permissions,
+            ownership: preserve_ownership,
+            xattrs: preserve_xattrs,
+        },
+        throttle: max_bytes_per_second.map(Throttle::new),
+        on_conflict: OnConflict::parse(on_conflict)?,
+        manifest: manifest.is_some(),
+        retries,
+        removal,
+    };
+    let template = parse_template(destination_template)?;
+    let manifest_error = |path: &Path, e: io::Error| {
+        PyValueError::new_err(format!("Cannot write {}: {}", path.display(), e))
+    };
+    // Created first, so a manifest that can't be written stops the
+    // shuffle before it starts
+    let manifest_file = manifest
+        .as_deref()
+        .map(|path| File::create(path).map_err(|e| manifest_error(path, e)))
+        .transpose()?;
+    fs::create_dir_all(&dest)
+        .map_err(|e| PyValueError::new_err(format!("Cannot create {}: {}", dest.display(), e)))?;
+    let sources = matches.directories();
+    py.allow_threads(|| {
+        let started = Instant::now();
+        let (directories, entries) = run(jobs(sources, &dest, template.as_ref()), &options);
+        if let (Some(path), Some(file)) = (&manifest, manifest_file) {
+            write_manifest(file, &entries).map_err(|e| manifest_error(path, e))?;
+        }
+        Ok(ShuffleReport {
+            directories,
+            elapsed: started.elapsed().as_secs_f64(),
+            verify: options.verify.map(Checksum::name),
+            manifest: manifest.map(|path| path.to_string_lossy().into_owned()),
+        })
+    })
+}
+
+/// Work out what ``shuffle`` would do, without changing anything
+///
+/// Lists every file with the path ``shuffle`` would give it, and checks
+/// for what would make directories fail: destinations that already exist
+/// or that two matches share, and files or directories this process
+/// can't read or write. Review the plan, then pass the same arguments to
+/// ``shuffle``.
+///
+/// Args:
+///     matches: ScanResults or list of ScanResult from a scan, or a list
+///         of directory paths
+///     dest: Directory to shuffle into
+///     mode: ``"copy"``, ``"move"``, ``"safe_move"`` or ``"hardlink"``
+///         (default: "copy")
+///     on_conflict: ``"error"``, ``"skip"``, ``"overwrite"``, ``"rename"``
+///         or ``"merge"``, as for ``shuffle`` (default: "error")
+///     destination_template: Layout under ``dest``, as for ``shuffle``
+///         (default: None)
+///
+/// Returns:
+///     ShufflePlan with the files, total bytes, collisions, conflicts,
+///     permission problems and template errors
+///
+/// Raises:
+///     ValueError: If mode or on_conflict is unknown, or the template
+///         is invalid
+#[pyfunction]
+#[pyo3(signature = (matches, dest, mode="copy", on_conflict="error", destination_template=None))]
+pub fn plan_shuffle(
+    py: Python<'_>,
+    matches: ShuffleSource,
+    dest: PathBuf,
+    mode: &str,
+    on_conflict: &str,
+    destination_template: Option<&str>,
+) -> PyResult<ShufflePlan> {
+    let mode = ShuffleMode::parse(mode)?;
+    let on_conflict = OnConflict::parse(on_conflict)?;
+    let template = parse_template(destination_template)?;
+    let sources = matches.directories();
+    Ok(py.allow_threads(|| plan(jobs(sources, &dest, template.as_ref()), mode, on_conflict)))
+}
+
+/// Parse a ``destination_template`` argument
+fn parse_template(template: Option<&str>) -> PyResult<Option<DestinationTemplate>> {
+    template
+        .map(|template| {
+            DestinationTemplate::parse(template).map_err(|e| {
+                PyValueError::new_err(format!(
+                    "Invalid destination template '{}': {}",
+                    template, e
+                ))
+            })
+        })
+        .transpose()
+}
+
+/// A job per source, each going to ``dest / <its name>``, or where
+/// `template` lays it out
+fn jobs(
+    sources: Vec<(PathBuf, Option<ScanResult>)>,
+    dest: &Path,
+    template: Option<&DestinationTemplate>,
+) -> Vec<Job> {
+    sources
+        .into_iter()
+        .map(|(source, result)| {
+            let name = PathBuf::from(source.file_name().unwrap_or(source.as_os_str()));
+            match template.map(|template| template.render(&source, result.as_ref())) {
+                None => Job::new(source, dest.join(name)),
+                Some(Ok(relative)) => Job::new(source, dest.join(relative)),
+                Some(Err(e)) => Job::unplaced(source, dest.join(name), e),
+            }
+        })
+        .collect()
+}
+
+/// How `shuffle` copies
+struct ShuffleOptions {
+    mode: ShuffleMode,
+    threads: usize,
+    /// Checksum to verify each copied file with
+    verify: Option<Checksum>,
+    preserve: Preserve,
+    throttle: Option<Throttle>,
+    on_conflict: OnConflict,
+    /// Whether to collect manifest entries
+    manifest: bool,
+    /// Extra attempts at each file whose copy fails or doesn't match
+    retries: u32,
+    /// How sources are removed once copied
+    removal: Removal,
+}
+
+/// Limit on the bytes all copying threads together write per second
+struct Throttle {
+    bytes_per_second: u64,
+    started: Instant,
+    /// Bytes let through so far
+    sent: AtomicU64,
+}
+
+impl Throttle {
+    fn new(bytes_per_second: u64) -> Self {
+        Throttle {
+            bytes_per_second,
+            started: Instant::now(),
+            sent: AtomicU64::new(0),
+        }
+    }
+
+    /// Wait until `bytes` more can be written without going over the rate
+    fn take(&self, bytes: u64) {
+        let sent = self.sent.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        let due = Duration::from_secs_f64(sent as f64 / self.bytes_per_second as f64);
+        if let Some(wait) = due.checked_sub(self.started.elapsed()) {
+            thread::sleep(wait);
+        }
+    }
+
+    /// Bytes to read at a time, so that a slow rate is spread over about
+    /// ten writes a second rather than a few large bursts
+    fn chunk(&self, buffer: usize) -> usize {
+        (self.bytes_per_second / 10).clamp(THROTTLE_CHUNK_MIN, buffer as u64) as usize
+    }
+}
+
+/// Metadata copied along with contents
+#[derive(Clone, Copy, Debug)]
+struct Preserve {
+    times: bool,
+    permissions: bool,
+    ownership: bool,
+    xattrs: bool,
+}
+
+/// One matched directory and where it goes
+struct Job {
+    source: PathBuf,
+    destination: PathBuf,
+    /// Hidden sibling of the destination that a copy is made in, then
+    /// renamed from once it's complete
+    staging: PathBuf,
+    /// How the destination is published over one that exists
+    conflict: Option<OnConflict>,
+    /// Why the destination template has no path for it; its listing is
+    /// this error too, so it's never shuffled
+    unplaced: Option<String>,
+    /// Its tree, or why it can't be listed
+    listing: Result<TreeListing, String>,
+}
+
+impl Job {
+    fn new(source: PathBuf, destination: PathBuf) -> Self {
+        let listing = TreeListing::read(&source)
+            .map_err(|e| format!("cannot list {}: {}", source.display(), e));
+        Job {
+            source,
+            staging: hidden_sibling(&destination, "partial"),
+            destination,
+            conflict: None,
+            unplaced: None,
+            listing,
+        }
+    }
+
+    fn unplaced(source: PathBuf, destination: PathBuf, error: String) -> Self {
+        Job {
+            source,
+            staging: hidden_sibling(&destination, "partial"),
+            destination,
+            conflict: None,
+            unplaced: Some(error.clone()),
+            listing: Err(error),
+        }
+    }
+
+    fn retarget(&mut self, destination: PathBuf) {
+        self.staging = hidden_sibling(&destination, "partial");
+        self.destination = destination;
+    }
+}
+
+/// ``.<name>.<pid>.<purpose>`` beside `path`, for files this process
+/// works on before they take or leave its place
+fn hidden_sibling(path: &Path, purpose: &str) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}.{}", std::process::id(), purpose));
+    path.with_file_name(name)
+}
+
+/// Where a job goes, given what's at its destination
+enum Resolved {
+    Go(PathBuf, Option<OnConflict>),
+    Skip,
+    Fail(String),
+}
+
+/// Resolve a job's destination against what's on disk and the
+/// destinations `claimed` by earlier jobs, mapped to their sources
+fn resolve(job: &Job, on_conflict: OnConflict, claimed: &HashMap<PathBuf, PathBuf>) -> Resolved {
+    let destination = &job.destination;
+    let taken = |path: &Path| claimed.contains_key(path) || fs::symlink_metadata(path).is_ok();
+    if !taken(destination) {
+        return Resolved::Go(destination.clone(), None);
+    }
+    match on_conflict {
+        OnConflict::Skip => Resolved::Skip,
+        OnConflict::Rename => {
+            let name = destination.file_name().unwrap_or_default();
+            let renamed = (1u64..)
+                .map(|n| {
+                    let mut renamed = name.to_owned();
+                    renamed.push(format!("-{}", n));
+                    destination.with_file_name(renamed)
+                })
+                .find(|path| !taken(path))
+                .expect("some name is free");
+            Resolved::Go(renamed, Some(OnConflict::Rename))
+        }
+        // Two jobs can't share a staging directory or replace each other
+        _ if claimed.contains_key(destination) => Resolved::Fail(format!(
+            "{} is the destination of both {} and {}",
+            destination.display(),
+            claimed[destination].display(),
+            job.source.display()
+        )),
+        OnConflict::Error => Resolved::Fail(format!("{} already exists", destination.display())),
+        OnConflict::Overwrite | OnConflict::Merge => {
+            Resolved::Go(destination.clone(), Some(on_conflict))
+        }
+    }
+}
+
+/// Everything beneath a directory, relative to it
+#[derive(Default)]
+struct TreeListing {
+    /// Subdirectories, parents before children
+    dirs: Vec<PathBuf>,
+    /// Regular files with their sizes
+    files: Vec<(PathBuf, u64)>,
+    /// Symbolic links, which are recreated rather than followed
+    links: Vec<PathBuf>,
+}
+
+impl TreeListing {
+    fn read(root: &Path) -> io::Result<Self> {
+        let mut listing = TreeListing::default();
+        let mut pending = vec![PathBuf::new()];
+        while let Some(relative) = pending.pop() {
+            for entry in fs::read_dir(root.join(&relative))? {
+                let entry = entry?;
+                let path = relative.join(entry.file_name());
+                let file_type = entry.file_type()?;
+                if file_type.is_symlink() {
+                    listing.links.push(path);
+                } else if file_type.is_dir() {
+                    listing.dirs.push(path.clone());
+                    pending.push(path);
+                } else if file_type.is_file() {
+                    listing.files.push((path, entry.metadata()?.len()));
+                }
+            }
+        }
+        Ok(listing)
+    }
+
+    fn bytes(&self) -> u64 {
+        self.files.iter().map(|(_, size)| size).sum()
+    }
+}
+
+fn plan(jobs: Vec<Job>, mode: ShuffleMode, on_conflict: OnConflict) -> ShufflePlan {
+    let mut plan = ShufflePlan {
+        mode: mode.name(),
+        directories: Vec::new(),
+        files: Vec::new(),
+        bytes: 0,
+        collisions: Vec::new(),
+        conflicts: Vec::new(),
+        permission_problems: Vec::new(),
+        template_errors: Vec::new(),
+    };
+    let mut claimed: HashMap<PathBuf, PathBuf> = HashMap::new();
+    let mut checked: HashSet<PathBuf> = HashSet::new();
+    for mut job in jobs {
+        if let Some(e) = &job.unplaced {
+            plan.template_errors.push(e.clone());
+            continue;
+        }
+        let mut skipped = false;
+        if job.listing.is_ok() {
+            let existing = job.destination.to_string_lossy().into_owned();
+            match resolve(&job, on_conflict, &claimed) {
+                Resolved::Fail(e) => plan.collisions.push(e),
+                Resolved::Skip => {
+                    plan.conflicts.push((existing, OnConflict::Skip.name()));
+                    skipped = true;
+                }
+                Resolved::Go(destination, conflict) => {
+                    if let Some(conflict) = conflict {
+                        plan.conflicts.push((existing, conflict.name()));
+                    }
+                    job.retarget(destination);
+                    claimed.insert(job.destination.clone(), job.source.clone());
+                }
+            }
+        }
+        plan.directories.push((
+            job.source.to_string_lossy().into_owned(),
+            job.destination.to_string_lossy().into_owned(),
+        ));
+        if skipped {
+            continue;
+        }
+        let listing = match &job.listing {
+            Ok(listing) => listing,
+            Err(e) => {
+                plan.permission_problems.push(e.clone());
+                continue;
+            }
+        };
+
+        // The destination is created under its nearest existing ancestor
+        let parent = job.destination.parent().unwrap_or(Path::new("."));
+        if let Some(existing) = parent.ancestors().find(|dir| dir.exists()) {
+            if checked.insert(existing.to_path_buf()) && !permitted(existing, Access::Write) {
+                plan.permission_problems
+                    .push(format!("cannot write to {}", existing.display()));
+            }
+        }
+        if mode.removes_source() {
+            let dirs = job
+                .source
+                .parent()
+                .map(Path::to_path_buf)
+                .into_iter()
+                .chain(Some(job.source.clone()))
+                .chain(listing.dirs.iter().map(|dir| job.source.join(dir)));
+            for dir in dirs {
+                if !permitted(&dir, Access::Write) {
+                    plan.permission_problems
+                        .push(format!("cannot remove files from {}", dir.display()));
+                }
+            }
+        }
+
+        for (relative, size) in &listing.files {
+            let source = job.source.join(relative);
+            if !permitted(&source, Access::Read) {
+                plan.permission_problems
+                    .push(format!("cannot read {}", source.display()));
+            }
+            plan.files.push(PlannedFile {
+                source: source.to_string_lossy().into_owned(),
+                destination: job
+                    .destination
+                    .join(relative)
+                    .to_string_lossy()
+                    .into_owned(),
+                size: *size,
+            });
+            plan.bytes += size;
+        }
+    }
+    plan
+}
+
+#[derive(Clone, Copy)]
+enum Access {
+    Read,
+    /// Creating and removing entries, for directories
+    Write,
+}
+
+/// Whether this process has `access` to `path`
+#[cfg(unix)]
+fn permitted(path: &Path, access: Access) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mode = match access {
+        Access::Read => libc::R_OK,
+        Access::Write => libc::W_OK | libc::X_OK,
+    };
+    // SAFETY: `path` is a NUL-terminated string that outlives the call
+    unsafe { libc::access(path.as_ptr(), mode) == 0 }
+}
+
+/// Whether this process has `access` to `path`, going by its read-only
+/// attribute
+#[cfg(not(unix))]
+fn permitted(path: &Path, access: Access) -> bool {
+    match fs::metadata(path) {
+        Ok(metadata) => matches!(access, Access::Read) || !metadata.permissions().readonly(),
+        Err(_) => false,
+    }
+}
+
+fn write_manifest(file: File, entries: &[ManifestEntry]) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(file);
+    for entry in entries {
+        serde_json::to_writer(&mut writer, entry)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
+
+/// Shuffle every job, copying the files of all of them at once; returns
+/// a report on each, and manifest entries for the files of those that
+/// succeeded if the options ask for them
+fn run(
+    mut jobs: Vec<Job>,
+    options: &ShuffleOptions,
+) -> (Vec<ShuffledDirectory>, Vec<ManifestEntry>) {
+    let mode = options.mode;
+    let mut outcomes: Vec<ShuffledDirectory> = jobs
+        .iter()
+        .map(|job| ShuffledDirectory {
+            source: job.source.to_string_lossy().into_owned(),
+            destination: job.destination.to_string_lossy().into_owned(),
+            status: "failed",
+            conflict: None,
+            files: 0,
+            bytes: 0,
+            error: job.listing.as_ref().err().cloned(),
+            verified: Vec::new(),
+            trashed: None,
+        })
+        .collect();
+
+    // Destinations are claimed one job at a time, so two matches with
+    // the same name can't both get one
+    let mut claimed = HashMap::new();
+    let mut to_copy = Vec::new();
+    let mut manifest: Vec<Vec<ManifestEntry>> = jobs.iter().map(|_| Vec::new()).collect();
+    for (idx, job) in jobs.iter_mut().enumerate() {
+        let outcome = &mut outcomes[idx];
+        if job.listing.is_err() {
+            continue;
+        }
+        match resolve(job, options.on_conflict, &claimed) {
+            Resolved::Fail(e) => {
+                outcome.error = Some(e);
+                continue;
+            }
+            Resolved::Skip => {
+                outcome.status = "skipped";
+                outcome.conflict = Some(OnConflict::Skip.name());
+                continue;
+            }
+            Resolved::Go(destination, conflict) => {
+                outcome.destination = destination.to_string_lossy().into_owned();
+                outcome.conflict = conflict.map(OnConflict::name);
+                job.retarget(destination);
+                job.conflict = conflict;
+            }
+        }
+        claimed.insert(job.destination.clone(), job.source.clone());
+        let Ok(listing) = &job.listing else {
+            continue;
+        };
+        // Only a free destination can be renamed to
+        let free = matches!(job.conflict, None | Some(OnConflict::Rename));
+        if mode == ShuffleMode::Move && free && fs::rename(&job.source, &job.destination).is_ok() {
+            outcome.status = "moved";
+            outcome.files = listing.files.len() as u64;
+            outcome.bytes = listing.bytes();
+            if options.manifest {
+                let shuffled = epoch_seconds(SystemTime::now());
+                manifest[idx] = listing
+                    .files
+                    .iter()
+                    .map(|(relative, size)| {
+                        let destination = job.destination.join(relative);
+                        ManifestEntry {
+                            source: job.source.join(relative).to_string_lossy().into_owned(),
+                            modified: fs::symlink_metadata(&destination)
+                                .and_then(|metadata| metadata.modified())
+                                .ok()
+                                .map(epoch_seconds),
+                            destination: destination.to_string_lossy().into_owned(),
+                            size: *size,
+                            hash: None,
+                            algorithm: None,
+                            shuffled,
+                            status: "moved",
+                        }
+                    })
+                    .collect();
+            }
+            continue;
+        }
+        match create_tree(&job.source, &job.staging, listing, options.preserve) {
+            Ok(()) => to_copy.push(idx),
+            Err(e) => outcome.error = Some(roll_back(job, e)),
+        }
+    }
+
+    let files: Vec<(usize, &Path)> = to_copy
+        .iter()
+        .flat_map(|&idx| {
+            let listing = jobs[idx].listing.as_ref().expect("listed above");
+            listing
+                .files
+                .iter()
+                .map(move |(path, _)| (idx, path.as_path()))
+        })
+        .collect();
+    let copied: Vec<AtomicU64> = jobs.iter().map(|_| AtomicU64::new(0)).collect();
+    let written: Vec<AtomicU64> = jobs.iter().map(|_| AtomicU64::new(0)).collect();
+    let errors: Vec<Mutex<Option<String>>> = jobs.iter().map(|_| Mutex::new(None)).collect();
+    let verified: Vec<Mutex<Vec<VerifiedFile>>> =
+        jobs.iter().map(|_| Mutex::new(Vec::new())).collect();
+    let entries: Vec<Mutex<Vec<ManifestEntry>>> =
+        jobs.iter().map(|_| Mutex::new(Vec::new())).collect();
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..options.threads.min(files.len()).max(1) {
+            scope.spawn(|| {
+                let mut buffer = vec![0; COPY_BUFFER];
+                while let Some(&(idx, relative)) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let job = &jobs[idx];
+                    let source = job.source.join(relative);
+                    let staged = job.staging.join(relative);
+                    let modified = match options.manifest {
+                        true => fs::symlink_metadata(&source)
+                            .and_then(|metadata| metadata.modified())
+                            .ok(),
+                        false => None,
+                    };
+                    let fail = |error: String| {
+                        errors[idx]
+                            .lock()
+                            .expect("error slot poisoned")
+                            .get_or_insert(error);
+                    };
+                    let mut attempts = 0;
+                    let result = loop {
+                        let result = shuffle_file(&source, &staged, &mut buffer, options);
+                        let retry = match &result {
+                            Ok((_, Some((source_hash, destination_hash)))) => {
+                                source_hash != destination_hash
+                            }
+                            Ok((_, None)) => false,
+                            Err(_) => true,
+                        };
+                        if !retry || attempts == options.retries {
+                            break result;
+                        }
+                        attempts += 1;
+                        let _ = fs::remove_file(&staged);
+                    };
+                    match result {
+                        Ok((bytes, hashes)) => {
+                            copied[idx].fetch_add(1, Ordering::Relaxed);
+                            written[idx].fetch_add(bytes, Ordering::Relaxed);
+                            if options.manifest {
+                                let entry = ManifestEntry {
+                                    source: source.to_string_lossy().into_owned(),
+                                    destination: job
+                                        .destination
+                                        .join(relative)
+                                        .to_string_lossy()
+                                        .into_owned(),
+                                    size: bytes,
+                                    hash: hashes.as_ref().map(|(hash, _)| hash.clone()),
+                                    algorithm: options.verify.map(Checksum::name),
+                                    modified: modified.map(epoch_seconds),
+                                    shuffled: epoch_seconds(SystemTime::now()),
+                                    status: "",
+                                };
+                                entries[idx].lock().expect("entries poisoned").push(entry);
+                            }
+                            if let Some((source_hash, destination_hash)) = hashes {
+                                let file = VerifiedFile {
+                                    source: source.to_string_lossy().into_owned(),
+                                    destination: job
+                                        .destination
+                                        .join(relative)
+                                        .to_string_lossy()
+                                        .into_owned(),
+                                    size: bytes,
+                                    source_hash,
+                                    destination_hash,
+                                };
+                                if !file.ok() {
+                                    fail(format!("checksum mismatch for {}", source.display()));
+                                }
+                                verified[idx].lock().expect("verified poisoned").push(file);
+                            }
+                        }
+                        Err(e) => fail(format!(
+                            "cannot {} {}: {}",
+                            if mode == ShuffleMode::Hardlink {
+                                "link"
+                            } else {
+                                "copy"
+                            },
+                            source.display(),
+                            e
+                        )),
+                    }
+                }
+            });
+        }
+    });
+
+    for idx in to_copy {
+        let job = &jobs[idx];
+        let outcome = &mut outcomes[idx];
+        outcome.verified = std::mem::take(&mut *verified[idx].lock().expect("verified poisoned"));
+        outcome.verified.sort_by(|a, b| a.source.cmp(&b.source));
+        if let Some(error) = errors[idx].lock().expect("error slot poisoned").take() {
+            outcome.error = Some(roll_back(job, error));
+            continue;
+        }
+        if let Err(e) = preserve_directories(job, options.preserve) {
+            outcome.error = Some(roll_back(job, e));
+            continue;
+        }
+        if let Err(e) = publish(job) {
+            outcome.error = Some(roll_back(job, e));
+            continue;
+        }
+        outcome.files = copied[idx].load(Ordering::Relaxed);
+        outcome.bytes = written[idx].load(Ordering::Relaxed);
+        outcome.status = match mode {
+            ShuffleMode::Hardlink => "linked",
+            _ => "copied",
+        };
+        if mode.removes_source() {
+            // Only a complete copy lets the source go
+            match options.removal.remove(&jobs[idx].source) {
+                Ok(trashed) => {
+                    outcome.status = "moved";
+                    outcome.trashed = trashed.map(|path| path.to_string_lossy().into_owned());
+                }
+                Err(e) => {
+                    outcome.error = Some(format!(
+                        "copied, but cannot remove {}: {}",
+                        jobs[idx].source.display(),
+                        e
+                    ))
+                }
+            }
+        }
+        let mut published = std::mem::take(&mut *entries[idx].lock().expect("entries poisoned"));
+        published.sort_by(|a, b| a.source.cmp(&b.source));
+        for entry in &mut published {
+            entry.status = outcome.status;
+        }
+        manifest[idx] = published;
+    }
+    (outcomes, manifest.into_iter().flatten().collect())
+}
+
+/// Link or copy one file, verifying it if the options say to, and copy
+/// its metadata; returns the bytes written and, when verified, the
+/// source's and destination's hashes
+fn shuffle_file(
+    source: &Path,
+    staged: &Path,
+    buffer: &mut [u8],
+    options: &ShuffleOptions,
+) -> io::Result<(u64, Option<(String, String)>)> {
+    let copied = match (options.mode, options.verify) {
+        (ShuffleMode::Hardlink, _) => return link_file(source, staged).map(|bytes| (bytes, None)),
+        (_, None) => {
+            copy_file(source, staged, buffer, options.throttle.as_ref()).map(|bytes| (bytes, None))
+        }
+        (_, Some(checksum)) => {
+            verified_copy(source, staged, buffer, checksum, options.throttle.as_ref())
+                .map(|(bytes, hashes)| (bytes, Some(hashes)))
+        }
+    }?;
+    preserve_metadata(source, staged, options.preserve)?;
+    Ok(copied)
+}
+
+/// Rename a complete copy from its staging directory to its destination,
+/// replacing or merging into what's there as the job's conflict says
+fn publish(job: &Job) -> Result<(), String> {
+    let rename_error = |e: io::Error| {
+        format!(
+            "cannot rename {} to {}: {}",
+            job.staging.display(),
+            job.destination.display(),
+            e
+        )
+    };
+    match job.conflict {
+        Some(OnConflict::Overwrite) => {
+            let replaced = hidden_sibling(&job.destination, "replaced");
+            let moved_aside = match fs::rename(&job.destination, &replaced) {
+                Ok(()) => true,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+                Err(e) => {
+                    return Err(format!(
+                        "cannot move {} aside: {}",
+                        job.destination.display(),
+                        e
+                    ))
+                }
+            };
+            if let Err(e) = fs::rename(&job.staging, &job.destination) {
+                if moved_aside {
+                    let _ = fs::rename(&replaced, &job.destination);
+                }
+                return Err(rename_error(e));
+            }
+            if moved_aside {
+                remove_path(&replaced).map_err(|e| {
+                    format!(
+                        "replaced, but cannot remove the old copy at {}: {}",
+                        replaced.display(),
+                        e
+                    )
+                })?;
+            }
+            Ok(())
+        }
+        Some(OnConflict::Merge) => merge_into(&job.staging, &job.destination)
+            .and_then(|()| fs::remove_dir_all(&job.staging))
+            .map_err(|e| format!("cannot merge into {}: {}", job.destination.display(), e)),
+        _ => {
+            // A rename replaces an empty directory, so one that appeared
+            // during the copy has to be checked for
+            if fs::symlink_metadata(&job.destination).is_ok() {
+                return Err(format!("{} already exists", job.destination.display()));
+            }
+            fs::rename(&job.staging, &job.destination).map_err(rename_error)
+        }
+    }
+}
+
+/// Move everything in `staged` into `destination`: new entries are
+/// renamed in whole, files replace files of the same name, and
+/// directories merge into directories
+fn merge_into(staged: &Path, destination: &Path) -> io::Result<()> {
+    fs::create_dir_all(destination)?;
+    for entry in fs::read_dir(staged)? {
+        let entry = entry?;
+        let target = destination.join(entry.file_name());
+        let dir = entry.file_type()?.is_dir();
+        match fs::symlink_metadata(&target) {
+            Ok(existing) if existing.is_dir() && dir => merge_into(&entry.path(), &target)?,
+            Ok(existing) if existing.is_dir() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("cannot replace directory {} with a file", target.display()),
+                ))
+            }
+            _ => fs::rename(entry.path(), &target)?,
+        }
+    }
+    Ok(())
+}
+
+/// Remove a file, link or whole directory
+fn remove_path(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Remove a failed job's partial copy; returns `error`, with why the copy
+/// is left behind if it can't be removed
+fn roll_back(job: &Job, error: String) -> String {
+    match fs::remove_dir_all(&job.staging) {
+        Ok(()) => error,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => error,
+        Err(e) => format!(
+            "{}; partial copy left at {}: {}",
+            error,
+            job.staging.display(),
+            e
+        ),
+    }
+}
+
+/// Create the staging root, its subdirectories and its links
+fn create_tree(
+    source: &Path,
+    staging: &Path,
+    listing: &TreeListing,
+    preserve: Preserve,
+) -> Result<(), String> {
+    let create = |dir: &Path| {
+        fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))
+    };
+    if let Some(parent) = staging.parent() {
+        create(parent)?;
+    }
+    fs::create_dir(staging).map_err(|e| format!("cannot create {}: {}", staging.display(), e))?;
+    for dir in &listing.dirs {
+        create(&staging.join(dir))?;
+    }
+    for link in &listing.links {
+        copy_link(&source.join(link), &staging.join(link))
+            .and_then(|()| preserve_metadata(&source.join(link), &staging.join(link), preserve))
+            .map_err(|e| format!("cannot copy link {}: {}", source.join(link).display(), e))?;
+    }
+    Ok(())
+}
+
+/// Hard link one file; returns its size
+fn link_file(source: &Path, destination: &Path) -> io::Result<u64> {
+    fs::hard_link(source, destination)?;
+    Ok(fs::symlink_metadata(destination)?.len())
+}
+
+/// Copy one file's contents; returns the bytes copied
+///
+/// The copy is a clone sharing the source's blocks where the filesystem
+/// supports it (Btrfs, XFS, APFS), then a copy within the kernel on
+/// Linux, and otherwise goes through `buffer`.
+fn copy_file(
+    source: &Path,
+    destination: &Path,
+    buffer: &mut [u8],
+    throttle: Option<&Throttle>,
+) -> io::Result<u64> {
+    // Clones and copies within the kernel can't be paced
+    #[cfg(target_os = "macos")]
+    if throttle.is_none() && clone_file(source, destination).is_ok() {
+        return fs::symlink_metadata(destination).map(|metadata| metadata.len());
+    }
+    let mut reader = File::open(source)?;
+    let mut writer = File::create(destination)?;
+    let accelerated = match throttle {
+        None => kernel_copy(&reader, &writer)?,
+        Some(_) => None,
+    };
+    match accelerated {
+        Some(copied) => Ok(copied),
+        None => buffered_copy(&mut reader, &mut writer, buffer, None, throttle),
+    }
+}
+
+/// Copy one file through `buffer`, hashing it as it's read, then read the
+/// copy back and hash that; returns the bytes copied and both digests
+fn verified_copy(
+    source: &Path,
+    destination: &Path,
+    buffer: &mut [u8],
+    checksum: Checksum,
+    throttle: Option<&Throttle>,
+) -> io::Result<(u64, (String, String))> {
+    let mut reader = File::open(source)?;
+    let mut writer = File::create(destination)?;
+    let mut hasher = checksum.hasher();
+    let copied = buffered_copy(
+        &mut reader,
+        &mut writer,
+        buffer,
+        Some(&mut hasher),
+        throttle,
+    )?;
+    drop(writer);
+    let destination_hash = checksum.hash_file(destination, buffer)?;
+    Ok((copied, (hasher.finish(), destination_hash)))
+}
+
+fn buffered_copy(
+    reader: &mut File,
+    writer: &mut File,
+    buffer: &mut [u8],
+    mut hasher: Option<&mut Hasher>,
+    throttle: Option<&Throttle>,
+) -> io::Result<u64> {
+    let chunk = throttle.map_or(buffer.len(), |throttle| throttle.chunk(buffer.len()));
+    let buffer = &mut buffer[..chunk];
+    let mut copied = 0;
+    loop {
+        let read = match reader.read(buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if let Some(hasher) = hasher.as_deref_mut() {
+            hasher.update(&buffer[..read]);
+        }
+        if let Some(throttle) = throttle {
+            throttle.take(read as u64);
+        }
+        writer.write_all(&buffer[..read])?;
+        copied += read as u64;
+    }
+    Ok(copied)
+}
+
+/// Bytes `copy_file_range` is asked for at a time
+#[cfg(target_os = "linux")]
+const KERNEL_COPY_CHUNK: usize = 1 << 30;
+
+/// Clone `reader` into the empty `writer` with ``FICLONE``, or copy it
+/// with ``copy_file_range``; None if neither works between these files,
+/// e.g. across filesystems on older kernels
+#[cfg(target_os = "linux")]
+fn kernel_copy(reader: &File, writer: &File) -> io::Result<Option<u64>> {
+    use std::os::unix::io::AsRawFd;
+    let (input, output) = (reader.as_raw_fd(), writer.as_raw_fd());
+    // SAFETY: both descriptors are open for the duration of the calls
+    if unsafe { libc::ioctl(output, libc::FICLONE, input) } == 0 {
+        return reader.metadata().map(|metadata| Some(metadata.len()));
+    }
+    let mut copied = 0;
+    loop {
+        // SAFETY: as above; null offsets use and advance the files' own
+        let written = unsafe {
+            libc::copy_file_range(
+                input,
+                std::ptr::null_mut(),
+                output,
+                std::ptr::null_mut(),
+                KERNEL_COPY_CHUNK,
+                0,
+            )
+        };
+        match written {
+            // Files like those in /proc report no size to the kernel, so
+            // nothing copied at all is left to the buffered copy
+            0 if copied == 0 => return Ok(None),
+            0 => return Ok(Some(copied)),
+            written if written > 0 => copied += written as u64,
+            _ => {
+                let e = io::Error::last_os_error();
+                let unsupported = matches!(
+                    e.raw_os_error(),
+                    Some(
+                        libc::EXDEV | libc::ENOSYS | libc::EOPNOTSUPP | libc::EINVAL | libc::EPERM
+                    )
+                );
+                return match e.kind() {
+                    io::ErrorKind::Interrupted => continue,
+                    _ if unsupported && copied == 0 => Ok(None),
+                    _ => Err(e),
+                };
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kernel_copy(_reader: &File, _writer: &File) -> io::Result<Option<u64>> {
+    Ok(None)
+}
+
+/// Clone `source` to `destination`, which must not exist, with
+/// ``clonefile``; fails on filesystems other than APFS
+#[cfg(target_os = "macos")]
+fn clone_file(source: &Path, destination: &Path) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = |path: &Path| {
+        std::ffi::CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    };
+    let (source, destination) = (path(source)?, path(destination)?);
+    // SAFETY: both paths are NUL-terminated strings that outlive the call
+    match unsafe { libc::clonefile(source.as_ptr(), destination.as_ptr(), 0) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Give a staged directory, and every directory in it, its source's
+/// metadata; children go first, since filling a directory changes its
+/// modification time
+fn preserve_directories(job: &Job, preserve: Preserve) -> Result<(), String> {
+    let Ok(listing) = &job.listing else {
+        return Ok(());
+    };
+    for dir in listing.dirs.iter().rev().chain(Some(&PathBuf::new())) {
+        let source = job.source.join(dir);
+        preserve_metadata(&source, &job.staging.join(dir), preserve)
+            .map_err(|e| format!("cannot copy metadata of {}: {}", source.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Copy the metadata `preserve` asks for from `source` to `destination`,
+/// either of which may be a symbolic link
+fn preserve_metadata(source: &Path, destination: &Path, preserve: Preserve) -> io::Result<()> {
+    if !(preserve.times || preserve.permissions || preserve.ownership || preserve.xattrs) {
+        return Ok(());
+    }
+    let metadata = fs::symlink_metadata(source)?;
+    let link = metadata.file_type().is_symlink();
+    // Attributes need a writable file, and a change of owner clears
+    // set-user-ID bits, so both go before permissions
+    if preserve.xattrs && !link {
+        copy_xattrs(source, destination)?;
+    }
+    if preserve.ownership {
+        copy_owner(destination, &metadata)?;
+    }
+    if preserve.permissions && !link {
+        fs::set_permissions(destination, metadata.permissions())?;
+    }
+    if preserve.times {
+        let accessed = FileTime::from_last_access_time(&metadata);
+        let modified = FileTime::from_last_modification_time(&metadata);
+        if link {
+            filetime::set_symlink_file_times(destination, accessed, modified)?;
+        } else {
+            filetime::set_file_times(destination, accessed, modified)?;
+        }
+    }
+    Ok(())
+}
+
+/// Give `path` the owner and group in `metadata`; without the privilege
+/// to, only the group, and if not that either, leave both, as ``cp -a``
+/// does
+#[cfg(unix)]
+fn copy_owner(path: &Path, metadata: &fs::Metadata) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // An ID of -1 leaves that ID as it is
+    for (uid, gid) in [(metadata.uid(), metadata.gid()), (u32::MAX, metadata.gid())] {
+        // SAFETY: `path` is a NUL-terminated string that outlives the call
+        if unsafe { libc::lchown(path.as_ptr(), uid, gid) } == 0 {
+            return Ok(());
+        }
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::EPERM) {
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_owner(_path: &Path, _metadata: &fs::Metadata) -> io::Result<()> {
+    Ok(())
+}
+
+/// Copy every extended attribute the destination takes; those in
+/// namespaces this process can't write, or on filesystems without them,
+/// are skipped
+#[cfg(unix)]
+fn copy_xattrs(source: &Path, destination: &Path) -> io::Result<()> {
+    let skipped = |e: &io::Error| matches!(e.raw_os_error(), Some(code) if code == libc::EPERM || code == libc::ENOTSUP);
+    let names = match xattr::list(source) {
+        Ok(names) => names,
+        Err(e) if skipped(&e) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for name in names {
+        let Some(value) = xattr::get(source, &name)? else {
+            continue;
+        };
+        match xattr::set(destination, &name, &value) {
+            Err(e) if !skipped(&e) => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_xattrs(_source: &Path, _destination: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_link(source: &Path, destination: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(source)?, destination)
+}
+
+/// Links can't be created without privileges on every platform, so
+/// elsewhere the file or directory linked to is copied
+#[cfg(not(unix))]
+fn copy_link(source: &Path, destination: &Path) -> io::Result<()> {
+    if fs::metadata(source)?.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "links to directories can't be copied on this platform",
+        ));
+    }
+    fs::copy(source, destination).map(|_| ())
+}
@@ -0,0 +1,816 @@
+use bincode::Options;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::file_pattern::DirectoryTree;
+use crate::pattern::PatternMatcher;
+use crate::walk::{
+    check_root, exclude_matcher, is_excluded, scan_walker, thread_count, Collector, DirContents,
+    DirEntry, Failure, WalkedTree,
+};
+
+/// First bytes of every snapshot file, before its version
+const MAGIC: &[u8; 6] = b"PVSNAP";
+
+/// Bumped whenever the layout changes; other versions can't be loaded
+const SNAPSHOT_VERSION: u16 = 2;
+
+/// What an entry in a snapshot is
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    /// Sockets, devices and pipes
+    Other,
+}
+
+impl EntryKind {
+    fn of(file_type: fs::FileType) -> Self {
+        if file_type.is_dir() {
+            EntryKind::Dir
+        } else if file_type.is_file() {
+            EntryKind::File
+        } else if file_type.is_symlink() {
+            EntryKind::Symlink
+        } else {
+            EntryKind::Other
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            EntryKind::File => "file",
+            EntryKind::Dir => "dir",
+            EntryKind::Symlink => "symlink",
+            EntryKind::Other => "other",
+        }
+    }
+}
+
+/// One entry of a snapshot, named relative to the directory holding it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct StoredEntry {
+    /// Index of the directory holding it; the root is its own parent
+    pub(crate) parent: u32,
+    pub(crate) name: String,
+    pub(crate) kind: EntryKind,
+    pub(crate) size: u64,
+    /// Nanoseconds since the epoch, negative before it
+    pub(crate) modified: i64,
+    /// Device and inode number, 0 where the platform has none
+    pub(crate) device: u64,
+    pub(crate) inode: u64,
+}
+
+/// Everything a snapshot file holds
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct SnapshotData {
+    pub(crate) root: String,
+    /// Seconds since the epoch when the walk started
+    pub(crate) created: f64,
+    /// Sorted by path, so every directory comes before what it holds; the
+    /// root is first
+    pub(crate) entries: Vec<StoredEntry>,
+    /// Entries the walk couldn't list or read
+    pub(crate) errors: Vec<Failure>,
+}
+
+impl SnapshotData {
+    /// Every entry's path relative to the root, ``""`` for the root itself
+    pub(crate) fn relative_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = Vec::with_capacity(self.entries.len());
+        for (index, entry) in self.entries.iter().enumerate() {
+            let path = match index {
+                0 => PathBuf::new(),
+                _ => paths[entry.parent as usize].join(&entry.name),
+            };
+            paths.push(path);
+        }
+        paths
+    }
+
+    /// Listings of every directory, keyed by the root joined with its
+    /// path, with each directory's depth below the root
+    pub(crate) fn listings(&self) -> Vec<(PathBuf, usize, DirContents)> {
+        let root = Path::new(&self.root);
+        let paths = self.relative_paths();
+        let mut listings: HashMap<usize, DirContents> = HashMap::new();
+        for (index, entry) in self.entries.iter().enumerate().skip(1) {
+            let (files, dirs) = listings.entry(entry.parent as usize).or_default();
+            if entry.kind == EntryKind::Dir {
+                dirs.push(OsString::from(&entry.name));
+                listings.entry(index).or_default();
+            } else {
+                files.push(OsString::from(&entry.name));
+            }
+        }
+        if !self.entries.is_empty() {
+            listings.entry(0).or_default();
+        }
+        let mut listings: Vec<_> = listings
+            .into_iter()
+            .map(|(index, listing)| {
+                let relative = &paths[index];
+                let key = match index {
+                    0 => root.to_path_buf(),
+                    _ => root.join(relative),
+                };
+                (key, relative.components().count(), listing)
+            })
+            .collect();
+        listings.sort_by(|a, b| a.0.cmp(&b.0));
+        listings
+    }
+
+    /// Write the snapshot next to `path` and move it into place, so a
+    /// failed write never leaves a truncated snapshot
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let written = (|| {
+            let mut file = BufWriter::new(File::create(&temporary)?);
+            file.write_all(MAGIC)?;
+            file.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+            let mut encoder = zstd::stream::write::Encoder::new(file, 0)?;
+            bincode::DefaultOptions::new()
+                .serialize_into(&mut encoder, self)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            encoder.finish()?.into_inner().map_err(|e| e.into_error())?;
+            fs::rename(&temporary, path)
+        })();
+        if written.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        written
+    }
+
+    pub(crate) fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut file = BufReader::new(File::open(path)?);
+        let mut header = [0; 8];
+        file.read_exact(&mut header)
+            .map_err(|_| invalid("not a pathvein snapshot".to_string()))?;
+        if &header[..6] != MAGIC {
+            return Err(invalid("not a pathvein snapshot".to_string()));
+        }
+        let version = u16::from_le_bytes([header[6], header[7]]);
+        if version != SNAPSHOT_VERSION {
+            return Err(invalid(format!(
+                "snapshot version {} isn't supported (expected {})",
+                version, SNAPSHOT_VERSION
+            )));
+        }
+        let decoder = zstd::stream::read::Decoder::with_buffer(file)?;
+        bincode::DefaultOptions::new()
+            .deserialize_from(decoder)
+            .map_err(|e| invalid(e.to_string()))
+    }
+}
+
+/// One entry of a ``Snapshot``
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct SnapshotEntry {
+    #[pyo3(get)]
+    pub path: String,
+    /// ``"file"``, ``"dir"``, ``"symlink"`` or ``"other"``
+    #[pyo3(get)]
+    pub kind: &'static str,
+    #[pyo3(get)]
+    pub size: u64,
+    /// Modification time, in seconds since the epoch
+    #[pyo3(get)]
+    pub modified: f64,
+}
+
+impl SnapshotEntry {
+    fn new(root: &Path, relative: &Path, entry: &StoredEntry) -> Self {
+        let path = if relative.as_os_str().is_empty() {
+            root.to_path_buf()
+        } else {
+            root.join(relative)
+        };
+        SnapshotEntry {
+            path: path.to_string_lossy().into_owned(),
+            kind: entry.kind.name(),
+            size: entry.size,
+            modified: entry.modified as f64 / 1e9,
+        }
+    }
+}
+
+#[pymethods]
+impl SnapshotEntry {
+    fn __repr__(&self) -> String {
+        format!(
+            "SnapshotEntry(path='{}', kind='{}', size={})",
+            self.path, self.kind, self.size
+        )
+    }
+}
+
+/// Index of a directory tree, from ``snapshot`` or ``load_snapshot``
+#[pyclass(module = "pathvein._pathvein_rs", frozen)]
+pub struct Snapshot {
+    pub(crate) data: Arc<SnapshotData>,
+}
+
+#[pymethods]
+impl Snapshot {
+    /// Root directory the snapshot was taken of
+    #[getter]
+    fn root(&self) -> &str {
+        &self.data.root
+    }
+
+    /// When the snapshot was taken, in seconds since the epoch
+    #[getter]
+    fn created(&self) -> f64 {
+        self.data.created
+    }
+
+    /// Entries that couldn't be listed or read when the snapshot was taken
+    #[getter]
+    fn errors(&self) -> Vec<Failure> {
+        self.data.errors.clone()
+    }
+
+    /// Number of regular files
+    #[getter]
+    fn files(&self) -> usize {
+        self.count(EntryKind::File)
+    }
+
+    /// Number of directories, the root included
+    #[getter]
+    fn directories(&self) -> usize {
+        self.count(EntryKind::Dir)
+    }
+
+    /// Total bytes of the regular files
+    #[getter]
+    fn size(&self) -> u64 {
+        self.data
+            .entries
+            .iter()
+            .filter(|entry| entry.kind == EntryKind::File)
+            .map(|entry| entry.size)
+            .sum()
+    }
+
+    /// Every entry, the root first, sorted by path
+    fn entries(&self, py: Python<'_>) -> Vec<SnapshotEntry> {
+        py.allow_threads(|| {
+            let root = Path::new(&self.data.root);
+            let paths = self.data.relative_paths();
+            self.data
+                .entries
+                .iter()
+                .zip(&paths)
+                .map(|(entry, relative)| SnapshotEntry::new(root, relative, entry))
+                .collect()
+        })
+    }
+
+    /// The snapshot's directories as ``walk_parallel`` would list them,
+    /// sorted by path
+    fn walk(&self, py: Python<'_>) -> Vec<DirEntry> {
+        py.allow_threads(|| {
+            let name = |name: &OsString| name.to_string_lossy().into_owned();
+            self.data
+                .listings()
+                .into_iter()
+                .map(|(dir, _, (files, dirs))| DirEntry {
+                    path: dir.to_string_lossy().into_owned(),
+                    dirnames: dirs.iter().map(name).collect(),
+                    filenames: files.iter().map(name).collect(),
+                })
+                .collect()
+        })
+    }
+
+    fn __len__(&self) -> usize {
+        self.data.entries.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Snapshot(root='{}', entries={})",
+            self.data.root,
+            self.data.entries.len()
+        )
+    }
+}
+
+impl Snapshot {
+    fn count(&self, kind: EntryKind) -> usize {
+        self.data
+            .entries
+            .iter()
+            .filter(|entry| entry.kind == kind)
+            .count()
+    }
+}
+
+/// Take a snapshot of a directory tree
+///
+/// Walks the tree once, in parallel, and writes every entry's path, type,
+/// size and modification time to ``out_file``: a zstd-compressed binary
+/// index, with each entry stored by name under its directory, that
+/// ``load_snapshot`` reads back. Analysing a snapshot never touches the
+/// tree again, so a slow or cold filer is walked once rather than once per
+/// question asked of it.
+///
+/// Symlinks are recorded as symlinks, unless ``follow_links`` is set.
+///
+/// Args:
+///     path: Root directory to snapshot
+///     out_file: Snapshot file to write; replaced if it exists
+///     max_depth: Optional maximum depth to traverse
+///     follow_links: Whether to follow symbolic links
+///     exclude: Optional gitignore-style globs, relative to the root, for
+///         entries to leave out, as for ``walk_parallel``
+///     threads: Directories listed at once (default: one per CPU)
+///
+/// Returns:
+///     The Snapshot written
+///
+/// Raises:
+///     ValueError: If threads is 0, an exclude pattern is invalid or the
+///         file can't be written
+///     WalkError: If ``path`` doesn't exist or isn't a directory
+#[pyfunction]
+#[pyo3(signature = (path, out_file, max_depth=None, follow_links=false, exclude=None, threads=None))]
+pub fn snapshot(
+    py: Python<'_>,
+    path: String,
+    out_file: PathBuf,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    exclude: Option<Vec<String>>,
+    threads: Option<usize>,
+) -> PyResult<Snapshot> {
+    let threads = thread_count(threads)?;
+    check_root(&path)?;
+    let matcher = exclude_matcher(exclude)?;
+    let data = py.allow_threads(|| {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |since| since.as_secs_f64());
+        let (entries, errors) = walk_tree(&path, max_depth, follow_links, matcher, threads);
+        let data = SnapshotData {
+            root: path,
+            created,
+            entries,
+            errors,
+        };
+        data.save(&out_file).map_err(|e| {
+            PyValueError::new_err(format!("Cannot write {}: {}", out_file.display(), e))
+        })?;
+        Ok::<_, PyErr>(data)
+    })?;
+    Ok(Snapshot {
+        data: Arc::new(data),
+    })
+}
+
+/// Load a snapshot written by ``snapshot``
+///
+/// Args:
+///     path: Snapshot file to read
+///
+/// Returns:
+///     The Snapshot
+///
+/// Raises:
+///     ValueError: If the file can't be read or isn't a snapshot this
+///         version of pathvein can read
+#[pyfunction]
+pub fn load_snapshot(py: Python<'_>, path: PathBuf) -> PyResult<Snapshot> {
+    let data = py
+        .allow_threads(|| SnapshotData::load(&path))
+        .map_err(|e| {
+            PyValueError::new_err(format!("Cannot read snapshot {}: {}", path.display(), e))
+        })?;
+    Ok(Snapshot {
+        data: Arc::new(data),
+    })
+}
+
+/// Whether `path` is a snapshot file, going by its first bytes
+pub(crate) fn is_snapshot_file(path: &str) -> bool {
+    let mut magic = [0; MAGIC.len()];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|()| &magic == MAGIC)
+}
+
+/// Snapshots' listings, with sizes, times and kinds from the snapshots
+/// rather than the filesystem they were taken of
+pub(crate) struct SnapshotTree<'a> {
+    listings: &'a WalkedTree,
+    /// Every entry by path, when patterns look at sizes or times
+    entries: HashMap<PathBuf, &'a StoredEntry>,
+    /// Bytes in every file beneath each directory, likewise
+    totals: HashMap<PathBuf, u64>,
+}
+
+impl<'a> SnapshotTree<'a> {
+    pub(crate) fn new(
+        listings: &'a WalkedTree,
+        snapshots: impl Iterator<Item = &'a SnapshotData>,
+        with_stats: bool,
+    ) -> Self {
+        let mut tree = SnapshotTree {
+            listings,
+            entries: HashMap::new(),
+            totals: HashMap::new(),
+        };
+        if !with_stats {
+            return tree;
+        }
+        for snapshot in snapshots {
+            let root = Path::new(&snapshot.root);
+            let paths = snapshot.relative_paths();
+            // Directories come before what they hold, so in reverse each
+            // total is complete before it's added to its parent's
+            let mut totals = vec![0; snapshot.entries.len()];
+            for (index, entry) in snapshot.entries.iter().enumerate().skip(1).rev() {
+                let size = match entry.kind {
+                    EntryKind::Dir => totals[index],
+                    EntryKind::File => entry.size,
+                    _ => 0,
+                };
+                totals[entry.parent as usize] += size;
+            }
+            for ((entry, relative), total) in snapshot.entries.iter().zip(paths).zip(totals) {
+                let path = if relative.as_os_str().is_empty() {
+                    root.to_path_buf()
+                } else {
+                    root.join(relative)
+                };
+                if entry.kind == EntryKind::Dir {
+                    tree.totals.insert(path.clone(), total);
+                }
+                tree.entries.insert(path, entry);
+            }
+        }
+        tree
+    }
+}
+
+impl DirectoryTree for SnapshotTree<'_> {
+    fn children(&self, dir: &Path) -> (&[OsString], &[OsString]) {
+        self.listings.children(dir)
+    }
+
+    fn file_size(&self, path: &Path) -> Option<u64> {
+        self.entries.get(path).map(|entry| entry.size)
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        let modified = self.entries.get(path)?.modified;
+        let since = Duration::from_nanos(modified.unsigned_abs());
+        Some(if modified < 0 {
+            UNIX_EPOCH - since
+        } else {
+            UNIX_EPOCH + since
+        })
+    }
+
+    /// Contents aren't kept in a snapshot
+    fn read_head(&self, _path: &Path, _limit: usize) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        self.entries
+            .get(path)
+            .is_some_and(|entry| entry.kind == EntryKind::Symlink)
+    }
+
+    /// Where a symlink leads isn't kept in a snapshot
+    fn linked_dirs(&self, _dir: &Path) -> Vec<OsString> {
+        Vec::new()
+    }
+
+    fn total_size(&self, dir: &Path) -> Option<u64> {
+        self.totals.get(dir).copied()
+    }
+}
+
+/// A Snapshot in memory, or a file ``snapshot`` wrote it to
+#[derive(FromPyObject)]
+pub enum SnapshotSource {
+    Snapshot(Py<Snapshot>),
+    File(PathBuf),
+}
+
+impl SnapshotSource {
+    fn load(&self) -> PyResult<Arc<SnapshotData>> {
+        match self {
+            SnapshotSource::Snapshot(snapshot) => Ok(snapshot.get().data.clone()),
+            SnapshotSource::File(path) => SnapshotData::load(path).map(Arc::new).map_err(|e| {
+                PyValueError::new_err(format!("Cannot read snapshot {}: {}", path.display(), e))
+            }),
+        }
+    }
+}
+
+/// What differs between two snapshots, from ``diff_snapshots``
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct SnapshotDiff {
+    /// Entries only the new snapshot has
+    #[pyo3(get)]
+    pub created: Vec<SnapshotEntry>,
+    /// Entries only the old snapshot has
+    #[pyo3(get)]
+    pub deleted: Vec<SnapshotEntry>,
+    /// Entries both snapshots have at the same path that differ
+    #[pyo3(get)]
+    pub modified: Vec<SnapshotChange>,
+    /// Entries that moved to another path
+    #[pyo3(get)]
+    pub moved: Vec<SnapshotChange>,
+}
+
+#[pymethods]
+impl SnapshotDiff {
+    /// Whether the snapshots hold the same entries
+    #[getter]
+    fn is_empty(&self) -> bool {
+        self.created.is_empty()
+            && self.deleted.is_empty()
+            && self.modified.is_empty()
+            && self.moved.is_empty()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "SnapshotDiff(created={}, deleted={}, modified={}, moved={})",
+            self.created.len(),
+            self.deleted.len(),
+            self.modified.len(),
+            self.moved.len()
+        )
+    }
+}
+
+/// One entry in both snapshots, and how it differs
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct SnapshotChange {
+    /// Fields that differ: ``"kind"``, ``"size"`` or ``"modified"``, and
+    /// ``"path"`` for a move
+    #[pyo3(get)]
+    pub fields: Vec<String>,
+    #[pyo3(get)]
+    pub old: SnapshotEntry,
+    #[pyo3(get)]
+    pub new: SnapshotEntry,
+}
+
+#[pymethods]
+impl SnapshotChange {
+    fn __repr__(&self) -> String {
+        format!(
+            "SnapshotChange(old='{}', new='{}', fields={:?})",
+            self.old.path, self.new.path, self.fields
+        )
+    }
+}
+
+/// Compare two snapshots of a tree
+///
+/// Entries are paired by path relative to each snapshot's root, so
+/// snapshots of one tree taken through different mount points compare.
+/// Paired entries are modified when their kind, size or modification time
+/// differ; directories only when their kind does, since their times change
+/// with every entry added or removed. Both snapshots are walked in path
+/// order at once, so comparing hundreds of millions of entries costs little
+/// more than loading them.
+///
+/// An entry deleted from one path and created at another is moved when it
+/// is the same file - the same device and inode, with the same kind, and
+/// for files the same size and modification time. Moving a directory moves
+/// everything inside it, but only the directory is listed.
+///
+/// Args:
+///     a: The earlier Snapshot, or the path of a file ``snapshot`` wrote
+///     b: The later snapshot, in either form
+///
+/// Returns:
+///     SnapshotDiff with created, deleted, modified and moved entries,
+///     each sorted by path
+///
+/// Raises:
+///     ValueError: If a file can't be read or isn't a snapshot
+#[pyfunction]
+pub fn diff_snapshots(
+    py: Python<'_>,
+    a: SnapshotSource,
+    b: SnapshotSource,
+) -> PyResult<SnapshotDiff> {
+    py.allow_threads(|| {
+        let (old, new) = (a.load()?, b.load()?);
+        Ok(diff(&old, &new))
+    })
+}
+
+fn diff(old: &SnapshotData, new: &SnapshotData) -> SnapshotDiff {
+    let (old_root, new_root) = (Path::new(&old.root), Path::new(&new.root));
+    let (old_paths, new_paths) = (old.relative_paths(), new.relative_paths());
+    let mut deleted = Vec::new();
+    let mut created = Vec::new();
+    let mut modified = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old_paths.len() || j < new_paths.len() {
+        let order = match (old_paths.get(i), new_paths.get(j)) {
+            (Some(old_path), Some(new_path)) => old_path.cmp(new_path),
+            (Some(_), None) => Ordering::Less,
+            _ => Ordering::Greater,
+        };
+        match order {
+            Ordering::Less => {
+                deleted.push(i);
+                i += 1;
+            }
+            Ordering::Greater => {
+                created.push(j);
+                j += 1;
+            }
+            Ordering::Equal => {
+                let (before, after) = (&old.entries[i], &new.entries[j]);
+                let mut fields = Vec::new();
+                if before.kind != after.kind {
+                    fields.push("kind".to_string());
+                } else if before.kind != EntryKind::Dir {
+                    if before.size != after.size {
+                        fields.push("size".to_string());
+                    }
+                    if before.modified != after.modified {
+                        fields.push("modified".to_string());
+                    }
+                }
+                if !fields.is_empty() {
+                    modified.push(SnapshotChange {
+                        fields,
+                        old: SnapshotEntry::new(old_root, &old_paths[i], before),
+                        new: SnapshotEntry::new(new_root, &new_paths[j], after),
+                    });
+                }
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+
+    // A deleted and a created entry are one moved entry when they're the
+    // same file
+    let same_file = |before: &StoredEntry, after: &StoredEntry| {
+        before.kind == after.kind
+            && (before.kind == EntryKind::Dir
+                || (before.size == after.size && before.modified == after.modified))
+    };
+    let by_id: HashMap<(u64, u64), usize> = deleted
+        .iter()
+        .filter(|&&i| old.entries[i].inode != 0)
+        .map(|&i| ((old.entries[i].device, old.entries[i].inode), i))
+        .collect();
+    let moves: HashMap<usize, usize> = created
+        .iter()
+        .filter_map(|&j| {
+            let after = &new.entries[j];
+            let &i = by_id.get(&(after.device, after.inode))?;
+            same_file(&old.entries[i], after).then_some((j, i))
+        })
+        .collect();
+    let moved_from: HashSet<usize> = moves.values().copied().collect();
+
+    // Only list a move its directory's move doesn't explain
+    let mut moved: Vec<SnapshotChange> = moves
+        .iter()
+        .filter(|&(&j, &i)| {
+            let (before, after) = (&old.entries[i], &new.entries[j]);
+            before.name != after.name
+                || moves.get(&(after.parent as usize)) != Some(&(before.parent as usize))
+        })
+        .map(|(&j, &i)| SnapshotChange {
+            fields: vec!["path".to_string()],
+            old: SnapshotEntry::new(old_root, &old_paths[i], &old.entries[i]),
+            new: SnapshotEntry::new(new_root, &new_paths[j], &new.entries[j]),
+        })
+        .collect();
+    moved.sort_by(|a, b| a.new.path.cmp(&b.new.path));
+    SnapshotDiff {
+        created: created
+            .into_iter()
+            .filter(|j| !moves.contains_key(j))
+            .map(|j| SnapshotEntry::new(new_root, &new_paths[j], &new.entries[j]))
+            .collect(),
+        deleted: deleted
+            .into_iter()
+            .filter(|i| !moved_from.contains(i))
+            .map(|i| SnapshotEntry::new(old_root, &old_paths[i], &old.entries[i]))
+            .collect(),
+        modified,
+        moved,
+    }
+}
+
+/// Walk `root` in parallel; returns its entries, sorted by path and linked
+/// to their directories, and the entries it couldn't read
+fn walk_tree(
+    root: &str,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    matcher: Option<PatternMatcher>,
+    threads: usize,
+) -> (Vec<StoredEntry>, Vec<Failure>) {
+    let mut builder = scan_walker(root, max_depth, follow_links);
+    builder.threads(threads);
+    if let Some(matcher) = matcher {
+        let matcher = Arc::new(matcher);
+        let root = PathBuf::from(root);
+        builder.filter_entry(move |entry| !is_excluded(&matcher, &root, entry));
+    }
+    let collector = Collector::new();
+    builder.build_parallel().run(|| {
+        Box::new(|entry| {
+            match entry {
+                Ok(entry) => match entry.metadata() {
+                    Ok(metadata) => collector.push((entry.into_path(), metadata)),
+                    Err(e) => collector.walk_error(&e),
+                },
+                Err(e) => collector.walk_error(&e),
+            }
+            ignore::WalkState::Continue
+        })
+    });
+    let (found, errors) = collector.finish(Vec::new(), |(path, _)| path);
+
+    let mut indices: HashMap<PathBuf, u32> = HashMap::new();
+    let mut entries = Vec::with_capacity(found.len());
+    for (path, metadata) in found {
+        // The root sorts first, and is its own parent
+        let (parent, name) = if entries.is_empty() {
+            (0, String::new())
+        } else {
+            match (
+                path.parent().and_then(|parent| indices.get(parent)),
+                path.file_name(),
+            ) {
+                (Some(&parent), Some(name)) => (parent, name.to_string_lossy().into_owned()),
+                _ => continue,
+            }
+        };
+        let kind = EntryKind::of(metadata.file_type());
+        let (device, inode) = file_id(&metadata);
+        if kind == EntryKind::Dir {
+            indices.insert(path, entries.len() as u32);
+        }
+        entries.push(StoredEntry {
+            parent,
+            name,
+            kind,
+            size: metadata.len(),
+            modified: epoch_nanos(&metadata),
+            device,
+            inode,
+        });
+    }
+    (entries, errors)
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.dev(), metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> (u64, u64) {
+    (0, 0)
+}
+
+fn epoch_nanos(metadata: &Metadata) -> i64 {
+    match metadata
+        .modified()
+        .map(|time| time.duration_since(UNIX_EPOCH))
+    {
+        Ok(Ok(since)) => since.as_nanos() as i64,
+        Ok(Err(before)) => -(before.duration().as_nanos() as i64),
+        Err(_) => 0,
+    }
+}
@@ -0,0 +1,207 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::Deserializer;
+
+use crate::file_pattern::FileStructurePattern;
+use crate::migrate;
+
+/// One problem found by `validate_spec`
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct SpecError {
+    /// Key path to the offending value, e.g. ``directories[0].files``
+    #[pyo3(get)]
+    pub path: String,
+    /// 1-based line of the error, if the parser reported one
+    #[pyo3(get)]
+    pub line: Option<usize>,
+    /// 1-based column of the error, if the parser reported one
+    #[pyo3(get)]
+    pub column: Option<usize>,
+    #[pyo3(get)]
+    pub message: String,
+}
+
+#[pymethods]
+impl SpecError {
+    fn __repr__(&self) -> String {
+        let location = match (self.line, self.column) {
+            (Some(line), Some(column)) => format!(", line={}, column={}", line, column),
+            _ => String::new(),
+        };
+        format!(
+            "SpecError(path='{}'{}, message={:?})",
+            self.path, location, self.message
+        )
+    }
+}
+
+/// Check a pattern specification without loading it
+///
+/// Reports keys the pattern doesn't know about, which loading silently
+/// ignores, followed by the first error that stops loading. Each error
+/// carries the key path to the offending value and, where the parser
+/// provides one, its line and column.
+///
+/// Args:
+///     text: Pattern specification
+///     format: ``"json"``, ``"yaml"`` or ``"toml"`` (default: JSON if the
+///         text starts with ``{``, YAML otherwise)
+///
+/// Returns:
+///     List of SpecError, empty if the specification is valid
+///
+/// Raises:
+///     ValueError: If format is not a known format
+#[pyfunction]
+#[pyo3(signature = (text, format=None))]
+pub fn validate_spec(text: &str, format: Option<&str>) -> PyResult<Vec<SpecError>> {
+    let format = format.unwrap_or(if text.trim_start().starts_with('{') {
+        "json"
+    } else {
+        "yaml"
+    });
+    let (mut errors, failure) = check_spec(text, format)?;
+    errors.extend(failure);
+    Ok(errors)
+}
+
+/// Unknown keys of a specification in `format`, and the error that stops
+/// it loading, if any
+pub(crate) fn check_spec(
+    text: &str,
+    format: &str,
+) -> PyResult<(Vec<SpecError>, Option<SpecError>)> {
+    let mut errors = Vec::new();
+    let failure = match format {
+        "json" => {
+            let mut deserializer = serde_json::Deserializer::from_str(text);
+            check(&mut deserializer, &mut errors)
+                .and_then(|()| deserializer.end().map_err(|e| (String::new(), e)))
+                .err()
+                .map(|(path, e)| {
+                    let (line, column) = (e.line(), e.column());
+                    let message = e.to_string();
+                    let message = message
+                        .strip_suffix(&format!(" at line {} column {}", line, column))
+                        .unwrap_or(&message)
+                        .to_string();
+                    let location = (line > 0).then_some((line, column));
+                    (path, message, location)
+                })
+        }
+        "yaml" => check(serde_yaml::Deserializer::from_str(text), &mut errors)
+            .err()
+            .map(|(path, e)| {
+                let location = e.location().map(|l| (l.line(), l.column()));
+                let mut message = e.to_string();
+                if let Some((line, column)) = location {
+                    let suffix = format!(" at line {} column {}", line, column);
+                    message.truncate(message.strip_suffix(&suffix).unwrap_or(&message).len());
+                }
+                // serde_yaml prefixes its own copy of the key path
+                let message = message
+                    .strip_prefix(&format!("{}: ", path))
+                    .unwrap_or(&message)
+                    .to_string();
+                (path, message, location)
+            }),
+        "toml" => check(toml::Deserializer::new(text), &mut errors)
+            .err()
+            .map(|(path, e)| {
+                let location = e.span().map(|span| line_column(text, span.start));
+                (path, e.message().to_string(), location)
+            }),
+        other => {
+            return Err(PyValueError::new_err(format!(
+                "Unknown spec format '{}': expected 'json', 'yaml' or 'toml'",
+                other
+            )))
+        }
+    };
+    let failure = failure.map(|(path, message, location)| SpecError {
+        path,
+        line: location.map(|(line, _)| line),
+        column: location.map(|(_, column)| column),
+        message,
+    });
+    let failure = failure.or_else(|| version_error(text, format));
+    Ok((errors, failure))
+}
+
+/// An unsupported ``version`` in a spec that otherwise loads
+fn version_error(text: &str, format: &str) -> Option<SpecError> {
+    let spec: serde_json::Value = match format {
+        "json" => serde_json::from_str(text).ok()?,
+        "yaml" => serde_yaml::from_str(text).ok()?,
+        _ => toml::from_str(text).ok()?,
+    };
+    let message = migrate::spec_version(spec.as_object()?).err()?;
+    Some(SpecError {
+        path: "version".to_string(),
+        line: None,
+        column: None,
+        message,
+    })
+}
+
+/// Deserialize a pattern, recording unknown keys and returning the key
+/// path of the error that stopped it
+fn check<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    errors: &mut Vec<SpecError>,
+) -> Result<(), (String, D::Error)> {
+    let mut unknown_key = |path: serde_ignored::Path| {
+        // The layout version is read before the pattern itself
+        if key_path(&path) == "version" {
+            return;
+        }
+        errors.push(SpecError {
+            message: format!("unknown key '{}'", key_name(&path)),
+            path: key_path(&path),
+            line: None,
+            column: None,
+        })
+    };
+    let ignored = serde_ignored::Deserializer::new(deserializer, &mut unknown_key);
+    serde_path_to_error::deserialize::<_, FileStructurePattern>(ignored)
+        .map(|_| ())
+        .map_err(|e| {
+            let path = e.path().to_string();
+            let path = if path == "." { String::new() } else { path };
+            (path, e.into_inner())
+        })
+}
+
+/// `serde_ignored` path written the way `serde_path_to_error` writes them
+fn key_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, index } => format!("{}[{}]", key_path(parent), index),
+        Path::Map { parent, key } => match key_path(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{}.{}", parent, key),
+        },
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => key_path(parent),
+    }
+}
+
+fn key_name(path: &serde_ignored::Path) -> String {
+    match path {
+        serde_ignored::Path::Map { key, .. } => key.clone(),
+        _ => key_path(path),
+    }
+}
+
+/// 1-based line and column of a byte offset
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
@@ -0,0 +1,164 @@
+use pyo3::prelude::*;
+
+/// Counts and timings of a ``scan_parallel(..., stats_only=True)`` run
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct ScanStats {
+    /// Directories listed and matched against the patterns
+    #[pyo3(get)]
+    pub directories_evaluated: u64,
+    /// Entries the walk could not read, e.g. for lack of permission
+    #[pyo3(get)]
+    pub walk_errors: u64,
+    /// Seconds spent walking the tree
+    #[pyo3(get)]
+    pub walk_seconds: f64,
+    /// Seconds spent matching the walked directories
+    #[pyo3(get)]
+    pub match_seconds: f64,
+    /// Seconds the whole scan took
+    #[pyo3(get)]
+    pub elapsed: f64,
+    /// One PatternStats per pattern, in pattern order
+    #[pyo3(get)]
+    pub patterns: Vec<PatternStats>,
+    /// Where the time went, with ``profile``
+    #[pyo3(get)]
+    pub profile: Option<ScanProfile>,
+}
+
+#[pymethods]
+impl ScanStats {
+    /// Results the scan would have returned, after ``overlap``
+    #[getter]
+    fn matched(&self) -> u64 {
+        self.patterns.iter().map(|pattern| pattern.matched).sum()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ScanStats(directories_evaluated={}, matched={}, walk_errors={}, elapsed={:.2})",
+            self.directories_evaluated,
+            self.matched(),
+            self.walk_errors,
+            self.elapsed
+        )
+    }
+}
+
+/// Per-pattern counts in ScanStats
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct PatternStats {
+    #[pyo3(get)]
+    pub pattern_index: usize,
+    #[pyo3(get)]
+    pub pattern_name: Option<String>,
+    /// Directories this pattern was checked against; fewer than the scan's
+    /// with ``first_match``, which stops at an earlier pattern's match
+    #[pyo3(get)]
+    pub evaluated: u64,
+    /// Directories reported as matching, after ``overlap``
+    #[pyo3(get)]
+    pub matched: u64,
+    /// Directories reaching ``min_score`` without matching
+    #[pyo3(get)]
+    pub near_missed: u64,
+}
+
+#[pymethods]
+impl PatternStats {
+    fn __repr__(&self) -> String {
+        format!(
+            "PatternStats(pattern_index={}, evaluated={}, matched={}, near_missed={})",
+            self.pattern_index, self.evaluated, self.matched, self.near_missed
+        )
+    }
+}
+
+/// Matches per pattern of a ScanResults, from ``ScanResults.summary``
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct ScanSummary {
+    /// Number of matches of each pattern, in pattern order
+    #[pyo3(get)]
+    pub match_counts: Vec<u64>,
+    /// Indices of the patterns that matched nothing
+    #[pyo3(get)]
+    pub unmatched: Vec<usize>,
+    /// Name of each pattern, in pattern order
+    #[pyo3(get)]
+    pub pattern_names: Vec<Option<String>>,
+}
+
+#[pymethods]
+impl ScanSummary {
+    /// Matches of all patterns together
+    #[getter]
+    fn total(&self) -> u64 {
+        self.match_counts.iter().sum()
+    }
+
+    /// Whether every pattern matched at least once
+    #[getter]
+    fn all_matched(&self) -> bool {
+        self.unmatched.is_empty()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ScanSummary(match_counts={:?}, unmatched={:?})",
+            self.match_counts, self.unmatched
+        )
+    }
+}
+
+/// Where a ``scan_parallel(..., profile=True)`` run spent its time
+///
+/// A walk that takes most of the time points at slow storage; matching
+/// that does, or one pattern that dominates it, at the patterns.
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug)]
+pub struct ScanProfile {
+    /// Seconds spent walking the tree, which includes matching without
+    /// ``descend_into_matches``
+    #[pyo3(get)]
+    pub walk_seconds: f64,
+    /// Seconds spent matching the walked directories
+    #[pyo3(get)]
+    pub match_seconds: f64,
+    /// Seconds the whole scan took
+    #[pyo3(get)]
+    pub elapsed: f64,
+    /// Seconds spent evaluating each pattern, in pattern order, summed
+    /// over the evaluation threads
+    #[pyo3(get)]
+    pub pattern_seconds: Vec<f64>,
+    /// Name of each pattern, in pattern order
+    #[pyo3(get)]
+    pub pattern_names: Vec<Option<String>>,
+    /// (path, seconds) of the directories that took longest to evaluate
+    /// against every pattern, slowest first
+    #[pyo3(get)]
+    pub slowest_directories: Vec<(String, f64)>,
+}
+
+#[pymethods]
+impl ScanProfile {
+    /// Index of the pattern that took longest to evaluate, if any
+    #[getter]
+    fn slowest_pattern(&self) -> Option<usize> {
+        self.pattern_seconds
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(idx, _)| idx)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ScanProfile(walk_seconds={:.2}, match_seconds={:.2}, elapsed={:.2})",
+            self.walk_seconds, self.match_seconds, self.elapsed
+        )
+    }
+}
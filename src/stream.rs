@@ -0,0 +1,347 @@
+use dashmap::DashMap;
+use pyo3::prelude::*;
+use smallvec::SmallVec;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
+
+use crate::file_pattern::CompiledPattern;
+use crate::walk::{evaluate_dir, scan_walker, DirContents, ExcludedRoots, ScanResult, WalkedTree};
+
+/// Iterator over scan results as directories finish evaluating
+///
+/// Returned by ``scan_parallel`` with ``stream=True``. The walk runs on a
+/// background thread; each call to ``next`` waits, without holding the
+/// GIL, for the next result.
+#[pyclass(module = "pathvein._pathvein_rs")]
+pub struct ScanIterator {
+    results: Mutex<Receiver<ScanResult>>,
+}
+
+#[pymethods]
+impl ScanIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> Option<ScanResult> {
+        py.allow_threads(|| self.results.lock().unwrap().recv().ok())
+    }
+}
+
+/// Scan `scan.root` on a background thread, sending each match as soon
+/// as it is known
+///
+/// A directory is evaluated as soon as its own listing, and those of the
+/// subdirectories its patterns look into, have been walked. Listings are
+/// built from the walker's own entries, so each directory is read once,
+/// and dropped once nothing left to evaluate can look at them.
+pub(crate) fn scan_stream(
+    scan: StreamingScan,
+    follow_links: bool,
+    excluded: Option<Arc<ExcludedRoots>>,
+) -> ScanIterator {
+    let scan = Arc::new(scan);
+    let (sender, receiver) = channel();
+    let mut builder = scan_walker(&scan.root.to_string_lossy(), scan.max_depth, follow_links);
+    // The walker passes a directory's entries through the filter on the
+    // thread reading that directory, before it moves on to other work
+    let reader = Arc::clone(&scan);
+    builder.filter_entry(move |entry| {
+        let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+        if is_dir
+            && excluded
+                .as_ref()
+                .is_some_and(|excluded| excluded.contains(entry.path()))
+        {
+            return false;
+        }
+        reader.add_entry(entry);
+        true
+    });
+
+    thread::spawn(move || {
+        builder.build_parallel().run(|| {
+            let scan = Arc::clone(&scan);
+            let sender = sender.clone();
+            Box::new(move |entry_result| {
+                // Errors can arrive while a listing is still being read
+                let Ok(entry) = entry_result else {
+                    return ignore::WalkState::Continue;
+                };
+                // Whatever this thread was reading is complete by now
+                if scan.finish_reading(&sender).is_err() {
+                    // The iterator was dropped; nobody wants more results
+                    return ignore::WalkState::Quit;
+                }
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                if is_dir && scan.is_listed(entry.path()) {
+                    scan.start_reading(entry.path());
+                }
+                ignore::WalkState::Continue
+            })
+        });
+        // Listings still open when their threads ran out of work
+        let open: Vec<ThreadId> = scan.reading.iter().map(|entry| *entry.key()).collect();
+        for thread in open {
+            if let Some((_, (dir, listing))) = scan.reading.remove(&thread) {
+                if scan.listed(dir, listing, &sender).is_err() {
+                    return;
+                }
+            }
+        }
+        // Directories left waiting on a listing that never arrived
+        let _ = scan.evaluate_remaining(&sender);
+    });
+
+    ScanIterator {
+        results: Mutex::new(receiver),
+    }
+}
+
+/// State shared by the walker threads of one streaming scan
+pub(crate) struct StreamingScan {
+    root: PathBuf,
+    max_depth: Option<usize>,
+    first_match: bool,
+    relative_paths: bool,
+    patterns: Vec<CompiledPattern>,
+    /// Subdirectory levels the deepest pattern looks into
+    depth: usize,
+    /// Each walked directory still needed for an evaluation
+    dirs: Mutex<HashMap<PathBuf, Listed>>,
+    /// The listing each walker thread is reading
+    reading: DashMap<ThreadId, (PathBuf, DirContents)>,
+}
+
+/// A walked directory in a streaming scan
+struct Listed {
+    /// None while a walker thread is still reading it
+    contents: Option<DirContents>,
+    evaluated: bool,
+}
+
+/// The results receiver has been dropped
+struct Disconnected;
+
+impl StreamingScan {
+    pub(crate) fn new(
+        root: String,
+        patterns: Vec<CompiledPattern>,
+        max_depth: Option<usize>,
+        first_match: bool,
+        relative_paths: bool,
+    ) -> Self {
+        StreamingScan {
+            root: PathBuf::from(root),
+            max_depth,
+            first_match,
+            relative_paths,
+            depth: patterns
+                .iter()
+                .map(CompiledPattern::depth)
+                .max()
+                .unwrap_or(0),
+            patterns,
+            dirs: Mutex::new(HashMap::new()),
+            reading: DashMap::new(),
+        }
+    }
+
+    /// Start `dir`'s listing on the current thread
+    fn start_reading(&self, dir: &Path) {
+        self.dirs.lock().unwrap().insert(
+            dir.to_path_buf(),
+            Listed {
+                contents: None,
+                evaluated: false,
+            },
+        );
+        self.reading.insert(
+            thread::current().id(),
+            (dir.to_path_buf(), (SmallVec::new(), SmallVec::new())),
+        );
+    }
+
+    /// Add an entry to the listing of its parent, classified the way
+    /// scan_parallel classifies it
+    fn add_entry(&self, entry: &ignore::DirEntry) {
+        let path = entry.path();
+        let (Some(parent), Some(name), Some(file_type)) =
+            (path.parent(), path.file_name(), entry.file_type())
+        else {
+            return;
+        };
+        if let Some(mut reading) = self.reading.get_mut(&thread::current().id()) {
+            let (dir, listing) = &mut *reading;
+            if dir == parent {
+                if file_type.is_file() {
+                    listing.0.push(name.to_os_string());
+                } else if file_type.is_dir() {
+                    listing.1.push(name.to_os_string());
+                }
+            }
+        }
+    }
+
+    /// Complete the listing the current thread was reading, if any
+    fn finish_reading(&self, sender: &Sender<ScanResult>) -> Result<(), Disconnected> {
+        match self.reading.remove(&thread::current().id()) {
+            Some((_, (dir, listing))) => self.listed(dir, listing, sender),
+            None => Ok(()),
+        }
+    }
+
+    /// Record a directory's listing and evaluate every directory that was
+    /// only waiting for it
+    fn listed(
+        &self,
+        dir: PathBuf,
+        listing: DirContents,
+        sender: &Sender<ScanResult>,
+    ) -> Result<(), Disconnected> {
+        let ready = {
+            let mut dirs = self.dirs.lock().unwrap();
+            if let Some(listed) = dirs.get_mut(&dir) {
+                listed.contents = Some(listing);
+            }
+            let mut ready = Vec::new();
+            for ancestor in dir.ancestors().take(self.depth.saturating_add(1)) {
+                if !ancestor.starts_with(&self.root) {
+                    break;
+                }
+                if dirs.get(ancestor).is_some_and(|listed| !listed.evaluated)
+                    && self.is_complete(&dirs, ancestor, self.depth)
+                {
+                    ready.push(self.take_tree(&mut dirs, ancestor));
+                }
+            }
+            for (_, tree) in &ready {
+                self.evict(&mut dirs, tree.keys());
+            }
+            ready
+        };
+        for (dir, tree) in ready {
+            self.evaluate(&dir, &tree, sender)?;
+        }
+        Ok(())
+    }
+
+    /// Evaluate each directory still waiting, with the listings there are
+    fn evaluate_remaining(&self, sender: &Sender<ScanResult>) -> Result<(), Disconnected> {
+        let ready: Vec<(PathBuf, WalkedTree)> = {
+            let mut dirs = self.dirs.lock().unwrap();
+            let waiting: Vec<PathBuf> = dirs
+                .iter()
+                .filter(|(_, listed)| !listed.evaluated)
+                .map(|(dir, _)| dir.clone())
+                .collect();
+            waiting
+                .iter()
+                .map(|dir| self.take_tree(&mut dirs, dir))
+                .collect()
+        };
+        for (dir, tree) in ready {
+            self.evaluate(&dir, &tree, sender)?;
+        }
+        Ok(())
+    }
+
+    /// Whether `dir` and its subdirectories `levels` deep have been listed
+    fn is_complete(&self, dirs: &HashMap<PathBuf, Listed>, dir: &Path, levels: usize) -> bool {
+        let Some(contents) = dirs.get(dir).and_then(|listed| listed.contents.as_ref()) else {
+            return false;
+        };
+        levels == 0
+            || contents.1.iter().all(|name| {
+                let subdir = dir.join(name);
+                !self.is_listed(&subdir) || self.is_complete(dirs, &subdir, levels - 1)
+            })
+    }
+
+    /// Whether the walk will list `dir` at all, given max_depth
+    fn is_listed(&self, dir: &Path) -> bool {
+        let depth = dir
+            .strip_prefix(&self.root)
+            .map_or(0, |relative| relative.components().count());
+        self.max_depth.map_or(true, |max| depth < max)
+    }
+
+    /// Mark `dir` evaluated, with a copy of the listings a match on it
+    /// can look at
+    fn take_tree(&self, dirs: &mut HashMap<PathBuf, Listed>, dir: &Path) -> (PathBuf, WalkedTree) {
+        if let Some(listed) = dirs.get_mut(dir) {
+            listed.evaluated = true;
+        }
+        let mut tree = WalkedTree::new();
+        copy_listings(dirs, dir, self.depth, &mut tree);
+        (dir.to_path_buf(), tree)
+    }
+
+    /// Drop the listings an evaluation looked at that no evaluation still
+    /// to come can look at: those of directories evaluated along with
+    /// every ancestor `depth` levels up
+    ///
+    /// A directory is walked before anything below it, so an ancestor
+    /// missing from `dirs` was itself dropped once evaluated.
+    fn evict<'a>(
+        &self,
+        dirs: &mut HashMap<PathBuf, Listed>,
+        looked_at: impl Iterator<Item = &'a PathBuf>,
+    ) {
+        for dir in looked_at {
+            let done = dir
+                .ancestors()
+                .take(self.depth.saturating_add(1))
+                .take_while(|ancestor| ancestor.starts_with(&self.root))
+                .all(|ancestor| dirs.get(ancestor).map_or(true, |listed| listed.evaluated));
+            if done {
+                dirs.remove(dir);
+            }
+        }
+    }
+
+    /// Match one directory against every pattern and send its results
+    fn evaluate(
+        &self,
+        dir: &Path,
+        tree: &WalkedTree,
+        sender: &Sender<ScanResult>,
+    ) -> Result<(), Disconnected> {
+        let path = match dir.strip_prefix(&self.root) {
+            Ok(relative) if self.relative_paths && relative.as_os_str().is_empty() => {
+                ".".to_string()
+            }
+            Ok(relative) if self.relative_paths => relative.to_string_lossy().into_owned(),
+            _ => dir.to_string_lossy().into_owned(),
+        };
+        let root = self.root.to_string_lossy().into_owned();
+        for (pattern_idx, found) in evaluate_dir(dir, tree, &self.patterns, self.first_match) {
+            let pattern = &self.patterns[pattern_idx];
+            let result = ScanResult::new(path.clone(), root.clone(), pattern_idx, pattern, found);
+            sender.send(result).map_err(|_| Disconnected)?;
+        }
+        Ok(())
+    }
+}
+
+/// Copy the finished listings of `dir` and its subdirectories `levels`
+/// deep
+fn copy_listings(
+    dirs: &HashMap<PathBuf, Listed>,
+    dir: &Path,
+    levels: usize,
+    tree: &mut WalkedTree,
+) {
+    let Some(listing) = dirs.get(dir).and_then(|listed| listed.contents.clone()) else {
+        return;
+    };
+    if levels > 0 {
+        for name in &listing.1 {
+            copy_listings(dirs, &dir.join(name), levels - 1, tree);
+        }
+    }
+    tree.insert(dir.to_path_buf(), listing);
+}
@@ -0,0 +1,179 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::walk::ScanResult;
+
+/// Where a shuffled directory goes under the destination, laid out from
+/// what its match found, e.g. ``{pattern_name}/{capture[0]}/{source_name}``
+#[derive(Clone, Debug)]
+pub struct DestinationTemplate {
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Text(String),
+    Field(Field),
+}
+
+#[derive(Clone, Debug)]
+enum Field {
+    /// Name of the matched directory
+    SourceName,
+    PatternName,
+    PatternIndex,
+    BranchName,
+    /// A ``directory_name`` wildcard's text, by index
+    Capture(usize),
+    /// Name of the first path bound to a role
+    Binding(String),
+}
+
+impl Field {
+    fn parse(field: &str) -> Result<Self, String> {
+        let indexed = |prefix: &str| {
+            field
+                .strip_prefix(prefix)
+                .and_then(|rest| rest.strip_prefix('['))
+                .and_then(|rest| rest.strip_suffix(']'))
+        };
+        match field {
+            "source_name" => Ok(Field::SourceName),
+            "pattern_name" => Ok(Field::PatternName),
+            "pattern_index" => Ok(Field::PatternIndex),
+            "branch_name" => Ok(Field::BranchName),
+            _ => {
+                if let Some(index) = indexed("capture") {
+                    return index
+                        .parse()
+                        .map(Field::Capture)
+                        .map_err(|_| format!("capture index '{}' isn't a number", index));
+                }
+                if let Some(role) = indexed("binding").filter(|role| !role.is_empty()) {
+                    return Ok(Field::Binding(role.to_string()));
+                }
+                Err(format!(
+                    "unknown field '{{{}}}': expected source_name, pattern_name, pattern_index, branch_name, capture[N] or binding[role]",
+                    field
+                ))
+            }
+        }
+    }
+
+    /// The field's value for a match, None if it has none
+    fn value(&self, source: &Path, result: Option<&ScanResult>) -> Option<String> {
+        let name = |path: &Path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        };
+        match self {
+            Field::SourceName => name(source),
+            Field::PatternName => result?.pattern_name.clone(),
+            Field::PatternIndex => Some(result?.pattern_index.to_string()),
+            Field::BranchName => result?.branch_name.clone(),
+            Field::Capture(index) => result?.captures.get(*index).cloned(),
+            Field::Binding(role) => name(Path::new(result?.bindings.get(role)?.first()?)),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Field::SourceName => "{source_name}".to_string(),
+            Field::PatternName => "{pattern_name}".to_string(),
+            Field::PatternIndex => "{pattern_index}".to_string(),
+            Field::BranchName => "{branch_name}".to_string(),
+            Field::Capture(index) => format!("{{capture[{}]}}", index),
+            Field::Binding(role) => format!("{{binding[{}]}}", role),
+        }
+    }
+}
+
+impl DestinationTemplate {
+    /// Parse a template of text and ``{field}``s, with ``{{`` and ``}}``
+    /// for literal braces
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        field.push(c);
+                    }
+                    if !closed {
+                        return Err(format!("unclosed '{{{}'", field));
+                    }
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(Segment::Field(Field::parse(field.trim())?));
+                }
+                '}' => return Err("unmatched '}': write '}}' for a literal brace".to_string()),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+        if segments.is_empty() {
+            return Err("template is empty".to_string());
+        }
+        Ok(DestinationTemplate { segments })
+    }
+
+    /// The relative path a matched directory goes to
+    ///
+    /// Fails if a field has no value for the match, a value holds a path
+    /// separator, or the path would leave the destination.
+    pub fn render(&self, source: &Path, result: Option<&ScanResult>) -> Result<PathBuf, String> {
+        let mut rendered = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => rendered.push_str(text),
+                Segment::Field(field) => {
+                    let value = field
+                        .value(source, result)
+                        .filter(|value| !value.is_empty())
+                        .ok_or_else(|| {
+                            format!("{} has no value for {}", field.describe(), source.display())
+                        })?;
+                    if value.contains(['/', '\\']) {
+                        return Err(format!(
+                            "{} is '{}' for {}, which isn't a single name",
+                            field.describe(),
+                            value,
+                            source.display()
+                        ));
+                    }
+                    rendered.push_str(&value);
+                }
+            }
+        }
+        let path = PathBuf::from(&rendered);
+        let inside = path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !inside || path.file_name().is_none() {
+            return Err(format!(
+                "'{}' for {} isn't a path inside the destination",
+                rendered,
+                source.display()
+            ));
+        }
+        Ok(path)
+    }
+}
@@ -0,0 +1,288 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::Serialize;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How sources are removed once shuffled
+#[derive(Clone, Debug)]
+pub enum Removal {
+    /// Delete them outright
+    Unlink,
+    /// Move them to the platform's trash
+    Trash,
+    /// Move them into a directory of our own, noting when they may go
+    Quarantine {
+        dir: PathBuf,
+        /// How long they're kept, None for no limit
+        keep: Option<Duration>,
+    },
+}
+
+/// Sidecar written beside each quarantined directory
+#[derive(Serialize)]
+struct QuarantineRecord {
+    /// Where the directory was
+    original: String,
+    /// When it was quarantined, in seconds since the epoch
+    quarantined: f64,
+    /// When it may be deleted, in seconds since the epoch
+    expires: Option<f64>,
+}
+
+impl Removal {
+    /// Parse ``delete``, ``trash_dir`` and ``trash_days`` arguments
+    pub fn parse(
+        delete: &str,
+        trash_dir: Option<PathBuf>,
+        trash_days: Option<f64>,
+    ) -> PyResult<Self> {
+        match delete {
+            "unlink" => {
+                if trash_dir.is_some() || trash_days.is_some() {
+                    return Err(PyValueError::new_err(
+                        "trash_dir and trash_days need delete='trash'",
+                    ));
+                }
+                Ok(Removal::Unlink)
+            }
+            "trash" => {
+                let keep = match trash_days {
+                    Some(days) if !(days.is_finite() && days > 0.0) => {
+                        return Err(PyValueError::new_err("trash_days must be positive"))
+                    }
+                    Some(days) => Some(Duration::from_secs_f64(days * 86_400.0)),
+                    None => None,
+                };
+                match trash_dir {
+                    Some(dir) => Ok(Removal::Quarantine { dir, keep }),
+                    None if keep.is_some() => Err(PyValueError::new_err(
+                        "trash_days needs trash_dir: the platform trash keeps its own time",
+                    )),
+                    None => Ok(Removal::Trash),
+                }
+            }
+            other => Err(PyValueError::new_err(format!(
+                "Unknown delete '{}': expected 'unlink' or 'trash'",
+                other
+            ))),
+        }
+    }
+
+    /// Remove a directory; returns where it went, None if it was deleted
+    pub fn remove(&self, path: &Path) -> io::Result<Option<PathBuf>> {
+        match self {
+            Removal::Unlink => fs::remove_dir_all(path).map(|()| None),
+            Removal::Trash => platform_trash(path).map(Some),
+            Removal::Quarantine { dir, keep } => quarantine(path, dir, *keep).map(Some),
+        }
+    }
+}
+
+/// Absolute path of `path`, resolving its parent but not `path` itself
+fn absolute(path: &Path) -> io::Result<PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no name"))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Ok(fs::canonicalize(parent)?.join(name))
+}
+
+/// ``<name>``, ``<name>-1``, ``<name>-2`` and so on
+fn candidates(name: &OsStr) -> impl Iterator<Item = OsString> + '_ {
+    (0..).map(move |n| {
+        let mut candidate = name.to_os_string();
+        if n > 0 {
+            candidate.push(format!("-{}", n));
+        }
+        candidate
+    })
+}
+
+/// Claim the first free ``<name>``, ``<name>-1``, … in `files` by creating
+/// its record, named by `record_path`, with `create_new`; returns the
+/// entry's path, the record's path and the open record
+fn reserve(
+    files: &Path,
+    name: &OsStr,
+    record_path: impl Fn(&OsString) -> PathBuf,
+) -> io::Result<(PathBuf, PathBuf, File)> {
+    for candidate in candidates(name) {
+        let entry = files.join(&candidate);
+        if fs::symlink_metadata(&entry).is_ok() {
+            continue;
+        }
+        let record = record_path(&candidate);
+        match File::options().write(true).create_new(true).open(&record) {
+            Ok(file) => return Ok((entry, record, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("names run out only after usize::MAX tries")
+}
+
+/// Move `path` into `dir`, with a ``<name>.quarantine.json`` record
+fn quarantine(path: &Path, dir: &Path, keep: Option<Duration>) -> io::Result<PathBuf> {
+    let original = absolute(path)?;
+    fs::create_dir_all(dir)?;
+    let name = original.file_name().expect("absolute paths have names");
+    let (entry, record_path, mut record) = reserve(dir, name, |candidate| {
+        let mut record = candidate.clone();
+        record.push(".quarantine.json");
+        dir.join(record)
+    })?;
+    let now = SystemTime::now();
+    let seconds = |time: SystemTime| {
+        time.duration_since(UNIX_EPOCH)
+            .map_or(0.0, |since| since.as_secs_f64())
+    };
+    let written = serde_json::to_writer_pretty(
+        &mut record,
+        &QuarantineRecord {
+            original: original.to_string_lossy().into_owned(),
+            quarantined: seconds(now),
+            expires: keep.map(|keep| seconds(now + keep)),
+        },
+    )
+    .map_err(io::Error::from)
+    .and_then(|()| record.write_all(b"\n"))
+    .and_then(|()| fs::rename(path, &entry));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&record_path);
+        return Err(e);
+    }
+    Ok(entry)
+}
+
+/// Move `path` to the Trash in the user's home
+#[cfg(target_os = "macos")]
+fn platform_trash(path: &Path) -> io::Result<PathBuf> {
+    let home = std::env::var_os("HOME")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME isn't set"))?;
+    let trash = Path::new(&home).join(".Trash");
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no name"))?;
+    for candidate in candidates(name) {
+        let entry = trash.join(candidate);
+        if fs::symlink_metadata(&entry).is_ok() {
+            continue;
+        }
+        return fs::rename(path, &entry).map(|()| entry);
+    }
+    unreachable!("names run out only after usize::MAX tries")
+}
+
+/// Move `path` to the trash the freedesktop.org specification gives it:
+/// the home trash when it's on the same filesystem, otherwise
+/// ``.Trash-<uid>`` at the top of its own
+#[cfg(all(unix, not(target_os = "macos")))]
+fn platform_trash(path: &Path) -> io::Result<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    let original = absolute(path)?;
+    let device = fs::symlink_metadata(&original)?.dev();
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")));
+    let home_trash = data_home.map(|dir| dir.join("Trash")).filter(|trash| {
+        trash
+            .ancestors()
+            .find_map(|dir| fs::metadata(dir).ok())
+            .is_some_and(|metadata| metadata.dev() == device)
+    });
+    // Paths in the home trash are absolute, and in a top directory's
+    // trash relative to the top directory
+    let (trash, recorded) = match home_trash {
+        Some(trash) => (trash, original.clone()),
+        None => {
+            let top = original
+                .ancestors()
+                .skip(1)
+                .take_while(|dir| fs::metadata(dir).is_ok_and(|metadata| metadata.dev() == device))
+                .last()
+                .unwrap_or(Path::new("/"));
+            // SAFETY: getuid can't fail
+            let uid = unsafe { libc::getuid() };
+            let recorded = original
+                .strip_prefix(top)
+                .unwrap_or(&original)
+                .to_path_buf();
+            (top.join(format!(".Trash-{}", uid)), recorded)
+        }
+    };
+    let (files, info) = (trash.join("files"), trash.join("info"));
+    for dir in [&files, &info] {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+    }
+
+    let name = original.file_name().expect("absolute paths have names");
+    let (entry, info_path, mut info_file) = reserve(&files, name, |candidate| {
+        let mut info_name = candidate.clone();
+        info_name.push(".trashinfo");
+        info.join(info_name)
+    })?;
+    let mut encoded = String::new();
+    for &byte in recorded.as_os_str().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    let written = write!(
+        info_file,
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        encoded,
+        local_timestamp()
+    )
+    .and_then(|()| fs::rename(path, &entry));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&info_path);
+        return Err(e);
+    }
+    Ok(entry)
+}
+
+#[cfg(not(unix))]
+fn platform_trash(_path: &Path) -> io::Result<PathBuf> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "no platform trash on this system: pass trash_dir",
+    ))
+}
+
+/// The local time as ``YYYY-MM-DDThh:mm:ss``
+#[cfg(all(unix, not(target_os = "macos")))]
+fn local_timestamp() -> String {
+    // SAFETY: `time` accepts a null pointer, and `localtime_r` only writes
+    // to the `tm` it's given
+    let tm = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        tm
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
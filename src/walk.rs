@@ -1,14 +1,48 @@
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use ignore::WalkBuilder;
+use pyo3::exceptions::{PyIndexError, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::{PyDict, PySlice};
+use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::OsString;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::archive::open_archive;
+use crate::errors::{pattern_syntax_error, walk_error};
+use crate::events::{EventSink, ScanEvent};
+use crate::explain::MatchFailure;
+use crate::file_pattern::{CompiledPattern, DirectoryTree, FileStructurePattern, StructureMatch};
+use crate::inherit;
+use crate::pattern::{MatcherOptions, PatternMatcher};
+use crate::progress::ProgressReporter;
+use crate::remote::{is_store_url, list_store};
+use crate::snapshot::{is_snapshot_file, SnapshotData, SnapshotTree};
+use crate::stats::{PatternStats, ScanProfile, ScanStats, ScanSummary};
+use crate::stream::{scan_stream, ScanIterator, StreamingScan};
 
 /// Type alias for directory contents: (filenames, dirnames)
 /// Uses OsString to avoid UTF-8 conversion overhead during parallel collection
-type DirContents = (SmallVec<[OsString; 32]>, SmallVec<[OsString; 8]>);
+pub(crate) type DirContents = (SmallVec<[OsString; 32]>, SmallVec<[OsString; 8]>);
+
+/// Walk results keyed by directory, for recursive pattern matching
+pub(crate) type WalkedTree = HashMap<PathBuf, DirContents>;
+
+impl DirectoryTree for WalkedTree {
+    fn children(&self, dir: &Path) -> (&[OsString], &[OsString]) {
+        match self.get(dir) {
+            Some((files, dirs)) => (dirs, files),
+            None => (&[], &[]),
+        }
+    }
+}
 
 /// Directory entry returned from walk
 #[pyclass]
@@ -43,29 +77,73 @@ impl DirEntry {
 ///     path: Root directory to walk
 ///     max_depth: Optional maximum depth to traverse (None = unlimited)
 ///     follow_links: Whether to follow symbolic links (default: false)
+///     exclude: Optional gitignore-style globs, relative to the root, for
+///         entries to skip entirely; a trailing ``/`` (e.g. ``build/``)
+///         only excludes directories
+///     archives: Treat ``.zip``, ``.tar``, ``.tar.gz``/``.tgz`` and
+///         ``.tar.zst``/``.tzst`` files as directories, listing what's
+///         inside them from their index without extracting anything, at
+///         paths like ``data.zip/images``. Compressed tars are streamed
+///         through to read their headers. An archive that can't be read
+///         stays a file (default: False)
+///     storage_options: Settings for an object store ``path``, such as
+///         ``{"aws_region": "us-east-1"}``, over those from ``AWS_*``,
+///         ``GOOGLE_*`` and ``AZURE_*`` environment variables
+///         (default: None)
+///
+/// ``path`` can also name a bucket or container, with an optional key
+/// prefix - ``s3://bucket/prefix``, ``gs://bucket`` or
+/// ``az://container/prefix`` - when pathvein is built with the
+/// ``object-store`` feature. Every key under the prefix is listed, and
+/// each ``/`` in a key makes a directory, so the same patterns match
+/// buckets and local trees alike. ``follow_links`` and ``archives``
+/// don't apply to buckets.
 ///
 /// Returns:
 ///     List of DirEntry objects, each containing (path, dirnames, filenames)
+///
+/// Raises:
+///     ValueError: If any exclude pattern is invalid
+///     WalkError: If an object store can't be listed
 #[pyfunction]
-#[pyo3(signature = (path, max_depth=None, follow_links=false))]
+#[pyo3(signature = (
+    path,
+    max_depth=None,
+    follow_links=false,
+    exclude=None,
+    archives=false,
+    storage_options=None,
+))]
 pub fn walk_parallel(
+    py: Python<'_>,
     path: String,
     max_depth: Option<usize>,
     follow_links: bool,
+    exclude: Option<Vec<String>>,
+    archives: bool,
+    storage_options: Option<HashMap<String, String>>,
 ) -> PyResult<Vec<DirEntry>> {
-    // Build parallel walker (same as ripgrep uses)
-    let mut builder = WalkBuilder::new(&path);
-
-    if let Some(depth) = max_depth {
-        builder.max_depth(Some(depth));
+    if is_store_url(&path) {
+        let options = storage_options.unwrap_or_default();
+        let listings = py
+            .allow_threads(|| list_store(&path, &options))
+            .map_err(|e| walk_error(Path::new(&path), e))?;
+        let matcher = exclude_matcher(exclude)?;
+        return Ok(store_entries(
+            Path::new(&path),
+            listings,
+            max_depth,
+            matcher.as_ref(),
+        ));
     }
 
-    builder.follow_links(follow_links);
-    builder.hidden(false); // Don't skip hidden files
-    builder.ignore(false); // Don't use .gitignore
-    builder.git_ignore(false); // Don't use .gitignore
-    builder.git_global(false); // Don't use global .gitignore
-    builder.git_exclude(false); // Don't use .git/info/exclude
+    // Build parallel walker (same as ripgrep uses)
+    let mut builder = scan_walker(&path, max_depth, follow_links);
+    if let Some(matcher) = exclude_matcher(exclude)? {
+        let matcher = Arc::new(matcher);
+        let root = PathBuf::from(&path);
+        builder.filter_entry(move |entry| !is_excluded(&matcher, &root, entry));
+    }
 
     // Collect all entries grouped by directory (using DashMap for lock-free concurrency)
     // Use PathBuf as key to avoid String allocation during walk
@@ -76,23 +154,15 @@ pub fn walk_parallel(
         let dir_contents = Arc::clone(&dir_contents);
         Box::new(move |entry_result| {
             if let Ok(dir_entry) = entry_result {
-                let path = dir_entry.path();
-
-                // Get parent directory and filename
-                if let (Some(parent), Some(name)) = (path.parent(), path.file_name()) {
-                    if let Some(file_type) = dir_entry.file_type() {
-                        // DashMap handles locking internally with sharding
-                        let mut entry = dir_contents
-                            .entry(parent.to_path_buf())
-                            .or_insert((SmallVec::new(), SmallVec::new()));
-
-                        // Use OsString - no UTF-8 validation needed during walk
-                        if file_type.is_file() {
-                            entry.0.push(name.to_os_string());
-                        } else if file_type.is_dir() {
-                            entry.1.push(name.to_os_string());
-                        }
-                    }
+                let is_file = dir_entry.file_type().is_some_and(|t| t.is_file());
+                let opened = (archives && is_file)
+                    .then(|| open_archive(dir_entry.path(), dir_entry.depth(), max_depth))
+                    .flatten()
+                    .and_then(Result::ok);
+                // OsString names - no UTF-8 validation needed during walk
+                list_entry(&dir_contents, &dir_entry, opened.is_some());
+                for (dir, listing) in opened.into_iter().flatten() {
+                    dir_contents.insert(dir, listing);
                 }
             }
             ignore::WalkState::Continue
@@ -123,23 +193,358 @@ pub fn walk_parallel(
     Ok(results)
 }
 
+/// DirEntries for an object store's listings, limited and excluded as a
+/// walk of a local tree would be
+fn store_entries(
+    root: &Path,
+    listings: Vec<(PathBuf, usize, DirContents)>,
+    max_depth: Option<usize>,
+    matcher: Option<&PatternMatcher>,
+) -> Vec<DirEntry> {
+    let excluded = |path: &Path, is_dir: bool| {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        !relative.as_os_str().is_empty()
+            && matcher.is_some_and(|matcher| matcher.is_match_path(relative, is_dir))
+    };
+    let names = |dir: &Path, names: &[OsString], is_dir: bool| -> Vec<String> {
+        names
+            .iter()
+            .filter(|name| !excluded(&dir.join(name), is_dir))
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect()
+    };
+    listings
+        .into_iter()
+        .filter(|(dir, depth, _)| {
+            max_depth.map_or(true, |max| *depth < max)
+                && !dir.ancestors().any(|dir| excluded(dir, true))
+        })
+        .map(|(dir, _, (files, dirs))| DirEntry {
+            path: dir.to_string_lossy().into_owned(),
+            dirnames: names(&dir, &dirs, true),
+            filenames: names(&dir, &files, false),
+        })
+        .collect()
+}
+
+/// The ``threads`` argument, defaulting to one per CPU
+pub(crate) fn thread_count(threads: Option<usize>) -> PyResult<usize> {
+    match threads {
+        Some(0) => Err(PyValueError::new_err("threads must be at least 1")),
+        Some(threads) => Ok(threads),
+        None => Ok(thread::available_parallelism().map_or(1, |n| n.get())),
+    }
+}
+
+/// Matcher for root-relative ``exclude`` globs
+pub(crate) fn exclude_matcher(exclude: Option<Vec<String>>) -> PyResult<Option<PatternMatcher>> {
+    exclude
+        .map(|patterns| {
+            PatternMatcher::with_options(
+                patterns,
+                MatcherOptions {
+                    full_path: true,
+                    ..MatcherOptions::default()
+                },
+            )
+        })
+        .transpose()
+}
+
+/// Check a walked entry against root-relative exclude patterns
+pub(crate) fn is_excluded(matcher: &PatternMatcher, root: &Path, entry: &ignore::DirEntry) -> bool {
+    let relative = match entry.path().strip_prefix(root) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative,
+        // Never exclude the root itself
+        _ => return false,
+    };
+    let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+    matcher.is_match_path(relative, is_dir)
+}
+
+/// An entry a walk couldn't list or read
+///
+/// Reported in the ``errors`` of tree hashes, snapshots, grep results and
+/// detected types and encodings, sorted by path.
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Failure {
+    /// The entry's path, or ``""`` if the error doesn't name one
+    #[pyo3(get)]
+    pub path: String,
+    /// Why it couldn't be listed or read
+    #[pyo3(get)]
+    pub reason: String,
+}
+
+#[pymethods]
+impl Failure {
+    fn __repr__(&self) -> String {
+        format!("Failure(path='{}', reason='{}')", self.path, self.reason)
+    }
+
+    fn __str__(&self) -> String {
+        self.to_string()
+    }
+
+    fn __hash__(&self) -> u64 {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+        let mut hasher = DefaultHasher::new();
+        self.path.hash(&mut hasher);
+        self.reason.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.reason)
+    }
+}
+
+impl Failure {
+    pub(crate) fn new(path: &Path, reason: impl Display) -> Self {
+        Failure {
+            path: path.to_string_lossy().into_owned(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// The entry a walk error is about, and the error without its path
+    pub(crate) fn walk(error: &ignore::Error) -> Self {
+        Failure {
+            path: error_path(error)
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            reason: error_reason(error),
+        }
+    }
+}
+
+/// The path a walk error is about, if it names one
+pub(crate) fn error_path(error: &ignore::Error) -> Option<&Path> {
+    match error {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::Loop { child, .. } => Some(child),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            error_path(err)
+        }
+        _ => None,
+    }
+}
+
+/// A walk error's message, less the path and depth wrapped around it
+fn error_reason(error: &ignore::Error) -> String {
+    match error {
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => error_reason(err),
+        _ => error.to_string(),
+    }
+}
+
+/// Results and failures gathered from many threads at once
+pub(crate) struct Collector<T> {
+    found: Mutex<Vec<T>>,
+    failures: Mutex<Vec<Failure>>,
+}
+
+impl<T> Collector<T> {
+    pub(crate) fn new() -> Self {
+        Collector {
+            found: Mutex::new(Vec::new()),
+            failures: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn push(&self, item: T) {
+        self.found.lock().expect("found poisoned").push(item);
+    }
+
+    /// Record that `path` couldn't be listed or read
+    pub(crate) fn fail(&self, path: &Path, reason: impl Display) {
+        self.record(Failure::new(path, reason));
+    }
+
+    /// Record an error from the walker
+    pub(crate) fn walk_error(&self, error: &ignore::Error) {
+        self.record(Failure::walk(error));
+    }
+
+    fn record(&self, failure: Failure) {
+        self.failures
+            .lock()
+            .expect("failures poisoned")
+            .push(failure);
+    }
+
+    /// Everything gathered, in the order it arrived
+    pub(crate) fn into_parts(self) -> (Vec<T>, Vec<Failure>) {
+        (
+            self.found.into_inner().expect("found poisoned"),
+            self.failures.into_inner().expect("failures poisoned"),
+        )
+    }
+
+    /// Everything gathered plus `more_failures`, with the results sorted
+    /// by `key` and the failures sorted
+    pub(crate) fn finish<K: Ord + ?Sized>(
+        self,
+        more_failures: Vec<Failure>,
+        key: impl Fn(&T) -> &K,
+    ) -> (Vec<T>, Vec<Failure>) {
+        let (mut found, mut failures) = self.into_parts();
+        failures.extend(more_failures);
+        found.sort_by(|a, b| key(a).cmp(key(b)));
+        failures.sort();
+        (found, failures)
+    }
+}
+
 /// Scan result - a directory that matched a pattern
-#[pyclass]
-#[derive(Clone, Debug)]
+///
+/// Deserializes from the objects ``export_results`` writes, less their
+/// ``failures``.
+#[pyclass(module = "pathvein._pathvein_rs")]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ScanResult {
     #[pyo3(get)]
     pub path: String,
+    /// Scan root the directory was found under, as given to the scan
+    #[pyo3(get)]
+    #[serde(default)]
+    pub root: String,
     #[pyo3(get)]
     pub pattern_index: usize,
+    /// `pattern_name` of the matching pattern, if it has one
+    #[pyo3(get)]
+    pub pattern_name: Option<String>,
+    /// Each required or optional file glob mapped to the names that
+    /// satisfied it
+    #[pyo3(get)]
+    #[serde(default)]
+    pub matched_files: BTreeMap<String, Vec<String>>,
+    /// Optional file globs of the pattern that were present
+    #[pyo3(get)]
+    #[serde(default)]
+    pub optional_files: Vec<String>,
+    /// Directory names of the pattern's optional sub-patterns that were present
+    #[pyo3(get)]
+    #[serde(default)]
+    pub optional_directories: Vec<String>,
+    /// Index into the pattern's ``any_of`` of the first branch that matched
+    #[pyo3(get)]
+    pub branch: Option<usize>,
+    /// ``pattern_name`` of that branch, if it has one
+    #[pyo3(get)]
+    pub branch_name: Option<String>,
+    /// Fraction of the pattern's requirements the directory satisfies:
+    /// 1.0 for a match, less for results kept by a scan's ``min_score``
+    #[pyo3(get)]
+    #[serde(default = "full_score")]
+    pub score: f64,
+    /// For partial results, the requirements the directory fails
+    #[pyo3(get)]
+    #[serde(skip_deserializing)]
+    pub failures: Vec<MatchFailure>,
+    /// Other paths the same physical directory matched under, with a
+    /// scan's ``dedupe_physical``
+    #[pyo3(get)]
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Indices of the other patterns that matched, highest priority
+    /// first, with a scan's ``best_match`` and ``runners_up``
+    #[pyo3(get)]
+    #[serde(default)]
+    pub runners_up: Vec<usize>,
+    /// Each of the pattern's ``roles`` mapped to the paths that satisfied
+    /// its requirement
+    #[pyo3(get)]
+    #[serde(default)]
+    pub bindings: BTreeMap<String, Vec<String>>,
+    /// Text matched by each wildcard of the pattern's ``directory_name``,
+    /// in order, e.g. ``["042", "2024"]`` for ``run_042_2024`` and
+    /// ``run_*_*``; the groups of a ``regex:`` name
+    #[pyo3(get)]
+    #[serde(default)]
+    pub captures: Vec<String>,
+}
+
+fn full_score() -> f64 {
+    1.0
+}
+
+impl ScanResult {
+    pub(crate) fn new(
+        path: String,
+        root: String,
+        pattern_index: usize,
+        pattern: &CompiledPattern,
+        found: StructureMatch,
+    ) -> Self {
+        ScanResult {
+            path,
+            root,
+            pattern_index,
+            pattern_name: pattern.pattern_name.clone(),
+            matched_files: found.matched_files.into_iter().collect(),
+            optional_files: found.optional_files,
+            optional_directories: found.optional_directories,
+            branch: found.branch,
+            branch_name: found
+                .branch
+                .and_then(|idx| pattern.any_of[idx].pattern_name.clone()),
+            score: 1.0,
+            failures: Vec::new(),
+            aliases: Vec::new(),
+            runners_up: Vec::new(),
+            bindings: found.bindings.into_iter().collect(),
+            captures: found.captures,
+        }
+    }
+
+    /// A directory that satisfies only part of the pattern
+    pub(crate) fn partial(
+        path: String,
+        root: String,
+        pattern_index: usize,
+        pattern: &CompiledPattern,
+        score: f64,
+        failures: Vec<MatchFailure>,
+    ) -> Self {
+        ScanResult {
+            score,
+            failures,
+            ..ScanResult::new(
+                path,
+                root,
+                pattern_index,
+                pattern,
+                StructureMatch::default(),
+            )
+        }
+    }
 }
 
 #[pymethods]
 impl ScanResult {
     fn __repr__(&self) -> String {
-        format!(
-            "ScanResult(path='{}', pattern_index={})",
+        let mut repr = format!(
+            "ScanResult(path='{}', pattern_index={}",
             self.path, self.pattern_index
-        )
+        );
+        if let Some(name) = &self.pattern_name {
+            repr += &format!(", pattern_name='{}'", name);
+        }
+        if self.score < 1.0 {
+            repr += &format!(", score={:.3}", self.score);
+        }
+        repr + ")"
     }
 
     fn __hash__(&self) -> u64 {
@@ -147,149 +552,1533 @@ impl ScanResult {
         use std::hash::{Hash, Hasher};
         let mut hasher = DefaultHasher::new();
         self.path.hash(&mut hasher);
+        self.root.hash(&mut hasher);
         self.pattern_index.hash(&mut hasher);
         hasher.finish()
     }
 
     fn __eq__(&self, other: &Self) -> bool {
-        self.path == other.path && self.pattern_index == other.pattern_index
+        self.path == other.path
+            && self.root == other.root
+            && self.pattern_index == other.pattern_index
+    }
+
+    /// Every field as a dict, e.g. for JSON encoding or assertions
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("path", &self.path)?;
+        dict.set_item("root", &self.root)?;
+        dict.set_item("pattern_index", self.pattern_index)?;
+        dict.set_item("pattern_name", &self.pattern_name)?;
+        dict.set_item("matched_files", &self.matched_files)?;
+        dict.set_item("optional_files", &self.optional_files)?;
+        dict.set_item("optional_directories", &self.optional_directories)?;
+        dict.set_item("branch", self.branch)?;
+        dict.set_item("branch_name", &self.branch_name)?;
+        dict.set_item("score", self.score)?;
+        dict.set_item("failures", self.failures.clone())?;
+        dict.set_item("aliases", &self.aliases)?;
+        dict.set_item("runners_up", &self.runners_up)?;
+        dict.set_item("bindings", &self.bindings)?;
+        dict.set_item("captures", &self.captures)?;
+        Ok(dict)
+    }
+}
+
+/// Results of a scan, as an immutable sequence of ScanResult
+///
+/// Results stay in Rust until accessed: ``len``, ``in`` and comparisons
+/// don't create Python objects, and indexing or iterating creates one
+/// per result visited.
+#[pyclass(module = "pathvein._pathvein_rs", sequence, frozen)]
+pub struct ScanResults {
+    pub(crate) results: Vec<ScanResult>,
+    /// Roots the scan was given, in order; with ``relative_paths``, result
+    /// paths are relative to their ``ScanResult.root``
+    #[pyo3(get)]
+    pub(crate) roots: Vec<String>,
+    /// Name of each pattern scanned for, in pattern order
+    pattern_names: Vec<Option<String>>,
+    /// Where the scan's time went, for a scan with ``profile``; None for
+    /// slices and groups
+    #[pyo3(get)]
+    pub(crate) profile: Option<ScanProfile>,
+}
+
+impl ScanResults {
+    pub(crate) fn new(
+        results: Vec<ScanResult>,
+        roots: Vec<String>,
+        patterns: &[CompiledPattern],
+    ) -> Self {
+        ScanResults {
+            results,
+            roots,
+            pattern_names: patterns
+                .iter()
+                .map(|pattern| pattern.pattern_name.clone())
+                .collect(),
+            profile: None,
+        }
+    }
+
+    /// Put the results in an order that doesn't depend on thread
+    /// scheduling or listing order: by path, then pattern name and index,
+    /// with the names, aliases, bound paths and failures within each
+    /// result sorted too
+    pub(crate) fn sort(&mut self) {
+        for result in &mut self.results {
+            for names in result.matched_files.values_mut() {
+                names.sort();
+            }
+            result.optional_files.sort();
+            result.optional_directories.sort();
+            result.aliases.sort();
+            for paths in result.bindings.values_mut() {
+                paths.sort();
+            }
+            result.failures.sort_by(|a, b| {
+                (&a.path, a.kind, &a.requirement, &a.message).cmp(&(
+                    &b.path,
+                    b.kind,
+                    &b.requirement,
+                    &b.message,
+                ))
+            });
+        }
+        self.results.sort_by(|a, b| {
+            (&a.path, &a.pattern_name, a.pattern_index, &a.root).cmp(&(
+                &b.path,
+                &b.pattern_name,
+                b.pattern_index,
+                &b.root,
+            ))
+        });
+    }
+}
+
+#[pymethods]
+impl ScanResults {
+    fn __len__(&self) -> usize {
+        self.results.len()
+    }
+
+    /// A result by index, or a ScanResults for a slice
+    fn __getitem__(&self, py: Python<'_>, index: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        if let Ok(slice) = index.downcast::<PySlice>() {
+            let indices = slice.indices(self.results.len() as isize)?;
+            let results = (0..indices.slicelength)
+                .map(|i| self.results[(indices.start + i as isize * indices.step) as usize].clone())
+                .collect();
+            let sliced = ScanResults {
+                results,
+                roots: self.roots.clone(),
+                pattern_names: self.pattern_names.clone(),
+                profile: None,
+            };
+            return Ok(sliced.into_pyobject(py)?.into_any().unbind());
+        }
+        let len = self.results.len() as isize;
+        let position: isize = index.extract()?;
+        let resolved = if position < 0 {
+            position + len
+        } else {
+            position
+        };
+        if !(0..len).contains(&resolved) {
+            return Err(PyIndexError::new_err("ScanResults index out of range"));
+        }
+        Ok(self.results[resolved as usize]
+            .clone()
+            .into_pyobject(py)?
+            .into_any()
+            .unbind())
+    }
+
+    fn __iter__(slf: Py<Self>) -> ScanResultsIterator {
+        ScanResultsIterator {
+            results: slf,
+            position: 0,
+        }
+    }
+
+    /// Whether a result for the same path, root and pattern is present
+    fn __contains__(&self, result: PyRef<'_, ScanResult>) -> bool {
+        self.results.iter().any(|r| r.__eq__(&result))
+    }
+
+    /// Equal to another ScanResults, or a list of ScanResult, with the same
+    /// results in the same order
+    fn __eq__(&self, other: &Bound<'_, PyAny>) -> bool {
+        if let Ok(other) = other.downcast::<ScanResults>() {
+            return same_results(&self.results, other.get().results.iter());
+        }
+        match other.extract::<Vec<PyRef<'_, ScanResult>>>() {
+            Ok(other) => same_results(&self.results, other.iter().map(|r| &**r)),
+            Err(_) => false,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ScanResults({} results)", self.results.len())
+    }
+
+    /// The results as a list of ScanResult
+    fn to_list(&self) -> Vec<ScanResult> {
+        self.results.clone()
+    }
+
+    /// Paths of the matched directories, in result order
+    fn paths(&self) -> Vec<String> {
+        self.results.iter().map(|r| r.path.clone()).collect()
+    }
+
+    /// The results in an order that is the same on every run, as with a
+    /// scan's ``deterministic``
+    ///
+    /// Returns:
+    ///     ScanResults sorted by path, then pattern name and index, with
+    ///     the names, aliases, bound paths and failures within each
+    ///     result sorted
+    fn sorted(&self) -> ScanResults {
+        let mut sorted = ScanResults {
+            results: self.results.clone(),
+            roots: self.roots.clone(),
+            pattern_names: self.pattern_names.clone(),
+            profile: self.profile.clone(),
+        };
+        sorted.sort();
+        sorted
+    }
+
+    /// The results grouped by pattern
+    ///
+    /// Keys are ``pattern_name``, or ``pattern_index`` for unnamed
+    /// patterns, in pattern order; patterns sharing a name share a group.
+    /// Every pattern gets a group, empty if it matched nothing. Each group
+    /// is a ScanResults in result order.
+    ///
+    /// Returns:
+    ///     Dict of pattern name or index to ScanResults
+    fn by_pattern<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let key = |pattern_index: usize| -> PyResult<Bound<'py, PyAny>> {
+            Ok(match &self.pattern_names[pattern_index] {
+                Some(name) => name.into_pyobject(py)?.into_any(),
+                None => pattern_index.into_pyobject(py)?.into_any(),
+            })
+        };
+        // Group index of each pattern; named patterns share by name
+        let mut group_of = Vec::with_capacity(self.pattern_names.len());
+        let mut first_named: HashMap<&str, usize> = HashMap::new();
+        let mut groups: Vec<(usize, Vec<ScanResult>)> = Vec::new();
+        for (pattern_index, name) in self.pattern_names.iter().enumerate() {
+            let shared = name
+                .as_deref()
+                .and_then(|name| first_named.get(name).copied());
+            group_of.push(shared.unwrap_or_else(|| {
+                if let Some(name) = name {
+                    first_named.insert(name, groups.len());
+                }
+                groups.push((pattern_index, Vec::new()));
+                groups.len() - 1
+            }));
+        }
+        for result in &self.results {
+            groups[group_of[result.pattern_index]]
+                .1
+                .push(result.clone());
+        }
+        let grouped = PyDict::new(py);
+        for (pattern_index, results) in groups {
+            let group = ScanResults {
+                results,
+                roots: self.roots.clone(),
+                pattern_names: self.pattern_names.clone(),
+                profile: None,
+            };
+            grouped.set_item(key(pattern_index)?, group)?;
+        }
+        Ok(grouped)
+    }
+
+    /// Matches per pattern, and the patterns that matched nothing
+    ///
+    /// Partial matches from ``min_score`` are not counted. A pattern with
+    /// no matches usually means a broken spec.
+    #[getter]
+    fn summary(&self) -> ScanSummary {
+        let mut match_counts = vec![0; self.pattern_names.len()];
+        for result in &self.results {
+            if result.failures.is_empty() {
+                match_counts[result.pattern_index] += 1;
+            }
+        }
+        ScanSummary {
+            unmatched: (0..match_counts.len())
+                .filter(|&idx| match_counts[idx] == 0)
+                .collect(),
+            match_counts,
+            pattern_names: self.pattern_names.clone(),
+        }
+    }
+}
+
+fn same_results<'a>(
+    ours: &[ScanResult],
+    theirs: impl ExactSizeIterator<Item = &'a ScanResult>,
+) -> bool {
+    ours.len() == theirs.len() && ours.iter().zip(theirs).all(|(a, b)| a.__eq__(b))
+}
+
+/// Iterator over a ScanResults
+#[pyclass(module = "pathvein._pathvein_rs")]
+pub struct ScanResultsIterator {
+    results: Py<ScanResults>,
+    position: usize,
+}
+
+#[pymethods]
+impl ScanResultsIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> Option<ScanResult> {
+        let result = self.results.get().results.get(self.position)?.clone();
+        self.position += 1;
+        Some(result)
+    }
+}
+
+/// Which of two nested matched directories `scan_parallel` reports
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Overlap {
+    All,
+    Outermost,
+    Innermost,
+}
+
+impl Overlap {
+    fn parse(overlap: &str) -> PyResult<Self> {
+        match overlap {
+            "all" => Ok(Overlap::All),
+            "outermost" => Ok(Overlap::Outermost),
+            "innermost" => Ok(Overlap::Innermost),
+            other => Err(PyValueError::new_err(format!(
+                "Unknown overlap strategy '{}': expected 'all', 'outermost' or 'innermost'",
+                other
+            ))),
+        }
+    }
+
+    /// The matched directories to report
+    fn resolve(self, matched: HashSet<PathBuf>) -> HashSet<PathBuf> {
+        match self {
+            Overlap::All => matched,
+            Overlap::Outermost => matched
+                .iter()
+                .filter(|dir| !dir.ancestors().skip(1).any(|a| matched.contains(a)))
+                .cloned()
+                .collect(),
+            Overlap::Innermost => {
+                let containing: HashSet<&Path> = matched
+                    .iter()
+                    .flat_map(|dir| dir.ancestors().skip(1))
+                    .collect();
+                matched
+                    .iter()
+                    .filter(|dir| !containing.contains(dir.as_path()))
+                    .cloned()
+                    .collect()
+            }
+        }
     }
 }
 
-/// Scan directory tree for pattern matches - streaming walk+match in Rust
+/// Scan directory tree for pattern matches - parallel walk+match in Rust
+///
+/// Patterns are compiled once, then the roots are walked in parallel
+/// workers that record every directory's listing in a WalkedTree. Each
+/// directory is matched against the patterns once the walk is done - or
+/// during it, without ``descend_into_matches`` - and the whole result set
+/// is built before anything is returned, so memory grows with the size of
+/// the tree. Pass ``stream`` to get results while the walk is going on.
+///
+/// Several roots are scanned in one walk sharing the compiled patterns and
+/// worker threads, and their results are merged; ``ScanResult.root`` tells
+/// which root each came from.
 ///
-/// This does TRUE streaming:
-/// 1. Precompile all patterns ONCE before walking
-/// 2. Walk directories in parallel workers
-/// 3. Match in parallel workers as we walk (no storing all entries)
-/// 4. Only store MATCHES in DashMap
-/// 5. No unbounded memory usage
+/// Roots can instead be object store URLs - ``s3://bucket/prefix``,
+/// ``gs://bucket``, ``az://container/prefix`` - listed as
+/// ``walk_parallel`` lists them, with ``/`` in keys as directories. Only
+/// keys are listed, so size, age and content limits never hold for
+/// objects.
+///
+/// Roots can also be snapshot files ``snapshot`` wrote, scanned as the
+/// trees they were taken of without touching those trees: results are
+/// under the snapshots' roots, and sizes and times come from the
+/// snapshots. Contents aren't in a snapshot, so content limits never hold.
 ///
 /// Args:
-///     path: Root directory to scan
+///     path: Root directory to scan, or a list of root directories
 ///     pattern_jsons: List of JSON-serialized FileStructurePattern objects
 ///     max_depth: Optional maximum depth to traverse
 ///     follow_links: Whether to follow symbolic links
+///     first_match: Attribute each directory only to the first pattern, in
+///         list order, that matches it and skip the remaining patterns
+///         (default: report every matching pattern)
+///     overlap: How to resolve matched directories nested in other matched
+///         directories: ``"all"`` keeps both, ``"outermost"`` keeps only
+///         the top-level match and ``"innermost"`` keeps only the deepest
+///         (default: "all")
+///     progress: Optional callable taking a ScanProgress, called from the
+///         scan's threads at most every ``progress_interval`` seconds and
+///         once more when the scan is done. An exception it raises stops
+///         the scan and is re-raised.
+///     progress_interval: Minimum seconds between progress calls
+///         (default: 0.1)
+///     exclude_roots: Subtrees to skip entirely, as absolute paths or as
+///         gitignore-style globs relative to ``path`` (e.g. ``.snapshot``
+///         at any depth, ``archive/2019``). Excluded directories are not
+///         walked, matched, or listed as subdirectories of their parent.
+///     min_score: Also report directories satisfying at least this
+///         fraction of a pattern's requirements, with their
+///         ``ScanResult.score`` (see ``FileStructurePattern.score``) and
+///         the requirements they fail in ``ScanResult.failures``.
+///         Partial results don't take part in ``overlap``, and with
+///         ``first_match`` are only reported for directories no pattern
+///         matches.
+///     descend_into_matches: Whether to keep walking inside a directory
+///         once it matches a pattern (default: True). With False, a
+///         directory is matched as soon as it is walked, and nothing below
+///         a match is walked, matched or reported - the way to skip the
+///         contents of large matched dataset roots. Each directory is
+///         listed once more, as deep as the patterns look, to check it.
+///     dedupe_physical: Report a directory reachable by several paths -
+///         through followed symlinks, bind mounts or overlapping roots -
+///         once per pattern, under its shortest path, with the other paths
+///         in ``ScanResult.aliases``. Directories are identified by device
+///         and inode (default: False).
+///     events: Optional callable taking a ScanEvent, called from the
+///         scan's threads as the scan goes: ``"directory_entered"`` for
+///         each directory walked, ``"io_error"`` for each entry that
+///         can't be read, then ``"match_found"`` for each match before
+///         ``overlap`` is applied and ``"requirement_failed"``, with its
+///         failures, for each directory whose name fits a pattern that
+///         it doesn't otherwise match. An exception it raises stops the
+///         scan and is re-raised.
+///     relative_paths: Give result paths, and aliases under the same root,
+///         relative to the result's root - ``"."`` for the root itself - so
+///         they compare equal across mount points (default: False). The
+///         roots are in ``ScanResults.roots``.
+///     best_match: Attribute each directory only to the matching pattern
+///         with the highest ``priority``, the first in list order among
+///         equals (default: False)
+///     runners_up: With ``best_match``, list the other matching patterns
+///         in ``ScanResult.runners_up`` (default: False)
+///     max_eval_threads: Most threads matching directories at once,
+///         separately from the walk's threads, so patterns with size,
+///         content or age limits don't flood a network filer with stats
+///         and reads (default: one per CPU)
+///     stats_only: Return only ScanStats - counts per pattern and timings
+///         - without building a result per match
+///     deterministic: Return results sorted by path, then pattern name
+///         and index, with the names, aliases, bound paths and failures
+///         within each result sorted, so runs over the same tree give
+///         identical output whatever the thread scheduling (default: False)
+///     profile: Time the walk, the matching, each pattern and each
+///         directory, reported as ``ScanResults.profile`` or
+///         ``ScanStats.profile``, to tell slow storage from slow
+///         patterns (default: False)
+///     archives: Treat ``.zip`` and ``.tar`` files, compressed or not, as
+///         directories, as for ``walk_parallel``, so patterns match
+///         layouts that were archived. Only names are read from an
+///         archive, so size, age and content limits never hold for files
+///         inside one; an archive that can't be read stays a file and
+///         counts as a walk error (default: False)
+///     storage_options: Settings for object store roots, as for
+///         ``walk_parallel`` (default: None)
+///     stream: Return a ScanIterator yielding each result as soon as it
+///         is known, while the walk goes on in the background, so long
+///         scans over slow storage start producing results immediately.
+///         A directory is matched once its own listing, and those of the
+///         subdirectories its patterns look into, have been walked, and
+///         results arrive in no particular order. Scans a single local
+///         root, with only ``max_depth``, ``follow_links``,
+///         ``first_match``, ``exclude_roots`` and ``relative_paths``
+///         (default: False)
 ///
 /// Returns:
-///     List of ScanResult tuples (path, pattern_index) for directories that matched
+///     ScanResults for directories that matched, each with the path,
+///     pattern_index and the optional components present, and a
+///     ``summary`` of matches per pattern; ScanStats with ``stats_only``;
+///     a ScanIterator over the same results with ``stream``
+///
+/// Raises:
+///     PatternSyntaxError: If a pattern is invalid
+///     WalkError: If a root doesn't exist or isn't a directory, or an
+///         object store can't be listed
+///     ValueError: If no root is given, roots mix local directories, object
+///         store URLs and snapshot files, a snapshot can't be read, an
+///         exclude glob is invalid, overlap is not a known strategy,
+///         min_score is not between 0.0 and 1.0, max_eval_threads is 0 or
+///         ``stream`` is combined with an option it doesn't support
 #[pyfunction]
-#[pyo3(signature = (path, pattern_jsons, max_depth=None, follow_links=false))]
+#[pyo3(signature = (
+    path,
+    pattern_jsons,
+    max_depth=None,
+    follow_links=false,
+    first_match=false,
+    overlap="all",
+    progress=None,
+    progress_interval=0.1,
+    exclude_roots=None,
+    min_score=None,
+    stats_only=false,
+    descend_into_matches=true,
+    dedupe_physical=false,
+    events=None,
+    relative_paths=false,
+    best_match=false,
+    runners_up=false,
+    max_eval_threads=None,
+    deterministic=false,
+    profile=false,
+    archives=false,
+    storage_options=None,
+    stream=false,
+))]
+#[allow(clippy::too_many_arguments)]
 pub fn scan_parallel(
-    path: String,
+    py: Python<'_>,
+    path: ScanRoots,
     pattern_jsons: Vec<String>,
     max_depth: Option<usize>,
     follow_links: bool,
-) -> PyResult<Vec<ScanResult>> {
-    use crate::file_pattern::{CompiledPattern, FileStructurePattern};
-
-    // 1. Deserialize patterns from JSON
-    let patterns: Vec<FileStructurePattern> = pattern_jsons
-        .iter()
-        .map(|json| {
-            FileStructurePattern::from_json(json).map_err(|e| {
-                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
-                    "Invalid pattern JSON: {}",
-                    e
-                ))
-            })
-        })
-        .collect::<PyResult<Vec<_>>>()?;
-
-    // 2. PRECOMPILE all patterns ONCE before walking
-    let compiled_patterns: Vec<CompiledPattern> = patterns
+    first_match: bool,
+    overlap: &str,
+    progress: Option<PyObject>,
+    progress_interval: f64,
+    exclude_roots: Option<Vec<String>>,
+    min_score: Option<f64>,
+    stats_only: bool,
+    descend_into_matches: bool,
+    dedupe_physical: bool,
+    events: Option<PyObject>,
+    relative_paths: bool,
+    best_match: bool,
+    runners_up: bool,
+    max_eval_threads: Option<usize>,
+    deterministic: bool,
+    profile: bool,
+    archives: bool,
+    storage_options: Option<HashMap<String, String>>,
+    stream: bool,
+) -> PyResult<ScanOutput> {
+    let mut roots = path.into_vec();
+    if roots.is_empty() {
+        return Err(PyValueError::new_err(
+            "scan_parallel needs at least one root",
+        ));
+    }
+    // Snapshot files stand in for the roots they were taken of
+    let mut snapshots: HashMap<String, Arc<SnapshotData>> = HashMap::new();
+    for root in &mut roots {
+        if is_snapshot_file(root) {
+            let snapshot = py
+                .allow_threads(|| SnapshotData::load(Path::new(root.as_str())))
+                .map_err(|e| {
+                    PyValueError::new_err(format!("Cannot read snapshot {}: {}", root, e))
+                })?;
+            *root = snapshot.root.clone();
+            snapshots.insert(root.clone(), Arc::new(snapshot));
+        }
+    }
+    let snapshot_roots = roots
         .iter()
-        .map(|p| {
-            p.compile().map_err(|e| {
-                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
-                    "Pattern compilation error: {}",
-                    e
-                ))
-            })
-        })
-        .collect::<PyResult<Vec<_>>>()?;
+        .filter(|root| snapshots.contains_key(*root))
+        .count();
+    let stores = roots.iter().filter(|root| is_store_url(root)).count();
+    if (stores > 0 && stores < roots.len()) || (snapshot_roots > 0 && snapshot_roots < roots.len())
+    {
+        return Err(PyValueError::new_err(
+            "roots must be all local directories, all object store URLs or all snapshot files",
+        ));
+    }
+    if stores == 0 && snapshots.is_empty() {
+        roots.iter().try_for_each(|root| check_root(root))?;
+    }
+    if best_match && first_match {
+        return Err(PyValueError::new_err(
+            "first_match and best_match are exclusive: pass one or the other",
+        ));
+    }
+    if runners_up && !best_match {
+        return Err(PyValueError::new_err("runners_up needs best_match=True"));
+    }
+    if max_eval_threads == Some(0) {
+        return Err(PyValueError::new_err("max_eval_threads must be at least 1"));
+    }
+    let overlap = Overlap::parse(overlap)?;
+    if let Some(min_score) = min_score.filter(|score| !(0.0..=1.0).contains(score)) {
+        return Err(PyValueError::new_err(format!(
+            "min_score must be between 0.0 and 1.0, got {}",
+            min_score
+        )));
+    }
+    let events = events.map(EventSink::new);
+    let excluded = exclude_roots
+        .map(|excludes| ExcludedRoots::new(&roots, excludes).map(Arc::new))
+        .transpose()?;
+    if stream {
+        // Results go out while the walk goes on, so nothing that looks at
+        // every match, or at the walk as a whole, applies
+        let unsupported = [
+            ("several roots", roots.len() > 1),
+            (
+                "object store or snapshot roots",
+                stores > 0 || !snapshots.is_empty(),
+            ),
+            ("overlap", overlap != Overlap::All),
+            ("progress", progress.is_some()),
+            ("min_score", min_score.is_some()),
+            ("stats_only", stats_only),
+            ("descend_into_matches=False", !descend_into_matches),
+            ("dedupe_physical", dedupe_physical),
+            ("events", events.is_some()),
+            ("best_match", best_match),
+            ("max_eval_threads", max_eval_threads.is_some()),
+            ("deterministic", deterministic),
+            ("profile", profile),
+            ("archives", archives),
+        ];
+        if let Some((option, _)) = unsupported.iter().find(|(_, given)| *given) {
+            return Err(PyValueError::new_err(format!(
+                "stream=True doesn't support {}",
+                option
+            )));
+        }
+        let patterns = compile_patterns(&pattern_jsons)?;
+        let scan = StreamingScan::new(
+            roots.remove(0),
+            patterns,
+            max_depth,
+            first_match,
+            relative_paths,
+        );
+        return Ok(ScanOutput::Stream(scan_stream(
+            scan,
+            follow_links,
+            excluded,
+        )));
+    }
 
-    // 3. Wrap in Arc for sharing across parallel workers
-    let compiled_patterns = Arc::new(compiled_patterns);
+    // 1. Deserialize and PRECOMPILE all patterns ONCE before walking,
+    //    wrapped in Arc for sharing across parallel workers
+    let compiled_patterns = Arc::new(compile_patterns(&pattern_jsons)?);
 
-    // 4. Build walker
-    let mut builder = WalkBuilder::new(&path);
-    if let Some(depth) = max_depth {
-        builder.max_depth(Some(depth));
+    // 2. Build walker, pruning excluded subtrees before they are read.
+    //    Roots inside other roots are walked once, as part of the outer.
+    let mut walked_roots: Vec<&String> = Vec::new();
+    for (idx, root) in roots.iter().enumerate() {
+        // Of repeated roots, only the first is walked
+        let nested = roots.iter().enumerate().any(|(other_idx, other)| {
+            other_idx != idx
+                && Path::new(root).starts_with(other)
+                && (root != other || other_idx < idx)
+        });
+        if !nested {
+            walked_roots.push(root);
+        }
+    }
+    let progress = progress
+        .map(|callback| ProgressReporter::new(callback, progress_interval, walked_roots.len()));
+    let mut builder = scan_walker(walked_roots[0], max_depth, follow_links);
+    for root in &walked_roots[1..] {
+        builder.add(root);
+    }
+    if let Some(excluded) = &excluded {
+        let excluded = Arc::clone(excluded);
+        builder.filter_entry(move |entry| {
+            !(entry.file_type().is_some_and(|t| t.is_dir()) && excluded.contains(entry.path()))
+        });
     }
-    builder.follow_links(follow_links);
-    builder.hidden(false);
-    builder.ignore(false);
-    builder.git_ignore(false);
-    builder.git_global(false);
-    builder.git_exclude(false);
 
-    // 5. DashMap to collect directory contents and matches
+    // 3. DashMap to collect directory contents and matches
     let dir_contents: Arc<DashMap<PathBuf, DirContents>> = Arc::new(DashMap::new());
-    let matches: Arc<DashMap<String, Vec<usize>>> = Arc::new(DashMap::new());
+    let matches: Arc<DashMap<PathBuf, Vec<(usize, StructureMatch)>>> = Arc::new(DashMap::new());
+    // Patterns beaten by each directory's best match, with runners_up
+    let mut beaten: HashMap<PathBuf, Vec<usize>> = HashMap::new();
+    // Directories that reach min_score without matching, with their scores
+    let mut partial: Vec<(PathBuf, usize, f64, Vec<MatchFailure>)> = Vec::new();
+    let walk_errors = AtomicU64::new(0);
+    let eval_threads =
+        max_eval_threads.unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+    // Matching during the walk, without descend_into_matches, shares the
+    // same limit
+    let walk_permits = EvalPermits::new(eval_threads);
+    let mut evaluated = vec![0u64; compiled_patterns.len()];
+    let mut pattern_nanos = vec![0u64; compiled_patterns.len()];
+    let mut slowest = Vec::new();
+    let started = Instant::now();
+    let mut walk_seconds = 0.0;
+    // Matched directories the walk didn't descend into
+    let not_descended: DashSet<PathBuf> = DashSet::new();
+    let match_levels = compiled_patterns
+        .iter()
+        .map(CompiledPattern::depth)
+        .max()
+        .unwrap_or(0);
 
-    // 6. Walk in parallel - collect directory contents
-    builder.build_parallel().run(|| {
-        let dir_contents = Arc::clone(&dir_contents);
-        Box::new(move |entry_result| {
-            if let Ok(dir_entry) = entry_result {
-                let path = dir_entry.path();
-                if let (Some(parent), Some(name)) = (path.parent(), path.file_name()) {
-                    if let Some(file_type) = dir_entry.file_type() {
-                        let mut entry = dir_contents
-                            .entry(parent.to_path_buf())
-                            .or_insert((SmallVec::new(), SmallVec::new()));
-
-                        if file_type.is_file() {
-                            entry.0.push(name.to_os_string());
-                        } else if file_type.is_dir() {
-                            entry.1.push(name.to_os_string());
+    // Object store roots and snapshots are listed up front, rather than
+    // walked
+    let listed = if stores > 0 || !snapshots.is_empty() {
+        let options = storage_options.unwrap_or_default();
+        let mut listings = Vec::new();
+        for root in &walked_roots {
+            let listed = match snapshots.get(*root) {
+                Some(snapshot) => py.allow_threads(|| snapshot.listings()),
+                None => py
+                    .allow_threads(|| list_store(root, &options))
+                    .map_err(|e| walk_error(Path::new(root.as_str()), e))?,
+            };
+            listings.extend(
+                listed
+                    .into_iter()
+                    .filter(|(dir, depth, (files, dirs))| {
+                        // Empty directories are never candidates, as when
+                        // walking
+                        !(files.is_empty() && dirs.is_empty())
+                            && max_depth.map_or(true, |max| *depth < max)
+                            && !dir.ancestors().any(|dir| {
+                                excluded
+                                    .as_ref()
+                                    .is_some_and(|excluded| excluded.contains(dir))
+                            })
+                    })
+                    .map(|(dir, _, mut listing)| {
+                        if let Some(excluded) = &excluded {
+                            listing
+                                .1
+                                .retain(|name| !excluded.contains(&dir.join(&*name)));
+                        }
+                        (dir, listing)
+                    }),
+            );
+        }
+        Some(listings)
+    } else {
+        None
+    };
+
+    // 4. Walk in parallel - collect directory contents. The GIL is
+    //    released so workers can call the progress and event callbacks.
+    py.allow_threads(|| {
+        if let Some(listings) = listed {
+            for (dir, listing) in listings {
+                dir_contents.insert(dir, listing);
+            }
+        } else {
+            builder.build_parallel().run(|| {
+                let dir_contents = Arc::clone(&dir_contents);
+                let progress = progress.as_ref();
+                let excluded = excluded.as_deref();
+                let walk_errors = &walk_errors;
+                let compiled_patterns = Arc::clone(&compiled_patterns);
+                let not_descended = &not_descended;
+                let events = events.as_ref();
+                let walk_permits = &walk_permits;
+                Box::new(move |entry_result| {
+                    if let Err(err) = &entry_result {
+                        walk_errors.fetch_add(1, Ordering::Relaxed);
+                        if events.is_some_and(|events| !events.emit(ScanEvent::io_error(err))) {
+                            return ignore::WalkState::Quit;
+                        }
+                    }
+                    if let Ok(dir_entry) = entry_result {
+                        let path = dir_entry.path();
+                        let is_dir = dir_entry.file_type().is_some_and(|t| t.is_dir());
+                        if is_dir
+                            && events.is_some_and(|events| {
+                                !events.emit(ScanEvent::directory_entered(path))
+                            })
+                        {
+                            return ignore::WalkState::Quit;
+                        }
+                        let descends =
+                            is_dir && max_depth.map_or(true, |max| dir_entry.depth() < max);
+                        // Stop at a match, keeping the listings it was matched
+                        // on since the walk won't produce them
+                        let mut stop = false;
+                        if descends && !descend_into_matches {
+                            // Listings below max_depth aren't walked either
+                            let levels = max_depth.map_or(match_levels, |max| {
+                                match_levels.min(max - dir_entry.depth() - 1)
+                            });
+                            let subtree = walk_permits.with(|| {
+                                matched_subtree(
+                                    path,
+                                    levels,
+                                    &compiled_patterns,
+                                    follow_links,
+                                    excluded,
+                                )
+                            });
+                            if let Some(subtree) = subtree {
+                                for (listed, listing) in subtree {
+                                    dir_contents.insert(listed, listing);
+                                }
+                                not_descended.insert(path.to_path_buf());
+                                stop = true;
+                            }
+                        }
+                        if let Some(progress) = progress {
+                            if is_dir {
+                                let pruned = |dir: &Path| {
+                                    excluded.is_some_and(|excluded| excluded.contains(dir))
+                                };
+                                if !progress.walked(path, descends && !stop, follow_links, pruned) {
+                                    return ignore::WalkState::Quit;
+                                }
+                            }
+                        }
+                        if stop {
+                            return ignore::WalkState::Skip;
+                        }
+                        let is_file = dir_entry.file_type().is_some_and(|t| t.is_file());
+                        let opened = (archives && is_file)
+                            .then(|| open_archive(path, dir_entry.depth(), max_depth))
+                            .flatten()
+                            .and_then(|opened| {
+                                if opened.is_err() {
+                                    walk_errors.fetch_add(1, Ordering::Relaxed);
+                                }
+                                opened.ok()
+                            });
+                        list_entry(&dir_contents, &dir_entry, opened.is_some());
+                        for (dir, listing) in opened.into_iter().flatten() {
+                            dir_contents.insert(dir, listing);
                         }
                     }
+                    ignore::WalkState::Continue
+                })
+            });
+        }
+
+        // 5. Match each directory against precompiled patterns. Nested
+        //    requirements look up child listings in the same tree.
+        //    The walk has finished, so the workers' handles are gone.
+        let tree: WalkedTree = Arc::try_unwrap(dir_contents)
+            .unwrap_or_else(|shared| (*shared).clone())
+            .into_iter()
+            .collect();
+        walk_seconds = started.elapsed().as_secs_f64();
+        // Snapshots stand in for the filesystem: sizes and times come from
+        // them, and contents can't be read
+        let snapshot_tree = (!snapshots.is_empty()).then(|| {
+            let needs_stat = compiled_patterns.iter().any(CompiledPattern::needs_stat);
+            SnapshotTree::new(&tree, snapshots.values().map(|s| &**s), needs_stat)
+        });
+        let match_tree: &(dyn DirectoryTree + Sync) = match &snapshot_tree {
+            Some(snapshot_tree) => snapshot_tree,
+            None => &tree,
+        };
+        // Listed roots weren't walked, so stop at matches as the walk would,
+        // parents first
+        if !descend_into_matches && (stores > 0 || !snapshots.is_empty()) {
+            let mut dirs: Vec<&PathBuf> = tree.keys().collect();
+            dirs.sort();
+            for dir in dirs {
+                let below_match = dir
+                    .ancestors()
+                    .skip(1)
+                    .any(|ancestor| not_descended.contains(ancestor));
+                if !below_match
+                    && compiled_patterns
+                        .iter()
+                        .any(|pattern| pattern.match_in(dir, match_tree).is_some())
+                {
+                    not_descended.insert(dir.clone());
                 }
             }
-            ignore::WalkState::Continue
-        })
+        }
+        // Listings read to check a match include its subdirectories,
+        // which the walk never reached and so aren't matched themselves
+        let to_evaluate: Vec<&PathBuf> = tree
+            .keys()
+            .filter(|dir| {
+                not_descended.is_empty()
+                    || !dir
+                        .ancestors()
+                        .skip(1)
+                        .any(|ancestor| not_descended.contains(ancestor))
+            })
+            .collect();
+        if let Some(progress) = &progress {
+            progress.start_evaluation(to_evaluate.len());
+        }
+        // Check one directory against each precompiled pattern, matching
+        // the walker's OsStrings directly - no String conversion per
+        // entry. Returns false once the scan should stop.
+        let evaluate = |dirpath: &PathBuf, outcome: &mut Evaluation| -> bool {
+            let mut found_here = 0;
+            for (pattern_idx, compiled_pattern) in compiled_patterns.iter().enumerate() {
+                // Use precompiled matchers - NO recompilation!
+                outcome.evaluated[pattern_idx] += 1;
+                let start = profile.then(Instant::now);
+                let found = compiled_pattern.match_in(dirpath, match_tree);
+                if let Some(start) = start {
+                    outcome.pattern_nanos[pattern_idx] += start.elapsed().as_nanos() as u64;
+                }
+                if let Some(found) = found {
+                    matches
+                        .entry(dirpath.clone())
+                        .or_default()
+                        .push((pattern_idx, found));
+                    found_here += 1;
+                    let event = || {
+                        let name = compiled_pattern.pattern_name.clone();
+                        ScanEvent::match_found(dirpath, pattern_idx, name)
+                    };
+                    if events.as_ref().is_some_and(|events| !events.emit(event())) {
+                        return false;
+                    }
+                    if first_match {
+                        break;
+                    }
+                } else if let Some(events) = &events {
+                    // Only directories the pattern's name picks out are
+                    // candidates worth reporting
+                    let failures = compiled_pattern.explain(dirpath, match_tree);
+                    if !failures.iter().any(|f| f.kind == "directory_name") {
+                        let name = compiled_pattern.pattern_name.clone();
+                        let event =
+                            ScanEvent::requirement_failed(dirpath, pattern_idx, name, failures);
+                        if !events.emit(event) {
+                            return false;
+                        }
+                    }
+                }
+            }
+            if best_match && found_here > 1 {
+                if let Some(mut found) = matches.get_mut(dirpath) {
+                    // A stable sort keeps list order among equal priorities
+                    found.sort_by_key(|(idx, _)| Reverse(compiled_patterns[*idx].priority));
+                    let losers = found.split_off(1);
+                    if runners_up {
+                        outcome.beaten.push((
+                            dirpath.clone(),
+                            losers.iter().map(|(idx, _)| *idx).collect(),
+                        ));
+                    }
+                }
+            }
+            if let Some(min_score) = min_score.filter(|_| !(first_match && found_here > 0)) {
+                for (pattern_idx, compiled_pattern) in compiled_patterns.iter().enumerate() {
+                    // A score of 1.0 is a match, already recorded
+                    let score = compiled_pattern.score(dirpath, match_tree);
+                    if score >= min_score && score < 1.0 {
+                        let failures = if stats_only {
+                            Vec::new()
+                        } else {
+                            compiled_pattern.explain(dirpath, match_tree)
+                        };
+                        outcome
+                            .partial
+                            .push((dirpath.clone(), pattern_idx, score, failures));
+                    }
+                }
+            }
+            progress
+                .as_ref()
+                .map_or(true, |progress| progress.evaluated(found_here))
+        };
+        // Workers take directories one at a time, so an expensive one
+        // doesn't hold up a whole share of the others
+        let next = AtomicUsize::new(0);
+        let stopped = AtomicBool::new(false);
+        let workers = eval_threads.min(to_evaluate.len()).max(1);
+        let outcomes: Vec<Evaluation> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut outcome = Evaluation::new(compiled_patterns.len());
+                        while !stopped.load(Ordering::Relaxed) {
+                            let Some(dirpath) =
+                                to_evaluate.get(next.fetch_add(1, Ordering::Relaxed))
+                            else {
+                                break;
+                            };
+                            let start = profile.then(Instant::now);
+                            if !evaluate(dirpath, &mut outcome) {
+                                stopped.store(true, Ordering::Relaxed);
+                            }
+                            if let Some(start) = start {
+                                outcome.time(dirpath, start.elapsed());
+                            }
+                        }
+                        outcome
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("evaluation worker panicked"))
+                .collect()
+        });
+        for outcome in outcomes {
+            for (total, count) in evaluated.iter_mut().zip(outcome.evaluated) {
+                *total += count;
+            }
+            beaten.extend(outcome.beaten);
+            partial.extend(outcome.partial);
+            for (total, nanos) in pattern_nanos.iter_mut().zip(outcome.pattern_nanos) {
+                *total += nanos;
+            }
+            slowest.extend(outcome.slowest);
+        }
     });
+    if let Some(events) = &events {
+        events.finish()?;
+    }
+    if let Some(progress) = &progress {
+        progress.finish()?;
+    }
 
-    // 7. Match each directory against precompiled patterns
-    for entry in dir_contents.iter() {
-        let (dirpath, (files, dirs)) = entry.pair();
-        let dirpath_str = dirpath.to_string_lossy().into_owned();
-        let dirpath_name = dirpath.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    // 6. Drop matches nested in (or containing) other matches, after
+    //    merging paths to the same directory
+    let mut aliases = if dedupe_physical {
+        merge_aliases(&matches)
+    } else {
+        HashMap::new()
+    };
+    let keep = overlap.resolve(matches.iter().map(|entry| entry.key().clone()).collect());
 
-        let filenames: Vec<String> = files
-            .iter()
-            .map(|s| s.to_string_lossy().into_owned())
-            .collect();
-        let dirnames: Vec<String> = dirs
+    let elapsed = started.elapsed().as_secs_f64();
+    let profile = profile.then(|| {
+        slowest.sort_by_key(|&(_, took)| Reverse(took));
+        slowest.truncate(SLOWEST_DIRECTORIES);
+        ScanProfile {
+            walk_seconds,
+            match_seconds: elapsed - walk_seconds,
+            elapsed,
+            pattern_seconds: pattern_nanos
+                .iter()
+                .map(|&nanos| nanos as f64 / 1e9)
+                .collect(),
+            pattern_names: compiled_patterns
+                .iter()
+                .map(|pattern| pattern.pattern_name.clone())
+                .collect(),
+            slowest_directories: slowest
+                .iter()
+                .map(|(dir, took)| (dir.to_string_lossy().into_owned(), took.as_secs_f64()))
+                .collect(),
+        }
+    });
+
+    if stats_only {
+        let mut patterns: Vec<PatternStats> = compiled_patterns
             .iter()
-            .map(|s| s.to_string_lossy().into_owned())
+            .enumerate()
+            .map(|(pattern_index, pattern)| PatternStats {
+                pattern_index,
+                pattern_name: pattern.pattern_name.clone(),
+                evaluated: evaluated[pattern_index],
+                matched: 0,
+                near_missed: 0,
+            })
             .collect();
-
-        // Check against each precompiled pattern
-        for (pattern_idx, compiled_pattern) in compiled_patterns.iter().enumerate() {
-            // Use precompiled matchers - NO recompilation!
-            if compiled_pattern.matches(dirpath_name, &dirnames, &filenames) {
-                matches
-                    .entry(dirpath_str.clone())
-                    .or_default()
-                    .push(pattern_idx);
+        for entry in matches.iter().filter(|entry| keep.contains(entry.key())) {
+            for (pattern_idx, _) in entry.value() {
+                patterns[*pattern_idx].matched += 1;
             }
         }
+        for (_, pattern_idx, _, _) in &partial {
+            patterns[*pattern_idx].near_missed += 1;
+        }
+        return Ok(ScanOutput::Stats(ScanStats {
+            directories_evaluated: evaluated.first().copied().unwrap_or(0),
+            walk_errors: walk_errors.into_inner(),
+            walk_seconds,
+            match_seconds: elapsed - walk_seconds,
+            elapsed,
+            patterns,
+            profile,
+        }));
     }
 
-    // 8. Convert to results
+    // 7. Convert to results
+    let display = |dir: &Path, root: &str| match dir.strip_prefix(root) {
+        Ok(relative) if relative_paths && relative.as_os_str().is_empty() => ".".to_string(),
+        Ok(relative) if relative_paths => relative.to_string_lossy().into_owned(),
+        _ => dir.to_string_lossy().into_owned(),
+    };
     let mut results = Vec::new();
     for entry in matches.iter() {
-        let (path, pattern_indices) = entry.pair();
-        for pattern_idx in pattern_indices {
-            results.push(ScanResult {
-                path: path.clone(),
-                pattern_index: *pattern_idx,
-            });
+        let (path, found) = entry.pair();
+        if !keep.contains(path) {
+            continue;
         }
+        let root = root_of(path, &roots);
+        let key = path;
+        let path = display(key, &root);
+        for (pattern_idx, found) in found {
+            let mut result = ScanResult::new(
+                path.clone(),
+                root.clone(),
+                *pattern_idx,
+                &compiled_patterns[*pattern_idx],
+                found.clone(),
+            );
+            if relative_paths {
+                for paths in result.bindings.values_mut() {
+                    for bound in paths.iter_mut() {
+                        *bound = display(Path::new(bound.as_str()), &root);
+                    }
+                }
+            }
+            if let Some(losers) = beaten.remove(key) {
+                result.runners_up = losers;
+            }
+            if let Some(paths) = aliases.remove(&(key.clone(), *pattern_idx)) {
+                result.aliases = paths.iter().map(|alias| display(alias, &root)).collect();
+            }
+            results.push(result);
+        }
+    }
+    for (path, pattern_idx, score, failures) in partial {
+        let root = root_of(&path, &roots);
+        results.push(ScanResult::partial(
+            display(&path, &root),
+            root,
+            pattern_idx,
+            &compiled_patterns[pattern_idx],
+            score,
+            failures,
+        ));
     }
 
-    Ok(results)
+    let mut results = ScanResults::new(results, roots, &compiled_patterns);
+    results.profile = profile;
+    if deterministic {
+        results.sort();
+    }
+    Ok(ScanOutput::Results(results))
+}
+
+/// Identity of a physical directory, whatever path it is reached by
+#[cfg(unix)]
+fn physical_id(dir: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(dir)
+        .ok()
+        .map(|metadata| (metadata.dev(), metadata.ino()))
+}
+
+/// Identity of a physical directory, whatever path it is reached by; the
+/// resolved path where device and inode numbers aren't available
+#[cfg(not(unix))]
+fn physical_id(dir: &Path) -> Option<PathBuf> {
+    std::fs::canonicalize(dir).ok()
+}
+
+/// Keep one path per physical directory and pattern among `matches`
+///
+/// The shortest path is kept, the first in order among equally short
+/// ones. Returns the other paths, keyed by the kept path and pattern.
+fn merge_aliases(
+    matches: &DashMap<PathBuf, Vec<(usize, StructureMatch)>>,
+) -> HashMap<(PathBuf, usize), Vec<PathBuf>> {
+    // Paths matched per physical directory, per pattern
+    let mut groups: HashMap<_, HashMap<usize, Vec<PathBuf>>> = HashMap::new();
+    for entry in matches.iter() {
+        if let Some(id) = physical_id(entry.key()) {
+            let by_pattern = groups.entry(id).or_default();
+            for (pattern_idx, _) in entry.value() {
+                by_pattern
+                    .entry(*pattern_idx)
+                    .or_default()
+                    .push(entry.key().clone());
+            }
+        }
+    }
+    let mut aliases = HashMap::new();
+    for (pattern_idx, mut paths) in groups.into_values().flatten() {
+        if paths.len() < 2 {
+            continue;
+        }
+        paths.sort_by(|a, b| (a.components().count(), a).cmp(&(b.components().count(), b)));
+        let kept = paths.remove(0);
+        for alias in &paths {
+            if let Some(mut found) = matches.get_mut(alias) {
+                found.retain(|(idx, _)| *idx != pattern_idx);
+            }
+        }
+        aliases.insert((kept, pattern_idx), paths);
+    }
+    matches.retain(|_, found| !found.is_empty());
+    aliases
+}
+
+/// The `path` given to `scan_parallel`: one root or several
+#[derive(FromPyObject)]
+pub enum ScanRoots {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl ScanRoots {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            ScanRoots::One(root) => vec![root],
+            ScanRoots::Many(roots) => roots,
+        }
+    }
+}
+
+/// The innermost of `roots` containing `dir`
+fn root_of(dir: &Path, roots: &[String]) -> String {
+    roots
+        .iter()
+        .filter(|root| dir.starts_with(root))
+        .max_by_key(|root| Path::new(root).components().count())
+        .unwrap_or(&roots[0])
+        .clone()
+}
+
+/// What `scan_parallel` returns, depending on `stats_only` and `stream`
+#[derive(IntoPyObject)]
+pub enum ScanOutput {
+    Results(ScanResults),
+    Stats(ScanStats),
+    Stream(ScanIterator),
+}
+
+/// Subtrees a scan skips, given as absolute paths or root-relative globs
+///
+/// With several scan roots, globs are relative to each of them.
+pub(crate) struct ExcludedRoots {
+    /// Each scan root, and the root resolved for comparing against
+    /// absolute paths
+    roots: Vec<(PathBuf, PathBuf)>,
+    paths: Vec<PathBuf>,
+    globs: PatternMatcher,
+}
+
+impl ExcludedRoots {
+    fn new(scan_roots: &[String], excludes: Vec<String>) -> PyResult<Self> {
+        let (paths, globs): (Vec<String>, Vec<String>) = excludes
+            .into_iter()
+            .partition(|entry| Path::new(entry).is_absolute());
+        Ok(ExcludedRoots {
+            paths: paths
+                .iter()
+                .map(|path| std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path)))
+                .collect(),
+            globs: PatternMatcher::with_options(
+                globs,
+                MatcherOptions {
+                    full_path: true,
+                    ..MatcherOptions::default()
+                },
+            )?,
+            roots: scan_roots
+                .iter()
+                .map(|root| {
+                    let root = PathBuf::from(root);
+                    let canonical_root =
+                        std::fs::canonicalize(&root).unwrap_or_else(|_| root.clone());
+                    (root, canonical_root)
+                })
+                .collect(),
+        })
+    }
+
+    /// Whether the directory at `dir`, below a scan root, is excluded
+    pub(crate) fn contains(&self, dir: &Path) -> bool {
+        // Never exclude a root itself
+        if self.roots.iter().any(|(root, _)| dir == root) {
+            return false;
+        }
+        self.roots.iter().any(|(root, canonical_root)| {
+            let Ok(relative) = dir.strip_prefix(root) else {
+                return false;
+            };
+            self.globs.is_match_path(relative, true)
+                || self
+                    .paths
+                    .iter()
+                    .any(|path| *path == canonical_root.join(relative))
+        })
+    }
+}
+
+/// Check that a scan root is a readable directory, rather than letting
+/// the walk find nothing
+pub(crate) fn check_root(root: &str) -> PyResult<()> {
+    let path = Path::new(root);
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => Ok(()),
+        Ok(_) => Err(walk_error(
+            path,
+            format!("Cannot scan {}: not a directory", root),
+        )),
+        Err(e) => Err(walk_error(path, format!("Cannot scan {}: {}", root, e))),
+    }
+}
+
+/// Deserialize and compile the JSON patterns given to a scan
+///
+/// A pattern's `extends` may name any other pattern of the scan by its
+/// `pattern_name`.
+pub(crate) fn compile_patterns(pattern_jsons: &[String]) -> PyResult<Vec<CompiledPattern>> {
+    let patterns = pattern_jsons
+        .iter()
+        .map(|json| {
+            FileStructurePattern::from_json(json)
+                .map_err(|e| pattern_syntax_error(format!("Invalid pattern JSON: {}", e)))
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    compile_specs(&patterns).map_err(pattern_syntax_error)
+}
+
+/// Compile patterns, resolving each `extends` against the others' names
+pub(crate) fn compile_specs(
+    patterns: &[FileStructurePattern],
+) -> Result<Vec<CompiledPattern>, String> {
+    let library: HashMap<String, FileStructurePattern> = patterns
+        .iter()
+        .filter_map(|pattern| Some((pattern.pattern_name.clone()?, pattern.clone())))
+        .collect();
+    patterns
+        .iter()
+        .map(|pattern| {
+            inherit::resolve(pattern, &library, Path::new(""))?
+                .compile()
+                .map_err(|e| format!("Pattern compilation error: {}", e))
+        })
+        .collect()
+}
+
+/// Add a walked entry to the listing of the directory holding it: as a
+/// subdirectory if it is one or `as_dir`, else as a file if it is one
+///
+/// The root's parent is outside the walk, so the root is never listed
+/// there, where it could be matched or scored.
+pub(crate) fn list_entry(
+    listings: &DashMap<PathBuf, DirContents>,
+    entry: &ignore::DirEntry,
+    as_dir: bool,
+) {
+    let path = entry.path();
+    let (Some(parent), Some(name), Some(file_type)) =
+        (path.parent(), path.file_name(), entry.file_type())
+    else {
+        return;
+    };
+    if entry.depth() == 0 {
+        return;
+    }
+    let mut listing = listings
+        .entry(parent.to_path_buf())
+        .or_insert((SmallVec::new(), SmallVec::new()));
+    if file_type.is_dir() || as_dir {
+        listing.1.push(name.to_os_string());
+    } else if file_type.is_file() {
+        listing.0.push(name.to_os_string());
+    }
+}
+
+/// (files, dirs) of a directory, classified the way the walker does
+///
+/// An unreadable directory counts as empty, as it does for scan_parallel.
+pub(crate) fn read_listing(dir: &Path, follow_links: bool) -> DirContents {
+    let mut listing: DirContents = (SmallVec::new(), SmallVec::new());
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return listing;
+    };
+    for entry in entries.flatten() {
+        let Ok(mut file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_symlink() && follow_links {
+            match std::fs::metadata(entry.path()) {
+                Ok(metadata) => file_type = metadata.file_type(),
+                Err(_) => continue,
+            }
+        }
+        if file_type.is_file() {
+            listing.0.push(entry.file_name());
+        } else if file_type.is_dir() {
+            listing.1.push(entry.file_name());
+        }
+    }
+    listing
+}
+
+/// List `dir` and its subdirectories `levels` deep, enough to match `dir`
+/// against patterns of that depth
+pub(crate) fn read_subtree(dir: &Path, levels: usize, follow_links: bool, tree: &mut WalkedTree) {
+    read_subtree_from(dir, levels, follow_links, tree, &mut HashSet::new());
+}
+
+/// `read_subtree`, skipping links back to a directory in `visiting`, the
+/// canonical paths on the current branch; patterns with `**/` read with
+/// no level limit, so a link loop would never end
+fn read_subtree_from(
+    dir: &Path,
+    levels: usize,
+    follow_links: bool,
+    tree: &mut WalkedTree,
+    visiting: &mut HashSet<PathBuf>,
+) {
+    let canonical = if follow_links {
+        match std::fs::canonicalize(dir) {
+            Ok(canonical) if visiting.insert(canonical.clone()) => Some(canonical),
+            _ => return,
+        }
+    } else {
+        None
+    };
+    let listing = read_listing(dir, follow_links);
+    if levels > 0 {
+        for name in &listing.1 {
+            read_subtree_from(&dir.join(name), levels - 1, follow_links, tree, visiting);
+        }
+    }
+    tree.insert(dir.to_path_buf(), listing);
+    if let Some(canonical) = canonical {
+        visiting.remove(&canonical);
+    }
+}
+
+/// `dir`'s listings `levels` deep, if `dir` matches any of `patterns`
+fn matched_subtree(
+    dir: &Path,
+    levels: usize,
+    patterns: &[CompiledPattern],
+    follow_links: bool,
+    excluded: Option<&ExcludedRoots>,
+) -> Option<WalkedTree> {
+    let mut tree = WalkedTree::new();
+    read_subtree(dir, levels, follow_links, &mut tree);
+    if let Some(excluded) = excluded {
+        tree.retain(|listed, _| !excluded.contains(listed));
+        for (listed, (_, dirs)) in tree.iter_mut() {
+            dirs.retain(|name| !excluded.contains(&listed.join(&*name)));
+        }
+    }
+    patterns
+        .iter()
+        .any(|pattern| pattern.match_in(dir, &tree).is_some())
+        .then_some(tree)
+}
+
+/// What one evaluation worker of `scan_parallel` found
+struct Evaluation {
+    /// Directories checked against each pattern
+    evaluated: Vec<u64>,
+    /// Patterns beaten by a directory's best match, with runners_up
+    beaten: Vec<(PathBuf, Vec<usize>)>,
+    /// Directories that reach min_score without matching
+    partial: Vec<(PathBuf, usize, f64, Vec<MatchFailure>)>,
+    /// Time spent matching each pattern, with profile
+    pattern_nanos: Vec<u64>,
+    /// The slowest directories to evaluate so far, with profile
+    slowest: Vec<(PathBuf, Duration)>,
+}
+
+impl Evaluation {
+    fn new(patterns: usize) -> Self {
+        Evaluation {
+            evaluated: vec![0; patterns],
+            beaten: Vec::new(),
+            partial: Vec::new(),
+            pattern_nanos: vec![0; patterns],
+            slowest: Vec::new(),
+        }
+    }
+
+    /// Record how long a directory took, keeping only the slowest
+    fn time(&mut self, dir: &Path, took: Duration) {
+        self.slowest.push((dir.to_path_buf(), took));
+        if self.slowest.len() >= 2 * SLOWEST_DIRECTORIES {
+            self.slowest.sort_by_key(|&(_, took)| Reverse(took));
+            self.slowest.truncate(SLOWEST_DIRECTORIES);
+        }
+    }
+}
+
+/// Directories listed in a ScanProfile
+const SLOWEST_DIRECTORIES: usize = 10;
+
+/// Caps how many walk threads match directories at once
+struct EvalPermits {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+impl EvalPermits {
+    fn new(permits: usize) -> Self {
+        EvalPermits {
+            available: Mutex::new(permits),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Run `f` once a permit is free
+    fn with<T>(&self, f: impl FnOnce() -> T) -> T {
+        let mut available = self
+            .released
+            .wait_while(self.available.lock().unwrap(), |available| *available == 0)
+            .unwrap();
+        *available -= 1;
+        drop(available);
+        let result = f();
+        *self.available.lock().unwrap() += 1;
+        self.released.notify_one();
+        result
+    }
+}
+
+/// Walker configured the way every scan traverses a tree
+pub(crate) fn scan_walker(path: &str, max_depth: Option<usize>, follow_links: bool) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(path);
+    if let Some(depth) = max_depth {
+        builder.max_depth(Some(depth));
+    }
+    builder.follow_links(follow_links);
+    builder.hidden(false);
+    builder.ignore(false);
+    builder.git_ignore(false);
+    builder.git_global(false);
+    builder.git_exclude(false);
+    builder
+}
+
+/// Every pattern matching `dir`, or only the first if `first_match`
+///
+/// Empty directories are never candidates, as in scan_parallel.
+pub(crate) fn evaluate_dir(
+    dir: &Path,
+    tree: &dyn DirectoryTree,
+    patterns: &[CompiledPattern],
+    first_match: bool,
+) -> Vec<(usize, StructureMatch)> {
+    let (dirs, files) = tree.children(dir);
+    if dirs.is_empty() && files.is_empty() {
+        return Vec::new();
+    }
+    let mut matches = Vec::new();
+    for (pattern_idx, pattern) in patterns.iter().enumerate() {
+        if let Some(found) = pattern.match_in(dir, tree) {
+            matches.push((pattern_idx, found));
+            if first_match {
+                break;
+            }
+        }
+    }
+    matches
 }
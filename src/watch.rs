@@ -0,0 +1,407 @@
+use notify::{RecommendedWatcher, RecursiveMode, Watcher as _};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::file_pattern::CompiledPattern;
+use crate::walk::{compile_patterns, evaluate_dir, read_listing, ScanResult, WalkedTree};
+
+enum Message {
+    Event(notify::Result<notify::Event>),
+    Stop,
+}
+
+/// A running `watch`
+///
+/// Stops when ``stop`` is called, when used as a context manager and the
+/// block exits, or when the watcher is garbage collected.
+#[pyclass(module = "pathvein._pathvein_rs")]
+pub struct Watcher {
+    control: Mutex<Option<Sender<Message>>>,
+    thread: Mutex<Option<JoinHandle<Option<PyErr>>>>,
+    state: Arc<Mutex<WatchState>>,
+}
+
+#[pymethods]
+impl Watcher {
+    /// Every directory currently matching, as ScanResult objects
+    #[getter]
+    fn matches(&self, py: Python<'_>) -> Vec<ScanResult> {
+        py.allow_threads(|| {
+            let state = self.state.lock().unwrap();
+            state.matches.values().flatten().cloned().collect()
+        })
+    }
+
+    /// Whether changes are still being watched
+    #[getter]
+    fn is_running(&self) -> bool {
+        self.thread
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|thread| !thread.is_finished())
+    }
+
+    /// Stop watching and wait for the watch thread to exit
+    ///
+    /// Raises:
+    ///     Exception: Whatever the callback raised, if it stopped the watch
+    fn stop(&self, py: Python<'_>) -> PyResult<()> {
+        if let Some(control) = self.control.lock().unwrap().take() {
+            // Fails only if the thread already exited
+            let _ = control.send(Message::Stop);
+        }
+        let Some(thread) = self.thread.lock().unwrap().take() else {
+            return Ok(());
+        };
+        match py.allow_threads(|| thread.join()) {
+            Ok(Some(err)) => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    #[pyo3(signature = (*_args))]
+    fn __exit__(&self, py: Python<'_>, _args: &Bound<'_, pyo3::types::PyTuple>) -> PyResult<bool> {
+        self.stop(py)?;
+        Ok(false)
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        if let Some(control) = self.control.get_mut().unwrap().take() {
+            let _ = control.send(Message::Stop);
+        }
+    }
+}
+
+/// Watch a directory tree and report directories that start or stop matching
+///
+/// Scans the tree once, then listens for filesystem change events. Events
+/// are batched until none has arrived for ``debounce`` seconds; the
+/// directories they touched, and those whose patterns look into them, are
+/// then re-evaluated. When any directory starts or stops matching,
+/// ``callback(matched, unmatched)`` is called from the watch thread with
+/// two lists of ScanResult objects. Matches found by the initial scan are
+/// not reported; read them from ``Watcher.matches``.
+///
+/// Args:
+///     path: Root directory to watch
+///     pattern_jsons: List of JSON-serialized FileStructurePattern objects
+///     callback: Callable taking the newly matched and no longer matching
+///         results. An exception it raises stops the watch and is
+///         re-raised by ``Watcher.stop``.
+///     max_depth: Optional maximum depth to traverse
+///     follow_links: Whether to follow symbolic links
+///     first_match: Attribute each directory only to the first pattern, in
+///         list order, that matches it
+///     debounce: Seconds without events before a batch is evaluated
+///         (default: 0.2)
+///
+/// Returns:
+///     Watcher controlling the running watch
+///
+/// Raises:
+///     ValueError: If a pattern is invalid, debounce is negative or the
+///         path can't be watched
+#[pyfunction]
+#[pyo3(signature = (
+    path,
+    pattern_jsons,
+    callback,
+    max_depth=None,
+    follow_links=false,
+    first_match=false,
+    debounce=0.2,
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn watch(
+    py: Python<'_>,
+    path: PathBuf,
+    pattern_jsons: Vec<String>,
+    callback: PyObject,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    first_match: bool,
+    debounce: f64,
+) -> PyResult<Watcher> {
+    let patterns = compile_patterns(&pattern_jsons)?;
+    let debounce = Duration::try_from_secs_f64(debounce).map_err(|_| {
+        PyValueError::new_err(format!(
+            "debounce must be a non-negative number of seconds, got {}",
+            debounce
+        ))
+    })?;
+    let watch_error = |e: &dyn std::fmt::Display| {
+        PyValueError::new_err(format!("Cannot watch {}: {}", path.display(), e))
+    };
+    let canonical_root = fs::canonicalize(&path).map_err(|e| watch_error(&e))?;
+
+    let (control, events) = channel();
+    let event_sender = control.clone();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = event_sender.send(Message::Event(event));
+    })
+    .map_err(|e| watch_error(&e))?;
+    watcher
+        .watch(&canonical_root, RecursiveMode::Recursive)
+        .map_err(|e| watch_error(&e))?;
+
+    let state = py.allow_threads(|| {
+        let mut state = WatchState {
+            depth: patterns
+                .iter()
+                .map(CompiledPattern::depth)
+                .max()
+                .unwrap_or(0),
+            patterns,
+            root: path.clone(),
+            canonical_root,
+            max_depth,
+            follow_links,
+            first_match,
+            tree: WalkedTree::new(),
+            matches: HashMap::new(),
+        };
+        let mut listed = HashSet::new();
+        state.list_subtree(&path, &mut HashSet::new(), &mut listed);
+        state.reevaluate(listed);
+        Arc::new(Mutex::new(state))
+    });
+
+    let shared = Arc::clone(&state);
+    let thread = thread::spawn(move || {
+        // Dropping the notify watcher ends the event stream
+        let _watcher: RecommendedWatcher = watcher;
+        run(&shared, &events, &callback, debounce)
+    });
+
+    Ok(Watcher {
+        control: Mutex::new(Some(control)),
+        thread: Mutex::new(Some(thread)),
+        state,
+    })
+}
+
+/// The watch thread: batch events, apply them and report the differences
+fn run(
+    state: &Mutex<WatchState>,
+    events: &Receiver<Message>,
+    callback: &PyObject,
+    debounce: Duration,
+) -> Option<PyErr> {
+    while let Ok(Message::Event(first)) = events.recv() {
+        let mut batch = vec![first];
+        loop {
+            match events.recv_timeout(debounce) {
+                Ok(Message::Event(event)) => batch.push(event),
+                Err(RecvTimeoutError::Timeout) => break,
+                Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+        let (matched, unmatched) = state.lock().unwrap().apply(batch);
+        if matched.is_empty() && unmatched.is_empty() {
+            continue;
+        }
+        if let Err(err) = Python::with_gil(|py| callback.call1(py, (matched, unmatched))) {
+            return Some(err);
+        }
+    }
+    None
+}
+
+/// The watched tree's listings and current matches
+struct WatchState {
+    root: PathBuf,
+    /// `root` as the watcher reports it in events
+    canonical_root: PathBuf,
+    patterns: Vec<CompiledPattern>,
+    /// Subdirectory levels the deepest pattern looks into
+    depth: usize,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    first_match: bool,
+    tree: WalkedTree,
+    matches: HashMap<PathBuf, Vec<ScanResult>>,
+}
+
+impl WatchState {
+    /// Update the tree for a batch of events and return the results that
+    /// appeared and disappeared
+    fn apply(
+        &mut self,
+        batch: Vec<notify::Result<notify::Event>>,
+    ) -> (Vec<ScanResult>, Vec<ScanResult>) {
+        let mut touched = HashSet::new();
+        for event in batch.into_iter().flatten() {
+            if event.kind.is_access() {
+                continue;
+            }
+            if event.need_rescan() {
+                touched.insert(self.root.clone());
+            }
+            for path in &event.paths {
+                let Ok(relative) = path.strip_prefix(&self.canonical_root) else {
+                    continue;
+                };
+                let path = self.root.join(relative);
+                if let Some(parent) = path.parent().filter(|_| path != self.root) {
+                    touched.insert(parent.to_path_buf());
+                }
+                if self.tree.contains_key(&path) || path.is_dir() {
+                    touched.insert(path);
+                }
+            }
+        }
+
+        // Parents first, so a directory's listing decides whether its
+        // children are still part of the tree
+        let mut touched: Vec<PathBuf> = touched.into_iter().collect();
+        touched.sort_by_key(|dir| dir.components().count());
+        let mut changed = HashSet::new();
+        for dir in &touched {
+            self.relist(dir, &mut changed);
+        }
+        self.reevaluate(changed)
+    }
+
+    /// Re-read one directory's listing, following added and removed
+    /// subdirectories
+    fn relist(&mut self, dir: &Path, changed: &mut HashSet<PathBuf>) {
+        let in_tree = dir == self.root
+            || dir
+                .parent()
+                .zip(dir.file_name())
+                .is_some_and(|(parent, name)| {
+                    self.tree
+                        .get(parent)
+                        .is_some_and(|(_, dirs)| dirs.iter().any(|d| d == name))
+                });
+        if !in_tree || !self.is_listed(dir) {
+            self.remove_subtree(dir, changed);
+            return;
+        }
+        let listing = read_listing(dir, self.follow_links);
+        let previous = self.tree.insert(dir.to_path_buf(), listing.clone());
+        changed.insert(dir.to_path_buf());
+        if let Some((_, old_dirs)) = previous {
+            for name in old_dirs.iter().filter(|name| !listing.1.contains(name)) {
+                self.remove_subtree(&dir.join(name), changed);
+            }
+        }
+        for name in &listing.1 {
+            let subdir = dir.join(name);
+            if !self.tree.contains_key(&subdir) {
+                self.list_subtree(&subdir, &mut HashSet::new(), changed);
+            }
+        }
+    }
+
+    /// List a directory and everything below it, down to max_depth
+    fn list_subtree(
+        &mut self,
+        dir: &Path,
+        visiting: &mut HashSet<PathBuf>,
+        listed: &mut HashSet<PathBuf>,
+    ) {
+        if !self.is_listed(dir) {
+            return;
+        }
+        let canonical = if self.follow_links {
+            match fs::canonicalize(dir) {
+                Ok(canonical) if visiting.insert(canonical.clone()) => Some(canonical),
+                _ => return,
+            }
+        } else {
+            None
+        };
+        let listing = read_listing(dir, self.follow_links);
+        for name in &listing.1 {
+            self.list_subtree(&dir.join(name), visiting, listed);
+        }
+        self.tree.insert(dir.to_path_buf(), listing);
+        listed.insert(dir.to_path_buf());
+        if let Some(canonical) = canonical {
+            visiting.remove(&canonical);
+        }
+    }
+
+    fn remove_subtree(&mut self, dir: &Path, changed: &mut HashSet<PathBuf>) {
+        self.tree.retain(|path, _| {
+            let removed = path.starts_with(dir);
+            if removed {
+                changed.insert(path.clone());
+            }
+            !removed
+        });
+    }
+
+    /// Whether a full scan would list `dir`, given max_depth
+    fn is_listed(&self, dir: &Path) -> bool {
+        let depth = dir
+            .strip_prefix(&self.root)
+            .map_or(0, |relative| relative.components().count());
+        self.max_depth.map_or(true, |max| depth < max)
+    }
+
+    /// Re-match every directory whose patterns can see a changed listing
+    fn reevaluate(&mut self, changed: HashSet<PathBuf>) -> (Vec<ScanResult>, Vec<ScanResult>) {
+        let affected: HashSet<&Path> = changed
+            .iter()
+            .flat_map(|dir| dir.ancestors().take(self.depth.saturating_add(1)))
+            .filter(|dir| dir.starts_with(&self.root))
+            .collect();
+
+        let mut matched = Vec::new();
+        let mut unmatched = Vec::new();
+        for dir in affected {
+            let results = self.evaluate(dir);
+            let previous = self.matches.remove(dir).unwrap_or_default();
+            let had =
+                |idx: usize, results: &[ScanResult]| results.iter().any(|r| r.pattern_index == idx);
+            matched.extend(
+                results
+                    .iter()
+                    .filter(|r| !had(r.pattern_index, &previous))
+                    .cloned(),
+            );
+            unmatched.extend(
+                previous
+                    .into_iter()
+                    .filter(|r| !had(r.pattern_index, &results)),
+            );
+            if !results.is_empty() {
+                self.matches.insert(dir.to_path_buf(), results);
+            }
+        }
+        (matched, unmatched)
+    }
+
+    fn evaluate(&self, dir: &Path) -> Vec<ScanResult> {
+        let path = dir.to_string_lossy().into_owned();
+        let root = self.root.to_string_lossy().into_owned();
+        evaluate_dir(dir, &self.tree, &self.patterns, self.first_match)
+            .into_iter()
+            .map(|(pattern_idx, found)| {
+                ScanResult::new(
+                    path.clone(),
+                    root.clone(),
+                    pattern_idx,
+                    &self.patterns[pattern_idx],
+                    found,
+                )
+            })
+            .collect()
+    }
+}
@@ -1,3 +1,4 @@
+import json
 import random
 import string
 from contextlib import contextmanager
@@ -36,3 +37,13 @@ def ephemeral_s3_bucket(**config) -> Generator[UPath, None, None]:
         yield path
     finally:
         path.rmdir(recursive=True)
+
+
+def spec(**fields):
+    return json.dumps(fields)
+
+
+def touch(path, content=""):
+    path.parent.mkdir(parents=True, exist_ok=True)
+    path.write_text(content)
+    return path
@@ -0,0 +1,174 @@
+//! Runs the `pathvein` binary, which is built with `--features cli`
+
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A fresh directory under the system temp directory, removed on drop
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "pathvein-cli-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(fs::canonicalize(path).unwrap())
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn touch(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+/// `root/{run_1,run_2}` with a csv each, `run_1/raw` with a fastq, and a
+/// `notes.txt` at the top
+fn sample_tree() -> TempDir {
+    let dir = TempDir::new();
+    let root = dir.0.join("root");
+    touch(&root.join("run_1/data.csv"), "1,2");
+    touch(&root.join("run_1/raw/a.fastq"), "reads");
+    touch(&root.join("run_2/data.csv"), "3");
+    touch(&root.join("notes.txt"), "");
+    touch(
+        &dir.0.join("run.json"),
+        r#"{"pattern_name": "runs", "directory_name": "run_*", "files": ["*.csv"]}"#,
+    );
+    dir
+}
+
+fn pathvein(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pathvein"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Each line of a successful run's output, parsed as JSON
+fn ndjson(args: &[&str]) -> Vec<Value> {
+    let output = pathvein(args);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn path(path: PathBuf) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn walk_ndjson_lists_each_directory() {
+    let dir = sample_tree();
+    let root = dir.0.join("root");
+    let listings = ndjson(&["walk", "--ndjson", &path(root.clone())]);
+    assert_eq!(
+        listings,
+        vec![
+            json!({"path": path(root.clone()), "dirnames": ["run_1", "run_2"], "filenames": ["notes.txt"]}),
+            json!({"path": path(root.join("run_1")), "dirnames": ["raw"], "filenames": ["data.csv"]}),
+            json!({"path": path(root.join("run_1/raw")), "dirnames": [], "filenames": ["a.fastq"]}),
+            json!({"path": path(root.join("run_2")), "dirnames": [], "filenames": ["data.csv"]}),
+        ]
+    );
+}
+
+#[test]
+fn walk_prints_every_path() {
+    let dir = sample_tree();
+    let root = dir.0.join("root");
+    let output = pathvein(&["walk", "--max-depth", "1", &path(root.clone())]);
+    assert!(output.status.success());
+    let expected: String = ["", "notes.txt", "run_1", "run_2"]
+        .iter()
+        .map(|name| format!("{}\n", path(root.join(name)).trim_end_matches('/')))
+        .collect();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
+
+#[test]
+fn scan_ndjson_writes_each_match() {
+    let dir = sample_tree();
+    let root = dir.0.join("root");
+    let pattern = path(dir.0.join("run.json"));
+    let results = ndjson(&["scan", "--ndjson", "-p", &pattern, &path(root.clone())]);
+    let found: Vec<(&str, &str, &Value)> = results
+        .iter()
+        .map(|result| {
+            (
+                result["path"].as_str().unwrap(),
+                result["pattern_name"].as_str().unwrap(),
+                &result["matched_files"],
+            )
+        })
+        .collect();
+    let (run_1, run_2) = (path(root.join("run_1")), path(root.join("run_2")));
+    assert_eq!(
+        found,
+        vec![
+            (&*run_1, "runs", &json!({"*.csv": ["data.csv"]})),
+            (&*run_2, "runs", &json!({"*.csv": ["data.csv"]})),
+        ]
+    );
+}
+
+#[test]
+fn stats_ndjson_counts_the_tree_and_matches() {
+    let dir = sample_tree();
+    let root = dir.0.join("root");
+    let pattern = path(dir.0.join("run.json"));
+    let stats = ndjson(&["stats", "--ndjson", "-p", &pattern, &path(root.clone())]);
+    assert_eq!(
+        stats,
+        vec![json!({
+            "root": path(root),
+            "directories": 4,
+            "files": 4,
+            "bytes": 9,
+            "errors": 0,
+            "patterns": [{"index": 0, "name": "runs", "matches": 2}],
+        })]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn stats_reports_unreadable_entries() {
+    let dir = sample_tree();
+    let root = dir.0.join("root");
+    let link = root.join("link");
+    std::os::unix::fs::symlink(root.join("gone"), &link).unwrap();
+    let output = pathvein(&["stats", "--ndjson", "--follow-links", &path(root)]);
+    assert!(output.status.success());
+    let stats: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats["errors"], 1);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with(&format!("pathvein: {}: ", path(link))));
+}
+
+#[test]
+fn missing_root_fails() {
+    let dir = TempDir::new();
+    let output = pathvein(&["walk", &path(dir.0.join("missing"))]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is not a directory"));
+}
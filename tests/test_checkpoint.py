@@ -0,0 +1,71 @@
+import json
+import os
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+from tests import spec, touch
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+
+def keys(results):
+    return sorted((result.path, result.pattern_index) for result in results)
+
+
+@pytest.fixture
+def tree(tmp_path):
+    root = tmp_path / "root"
+    for name in ["a/x.csv", "a/b/y.csv", "c/z.csv", "c/d/e/w.csv"]:
+        touch(root / name)
+    return root
+
+
+def test_finds_what_scan_parallel_finds(tree, tmp_path):
+    patterns = [spec(files=["*.csv"]), spec(directories=[spec(files=["*.csv"])])]
+    checkpoint = tmp_path / "scan.checkpoint"
+    results = _pathvein_rs.scan_checkpointed(
+        str(tree), patterns, str(checkpoint), checkpoint_interval=0
+    )
+    assert keys(results) == keys(_pathvein_rs.scan_parallel(str(tree), patterns))
+    assert not checkpoint.exists()
+
+
+def test_resumes_from_a_saved_frontier(tree, tmp_path):
+    patterns = [spec(files=["*.csv"])]
+    checkpoint = tmp_path / "scan.checkpoint"
+    # A scan interrupted with only c left to visit
+    saved = {
+        "version": 1,
+        "root": str(tree),
+        "patterns": patterns,
+        "max_depth": None,
+        "follow_links": False,
+        "first_match": False,
+        "frontier": [[str(tree / "c"), 1]],
+        "matches": [],
+    }
+    checkpoint.write_text(json.dumps(saved))
+    results = _pathvein_rs.scan_checkpointed(str(tree), patterns, str(checkpoint))
+    found = sorted(os.path.relpath(result.path, tree) for result in results)
+    assert found == ["c", "c/d/e"]
+    assert not checkpoint.exists()
+
+
+def test_refuses_a_checkpoint_of_another_scan(tree, tmp_path):
+    checkpoint = str(tmp_path / "scan.checkpoint")
+    patterns = [spec(files=["*.csv"])]
+    saved = {
+        "version": 1,
+        "root": str(tree),
+        "patterns": [spec(files=["*.json"])],
+        "max_depth": None,
+        "follow_links": False,
+        "first_match": False,
+        "frontier": [],
+        "matches": [],
+    }
+    with open(checkpoint, "w") as f:
+        json.dump(saved, f)
+    with pytest.raises(ValueError, match="different scan"):
+        _pathvein_rs.scan_checkpointed(str(tree), patterns, checkpoint)
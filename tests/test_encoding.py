@@ -0,0 +1,50 @@
+import codecs
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+SAMPLES = {
+    "ascii.txt": (b"plain text\n", "utf-8"),
+    "utf8.txt": ("naïve café\n".encode("utf-8"), "utf-8"),
+    "bom.txt": (codecs.BOM_UTF8 + b"text\n", "utf-8-sig"),
+    "utf16.txt": ("text\n".encode("utf-16"), "utf-16"),
+    "utf16le.txt": ("some longer text\n".encode("utf-16-le"), "utf-16-le"),
+    "utf16be.txt": ("some longer text\n".encode("utf-16-be"), "utf-16-be"),
+    "latin1.txt": ("café crème\n".encode("latin-1"), "latin-1"),
+    "binary.bin": (bytes(range(256)), None),
+}
+
+
+def test_detect_encodings(tmp_path):
+    for name, (data, _) in SAMPLES.items():
+        (tmp_path / name).write_bytes(data)
+    detected = _pathvein_rs.detect_encodings(str(tmp_path))
+    assert detected.errors == []
+    found = {f.path: (f.is_text, f.encoding) for f in detected.files}
+    assert found == {
+        str(tmp_path / name): (encoding is not None, encoding)
+        for name, (_, encoding) in SAMPLES.items()
+    }
+    assert sorted(detected.text) == sorted(
+        str(tmp_path / name) for name, (_, encoding) in SAMPLES.items() if encoding
+    )
+
+
+def test_detect_encodings_reads_only_a_sample(tmp_path):
+    (tmp_path / "late.bin").write_bytes(b"a" * 100 + b"\x00\x01\x02")
+    [short] = _pathvein_rs.detect_encodings([str(tmp_path / "late.bin")], sample_size=50).files
+    assert short.is_text
+    [full] = _pathvein_rs.detect_encodings([str(tmp_path / "late.bin")]).files
+    assert not full.is_text
+
+
+def test_detect_encodings_reports_unreadable_files(tmp_path):
+    (tmp_path / "a.txt").write_bytes(b"text\n")
+    detected = _pathvein_rs.detect_encodings([str(tmp_path / "a.txt"), str(tmp_path / "missing")])
+    assert [f.path for f in detected.files] == [str(tmp_path / "a.txt")]
+    [error] = detected.errors
+    assert error.path == str(tmp_path / "missing")
+    assert error.reason
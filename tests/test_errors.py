@@ -0,0 +1,57 @@
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+
+def test_pattern_syntax_error_is_a_value_error():
+    assert issubclass(_pathvein_rs.PatternSyntaxError, _pathvein_rs.PathveinError)
+    assert issubclass(_pathvein_rs.PatternSyntaxError, ValueError)
+
+
+def test_pattern_error_is_not_a_syntax_error():
+    assert issubclass(_pathvein_rs.PatternError, _pathvein_rs.PathveinError)
+    assert issubclass(_pathvein_rs.PatternError, ValueError)
+    assert not issubclass(_pathvein_rs.PatternError, _pathvein_rs.PatternSyntaxError)
+
+
+def test_walk_error_is_an_os_error():
+    assert issubclass(_pathvein_rs.WalkError, _pathvein_rs.PathveinError)
+    assert issubclass(_pathvein_rs.WalkError, OSError)
+
+
+def test_invalid_glob_raises_pattern_error():
+    with pytest.raises(_pathvein_rs.PatternError) as excinfo:
+        _pathvein_rs.PatternMatcher(["*.txt", "[a"])
+    assert excinfo.value.pattern_index == 1
+    assert excinfo.value.pattern == "[a"
+
+
+def test_malformed_spec_raises_pattern_syntax_error():
+    with pytest.raises(_pathvein_rs.PatternSyntaxError, match="Invalid pattern JSON"):
+        _pathvein_rs.FileStructurePattern.from_json("{")
+
+
+def test_invalid_glob_in_match_pattern_raises_pattern_syntax_error():
+    with pytest.raises(_pathvein_rs.PatternSyntaxError):
+        _pathvein_rs.match_pattern("a", "[a")
+
+
+def test_missing_root_raises_walk_error(tmp_path):
+    missing = tmp_path / "missing"
+    with pytest.raises(_pathvein_rs.WalkError) as excinfo:
+        _pathvein_rs.scan_parallel(str(missing), ["{}"])
+    assert excinfo.value.path == str(missing)
+
+
+def test_scan_cancelled_from_progress(tmp_path):
+    (tmp_path / "a").mkdir()
+
+    def progress(*args):
+        raise _pathvein_rs.ScanCancelled()
+
+    with pytest.raises(_pathvein_rs.ScanCancelled):
+        _pathvein_rs.scan_parallel(
+            str(tmp_path), ['{"directory_name": "*"}'], progress=progress
+        )
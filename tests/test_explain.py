@@ -0,0 +1,26 @@
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+
+def test_matching_directory_has_no_failures(tmp_path):
+    (tmp_path / "config.yaml").touch()
+    pattern = _pathvein_rs.FileStructurePattern(files=["config.yaml"])
+    assert pattern.explain(str(tmp_path)) == []
+
+
+def test_missing_file_is_explained(tmp_path):
+    pattern = _pathvein_rs.FileStructurePattern(files=["config.yaml"])
+    [failure] = pattern.explain(str(tmp_path))
+    assert failure.kind == "missing_file"
+    assert failure.requirement == "config.yaml"
+
+
+def test_excluded_file_names_the_culprit(tmp_path):
+    (tmp_path / "core.dump").touch()
+    pattern = _pathvein_rs.FileStructurePattern(excluded_files=["*.dump"])
+    [failure] = pattern.explain(str(tmp_path))
+    assert failure.kind == "excluded"
+    assert failure.message == "'core.dump' matches excluded '*.dump'"
@@ -0,0 +1,80 @@
+import csv
+import json
+import os
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+from tests import spec, touch
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+
+@pytest.fixture
+def tree(tmp_path):
+    root = tmp_path / "root"
+    touch(root / "run_1" / "a.csv")
+    touch(root / "run_1" / "notes.md")
+    touch(root / "run_2" / "b.csv")
+    return root
+
+
+def run_spec():
+    return spec(
+        directory_name="run_*",
+        files=["*.csv"],
+        optional_files=["notes.md"],
+        pattern_name="runs",
+    )
+
+
+def test_export_json(tree, tmp_path):
+    results = _pathvein_rs.scan_parallel(str(tree), [run_spec()])
+    output = tmp_path / "out.json"
+    assert _pathvein_rs.export_results(list(results), str(output)) == 2
+    exported = sorted(json.loads(output.read_text()), key=lambda row: row["path"])
+    assert [row["path"] for row in exported] == [
+        str(tree / "run_1"),
+        str(tree / "run_2"),
+    ]
+    assert exported[0]["pattern_name"] == "runs"
+    assert exported[0]["optional_files"] == ["notes.md"]
+
+
+def test_export_ndjson_and_csv(tree, tmp_path):
+    results = list(_pathvein_rs.scan_parallel(str(tree), [run_spec()]))
+    ndjson = tmp_path / "out.ndjson"
+    _pathvein_rs.export_results(results, str(ndjson))
+    lines = ndjson.read_text().splitlines()
+    assert sorted(json.loads(line)["path"] for line in lines) == sorted(
+        result.path for result in results
+    )
+
+    table = tmp_path / "out.csv"
+    _pathvein_rs.export_results(results, str(table))
+    with open(table, newline="") as f:
+        rows = list(csv.DictReader(f))
+    assert sorted(row["path"] for row in rows) == sorted(result.path for result in results)
+    by_path = {os.path.basename(row["path"]): row for row in rows}
+    assert by_path["run_1"]["optional_files"] == "notes.md"
+    assert json.loads(by_path["run_2"]["matched_files"]) == {"*.csv": ["b.csv"]}
+
+
+def test_export_format_override(tree, tmp_path):
+    results = list(_pathvein_rs.scan_parallel(str(tree), [run_spec()]))
+    output = tmp_path / "out.txt"
+    _pathvein_rs.export_results(results, str(output), format="ndjson")
+    assert len(output.read_text().splitlines()) == 2
+
+
+def test_export_unknown_format(tree, tmp_path):
+    with pytest.raises(ValueError, match="Cannot infer export format"):
+        _pathvein_rs.export_results([], str(tmp_path / "out.txt"))
+
+
+def test_scan_to_file(tree, tmp_path):
+    output = tmp_path / "out.ndjson"
+    written = _pathvein_rs.scan_to_file(str(tree), [run_spec()], str(output))
+    assert written == 2
+    paths = sorted(json.loads(line)["path"] for line in output.read_text().splitlines())
+    assert paths == [str(tree / "run_1"), str(tree / "run_2")]
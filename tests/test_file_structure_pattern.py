@@ -0,0 +1,191 @@
+import json
+import pickle
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+Pattern = _pathvein_rs.FileStructurePattern if HAS_RUST_BACKEND else None
+
+
+def test_defaults():
+    pattern = Pattern()
+    assert pattern.directory_name == "*"
+    assert pattern.files == []
+    assert pattern.directories == []
+    assert pattern.optional_files == []
+    assert pattern.optional_directories == []
+    assert pattern.pattern_name is None
+
+
+def test_fields_are_settable():
+    pattern = Pattern(files=["a.txt"])
+    pattern.files = ["b.txt"]
+    pattern.directory_name = "run_*"
+    assert (pattern.directory_name, pattern.files) == ("run_*", ["b.txt"])
+
+
+def test_json_round_trip():
+    pattern = Pattern(
+        directory_name="run_*",
+        files=["*.csv"],
+        optional_files=["notes.md"],
+        directories=[Pattern(directory_name="raw")],
+    )
+    assert Pattern.from_json(pattern.to_json()) == pattern
+    assert json.loads(pattern.to_json())["directory_name"] == "run_*"
+
+
+def test_reads_python_pattern_json():
+    spec = json.dumps({"directory_name": "x", "files": ["a"]})
+    assert Pattern.from_json(spec) == Pattern(directory_name="x", files=["a"])
+
+
+def test_equality_and_hashing():
+    assert Pattern(files=["a"]) == Pattern(files=["a"])
+    assert Pattern(files=["a"]) != Pattern(files=["b"])
+    assert hash(Pattern(files=["a"])) == hash(Pattern(files=["a"]))
+
+
+def test_pickle_round_trip():
+    pattern = Pattern(directory_name="x", directories=[Pattern(files=["y"])])
+    assert pickle.loads(pickle.dumps(pattern)) == pattern
+
+
+def test_repr():
+    assert repr(Pattern(directory_name="x", files=["a"])) == (
+        'FileStructurePattern(directory_name="x", files=["a"], directories=0, '
+        "optional_files=[], optional_directories=0)"
+    )
+
+
+def test_malformed_json():
+    with pytest.raises(_pathvein_rs.PatternSyntaxError, match="Invalid pattern JSON"):
+        Pattern.from_json("[1, 2]")
+
+
+def test_scan_accepts_pattern_json(tmp_path):
+    (tmp_path / "run_1").mkdir()
+    (tmp_path / "run_1" / "a.csv").touch()
+    pattern = Pattern(directory_name="run_*", files=["*.csv"])
+    results = _pathvein_rs.scan_parallel(str(tmp_path), [pattern.to_json()])
+    assert [result.path for result in results] == [str(tmp_path / "run_1")]
+
+
+def test_from_yaml():
+    pattern = Pattern.from_yaml(
+        """
+directory_name: run_*
+files: [config.yaml]
+directories:
+  - directory_name: raw
+    files: ["*.fastq"]
+"""
+    )
+    assert pattern == Pattern(
+        directory_name="run_*",
+        files=["config.yaml"],
+        directories=[Pattern(directory_name="raw", files=["*.fastq"])],
+    )
+
+
+def test_from_yaml_reports_the_location():
+    with pytest.raises(_pathvein_rs.PatternSyntaxError, match="line 1 column 8"):
+        Pattern.from_yaml("files: 3")
+
+PYPROJECT = """
+[project]
+name = "example"
+
+[tool.pathvein.pattern]
+directory_name = "run_*"
+files = ["config.yaml"]
+
+[[tool.pathvein.pattern.directories]]
+directory_name = "raw"
+files = ["*.fastq"]
+"""
+
+
+def test_from_toml_table():
+    pattern = Pattern.from_toml(PYPROJECT, table="tool.pathvein.pattern")
+    assert pattern == Pattern(
+        directory_name="run_*",
+        files=["config.yaml"],
+        directories=[Pattern(directory_name="raw", files=["*.fastq"])],
+    )
+
+
+def test_from_toml_whole_document():
+    assert Pattern.from_toml('directory_name = "x"') == Pattern(directory_name="x")
+
+
+def test_from_toml_missing_table():
+    with pytest.raises(ValueError, match="No TOML table 'tool.missing'"):
+        Pattern.from_toml(PYPROJECT, table="tool.missing")
+
+
+def test_toml_round_trip():
+    pattern = Pattern.from_toml(PYPROJECT, table="tool.pathvein.pattern")
+    assert Pattern.from_toml(pattern.to_toml()) == pattern
+
+
+def test_malformed_toml():
+    with pytest.raises(_pathvein_rs.PatternSyntaxError, match="Invalid pattern TOML"):
+        Pattern.from_toml("files = 3")
+
+
+def test_extends_a_named_pattern():
+    base = Pattern(pattern_name="run", directory_name="run_*", files=["config.yaml"])
+    child = Pattern(extends=["run"], files=["*.fastq"])
+    resolved = child.resolve([base])
+    assert resolved.directory_name == "run_*"
+    assert resolved.files == ["config.yaml", "*.fastq"]
+    assert resolved.extends == []
+
+
+def test_extends_a_spec_file(tmp_path):
+    (tmp_path / "base.json").write_text(
+        json.dumps({"directory_name": "run_*", "files": ["config.yaml"]})
+    )
+    (tmp_path / "child.json").write_text(
+        json.dumps({"extends": "base.json", "optional_files": ["notes.md"]})
+    )
+    pattern = Pattern.load(str(tmp_path / "child.json"))
+    assert (pattern.directory_name, pattern.files) == ("run_*", ["config.yaml"])
+    assert pattern.optional_files == ["notes.md"]
+
+
+def test_extends_cycle():
+    a = Pattern(pattern_name="a", extends=["b"])
+    b = Pattern(pattern_name="b", extends=["a"])
+    with pytest.raises(ValueError):
+        a.resolve([a, b])
+
+
+def test_check_a_hypothetical_tree():
+    pattern = Pattern(
+        directory_name="run_*",
+        files=["config.yaml"],
+        directories=[Pattern(directory_name="raw", files=["*.fastq"])],
+    )
+    tree = {"config.yaml": None, "raw": {"a.fastq": 1024}}
+    assert pattern.test(tree)
+    assert pattern.test(tree, name="run_1")
+    assert not pattern.test(tree, name="other")
+    assert not pattern.test({"config.yaml": None, "raw": {}})
+
+
+def test_hypothetical_sizes_and_contents():
+    limits = _pathvein_rs.FileConstraint(min_size=10)
+    pattern = Pattern(files=["a.bin"], file_constraints={"a.bin": limits})
+    assert pattern.test({"a.bin": 100})
+    assert pattern.test({"a.bin": b"0123456789"})
+    assert not pattern.test({"a.bin": "short"})
+
+
+def test_hypothetical_tree_keys_must_be_strings():
+    with pytest.raises(TypeError):
+        Pattern().test({1: None})
@@ -0,0 +1,36 @@
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+SAMPLES = {
+    "image.png": (b"\x89PNG\r\n\x1a\n" + b"\x00" * 8, "png", "image/png"),
+    "doc.pdf": (b"%PDF-1.7\n", "pdf", "application/pdf"),
+    "reads.gz": (b"\x1f\x8b\x08\x00" + b"\x00" * 8, "gzip", "application/gzip"),
+    "notes.txt": (b"just text", None, None),
+    "empty": (b"", None, None),
+}
+
+
+def write_samples(root):
+    for name, (data, _, _) in SAMPLES.items():
+        (root / name).write_bytes(data)
+
+
+def test_detect_types_in_a_directory(tmp_path):
+    write_samples(tmp_path)
+    detected = _pathvein_rs.detect_types(str(tmp_path))
+    assert detected.errors == []
+    assert [f.path for f in detected.files] == sorted(str(tmp_path / name) for name in SAMPLES)
+    found = {f.path: (f.name, f.mime) for f in detected.files}
+    assert found == {str(tmp_path / name): (kind, mime) for name, (_, kind, mime) in SAMPLES.items()}
+
+
+def test_detect_types_of_listed_files(tmp_path):
+    write_samples(tmp_path)
+    detected = _pathvein_rs.detect_types([str(tmp_path / "doc.pdf"), str(tmp_path / "missing")])
+    assert [(f.path, f.name) for f in detected.files] == [(str(tmp_path / "doc.pdf"), "pdf")]
+    [error] = detected.errors
+    assert error.path == str(tmp_path / "missing")
+    assert str(error) == f"{error.path}: {error.reason}"
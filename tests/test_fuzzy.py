@@ -0,0 +1,50 @@
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+CANDIDATES = ["config.yaml", "conftest.py", "README.md", "readme.txt"]
+
+
+def test_exact_match_scores_one():
+    matcher = _pathvein_rs.FuzzyMatcher(CANDIDATES)
+    assert matcher.best_matches("config.yaml")[0] == ("config.yaml", 1.0)
+
+
+def test_subsequence_mode_ranks_best_first():
+    matcher = _pathvein_rs.FuzzyMatcher(CANDIDATES)
+    names = [name for name, _ in matcher.best_matches("cfg")]
+    assert names[0] == "config.yaml"
+    assert "README.md" not in names
+
+
+def test_limit_truncates_results():
+    matcher = _pathvein_rs.FuzzyMatcher(CANDIDATES)
+    assert len(matcher.best_matches("e", limit=2)) == 2
+
+
+def test_case_insensitive_by_default():
+    matcher = _pathvein_rs.FuzzyMatcher(CANDIDATES)
+    names = [name for name, _ in matcher.best_matches("readme")]
+    assert {"README.md", "readme.txt"} <= set(names)
+
+
+def test_case_sensitive():
+    matcher = _pathvein_rs.FuzzyMatcher(CANDIDATES, case_sensitive=True)
+    names = [name for name, _ in matcher.best_matches("readme")]
+    assert names == ["readme.txt"]
+
+
+def test_edit_mode_bounds_distance():
+    matcher = _pathvein_rs.FuzzyMatcher(CANDIDATES, mode="edit", max_distance=1)
+    assert [name for name, _ in matcher.best_matches("README.mf")] == ["README.md"]
+
+
+def test_unknown_mode_raises():
+    with pytest.raises(ValueError):
+        _pathvein_rs.FuzzyMatcher(CANDIDATES, mode="phonetic")
+
+
+def test_len():
+    assert len(_pathvein_rs.FuzzyMatcher(CANDIDATES)) == 4
@@ -0,0 +1,69 @@
+import re
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+# `?` and character classes are left out: globset matches them against
+# single bytes, so they can't agree with a character regex on non-ASCII names
+PATTERNS = [
+    "*.txt",
+    "é*.txt",
+    "日*",
+    "**/*.txt",
+    "a/**",
+    "é/*",
+    "{a,b}.c",
+    "{é,日}*",
+    "[!a].c",
+    "\\*",
+    "*",
+]
+
+NAMES = [
+    "x.txt",
+    "éa.txt",
+    "a\nb.txt",
+    "a/b.txt",
+    "é/日.txt",
+    "日本.txt",
+    "a.c",
+    "b.c",
+    "é\n",
+    "\n",
+    "*",
+    "a",
+]
+
+
+@pytest.mark.parametrize("pattern", PATTERNS)
+@pytest.mark.parametrize("dialect", ["python", "pcre"])
+def test_translated_regex_agrees_with_match_pattern(pattern: str, dialect: str):
+    regex = _pathvein_rs.glob_to_regex(pattern, dialect)
+    # Python's `re` spells PCRE's strict end anchor \Z
+    compiled = re.compile(regex.replace("\\z", "\\Z"))
+    for name in NAMES:
+        expected = _pathvein_rs.match_pattern(name, pattern)
+        assert bool(compiled.match(name)) == expected, (pattern, name, regex)
+
+
+def test_non_ascii_literals_are_characters():
+    assert _pathvein_rs.glob_to_regex("é*.txt", "python") == "(?s)^é.*\\.txt\\Z"
+    assert _pathvein_rs.glob_to_regex("é*.txt", "pcre") == "(?s)^é.*\\.txt\\z"
+    assert _pathvein_rs.glob_to_regex("é*.txt", "postgres") == "^é.*\\.txt$"
+
+
+def test_rust_dialect_is_globset_regex():
+    assert _pathvein_rs.glob_to_regex("é*.txt") == "(?-u)^\\xc3\\xa9.*\\.txt$"
+
+
+def test_newlines_match_wildcards():
+    assert _pathvein_rs.match_pattern("a\nb.txt", "*.txt")
+    assert re.match(_pathvein_rs.glob_to_regex("*.txt", "python"), "a\nb.txt")
+
+
+def test_unknown_dialect_raises():
+    with pytest.raises(ValueError, match="Unknown regex dialect"):
+        _pathvein_rs.glob_to_regex("*.txt", "perl")
@@ -0,0 +1,62 @@
+import os
+import sys
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+from tests import touch
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+
+@pytest.fixture
+def logs(tmp_path):
+    touch(tmp_path / "a.log", "start\nERROR disk full\nok\nerror retry\n")
+    touch(tmp_path / "sub" / "b.log", "ERROR one\nERROR two\nERROR three\n")
+    touch(tmp_path / "sub" / "c.txt", "ERROR in text\n")
+    touch(tmp_path / "clean.log", "all good\n")
+    (tmp_path / "blob.log").write_bytes(b"ERROR\x00\x01\x02")
+    return tmp_path
+
+
+def hits(results, root):
+    return {
+        os.path.relpath(f.path, root): [(line.line_number, line.line) for line in f.lines]
+        for f in results.files
+    }
+
+
+def test_grep_tree(logs):
+    results = _pathvein_rs.grep_tree(str(logs), "ERROR", include=["*.log"])
+    assert results.root == str(logs)
+    assert results.errors == []
+    assert hits(results, logs) == {
+        "a.log": [(2, "ERROR disk full")],
+        os.path.join("sub", "b.log"): [(1, "ERROR one"), (2, "ERROR two"), (3, "ERROR three")],
+    }
+
+
+def test_grep_tree_options(logs):
+    results = _pathvein_rs.grep_tree(
+        str(logs), "^error", ignore_case=True, max_count=1, exclude=["sub"]
+    )
+    assert hits(results, logs) == {"a.log": [(2, "ERROR disk full")]}
+    shallow = _pathvein_rs.grep_tree(str(logs), "ERROR", max_depth=1)
+    assert list(hits(shallow, logs)) == ["a.log"]
+
+
+@pytest.mark.skipif(sys.platform == "win32", reason="needs symlinks")
+def test_grep_tree_reports_unreadable_entries(logs):
+    os.symlink(logs / "gone.log", logs / "link.log")
+    results = _pathvein_rs.grep_tree(str(logs), "ERROR", include=["*.log"], follow_links=True)
+    assert [error.path for error in results.errors] == [str(logs / "link.log")]
+    assert "a.log" in hits(results, logs)
+
+
+def test_grep_tree_invalid_arguments(logs):
+    with pytest.raises(ValueError):
+        _pathvein_rs.grep_tree(str(logs), "(")
+    with pytest.raises(ValueError):
+        _pathvein_rs.grep_tree(str(logs), "x", max_count=0)
+    with pytest.raises(_pathvein_rs.WalkError):
+        _pathvein_rs.grep_tree(str(logs / "missing"), "x")
@@ -0,0 +1,60 @@
+import os
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+from tests import spec, touch
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+
+def keys(results):
+    return sorted((result.path, result.pattern_index) for result in results)
+
+
+def test_first_run_matches_scan_parallel(tmp_path):
+    root = tmp_path / "root"
+    touch(root / "a" / "x.csv")
+    touch(root / "b" / "c" / "y.csv")
+    touch(root / "d" / "z.txt")
+    patterns = [spec(files=["*.csv"]), spec(directories=[spec(files=["*.csv"])])]
+    cache = tmp_path / "scan.cache"
+    results = _pathvein_rs.scan_incremental(str(root), patterns, str(cache))
+    assert keys(results) == keys(_pathvein_rs.scan_parallel(str(root), patterns))
+    assert cache.exists()
+
+
+def test_rescan_sees_changes(tmp_path):
+    root = tmp_path / "root"
+    touch(root / "a" / "x.csv")
+    touch(root / "b" / "x.txt")
+    patterns = [spec(files=["*.csv"])]
+    cache = str(tmp_path / "scan.cache")
+    first = _pathvein_rs.scan_incremental(str(root), patterns, cache)
+    assert [os.path.basename(result.path) for result in first] == ["a"]
+
+    touch(root / "b" / "y.csv")
+    os.remove(root / "a" / "x.csv")
+    second = _pathvein_rs.scan_incremental(str(root), patterns, cache)
+    assert [os.path.basename(result.path) for result in second] == ["b"]
+
+
+def test_unchanged_rescan_gives_the_same_results(tmp_path):
+    root = tmp_path / "root"
+    for name in ["a/x.csv", "a/b/y.csv", "c/z.csv"]:
+        touch(root / name)
+    patterns = [spec(files=["*.csv"], pattern_name="csv")]
+    cache = str(tmp_path / "scan.cache")
+    first = _pathvein_rs.scan_incremental(str(root), patterns, cache)
+    second = _pathvein_rs.scan_incremental(str(root), patterns, cache)
+    assert keys(second) == keys(first)
+    assert len(first) == 3
+
+
+def test_unreadable_cache_is_ignored(tmp_path):
+    root = tmp_path / "root"
+    touch(root / "a" / "x.csv")
+    cache = tmp_path / "scan.cache"
+    cache.write_bytes(b"not a cache")
+    results = _pathvein_rs.scan_incremental(str(root), [spec(files=["*.csv"])], str(cache))
+    assert len(results) == 1
@@ -0,0 +1,39 @@
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+from tests import touch
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+
+def test_infer_generalizes_numbers_and_dates(tmp_path):
+    example = tmp_path / "run_001"
+    touch(example / "2024-01-31_summary.csv")
+    touch(example / "lane_1" / "reads.fastq")
+    touch(example / "lane_2" / "reads.fastq")
+    touch(example / "lane_2" / "extra.log")
+    pattern = _pathvein_rs.infer_pattern(str(example))
+    assert pattern.directory_name == "run_*"
+    assert pattern.files == ["*_summary.csv"]
+    [lanes] = pattern.directories
+    assert lanes.directory_name == "lane_*"
+    assert lanes.files == ["reads.fastq"]
+
+
+def test_infer_without_generalizing(tmp_path):
+    example = tmp_path / "run_001"
+    touch(example / "a_1.csv")
+    touch(example / "sub" / "b.txt")
+    pattern = _pathvein_rs.infer_pattern(str(example), generalize=False, max_depth=0)
+    assert pattern.directory_name == "run_001"
+    assert pattern.files == ["a_1.csv"]
+    assert pattern.directories == []
+
+
+def test_inferred_pattern_matches_its_example(tmp_path):
+    example = tmp_path / "run_7"
+    touch(example / "config.yaml")
+    touch(example / "raw" / "x_1.fastq")
+    pattern = _pathvein_rs.infer_pattern(str(example))
+    results = _pathvein_rs.scan_parallel(str(tmp_path), [pattern.to_json()])
+    assert str(example) in [result.path for result in results]
@@ -0,0 +1,115 @@
+import hashlib
+import os
+import sys
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+from tests import touch
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+# BLAKE3 of the empty input and of b"abc", from the reference test vectors
+BLAKE3_EMPTY = "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+BLAKE3_ABC = "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+
+
+def sample_tree(root):
+    touch(root / "empty.txt")
+    touch(root / "sub" / "abc.txt", "abc")
+    touch(root / "sub" / "deeper" / "big.bin", "x" * 100_000)
+    return root
+
+
+def test_hash_tree_blake3(tmp_path):
+    sample_tree(tmp_path)
+    hashes = _pathvein_rs.hash_tree(str(tmp_path))
+    assert hashes.algorithm == "blake3"
+    assert hashes.errors == []
+    assert [(f.path, f.size) for f in hashes.files] == [
+        ("empty.txt", 0),
+        (os.path.join("sub", "abc.txt"), 3),
+        (os.path.join("sub", "deeper", "big.bin"), 100_000),
+    ]
+    digests = hashes.digests()
+    assert digests["empty.txt"] == BLAKE3_EMPTY
+    assert digests[os.path.join("sub", "abc.txt")] == BLAKE3_ABC
+
+
+def test_hash_tree_sha256(tmp_path):
+    sample_tree(tmp_path)
+    hashes = _pathvein_rs.hash_tree(str(tmp_path), algorithm="sha256", threads=2)
+    assert hashes.digests() == {
+        "empty.txt": hashlib.sha256(b"").hexdigest(),
+        os.path.join("sub", "abc.txt"): hashlib.sha256(b"abc").hexdigest(),
+        os.path.join("sub", "deeper", "big.bin"): hashlib.sha256(b"x" * 100_000).hexdigest(),
+    }
+
+
+def test_hash_tree_walk_options(tmp_path):
+    sample_tree(tmp_path)
+    shallow = _pathvein_rs.hash_tree(str(tmp_path), max_depth=2)
+    assert [f.path for f in shallow.files] == ["empty.txt", os.path.join("sub", "abc.txt")]
+    excluded = _pathvein_rs.hash_tree(str(tmp_path), exclude=["deeper"])
+    assert len(excluded.files) == 2
+
+
+@pytest.mark.skipif(sys.platform == "win32", reason="needs symlinks")
+def test_hash_tree_reports_unreadable_entries(tmp_path):
+    touch(tmp_path / "a.txt")
+    os.symlink(tmp_path / "gone", tmp_path / "link")
+    hashes = _pathvein_rs.hash_tree(str(tmp_path), follow_links=True)
+    assert [f.path for f in hashes.files] == ["a.txt"]
+    [error] = hashes.errors
+    assert error.path == str(tmp_path / "link")
+    assert str(error) == f"{error.path}: {error.reason}"
+
+
+def test_hash_tree_invalid_arguments(tmp_path):
+    with pytest.raises(ValueError):
+        _pathvein_rs.hash_tree(str(tmp_path), algorithm="md5")
+    with pytest.raises(ValueError):
+        _pathvein_rs.hash_tree(str(tmp_path), threads=0)
+
+
+@pytest.mark.parametrize(
+    "name, digest",
+    [("SHA256SUMS", hashlib.sha256(b"abc").hexdigest()), ("B3SUMS", BLAKE3_ABC)],
+)
+def test_write_checksums(tmp_path, name, digest):
+    root = sample_tree(tmp_path / "data")
+    output = tmp_path / name
+    _pathvein_rs.write_checksums(str(root), str(output))
+    lines = output.read_text().splitlines()
+    assert len(lines) == 3
+    assert f"{digest}  sub/abc.txt" in lines
+
+
+def test_verify_checksums_finds_every_change(tmp_path):
+    root = sample_tree(tmp_path)
+    manifest = tmp_path / "SHA256SUMS"
+    _pathvein_rs.write_checksums(str(root), str(manifest))
+    verification = _pathvein_rs.verify_checksums(str(manifest))
+    assert verification.algorithm == "sha256"
+    assert verification.missing == verification.extra == verification.corrupted == []
+    assert len(verification.matched) == 3
+    assert verification.ok
+
+    touch(root / "sub" / "abc.txt", "abd")
+    (root / "empty.txt").unlink()
+    touch(root / "new.txt", "new")
+    verification = _pathvein_rs.verify_checksums(str(manifest))
+    assert verification.corrupted == ["sub/abc.txt"]
+    assert verification.missing == ["empty.txt"]
+    assert verification.extra == ["new.txt"]
+    assert not verification.ok
+
+
+def test_checksum_algorithm_from_the_name(tmp_path):
+    root = sample_tree(tmp_path / "data")
+    with pytest.raises(ValueError):
+        _pathvein_rs.write_checksums(str(root), str(tmp_path / "CHECKSUMS"))
+    output = tmp_path / "CHECKSUMS"
+    _pathvein_rs.write_checksums(str(root), str(output), algorithm="blake3")
+    verification = _pathvein_rs.verify_checksums(str(output), root=str(root), algorithm="blake3")
+    assert verification.ok
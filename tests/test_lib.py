@@ -276,6 +276,14 @@ def test_scan_local_fs():
     assert result == {ScanResult(match, pattern)}
 
 
+def test_scan_stream_local_fs():
+    pattern = FileStructurePattern("tests", files=["strategies.py"])
+    match = Path("tests").resolve()
+    result = scan(match, [pattern], stream=True)
+    assert not isinstance(result, set)
+    assert list(result) == [ScanResult(match, pattern)]
+
+
 def test_shuffle_simple():
     with isolated_memory_filesystem():
         filename = "file.txt"
@@ -0,0 +1,59 @@
+import json
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+
+def write_spec(path, **fields):
+    path.write_text(json.dumps(fields))
+    return str(path)
+
+
+def kinds(diagnostics):
+    return [(d.kind, d.pattern_name) for d in diagnostics]
+
+
+def test_clean_library(tmp_path):
+    write_spec(tmp_path / "a.json", pattern_name="a", files=["*.csv"])
+    write_spec(tmp_path / "b.json", pattern_name="b", files=["*.json"])
+    assert _pathvein_rs.lint_patterns(str(tmp_path)) == []
+
+
+def test_duplicates_and_conflicting_names(tmp_path):
+    first = write_spec(tmp_path / "a.json", pattern_name="a", files=["*.csv"])
+    write_spec(tmp_path / "b.json", pattern_name="b", files=["*.csv"])
+    write_spec(tmp_path / "c.json", pattern_name="a", files=["*.txt"])
+    diagnostics = _pathvein_rs.lint_patterns(str(tmp_path), strategy="all")
+    assert kinds(diagnostics) == [("duplicate", "b"), ("conflicting_name", "a")]
+    assert all(d.related == first for d in diagnostics)
+
+
+def test_unreachable_pattern(tmp_path):
+    broad = write_spec(tmp_path / "a.json", pattern_name="broad", files=["*.csv"])
+    narrow = write_spec(
+        tmp_path / "b.json", pattern_name="narrow", files=["data.csv", "x.json"]
+    )
+    [diagnostic] = _pathvein_rs.lint_patterns([broad, narrow])
+    assert (diagnostic.kind, diagnostic.source, diagnostic.related) == (
+        "unreachable",
+        narrow,
+        broad,
+    )
+    assert _pathvein_rs.lint_patterns([broad, narrow], strategy="all") == []
+
+
+def test_invalid_specs(tmp_path):
+    bad_glob = write_spec(tmp_path / "a.json", files=["[a"])
+    unknown = write_spec(tmp_path / "b.json", filez=["*.csv"])
+    broken = tmp_path / "c.json"
+    broken.write_text("{")
+    diagnostics = _pathvein_rs.lint_patterns([bad_glob, unknown, str(broken)])
+    assert [d.kind for d in diagnostics] == ["invalid", "unknown_key", "invalid"]
+
+
+def test_unknown_strategy(tmp_path):
+    with pytest.raises(ValueError):
+        _pathvein_rs.lint_patterns(str(tmp_path), strategy="sometimes")
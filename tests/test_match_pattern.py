@@ -0,0 +1,67 @@
+import threading
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+PATHS = ["a.py", "src/b.py", "c.txt", "src/pkg/d.py", "", "é.py"]
+
+
+@pytest.mark.parametrize("pattern", ["*.py", "src/**/*.py", "?.txt", "{a,c}.*"])
+@pytest.mark.parametrize("dialect", ["globset", "pathlib"])
+def test_match_pattern_many_agrees_with_match_pattern(pattern, dialect):
+    expected = [
+        _pathvein_rs.match_pattern(path, pattern, dialect=dialect) for path in PATHS
+    ]
+    assert _pathvein_rs.match_pattern_many(PATHS, pattern, dialect=dialect) == expected
+
+
+def test_match_pattern_many_depth():
+    paths = ["x/data", "y/x/data"]
+    assert _pathvein_rs.match_pattern_many(paths, "**/data", depth=2) == [True, False]
+
+
+def test_match_pattern_many_empty():
+    assert _pathvein_rs.match_pattern_many([], "*.py") == []
+
+
+def test_match_pattern_many_invalid_pattern():
+    with pytest.raises(ValueError, match="Invalid glob pattern"):
+        _pathvein_rs.match_pattern_many(["a"], "[a")
+
+
+def test_cache_counts_hits_and_misses():
+    _pathvein_rs.match_pattern_cache_clear()
+    assert _pathvein_rs.match_pattern_cache_info().misses == 0
+    _pathvein_rs.match_pattern("a", "*")
+    _pathvein_rs.match_pattern("b", "*")
+    _pathvein_rs.match_pattern("a", "b*")
+    info = _pathvein_rs.match_pattern_cache_info()
+    assert (info.hits, info.shared_hits, info.misses) == (1, 0, 2)
+    assert info.maxsize == 256
+
+
+def test_other_threads_hit_the_shared_cache():
+    _pathvein_rs.match_pattern_cache_clear()
+    _pathvein_rs.match_pattern("a", "*.cache")
+    thread = threading.Thread(target=_pathvein_rs.match_pattern, args=("a", "*.cache"))
+    thread.start()
+    thread.join()
+    info = _pathvein_rs.match_pattern_cache_info()
+    assert (info.shared_hits, info.misses) == (1, 1)
+
+
+def test_cache_clear_empties_every_thread():
+    _pathvein_rs.match_pattern("a", "*.cleared")
+    _pathvein_rs.match_pattern_cache_clear()
+    _pathvein_rs.match_pattern("a", "*.cleared")
+    assert _pathvein_rs.match_pattern_cache_info().misses == 1
+
+
+def test_dialects_are_cached_separately():
+    _pathvein_rs.match_pattern_cache_clear()
+    assert _pathvein_rs.match_pattern("a/b", "a/**", dialect="pathlib")
+    assert _pathvein_rs.match_pattern("a/b", "a/**")
+    assert _pathvein_rs.match_pattern_cache_info().misses == 2
@@ -0,0 +1,95 @@
+import sys
+from pathlib import PurePosixPath
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+
+pytestmark = [
+    pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend"),
+    pytest.mark.skipif(
+        sys.version_info < (3, 13), reason="PurePath.full_match is new in 3.13"
+    ),
+]
+
+# Cases adapted from CPython's test_pathlib full_match tests, plus the
+# edge cases where globset and pathlib disagree (leading **/, trailing /).
+PATTERNS = [
+    "a",
+    "b.py",
+    "*.py",
+    "a/*.py",
+    "/*.py",
+    "/a/*.py",
+    "**",
+    "**/",
+    "**/*.py",
+    "/**/*.py",
+    "a/**",
+    "a/**/b",
+    "x/**/**/y",
+    "*",
+    "*/",
+    "*/*",
+    "a/*/c",
+    "a**b",
+    "?",
+    "[a-c]*",
+    "[!a]?",
+    "[z-a]x",
+    "[]]",
+    "[!]]",
+    "[a-]",
+    "[^a]",
+    "a[",
+    "{a,b}",
+    "a/b/",
+    "a//b",
+    "a/./b",
+    "//a",
+]
+
+PATHS = [
+    "",
+    "a",
+    "a/",
+    "b",
+    "ab",
+    "xb",
+    "za",
+    "]",
+    "^",
+    "-",
+    ".hid",
+    "a.py",
+    "b.py",
+    "a/b",
+    "a/b/",
+    "a/b.py",
+    "a/b/c.py",
+    "a/x/c",
+    "a/x/y/c",
+    "a/xxb",
+    "axxb",
+    "x/y",
+    "x/a/b/y",
+    "{a,b}",
+    "a[",
+    "/a.py",
+    "/a/b.py",
+    "//a",
+    "///a",
+    "./a",
+    "a//b",
+]
+
+
+@pytest.mark.parametrize("pattern", PATTERNS)
+def test_pathlib_dialect_matches_cpython(pattern: str):
+    matcher = _pathvein_rs.PatternMatcher([pattern], dialect="pathlib")
+    for path in PATHS:
+        expected = PurePosixPath(path).full_match(pattern)
+        assert matcher.matches(path) == expected, (pattern, path)
+        assert (
+            _pathvein_rs.match_pattern(path, pattern, dialect="pathlib") == expected
+        ), (pattern, path)
@@ -0,0 +1,443 @@
+import pickle
+import sys
+import warnings
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+
+def matcher(*patterns, **options):
+    return _pathvein_rs.PatternMatcher(list(patterns), **options)
+
+
+@pytest.mark.parametrize(
+    "path, depth, expected",
+    [
+        ("raw/data", 2, True),
+        ("a/raw/data", 2, False),
+        ("a/raw/data", 3, True),
+        ("raw/data", None, True),
+        ("raw/data", 1, False),
+    ],
+)
+def test_depth_anchors_matches(path, depth, expected):
+    assert matcher("*/data").matches(path, depth=depth) is expected
+    assert _pathvein_rs.match_pattern(path, "*/data", depth=depth) is expected
+
+
+def test_depth_applies_to_every_query():
+    m = matcher("*.txt", "**/*.csv")
+    assert m.matching_patterns("a/b.csv", depth=2) == ["**/*.csv"]
+    assert m.matching_patterns("a/b.csv", depth=1) == []
+    assert m.matches_count("a.txt", depth=1) == 1
+    assert m.first_match("a/b.csv", depth=3) is None
+
+
+
+def test_to_bytes_round_trip():
+    original = matcher(
+        "*.py", "/build/", "docs/**", full_path=True, case_fold="unicode"
+    )
+    restored = _pathvein_rs.PatternMatcher.from_bytes(original.to_bytes())
+    assert restored == original
+    for path in ["a.PY", "build", "x/build", "docs/a/b.md", "src/docs/a"]:
+        for is_dir in [False, True]:
+            assert restored.matches(path, is_dir=is_dir) == original.matches(
+                path, is_dir=is_dir
+            )
+
+
+def test_pickle_round_trip():
+    original = matcher("*.py", "test_*")
+    restored = pickle.loads(pickle.dumps(original))
+    assert list(restored) == ["*.py", "test_*"]
+    assert restored.matches("test_a.txt")
+
+
+@pytest.mark.parametrize(
+    "data, message",
+    [
+        (b"not a matcher", "Not a serialized PatternMatcher"),
+        (b"PVPM", "Truncated"),
+        (b"PVPM\x63[]", "Unsupported PatternMatcher format version 99"),
+    ],
+)
+def test_from_bytes_rejects_bad_data(data, message):
+    with pytest.raises(ValueError, match=message):
+        _pathvein_rs.PatternMatcher.from_bytes(data)
+
+
+def test_from_bytes_rejects_corrupt_payload():
+    data = matcher("*.py").to_bytes()
+    with pytest.raises(ValueError, match="Corrupt serialized PatternMatcher"):
+        _pathvein_rs.PatternMatcher.from_bytes(data[:-3])
+
+
+def test_literal_patterns_match_exactly():
+    m = matcher("Makefile", "README.md", "*.py")
+    assert m.matches("Makefile")
+    assert m.matches("README.md")
+    assert not m.matches("README.mdx")
+    assert not m.matches("src/Makefile")
+    assert m.matches("setup.py")
+
+
+def test_literals_and_globs_report_in_pattern_order():
+    m = matcher("*.md", "README.md", "README*", "README.md")
+    assert m.matching_patterns("README.md") == [
+        "*.md",
+        "README.md",
+        "README*",
+        "README.md",
+    ]
+    assert m.matches_count("README.md") == 4
+    assert m.first_match("README.md") == "*.md"
+    assert matcher("README.md", "*.md").first_match("README.md") == "README.md"
+
+
+def test_large_literal_set():
+    names = [f"file_{i:05}.dat" for i in range(20000)]
+    m = matcher(*names, "*.tmp")
+    assert m.matches("file_12345.dat")
+    assert not m.matches("file_20000.dat")
+    assert m.first_match("file_00042.dat") == "file_00042.dat"
+    assert m.matches("x.tmp")
+
+
+def test_literals_honour_case_folding():
+    m = matcher("Straße.txt", case_fold="unicode")
+    assert m.matches("STRASSE.TXT")
+
+
+def test_unanchored_literals_match_at_any_depth_in_full_path_mode():
+    m = matcher("Makefile", "/setup.py", full_path=True)
+    assert m.matches("a/b/Makefile")
+    assert m.matches("setup.py")
+    assert not m.matches("a/setup.py")
+
+
+def test_normalize_separators_matches_backslash_paths():
+    m = matcher("src/**/*.py", normalize_separators=True)
+    assert m.matches("src\\pkg\\mod.py")
+    assert m.matches("src/pkg/mod.py")
+    assert m.matches("src\\pkg/mod.py")
+
+
+def test_separators_kept_when_not_normalizing():
+    m = matcher("src/**/*.py", normalize_separators=False)
+    assert not m.matches("src\\pkg\\mod.py")
+
+
+def test_normalize_separators_in_match_pattern():
+    assert _pathvein_rs.match_pattern("a\\b.txt", "a/*.txt", normalize_separators=True)
+    assert not _pathvein_rs.match_pattern(
+        "a\\b.txt", "a/*.txt", normalize_separators=False
+    )
+    assert _pathvein_rs.match_pattern_many(
+        ["a\\b.txt", "b\\b.txt"], "a/*.txt", normalize_separators=True
+    ) == [True, False]
+
+
+@pytest.mark.skipif(sys.platform == "win32", reason="normalizes by default on Windows")
+def test_separators_not_normalized_by_default():
+    assert not matcher("a/*.txt").matches("a\\b.txt")
+    assert not _pathvein_rs.match_pattern("a\\b.txt", "a/*.txt")
+
+
+def test_first_match_returns_earliest_listed_pattern():
+    m = matcher("*.tmp", "build/**", "**/*.py", "*")
+    assert m.first_match("build/gen.py") == "build/**"
+    assert m.first_match("src/mod.py") == "**/*.py"
+    assert m.first_match("notes") == "*"
+    assert matcher("*.py").first_match("notes") is None
+
+
+def test_first_match_agrees_with_matching_patterns():
+    m = matcher("a*", "*b", "ab", "[a-z]b", "{x,ab}")
+    for path in ["ab", "ax", "xb", "zz"]:
+        found = m.matching_patterns(path)
+        assert m.first_match(path) == (found[0] if found else None)
+
+
+def test_first_match_with_profile():
+    m = matcher("*.py", "a*", profile=True)
+    assert m.first_match("a.py") == "*.py"
+
+
+@pytest.mark.parametrize(
+    "pattern, path, expected",
+    [
+        ("/README.md", "README.md", True),
+        ("/README.md", "docs/README.md", False),
+        ("README.md", "docs/README.md", True),
+        ("*.log", "a/b/debug.log", True),
+        ("/*.log", "a/debug.log", False),
+        ("docs/*.md", "docs/a.md", True),
+        ("docs/*.md", "x/docs/a.md", False),
+        ("/docs/*.md", "docs/a.md", True),
+    ],
+)
+def test_full_path_anchoring(pattern, path, expected):
+    assert matcher(pattern, full_path=True).matches(path) is expected
+
+
+def test_full_path_strips_leading_slash_from_candidates():
+    m = matcher("/README.md", full_path=True)
+    assert m.matches("/README.md")
+
+
+def test_leading_slash_is_literal_without_full_path():
+    m = matcher("/README.md")
+    assert m.matches("/README.md")
+    assert not m.matches("README.md")
+
+
+def test_trailing_slash_patterns_only_match_directories():
+    m = matcher("build/", "*.py")
+    assert m.matches("build", is_dir=True)
+    assert m.matches("build/")
+    assert not m.matches("build")
+    assert m.matching_patterns("build", is_dir=True) == ["build/"]
+    assert m.matching_patterns("build") == []
+
+
+def test_directory_candidates_still_match_plain_patterns():
+    m = matcher("build/", "build*")
+    assert m.matching_patterns("build/") == ["build/", "build*"]
+    assert m.first_match("build") == "build*"
+    assert m.matches_count("build", is_dir=True) == 2
+
+
+def test_directory_only_patterns_with_full_path():
+    m = matcher("node_modules/", full_path=True)
+    assert m.matches("a/b/node_modules", is_dir=True)
+    assert not m.matches("a/b/node_modules")
+
+
+def test_directory_only_patterns_in_matches_all():
+    m = matcher("out/", "o*")
+    assert m.matches_all("out", is_dir=True)
+    assert not m.matches_all("out")
+
+
+def test_union_keeps_first_seen_order_without_duplicates():
+    combined = matcher("*.py", "*.md") | matcher("*.md", "*.txt")
+    assert list(combined) == ["*.py", "*.md", "*.txt"]
+    assert combined.matches("a.txt")
+
+
+def test_intersection():
+    both = matcher("*.py", "*.md", "*.rs") & matcher("*.rs", "*.py")
+    assert list(both) == ["*.py", "*.rs"]
+    assert not both.matches("a.md")
+
+
+def test_difference():
+    rest = matcher("*.py", "*.md", "*.rs") - matcher("*.md")
+    assert list(rest) == ["*.py", "*.rs"]
+
+
+def test_set_operations_compare_patterns_as_written():
+    assert list(matcher("*.py") & matcher("**/*.py", allow_empty=True)) == []
+
+
+def test_set_operations_keep_options():
+    folded = matcher("*.PY", case_fold="unicode")
+    assert (folded | matcher("*.md", case_fold="unicode")).matches("a.py")
+
+
+def test_set_operations_reject_mixed_options():
+    with pytest.raises(ValueError):
+        matcher("*.py") | matcher("*.md", full_path=True)
+
+
+def test_container_protocol():
+    m = matcher("*.py", "docs/", "*.md")
+    assert len(m) == 3
+    assert "docs/" in m
+    assert "docs" not in m
+    assert list(m) == ["*.py", "docs/", "*.md"]
+
+
+def test_equality_and_hashing():
+    assert matcher("*.py", "*.md") == matcher("*.py", "*.md")
+    assert matcher("*.py", "*.md") != matcher("*.md", "*.py")
+    assert matcher("*.py") != matcher("*.py", full_path=True)
+    assert hash(matcher("*.py")) == hash(matcher("*.py"))
+    assert len({matcher("*.py"), matcher("*.py"), matcher("*.md")}) == 2
+
+
+def test_repr():
+    assert repr(matcher("*.py", "*.md")) == "PatternMatcher(2 patterns)"
+
+
+def test_analyze_reports_duplicates_and_shadowed_patterns():
+    analysis = matcher("*.py", "test_*.py", "*.py", "*.rs").analyze()
+    assert analysis.duplicates == ["*.py"]
+    assert analysis.shadowed == [("test_*.py", "*.py")]
+    assert analysis.unmatched == []
+
+
+def test_analyze_reports_patterns_no_sample_matches():
+    analysis = matcher("*.py", "*.md", "*.rs").analyze(["a.py", "b/c.md"])
+    assert analysis.unmatched == ["*.rs"]
+
+
+def test_analyze_clean_pattern_set():
+    analysis = matcher("*.py", "*.md").analyze()
+    assert (analysis.duplicates, analysis.shadowed) == ([], [])
+
+
+def test_profile_counts_hits_per_pattern():
+    m = matcher("*.py", "a*", "zz", profile=True)
+    for path in ["a.py", "b.py", "c"]:
+        assert m.matches(path) == path.endswith(".py")
+    report = m.profile_report()
+    hits = {pattern: hits for pattern, _, hits in report}
+    assert hits == {"*.py": 2, "a*": 1, "zz": 0}
+    assert all(seconds >= 0 for _, seconds, _ in report)
+    assert m.profile_calls == 3
+
+
+def test_profile_report_is_slowest_first():
+    m = matcher("*.py", "a*", profile=True)
+    m.matches("a.py")
+    seconds = [seconds for _, seconds, _ in m.profile_report()]
+    assert seconds == sorted(seconds, reverse=True)
+
+
+def test_reset_profile():
+    m = matcher("*.py", profile=True)
+    m.matches("a.py")
+    m.reset_profile()
+    assert m.profile_calls == 0
+    assert m.profile_report() == [("*.py", 0.0, 0)]
+
+
+def test_profile_requires_profile_flag():
+    m = matcher("*.py")
+    with pytest.raises(ValueError, match="profile=True"):
+        m.profile_report()
+    with pytest.raises(ValueError, match="profile=True"):
+        m.profile_calls
+
+
+@pytest.mark.parametrize(
+    "pattern, path",
+    [
+        ("*.TXT", "a.txt"),
+        ("straße*", "STRASSE.txt"),
+        ("ΣΟΦΟΣ", "σοφος"),
+        ("ΣΟΦΟΣ", "σοφοσ"),
+    ],
+)
+def test_unicode_case_folding(pattern, path):
+    assert matcher(pattern, case_fold="unicode").matches(path)
+    assert not matcher(pattern).matches(path)
+
+
+def test_turkic_case_folding():
+    assert matcher("DİR*", case_fold="turkic").matches("dir1")
+    assert matcher("I*", case_fold="turkic").matches("ı")
+    assert not matcher("DİR*", case_fold="unicode").matches("dir1")
+    assert not matcher("I*", case_fold="unicode").matches("ı")
+
+
+def test_unknown_case_fold():
+    with pytest.raises(ValueError, match="Unknown case folding"):
+        matcher("*", case_fold="ascii")
+
+
+def test_empty_pattern_list_warns_by_default():
+    with pytest.warns(UserWarning, match="allow_empty=True"):
+        m = _pathvein_rs.PatternMatcher([])
+    assert not m.matches("anything")
+
+
+def test_empty_pattern_list_allowed():
+    with warnings.catch_warnings():
+        warnings.simplefilter("error")
+        m = _pathvein_rs.PatternMatcher([], allow_empty=True)
+    assert len(m) == 0
+
+
+def test_empty_pattern_list_rejected():
+    with pytest.raises(ValueError, match="at least one pattern"):
+        _pathvein_rs.PatternMatcher([], allow_empty=False)
+
+
+def test_max_patterns():
+    assert len(matcher("*.py", "*.md", max_patterns=2)) == 2
+    with pytest.raises(ValueError, match="3 patterns, more than max_patterns=2"):
+        matcher("*.py", "*.md", "*.rs", max_patterns=2)
+
+
+def test_compile_failure_names_the_pattern():
+    with pytest.raises(_pathvein_rs.PatternError) as excinfo:
+        matcher("*.py", "ok", "[")
+    assert excinfo.value.pattern_index == 2
+    assert excinfo.value.pattern == "["
+    assert "at index 2" in str(excinfo.value)
+
+
+def test_unclosed_class_is_literal_in_pathlib_dialect():
+    assert matcher("a[", dialect="pathlib").matches("a[")
+
+
+def test_matches_count_counts_duplicates():
+    m = matcher("*.py", "a*", "*.py", "*.md")
+    assert m.matches_count("a.py") == 3
+    assert m.matches_count("b.rs") == 0
+    assert m.matches_count("a.py") == len(m.matching_patterns("a.py"))
+
+
+def test_matches_any():
+    m = matcher("*.py")
+    assert m.matches_any(["a.md", "b.py"])
+    assert not m.matches_any(["a.md", "b.rs"])
+    assert not m.matches_any([])
+
+
+def test_all_match():
+    m = matcher("*.py", "*.md")
+    assert m.all_match(["a.py", "b.md"])
+    assert not m.all_match(["a.py", "b.rs"])
+    assert m.all_match([])
+
+
+def test_batch_helpers_honour_depth_and_is_dir():
+    m = matcher("*/out/")
+    assert m.all_match(["a/out", "b/out"], depth=2, is_dir=True)
+    assert not m.all_match(["a/out", "b/out"], depth=2)
+    assert not m.matches_any(["a/out"], depth=3, is_dir=True)
+
+
+@pytest.mark.parametrize(
+    "pattern, path, captures",
+    [
+        ("run_*/sample_*.csv", "run_042/sample_07.csv", ["042", "07"]),
+        ("**/x/*.txt", "p/q/x/y.txt", ["p/q", "y"]),
+        ("a?c", "abc", ["b"]),
+        ("{a,b}*", "bq", ["q"]),
+        ("[ab]*z", "az", [""]),
+        ("README", "README", []),
+        ("run_*/sample_*.csv", "run_1/other.csv", None),
+    ],
+)
+def test_extract_captures_wildcards(pattern, path, captures):
+    assert matcher(pattern).extract(path, 0) == captures
+
+
+def test_extract_honours_directory_only_patterns():
+    m = matcher("*.d/")
+    assert m.extract("conf.d", 0, is_dir=True) == ["conf"]
+    assert m.extract("conf.d/", 0) == ["conf"]
+    assert m.extract("conf.d", 0) is None
+
+
+def test_extract_index_out_of_range():
+    with pytest.raises(IndexError, match="pattern_index 3 out of range for 1 patterns"):
+        matcher("*").extract("a", 3)
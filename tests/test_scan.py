@@ -0,0 +1,968 @@
+import json
+import os
+import shutil
+import subprocess
+import sys
+import tarfile
+import time
+import zipfile
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+from tests import spec, touch
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+
+def scan(root, *patterns, **options):
+    return _pathvein_rs.scan_parallel(str(root), list(patterns), **options)
+
+
+def paths(results, root):
+    return sorted(os.path.relpath(result.path, root) for result in results)
+
+
+@pytest.mark.skipif(sys.platform != "linux", reason="needs non-UTF-8 file names")
+def test_non_utf8_names_are_matched_lossily(tmp_path):
+    run = os.path.join(bytes(tmp_path), b"run_\xff")
+    os.mkdir(run)
+    open(os.path.join(run, b"\xfe.csv"), "w").close()
+    results = scan(tmp_path, spec(directory_name="run_*", files=["*.csv"]))
+    assert [os.path.basename(result.path) for result in results] == ["run_�"]
+
+
+def test_the_root_parent_is_never_reported(tmp_path):
+    root = tmp_path / "root"
+    touch(root / "run" / "data.csv")
+    touch(tmp_path / "beside.csv")
+    patterns = [spec(), spec(files=["*.csv"])]
+    assert paths(scan(root, *patterns), root) == [".", "run", "run"]
+    # Not even as a partial match
+    assert paths(scan(root, *patterns, min_score=0.0), root) == [".", ".", "run", "run"]
+
+
+def nested_spec():
+    return spec(
+        directory_name="run_*",
+        files=["config.yaml"],
+        directories=[spec(directory_name="raw", files=["*.fastq"])],
+    )
+
+
+def test_nested_requirements_are_checked_recursively(tmp_path):
+    touch(tmp_path / "run_1" / "config.yaml")
+    touch(tmp_path / "run_1" / "raw" / "a.fastq")
+    touch(tmp_path / "run_2" / "config.yaml")
+    touch(tmp_path / "run_2" / "raw" / "a.txt")
+    assert paths(scan(tmp_path, nested_spec()), tmp_path) == ["run_1"]
+
+
+def test_nested_requirements_at_several_levels(tmp_path):
+    leaf = spec(directory_name="leaf", files=["x"])
+    deep = spec(
+        directory_name="top",
+        directories=[spec(directory_name="mid", directories=[leaf])],
+    )
+    touch(tmp_path / "a" / "top" / "mid" / "leaf" / "x")
+    touch(tmp_path / "b" / "top" / "mid" / "leaf" / "y")
+    assert paths(scan(tmp_path, deep), tmp_path) == ["a/top"]
+
+
+def test_any_child_may_satisfy_a_nested_pattern(tmp_path):
+    pattern = spec(directories=[spec(directory_name="raw_*", files=["*.fastq"])])
+    touch(tmp_path / "run" / "raw_1" / "a.txt")
+    touch(tmp_path / "run" / "raw_2" / "b.fastq")
+    assert "run" in paths(scan(tmp_path, pattern), tmp_path)
+
+
+def run_tree(root):
+    for name in ["config.yaml", "qc.html", "a.csv", "b.csv", "logs/x"]:
+        touch(root / "run" / name)
+
+
+def run_spec(**fields):
+    return spec(
+        directory_name="run",
+        files=["config.yaml", "*.csv"],
+        optional_files=["qc.html", "notes.md"],
+        optional_directories=[spec(directory_name="logs"), spec(directory_name="tmp")],
+        **fields,
+    )
+
+
+def test_results_report_optional_components_present(tmp_path):
+    run_tree(tmp_path)
+    [result] = scan(tmp_path, run_spec())
+    assert result.optional_files == ["qc.html"]
+    assert result.optional_directories == ["logs"]
+
+
+def test_optional_components_absent(tmp_path):
+    touch(tmp_path / "run" / "config.yaml")
+    touch(tmp_path / "run" / "a.csv")
+    [result] = scan(tmp_path, run_spec())
+    assert (result.optional_files, result.optional_directories) == ([], [])
+
+
+def test_results_name_the_matching_pattern(tmp_path):
+    run_tree(tmp_path)
+    results = scan(tmp_path, run_spec(pattern_name="runs"), spec(files=["*.csv"]))
+    found = sorted((result.pattern_index, result.pattern_name) for result in results)
+    assert found == [(0, "runs"), (1, None)]
+    assert all(result.root == str(tmp_path) for result in results)
+
+
+def test_pattern_name_in_repr(tmp_path):
+    run_tree(tmp_path)
+    [result] = scan(tmp_path, run_spec(pattern_name="runs"))
+    assert repr(result) == (
+        f"ScanResult(path='{tmp_path / 'run'}', pattern_index=0, pattern_name='runs')"
+    )
+
+
+def test_results_map_each_glob_to_the_files_satisfying_it(tmp_path):
+    run_tree(tmp_path)
+    [result] = scan(tmp_path, run_spec())
+    matched = {glob: sorted(names) for glob, names in result.matched_files.items()}
+    assert matched == {
+        "*.csv": ["a.csv", "b.csv"],
+        "config.yaml": ["config.yaml"],
+        "qc.html": ["qc.html"],
+    }
+
+
+def test_first_match_attributes_each_directory_once(tmp_path):
+    touch(tmp_path / "run_1" / "a.csv")
+    touch(tmp_path / "run_2" / "b.txt")
+    patterns = [spec(files=["*.csv"]), spec(directory_name="run_*"), spec()]
+    everything = scan(tmp_path, *patterns)
+    first = scan(tmp_path, *patterns, first_match=True)
+    assert len(everything) > len(first)
+    attributed = {os.path.relpath(r.path, tmp_path): r.pattern_index for r in first}
+    assert attributed == {".": 2, "run_1": 0, "run_2": 1}
+
+
+def test_first_match_follows_list_order(tmp_path):
+    touch(tmp_path / "run_1" / "a.csv")
+    patterns = [spec(directory_name="run_*"), spec(files=["*.csv"])]
+    [result] = scan(tmp_path, *patterns, first_match=True)
+    assert result.pattern_index == 0
+
+
+def nested_datasets(root):
+    for name in ["a/data.h5", "a/b/data.h5", "a/b/c/data.h5", "d/data.h5"]:
+        touch(root / name)
+    return spec(files=["data.h5"])
+
+
+@pytest.mark.parametrize(
+    "overlap, expected",
+    [
+        ("all", ["a", "a/b", "a/b/c", "d"]),
+        ("outermost", ["a", "d"]),
+        ("innermost", ["a/b/c", "d"]),
+    ],
+)
+def test_overlap(tmp_path, overlap, expected):
+    pattern = nested_datasets(tmp_path)
+    assert paths(scan(tmp_path, pattern, overlap=overlap), tmp_path) == expected
+
+
+def test_unknown_overlap(tmp_path):
+    with pytest.raises(ValueError):
+        scan(tmp_path, spec(), overlap="middle")
+
+
+def result_keys(results):
+    return sorted((result.path, result.pattern_index) for result in results)
+
+
+def test_stream_finds_what_scan_parallel_finds(tmp_path):
+    run_tree(tmp_path)
+    nested = nested_datasets(tmp_path)
+    patterns = [run_spec(), nested, spec(files=["*.csv"])]
+    iterator = scan(tmp_path, *patterns, stream=True)
+    assert iter(iterator) is iterator
+    assert result_keys(iterator) == result_keys(scan(tmp_path, *patterns))
+    assert list(iterator) == []
+
+
+def test_stream_first_match_and_max_depth(tmp_path):
+    pattern = nested_datasets(tmp_path)
+    patterns = [pattern, spec()]
+    streamed = scan(tmp_path, *patterns, max_depth=2, first_match=True, stream=True)
+    expected = scan(tmp_path, *patterns, max_depth=2, first_match=True)
+    assert result_keys(streamed) == result_keys(expected)
+
+
+def test_stream_matches_nested_requirements_at_any_depth(tmp_path):
+    for run in ["x/run_1", "x/y/run_2", "x/y/z/run_3", "x/y/run_4"]:
+        touch(tmp_path / run / "config.yaml")
+    for run in ["x/run_1", "x/y/run_2", "x/y/z/run_3"]:
+        touch(tmp_path / run / "raw" / "a.fastq")
+    streamed = scan(tmp_path, nested_spec(), stream=True)
+    assert paths(streamed, tmp_path) == ["x/run_1", "x/y/run_2", "x/y/z/run_3"]
+
+
+def test_stream_excludes_roots_and_gives_relative_paths(tmp_path):
+    run_tree(tmp_path)
+    run_tree(tmp_path / "old")
+    streamed = scan(
+        tmp_path, run_spec(), exclude_roots=["old"], relative_paths=True, stream=True
+    )
+    assert [result.path for result in streamed] == ["run"]
+
+
+@pytest.mark.parametrize(
+    "option",
+    [
+        {"overlap": "outermost"},
+        {"stats_only": True},
+        {"min_score": 0.5},
+        {"deterministic": True},
+        {"profile": True},
+    ],
+)
+def test_stream_rejects_options_needing_the_whole_scan(tmp_path, option):
+    with pytest.raises(ValueError, match="stream=True"):
+        scan(tmp_path, spec(), stream=True, **option)
+
+
+def test_stream_scans_a_single_root(tmp_path):
+    (tmp_path / "a").mkdir()
+    (tmp_path / "b").mkdir()
+    roots = [str(tmp_path / "a"), str(tmp_path / "b")]
+    with pytest.raises(ValueError, match="several roots"):
+        _pathvein_rs.scan_parallel(roots, [spec()], stream=True)
+
+
+def test_stream_missing_root(tmp_path):
+    with pytest.raises(_pathvein_rs.WalkError):
+        scan(tmp_path / "missing", spec(), stream=True)
+
+
+def test_progress_reports_the_finished_scan(tmp_path):
+    pattern = nested_datasets(tmp_path)
+    reports = []
+    results = scan(tmp_path, pattern, progress=reports.append, progress_interval=0)
+    final = reports[-1]
+    assert final.directories_walked == final.directories_discovered == 5
+    assert final.directories_evaluated == 5
+    assert final.matches == len(results) == 4
+    assert final.elapsed >= 0
+    assert final.eta == 0.0
+
+
+def test_progress_counts_never_go_backwards(tmp_path):
+    for i in range(20):
+        touch(tmp_path / f"d{i}" / "data.h5")
+    reports = []
+    pattern = spec(files=["data.h5"])
+    scan(tmp_path, pattern, progress=reports.append, progress_interval=0)
+    walked = [report.directories_walked for report in reports]
+    assert walked == sorted(walked)
+
+
+def test_progress_exception_stops_the_scan(tmp_path):
+    nested_datasets(tmp_path)
+
+    def progress(report):
+        raise RuntimeError("stop")
+
+    with pytest.raises(RuntimeError, match="stop"):
+        scan(tmp_path, spec(), progress=progress, progress_interval=0)
+
+
+def test_excluded_files_and_directories_rule_a_directory_out(tmp_path):
+    for name in ["done/a.csv", "partial/a.csv", "partial/.incomplete", "scratch/a.csv"]:
+        touch(tmp_path / name)
+    (tmp_path / "scratch" / "tmp").mkdir()
+    pattern = spec(
+        files=["*.csv"],
+        excluded_files=[".incomplete"],
+        excluded_directories=["tmp"],
+    )
+    assert paths(scan(tmp_path, pattern), tmp_path) == ["done"]
+
+
+def test_file_count_limits(tmp_path):
+    for count in [1, 3, 5]:
+        for i in range(count):
+            touch(tmp_path / f"run_{count}" / f"r{i}.fastq.gz")
+        touch(tmp_path / f"run_{count}" / "sheet.csv")
+    per_glob = spec(
+        directory_name="run_*",
+        file_constraints={"*.fastq.gz": {"min_count": 2, "max_count": 4}},
+    )
+    assert paths(scan(tmp_path, per_glob), tmp_path) == ["run_3"]
+    overall = spec(directory_name="run_*", min_file_count=3, max_file_count=4)
+    assert paths(scan(tmp_path, overall), tmp_path) == ["run_3"]
+
+
+def test_file_size_limits(tmp_path):
+    touch(tmp_path / "full" / "a.bam", "x" * 2000)
+    touch(tmp_path / "truncated" / "a.bam", "x" * 10)
+    touch(tmp_path / "huge" / "a.bam", "x" * 5000)
+    pattern = spec(
+        files=["*.bam"],
+        file_constraints={"*.bam": {"min_size": "1KB", "max_size": "4 KiB"}},
+    )
+    assert paths(scan(tmp_path, pattern), tmp_path) == ["full"]
+
+
+def test_invalid_size_limit(tmp_path):
+    pattern = spec(file_constraints={"*.bam": {"min_size": "lots"}})
+    with pytest.raises(_pathvein_rs.PatternSyntaxError):
+        scan(tmp_path, pattern)
+
+
+def age(path, seconds):
+    then = time.time() - seconds
+    os.utime(path, (then, then))
+
+
+def test_age_limits(tmp_path):
+    touch(tmp_path / "settled" / "a.csv")
+    touch(tmp_path / "uploading" / "a.csv")
+    age(tmp_path / "settled" / "a.csv", 3600)
+    age(tmp_path / "settled", 3600)
+    stable = spec(files=["*.csv"], stable_for="15m")
+    assert paths(scan(tmp_path, stable), tmp_path) == ["settled"]
+    assert paths(scan(tmp_path, spec(files=["*.csv"], older_than=1800)), tmp_path) == [
+        "settled"
+    ]
+    assert paths(scan(tmp_path, spec(files=["*.csv"], newer_than="5m")), tmp_path) == [
+        "uploading"
+    ]
+
+
+def test_regex_names_and_requirements(tmp_path):
+    touch(tmp_path / "run_20240101" / "lane1.fastq")
+    touch(tmp_path / "run_2024" / "lane1.fastq")
+    touch(tmp_path / "run_20240102" / "notes.fastq")
+    pattern = spec(
+        directory_name=r"regex:^run_\d{8}$",
+        files=[r"regex:^lane\d+\.fastq$"],
+    )
+    assert paths(scan(tmp_path, pattern), tmp_path) == ["run_20240101"]
+
+
+def test_invalid_regex(tmp_path):
+    with pytest.raises(_pathvein_rs.PatternSyntaxError):
+        scan(tmp_path, spec(directory_name="regex:run_("))
+
+
+def test_any_of_reports_the_matching_branch(tmp_path):
+    touch(tmp_path / "illumina" / "RunInfo.xml")
+    touch(tmp_path / "nanopore" / "final_summary.txt")
+    touch(tmp_path / "other" / "readme.md")
+    pattern = spec(
+        any_of=[
+            spec(pattern_name="illumina", files=["RunInfo.xml"]),
+            spec(pattern_name="nanopore", files=["final_summary*.txt"]),
+        ]
+    )
+    branches = {
+        os.path.relpath(result.path, tmp_path): (result.branch, result.branch_name)
+        for result in scan(tmp_path, pattern)
+    }
+    assert branches == {"illumina": (0, "illumina"), "nanopore": (1, "nanopore")}
+
+
+def test_all_of_and_none_of(tmp_path):
+    touch(tmp_path / "both" / "a.csv")
+    touch(tmp_path / "both" / "b.json")
+    touch(tmp_path / "csv_only" / "a.csv")
+    touch(tmp_path / "locked" / "a.csv")
+    touch(tmp_path / "locked" / "b.json")
+    touch(tmp_path / "locked" / ".lock")
+    pattern = spec(
+        all_of=[spec(files=["*.csv"]), spec(files=["*.json"])],
+        none_of=[spec(files=[".lock"])],
+    )
+    assert paths(scan(tmp_path, pattern), tmp_path) == ["both"]
+
+
+def test_results_support_len_indexing_and_slicing(tmp_path):
+    pattern = nested_datasets(tmp_path)
+    results = scan(tmp_path, pattern)
+    assert len(results) == 4
+    assert results[-1] == results[3]
+    assert list(results[1:3]) == [results[1], results[2]]
+    assert results[::2] == [results[0], results[2]]
+    with pytest.raises(IndexError):
+        results[4]
+    assert results[0] in results
+    assert list(results) == results.to_list()
+
+
+def test_result_equality_hash_and_to_dict(tmp_path):
+    run_tree(tmp_path)
+    [first] = scan(tmp_path, run_spec(pattern_name="runs"))
+    [second] = scan(tmp_path, run_spec(pattern_name="runs"))
+    assert first == second
+    assert len({first, second}) == 1
+    as_dict = first.to_dict()
+    assert as_dict["path"] == str(tmp_path / "run")
+    assert as_dict["pattern_index"] == 0
+    assert as_dict["pattern_name"] == "runs"
+    assert as_dict["optional_files"] == ["qc.html"]
+    assert json.loads(json.dumps(as_dict)) == as_dict
+
+
+def test_case_insensitive_patterns(tmp_path):
+    touch(tmp_path / "RUN_1" / "Config.YAML")
+    touch(tmp_path / "RUN_1" / "RAW" / "a.FASTQ")
+    pattern = dict(
+        directory_name="run_*",
+        files=["config.yaml"],
+        directories=[spec(directory_name="raw", files=["*.fastq"])],
+    )
+    assert scan(tmp_path, spec(**pattern)) == []
+    matched = scan(tmp_path, spec(case_insensitive=True, **pattern))
+    assert paths(matched, tmp_path) == ["RUN_1"]
+
+
+def test_exclude_roots_prunes_subtrees(tmp_path):
+    for name in ["a/data.h5", ".snapshot/a/data.h5", "b/.snapshot/c/data.h5", "old/x/data.h5"]:
+        touch(tmp_path / name)
+    pattern = spec(files=["data.h5"])
+    results = scan(tmp_path, pattern, exclude_roots=[".snapshot", str(tmp_path / "old")])
+    assert paths(results, tmp_path) == ["a"]
+
+
+def test_excluded_directories_are_not_subdirectories(tmp_path):
+    touch(tmp_path / "run" / "skip" / "a.csv")
+    pattern = spec(directory_name="run", directories=[spec(directory_name="skip")])
+    assert paths(scan(tmp_path, pattern), tmp_path) == ["run"]
+    assert scan(tmp_path, pattern, exclude_roots=["run/skip"]) == []
+
+
+def test_min_score_reports_partial_matches(tmp_path):
+    touch(tmp_path / "complete" / "a.csv")
+    touch(tmp_path / "complete" / "b.json")
+    touch(tmp_path / "half" / "a.csv")
+    pattern = spec(files=["*.csv", "*.json"])
+    results = {
+        os.path.relpath(result.path, tmp_path): result
+        for result in scan(tmp_path, pattern, min_score=0.5)
+    }
+    assert sorted(results) == ["complete", "half"]
+    assert results["complete"].score == 1.0
+    assert results["half"].score == 0.5
+    assert paths(scan(tmp_path, pattern), tmp_path) == ["complete"]
+
+
+def test_score(tmp_path):
+    touch(tmp_path / "half" / "a.csv")
+    pattern = _pathvein_rs.FileStructurePattern(files=["*.csv", "*.json"])
+    assert pattern.score(str(tmp_path / "half")) == 0.5
+
+
+def test_min_score_out_of_range(tmp_path):
+    with pytest.raises(ValueError):
+        scan(tmp_path, spec(), min_score=1.5)
+
+
+def test_partial_results_carry_their_failures(tmp_path):
+    touch(tmp_path / "run_1" / "config.yaml")
+    pattern = spec(directory_name="run_*", files=["config.yaml", "*.csv"])
+    [result] = scan(tmp_path, pattern, min_score=0.5)
+    # The directory's name counts as one of three requirements
+    assert result.score == pytest.approx(2 / 3)
+    [failure] = result.failures
+    assert (failure.kind, failure.requirement) == ("missing_file", "*.csv")
+    assert failure.path == str(tmp_path / "run_1")
+    assert str(failure).startswith(str(tmp_path / "run_1"))
+
+
+def test_explain_a_near_miss(tmp_path):
+    touch(tmp_path / "run_1" / "config.yaml")
+    touch(tmp_path / "run_1" / "raw" / "a.txt")
+    pattern = _pathvein_rs.FileStructurePattern(
+        directory_name="run_*",
+        directories=[_pathvein_rs.FileStructurePattern(directory_name="raw", files=["*.fastq"])],
+    )
+    [failure] = pattern.explain(str(tmp_path / "run_1"))
+    assert (failure.kind, failure.requirement) == ("missing_file", "*.fastq")
+    assert failure.path == str(tmp_path / "run_1" / "raw")
+
+
+def test_stats_only_counts_per_pattern(tmp_path):
+    nested_datasets(tmp_path)
+    touch(tmp_path / "e" / "data.txt")
+    patterns = [spec(files=["data.h5"], pattern_name="h5"), spec(files=["*.parquet"])]
+    stats = scan(tmp_path, *patterns, stats_only=True)
+    assert stats.directories_evaluated == 6
+    assert stats.walk_errors == 0
+    h5, parquet = stats.patterns
+    assert (h5.pattern_name, h5.evaluated, h5.matched) == ("h5", 6, 4)
+    assert (parquet.pattern_index, parquet.matched) == (1, 0)
+    assert stats.elapsed >= stats.walk_seconds >= 0
+
+
+def test_stats_only_counts_near_misses(tmp_path):
+    touch(tmp_path / "half" / "a.csv")
+    stats = scan(tmp_path, spec(files=["*.csv", "*.json"]), stats_only=True, min_score=0.5)
+    [pattern] = stats.patterns
+    assert (pattern.matched, pattern.near_missed) == (0, 1)
+
+
+def test_descend_into_matches(tmp_path):
+    pattern = nested_datasets(tmp_path)
+    results = scan(tmp_path, pattern, descend_into_matches=False)
+    assert paths(results, tmp_path) == ["a", "d"]
+
+
+def test_matches_below_a_stop_are_not_walked(tmp_path):
+    nested_datasets(tmp_path)
+    walked = []
+
+    def events(event):
+        if event.kind == "directory_entered":
+            walked.append(os.path.relpath(event.path, tmp_path))
+
+    scan(tmp_path, spec(files=["data.h5"]), descend_into_matches=False, events=events)
+    assert "a/b" not in walked
+
+
+def test_several_roots_are_merged(tmp_path):
+    touch(tmp_path / "one" / "a" / "x.csv")
+    touch(tmp_path / "two" / "b" / "y.csv")
+    roots = [str(tmp_path / "one"), str(tmp_path / "two")]
+    results = _pathvein_rs.scan_parallel(roots, [spec(files=["*.csv"])])
+    found = sorted((result.root, os.path.basename(result.path)) for result in results)
+    assert found == [(roots[0], "a"), (roots[1], "b")]
+
+
+def test_nested_and_repeated_roots_are_walked_once(tmp_path):
+    touch(tmp_path / "outer" / "inner" / "x.csv")
+    outer = str(tmp_path / "outer")
+    inner = str(tmp_path / "outer" / "inner")
+    results = _pathvein_rs.scan_parallel([outer, inner, outer], [spec(files=["*.csv"])])
+    [result] = results
+    assert (result.path, result.root) == (inner, inner)
+
+
+def test_no_roots(tmp_path):
+    with pytest.raises(ValueError):
+        _pathvein_rs.scan_parallel([], [spec()])
+
+
+@pytest.mark.skipif(sys.platform == "win32", reason="needs symlinks")
+def test_dedupe_physical_merges_paths_to_one_directory(tmp_path):
+    touch(tmp_path / "data" / "run" / "a.csv")
+    os.symlink(tmp_path / "data", tmp_path / "link")
+    pattern = spec(directory_name="run", files=["*.csv"])
+    linked = scan(tmp_path, pattern, follow_links=True)
+    assert paths(linked, tmp_path) == ["data/run", "link/run"]
+    [result] = scan(tmp_path, pattern, follow_links=True, dedupe_physical=True)
+    assert result.path == str(tmp_path / "data" / "run")
+    assert result.aliases == [str(tmp_path / "link" / "run")]
+
+
+def test_events_report_the_scan_as_it_goes(tmp_path):
+    touch(tmp_path / "run_1" / "a.csv")
+    touch(tmp_path / "run_2" / "a.txt")
+    events = []
+    pattern = spec(directory_name="run_*", files=["*.csv"], pattern_name="runs")
+    scan(tmp_path, pattern, events=events.append)
+    entered = sorted(e.path for e in events if e.kind == "directory_entered")
+    assert entered == [str(tmp_path), str(tmp_path / "run_1"), str(tmp_path / "run_2")]
+    [found] = [e for e in events if e.kind == "match_found"]
+    assert (found.path, found.pattern_index, found.pattern_name) == (
+        str(tmp_path / "run_1"),
+        0,
+        "runs",
+    )
+    [failed] = [e for e in events if e.kind == "requirement_failed"]
+    assert failed.path == str(tmp_path / "run_2")
+    assert [failure.kind for failure in failed.failures] == ["missing_file"]
+
+
+def test_event_callback_exception_stops_the_scan(tmp_path):
+    touch(tmp_path / "run_1" / "a.csv")
+
+    def events(event):
+        raise RuntimeError("stop")
+
+    with pytest.raises(RuntimeError, match="stop"):
+        scan(tmp_path, spec(), events=events)
+
+
+def test_relative_paths(tmp_path):
+    touch(tmp_path / "a" / "b" / "x.csv")
+    touch(tmp_path / "y.csv")
+    results = scan(tmp_path, spec(files=["*.csv"]), relative_paths=True)
+    assert sorted(result.path for result in results) == [".", os.path.join("a", "b")]
+    assert all(result.root == str(tmp_path) for result in results)
+    assert results.roots == [str(tmp_path)]
+
+
+def test_relative_results_from_different_roots_differ(tmp_path):
+    for root in ["a", "b"]:
+        touch(tmp_path / root / "run" / "x.csv")
+    roots = [str(tmp_path / "a"), str(tmp_path / "b")]
+    results = _pathvein_rs.scan_parallel(roots, [spec(files=["*.csv"])], relative_paths=True)
+    in_a, in_b = sorted(results, key=lambda result: result.root)
+    assert in_a.path == in_b.path == "run"
+    assert in_a != in_b
+    assert len({in_a, in_b}) == 2
+    only_a = _pathvein_rs.scan_parallel(roots[:1], [spec(files=["*.csv"])], relative_paths=True)
+    assert in_a in only_a
+    assert in_b not in only_a
+
+
+def test_best_match_picks_the_highest_priority(tmp_path):
+    touch(tmp_path / "run" / "a.csv")
+    touch(tmp_path / "run" / "b.json")
+    patterns = [
+        spec(files=["*.csv"], pattern_name="csv"),
+        spec(files=["*.json"], pattern_name="json", priority=5),
+        spec(files=["*.csv", "*.json"], pattern_name="both", priority=5),
+    ]
+    [result] = scan(tmp_path, *patterns, best_match=True, runners_up=True)
+    # Equal priorities go to the first in list order
+    assert result.pattern_name == "json"
+    assert result.runners_up == [2, 0]
+
+
+def test_best_match_options_are_checked(tmp_path):
+    with pytest.raises(ValueError):
+        scan(tmp_path, spec(), best_match=True, first_match=True)
+    with pytest.raises(ValueError):
+        scan(tmp_path, spec(), runners_up=True)
+
+
+def test_roles_bind_to_the_paths_that_satisfied_them(tmp_path):
+    run = tmp_path / "run"
+    touch(run / "manifest.json")
+    touch(run / "raw_1" / "a.fastq")
+    pattern = spec(
+        files=["*.json"],
+        directories=[spec(directory_name="raw_*")],
+        roles={"manifest": "*.json", "raw_dir": "raw_*"},
+    )
+    [result] = [r for r in scan(tmp_path, pattern) if r.path == str(run)]
+    assert result.bindings == {
+        "manifest": [str(run / "manifest.json")],
+        "raw_dir": [str(run / "raw_1")],
+    }
+
+
+def test_role_for_an_unknown_requirement(tmp_path):
+    with pytest.raises(_pathvein_rs.PatternSyntaxError):
+        scan(tmp_path, spec(files=["*.json"], roles={"manifest": "*.yaml"}))
+
+
+def test_content_contains_and_first_bytes(tmp_path):
+    touch(tmp_path / "good" / "manifest.json", '{"schema_version": 2}')
+    (tmp_path / "good" / "raw.bin").write_bytes(b"\x89RAW rest")
+    touch(tmp_path / "old" / "manifest.json", '{"version": 1}')
+    (tmp_path / "old" / "raw.bin").write_bytes(b"\x89RAW rest")
+    touch(tmp_path / "bad" / "manifest.json", '{"schema_version": 2}')
+    (tmp_path / "bad" / "raw.bin").write_bytes(b"junk")
+    pattern = spec(
+        files=["manifest.json", "raw.bin"],
+        file_constraints={
+            "manifest.json": {"content_contains": "schema_version"},
+            "raw.bin": {"first_bytes": "89524157"},
+        },
+    )
+    assert paths(scan(tmp_path, pattern), tmp_path) == ["good"]
+
+
+def test_file_type_requirements(tmp_path):
+    gzip_bytes = b"\x1f\x8b\x08\x00" + b"\x00" * 16
+    (tmp_path / "real").mkdir()
+    (tmp_path / "real" / "reads.gz").write_bytes(gzip_bytes)
+    touch(tmp_path / "misnamed" / "reads.gz", "plain text")
+    by_name = spec(files=["*.gz"], file_constraints={"*.gz": {"type": "gzip"}})
+    by_mime = spec(files=["*.gz"], file_constraints={"*.gz": {"type": "application/gzip"}})
+    assert paths(scan(tmp_path, by_name), tmp_path) == ["real"]
+    assert paths(scan(tmp_path, by_mime), tmp_path) == ["real"]
+
+
+def test_unknown_file_type(tmp_path):
+    with pytest.raises(_pathvein_rs.PatternSyntaxError):
+        scan(tmp_path, spec(file_constraints={"*": {"type": "not-a-type"}}))
+
+
+def test_summary_counts_matches_per_pattern(tmp_path):
+    nested_datasets(tmp_path)
+    patterns = [spec(files=["data.h5"], pattern_name="h5"), spec(files=["*.parquet"])]
+    summary = scan(tmp_path, *patterns, min_score=0.0).summary
+    assert summary.match_counts == [4, 0]
+    assert summary.unmatched == [1]
+    assert summary.pattern_names == ["h5", None]
+    assert summary.total == 4
+
+
+def test_max_eval_threads(tmp_path):
+    pattern = nested_datasets(tmp_path)
+    limited = scan(tmp_path, pattern, max_eval_threads=1, descend_into_matches=False)
+    assert paths(limited, tmp_path) == ["a", "d"]
+    assert paths(scan(tmp_path, pattern, max_eval_threads=1), tmp_path) == [
+        "a",
+        "a/b",
+        "a/b/c",
+        "d",
+    ]
+    with pytest.raises(ValueError):
+        scan(tmp_path, pattern, max_eval_threads=0)
+
+
+def test_directory_name_captures(tmp_path):
+    touch(tmp_path / "run_042_2024" / "a.csv")
+    [result] = scan(tmp_path, spec(directory_name="run_*_*", files=["*.csv"]))
+    assert result.captures == ["042", "2024"]
+    assert result.to_dict()["captures"] == ["042", "2024"]
+    [result] = scan(tmp_path, spec(directory_name="regex:run_(\\d+)_\\d+", files=["*.csv"]))
+    assert result.captures == ["042"]
+
+
+def test_by_pattern_groups_results(tmp_path):
+    touch(tmp_path / "a" / "x.csv")
+    touch(tmp_path / "b" / "x.json")
+    patterns = [
+        spec(files=["*.csv"], pattern_name="csv"),
+        spec(files=["*.json"]),
+        spec(files=["*.txt"], pattern_name="txt"),
+    ]
+    groups = scan(tmp_path, *patterns).by_pattern()
+    assert list(groups) == ["csv", 1, "txt"]
+    assert paths(groups["csv"], tmp_path) == ["a"]
+    assert paths(groups[1], tmp_path) == ["b"]
+    assert len(groups["txt"]) == 0
+
+
+def test_total_size_limits(tmp_path):
+    touch(tmp_path / "stub" / "data.csv", "x")
+    touch(tmp_path / "full" / "data.csv", "x" * 600)
+    touch(tmp_path / "full" / "sub" / "more.bin", "x" * 600)
+    touch(tmp_path / "huge" / "data.csv", "x" * 5000)
+    pattern = spec(files=["*.csv"], min_total_size=1000, max_total_size="4KB")
+    assert paths(scan(tmp_path, pattern), tmp_path) == ["full"]
+
+
+def test_empty_directories(tmp_path):
+    (tmp_path / "ready" / "out").mkdir(parents=True)
+    touch(tmp_path / "ready" / "in.csv")
+    touch(tmp_path / "done" / "out" / "result.csv")
+    touch(tmp_path / "done" / "in.csv")
+    pattern = spec(files=["in.csv"], empty_directories=["out"])
+    assert paths(scan(tmp_path, pattern), tmp_path) == ["ready"]
+
+
+def test_no_extra_files(tmp_path):
+    touch(tmp_path / "clean" / "a.csv")
+    touch(tmp_path / "clean" / "notes.txt")
+    touch(tmp_path / "dirty" / "a.csv")
+    touch(tmp_path / "dirty" / "stray.bin")
+    pattern = spec(files=["*.csv"], optional_files=["*.txt"], no_extra_files=True)
+    assert paths(scan(tmp_path, pattern), tmp_path) == ["clean"]
+
+
+def test_any_depth_nested_directories(tmp_path):
+    touch(tmp_path / "deep" / "run.json")
+    touch(tmp_path / "deep" / "a" / "b" / "metadata" / "m.xml")
+    touch(tmp_path / "shallow" / "run.json")
+    touch(tmp_path / "shallow" / "metadata" / "m.xml")
+    touch(tmp_path / "none" / "run.json")
+    any_depth = spec(
+        files=["run.json"],
+        directories=[spec(directory_name="**/metadata", files=["*.xml"])],
+        roles={"meta": "**/metadata"},
+    )
+    results = scan(tmp_path, any_depth)
+    assert paths(results, tmp_path) == ["deep", "shallow"]
+    [deep] = [r for r in results if r.path == str(tmp_path / "deep")]
+    assert deep.bindings == {"meta": [str(tmp_path / "deep" / "a" / "b" / "metadata")]}
+    direct = spec(files=["run.json"], directories=[spec(directory_name="metadata")])
+    assert paths(scan(tmp_path, direct), tmp_path) == ["shallow"]
+
+
+@pytest.mark.skipif(sys.platform == "win32", reason="needs symbolic links")
+def test_follow_symlinked_dirs(tmp_path):
+    touch(tmp_path / "store" / "raw" / "a.fastq")
+    (tmp_path / "runs" / "run").mkdir(parents=True)
+    (tmp_path / "runs" / "run" / "raw").symlink_to(tmp_path / "store" / "raw")
+    root = tmp_path / "runs"
+    raw = spec(directory_name="raw", files=["*.fastq"])
+    following = spec(directory_name="run", directories=[raw], follow_symlinked_dirs=True)
+    strict = spec(directory_name="run", directories=[raw])
+    assert paths(scan(root, following), root) == ["run"]
+    assert paths(scan(root, strict), root) == []
+    assert paths(scan(root, strict, follow_links=True), root) == []
+
+
+def test_diff_scans(tmp_path):
+    root = tmp_path / "root"
+    touch(root / "kept" / "a.csv")
+    touch(root / "gone" / "a.csv")
+    touch(root / "grown" / "a.csv")
+    pattern = spec(files=["*.csv"], optional_files=["*.txt"])
+    old = scan(root, pattern)
+    exported = tmp_path / "old.ndjson"
+    _pathvein_rs.export_results(old, str(exported))
+    (root / "gone" / "a.csv").unlink()
+    touch(root / "grown" / "notes.txt")
+    touch(root / "new" / "a.csv")
+    new = scan(root, pattern)
+    for before in [old, str(exported)]:
+        diff = _pathvein_rs.diff_scans(before, new)
+        assert paths(diff.added, root) == ["new"]
+        assert paths(diff.removed, root) == ["gone"]
+        [change] = diff.changed
+        assert change.path == str(root / "grown")
+        assert change.fields == ["matched_files", "optional_files"]
+        assert change.requirements == ["*.txt"]
+    assert _pathvein_rs.diff_scans(new, list(new)).is_empty
+
+
+def test_profile(tmp_path):
+    patterns = [nested_datasets(tmp_path), spec(files=["*.csv"], pattern_name="csv")]
+    profile = scan(tmp_path, *patterns, profile=True).profile
+    assert profile.pattern_names == [None, "csv"]
+    assert len(profile.pattern_seconds) == 2
+    assert profile.slowest_pattern in (0, 1)
+    assert 0 <= profile.walk_seconds <= profile.elapsed
+    assert profile.slowest_directories
+    assert all(seconds >= 0 for _, seconds in profile.slowest_directories)
+    assert scan(tmp_path, *patterns, stats_only=True, profile=True).profile is not None
+    assert scan(tmp_path, *patterns).profile is None
+
+
+def test_deterministic_order(tmp_path):
+    for name in ["b", "a/y", "a/x", "c"]:
+        touch(tmp_path / name / "data.csv")
+        touch(tmp_path / name / "data.json")
+    patterns = [spec(files=["*.json"], pattern_name="z"), spec(files=["*.csv"], pattern_name="a")]
+    results = scan(tmp_path, *patterns, deterministic=True)
+    expected = [
+        (str(tmp_path / name), pattern)
+        for name in ["a/x", "a/y", "b", "c"]
+        for pattern in ["a", "z"]
+    ]
+    assert [(r.path, r.pattern_name) for r in results] == expected
+    sorted_results = scan(tmp_path, *patterns).sorted()
+    assert [(r.path, r.pattern_name) for r in sorted_results] == expected
+
+
+@pytest.mark.skipif(sys.platform == "win32", reason="needs symlinks")
+def test_deterministic_output_is_identical_across_runs(tmp_path):
+    for run in ["run_1", "run_2", "run_3"]:
+        for name in ["c.json", "a.json", "b.json", "d.csv", "raw_b/x", "raw_a/x"]:
+            touch(tmp_path / "data" / run / name)
+    for link in ["link_b", "link_a", "link_c"]:
+        os.symlink(tmp_path / "data", tmp_path / link)
+    patterns = [
+        spec(directory_name="run_*", files=["*.json"], roles={"meta": "*.json"}),
+        spec(
+            directory_name="run_*",
+            files=["*.yaml", "*.txt", "*.csv"],
+            directories=[spec(directory_name="raw_*", files=["x", "y"])],
+        ),
+    ]
+
+    def serialized():
+        results = scan(
+            tmp_path,
+            *patterns,
+            follow_links=True,
+            dedupe_physical=True,
+            min_score=0.0,
+            deterministic=True,
+        )
+        return json.dumps([r.to_dict() for r in results], default=str)
+
+    first = serialized()
+    assert '"aliases": ["' in first and '"meta": ["' in first
+    assert "raw_a: no file matches 'y'" in first
+    assert "raw_b: no file matches 'y'" not in first
+    assert all(serialized() == first for _ in range(5))
+
+
+def archived_runs(tmp_path):
+    with zipfile.ZipFile(tmp_path / "set1.zip", "w") as archive:
+        archive.writestr("run_1/data.csv", "1")
+        archive.writestr("run_1/images/a.png", "")
+        archive.writestr("other/notes.txt", "")
+    run = touch(tmp_path / "staging" / "run_2" / "data.csv").parent
+    touch(run / "images" / "b.png")
+    with tarfile.open(tmp_path / "set2.tar", "w") as archive:
+        archive.add(run, arcname="run_2")
+    return spec(directory_name="run_*", files=["*.csv"], directories=[spec(directory_name="images")])
+
+
+def test_archives_are_scanned_as_directories(tmp_path):
+    pattern = archived_runs(tmp_path)
+    results = scan(tmp_path, pattern, archives=True)
+    assert paths(results, tmp_path) == [
+        os.path.join("set1.zip", "run_1"),
+        os.path.join("set2.tar", "run_2"),
+        os.path.join("staging", "run_2"),
+    ]
+    assert paths(scan(tmp_path, pattern), tmp_path) == [os.path.join("staging", "run_2")]
+
+
+def test_archive_members_are_walked(tmp_path):
+    archived_runs(tmp_path)
+    listings = {
+        os.path.relpath(entry.path, tmp_path): (sorted(entry.dirnames), sorted(entry.filenames))
+        for entry in _pathvein_rs.walk_parallel(str(tmp_path), archives=True)
+    }
+    assert listings["set1.zip"] == (["other", "run_1"], [])
+    assert listings[os.path.join("set1.zip", "run_1")] == (["images"], ["data.csv"])
+    assert listings[os.path.join("set2.tar", "run_2", "images")] == ([], ["b.png"])
+    assert sorted(listings["."][0]) == ["set1.zip", "set2.tar", "staging"]
+
+
+def test_unreadable_archive_stays_a_file(tmp_path):
+    touch(tmp_path / "broken.zip", "not a zip")
+    [root] = [
+        entry
+        for entry in _pathvein_rs.walk_parallel(str(tmp_path), archives=True)
+        if entry.path == str(tmp_path)
+    ]
+    assert root.filenames == ["broken.zip"]
+    assert root.dirnames == []
+
+
+def tarred_run(tmp_path, name, mode="w"):
+    run = touch(tmp_path / "staging" / "run_1" / "data.csv").parent
+    with tarfile.open(tmp_path / name, mode) as archive:
+        archive.add(run, arcname="run_1")
+    shutil.rmtree(tmp_path / "staging")
+    return spec(directory_name="run_*", files=["*.csv"])
+
+
+@pytest.mark.parametrize("name", ["set.tar.gz", "set.tgz"])
+def test_gzip_tars_are_scanned(tmp_path, name):
+    pattern = tarred_run(tmp_path, name, "w:gz")
+    assert paths(scan(tmp_path, pattern, archives=True), tmp_path) == [
+        os.path.join(name, "run_1")
+    ]
+
+
+@pytest.mark.skipif(shutil.which("zstd") is None, reason="needs the zstd command")
+@pytest.mark.parametrize("name", ["set.tar.zst", "set.tzst"])
+def test_zstd_tars_are_scanned(tmp_path, name):
+    pattern = tarred_run(tmp_path, "set.tar")
+    subprocess.run(["zstd", "-q", "--rm", "set.tar", "-o", name], cwd=tmp_path, check=True)
+    assert paths(scan(tmp_path, pattern, archives=True), tmp_path) == [
+        os.path.join(name, "run_1")
+    ]
+
+
+def test_object_store_and_local_roots_cannot_mix(tmp_path):
+    with pytest.raises(ValueError):
+        _pathvein_rs.scan_parallel([str(tmp_path), "s3://bucket/runs"], [spec()])
+
+
+def test_object_store_root_that_cannot_be_listed():
+    # Without the object-store feature, no URL can be listed
+    with pytest.raises(_pathvein_rs.WalkError) as excinfo:
+        _pathvein_rs.walk_parallel("s3://bucket/runs")
+    assert "s3://bucket/runs" in str(excinfo.value)
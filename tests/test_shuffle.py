@@ -0,0 +1,474 @@
+import hashlib
+import json
+import os
+import sys
+import time
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+from tests import touch
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+# Root reads files whatever their permissions
+needs_permissions = pytest.mark.skipif(
+    sys.platform == "win32" or os.geteuid() == 0,
+    reason="needs file permissions that apply",
+)
+
+
+def tree(root):
+    """Relative path to contents of every file beneath root"""
+    found = {}
+    for dirpath, _, filenames in os.walk(root):
+        for name in filenames:
+            path = os.path.join(dirpath, name)
+            with open(path) as f:
+                found[os.path.relpath(path, root)] = f.read()
+    return found
+
+
+def runs(root):
+    touch(root / "run_1" / "a.csv", "1")
+    touch(root / "run_1" / "raw" / "b.fastq", "reads")
+    touch(root / "run_2" / "a.csv", "2")
+    return [root / "run_1", root / "run_2"]
+
+
+def test_copy(tmp_path):
+    sources = runs(tmp_path / "src")
+    dest = tmp_path / "dest"
+    report = _pathvein_rs.shuffle(sources, dest)
+    assert [d.status for d in report.directories] == ["copied", "copied"]
+    assert [d.destination for d in report.directories] == [
+        str(dest / "run_1"),
+        str(dest / "run_2"),
+    ]
+    assert (report.files, report.bytes) == (3, 7)
+    assert tree(dest / "run_1") == {"a.csv": "1", os.path.join("raw", "b.fastq"): "reads"}
+    assert tree(dest / "run_2") == {"a.csv": "2"}
+    assert all(source.exists() for source in sources)
+
+
+def test_move(tmp_path):
+    sources = runs(tmp_path / "src")
+    dest = tmp_path / "dest"
+    report = _pathvein_rs.shuffle(sources, dest, mode="move")
+    assert [d.status for d in report.directories] == ["moved", "moved"]
+    assert report.failed == []
+    assert tree(dest / "run_1") == {"a.csv": "1", os.path.join("raw", "b.fastq"): "reads"}
+    assert not any(source.exists() for source in sources)
+
+
+def test_shuffle_scan_results(tmp_path):
+    runs(tmp_path / "src")
+    results = _pathvein_rs.scan_parallel(
+        str(tmp_path / "src"), [json.dumps({"directory_name": "run_*", "files": ["a.csv"]})]
+    )
+    report = _pathvein_rs.shuffle(results, tmp_path / "dest")
+    assert sorted(d.status for d in report.directories) == ["copied", "copied"]
+    assert sorted(os.listdir(tmp_path / "dest")) == ["run_1", "run_2"]
+
+
+def test_existing_destination_fails(tmp_path):
+    sources = runs(tmp_path / "src")
+    touch(tmp_path / "dest" / "run_1" / "old.txt", "old")
+    report = _pathvein_rs.shuffle(sources, tmp_path / "dest", mode="move")
+    [failed] = report.failed
+    assert failed.source == str(sources[0])
+    assert "already exists" in failed.error
+    assert tree(tmp_path / "dest" / "run_1") == {"old.txt": "old"}
+    assert sources[0].exists()
+    assert report.directories[1].status == "moved"
+
+
+@needs_permissions
+def test_failed_copy_keeps_the_source(tmp_path):
+    sources = runs(tmp_path / "src")
+    unreadable = sources[0] / "raw" / "b.fastq"
+    unreadable.chmod(0)
+    try:
+        report = _pathvein_rs.shuffle(sources, tmp_path / "dest")
+    finally:
+        unreadable.chmod(0o644)
+    [failed] = report.failed
+    assert failed.source == str(sources[0])
+    assert "b.fastq" in failed.error
+    assert tree(sources[0]) == {"a.csv": "1", os.path.join("raw", "b.fastq"): "reads"}
+    assert report.directories[1].status == "copied"
+
+
+def test_invalid_arguments(tmp_path):
+    with pytest.raises(ValueError):
+        _pathvein_rs.shuffle([], tmp_path, mode="teleport")
+    with pytest.raises(ValueError):
+        _pathvein_rs.shuffle([], tmp_path, threads=0)
+
+
+def test_plan_shuffle(tmp_path):
+    sources = runs(tmp_path / "src")
+    dest = tmp_path / "dest"
+    touch(dest / "run_2" / "old.txt")
+    plan = _pathvein_rs.plan_shuffle(sources, dest)
+    assert plan.mode == "copy"
+    assert plan.directories == [
+        (str(sources[0]), str(dest / "run_1")),
+        (str(sources[1]), str(dest / "run_2")),
+    ]
+    assert sorted((f.destination, f.size) for f in plan.files) == [
+        (str(dest / "run_1" / "a.csv"), 1),
+        (str(dest / "run_1" / "raw" / "b.fastq"), 5),
+        (str(dest / "run_2" / "a.csv"), 1),
+    ]
+    assert (len(plan), plan.bytes) == (3, 7)
+    assert plan.collisions == [f"{dest / 'run_2'} already exists"]
+    assert not plan.ok
+    # Nothing was copied
+    assert os.listdir(dest) == ["run_2"]
+
+
+def test_plan_shuffle_collisions_between_matches(tmp_path):
+    first = touch(tmp_path / "a" / "run" / "x.csv").parent
+    second = touch(tmp_path / "b" / "run" / "x.csv").parent
+    plan = _pathvein_rs.plan_shuffle([first, second], tmp_path / "dest")
+    assert len(plan.collisions) == 1
+    assert "both" in plan.collisions[0]
+
+
+@needs_permissions
+def test_plan_shuffle_permission_problems(tmp_path):
+    sources = runs(tmp_path / "src")
+    unreadable = sources[0] / "a.csv"
+    unreadable.chmod(0)
+    try:
+        plan = _pathvein_rs.plan_shuffle(sources, tmp_path / "dest")
+    finally:
+        unreadable.chmod(0o644)
+    assert plan.permission_problems == [f"cannot read {unreadable}"]
+
+
+def hidden(dest):
+    return [name for name in os.listdir(dest) if name.startswith(".")]
+
+
+@needs_permissions
+def test_failed_copy_is_rolled_back(tmp_path):
+    sources = runs(tmp_path / "src")
+    # Files beside the one that fails are copied before the failure
+    for n in range(20):
+        touch(sources[0] / f"extra_{n}.csv", "x")
+    unreadable = sources[0] / "raw" / "b.fastq"
+    unreadable.chmod(0)
+    dest = tmp_path / "dest"
+    try:
+        report = _pathvein_rs.shuffle(sources, dest, threads=1)
+    finally:
+        unreadable.chmod(0o644)
+    [failed] = report.failed
+    assert "partial copy left" not in failed.error
+    assert os.listdir(dest) == ["run_2"]
+    assert hidden(dest) == []
+    assert len(tree(sources[0])) == 22
+
+
+def test_copies_are_staged_beside_the_destination(tmp_path):
+    sources = runs(tmp_path / "src")
+    report = _pathvein_rs.shuffle(sources, tmp_path / "dest")
+    assert report.failed == []
+    assert hidden(tmp_path / "dest") == []
+
+
+def test_large_files_are_copied_whole(tmp_path):
+    source = tmp_path / "src" / "run"
+    source.mkdir(parents=True)
+    data = os.urandom(3 * 1024 * 1024 + 17)
+    (source / "big.bin").write_bytes(data)
+    (source / "empty.bin").write_bytes(b"")
+    report = _pathvein_rs.shuffle([source], tmp_path / "dest")
+    assert report.bytes == len(data)
+    assert (tmp_path / "dest" / "run" / "big.bin").read_bytes() == data
+    assert (tmp_path / "dest" / "run" / "empty.bin").read_bytes() == b""
+
+
+@pytest.mark.parametrize("algorithm", ["sha256", "blake3"])
+def test_verify(tmp_path, algorithm):
+    sources = runs(tmp_path / "src")
+    report = _pathvein_rs.shuffle(sources, tmp_path / "dest", verify=algorithm)
+    assert report.verify == algorithm
+    assert report.mismatches == []
+    verified = report.directories[0].verified
+    assert [os.path.basename(f.source) for f in verified] == ["a.csv", "b.fastq"]
+    assert all(f.ok for f in verified)
+    if algorithm == "sha256":
+        assert verified[0].source_hash == hashlib.sha256(b"1").hexdigest()
+
+
+def test_unknown_checksum(tmp_path):
+    with pytest.raises(ValueError):
+        _pathvein_rs.shuffle([], tmp_path, verify="md5")
+
+
+@pytest.mark.skipif(sys.platform == "win32", reason="needs POSIX permissions")
+def test_preserve_times_and_permissions(tmp_path):
+    source = touch(tmp_path / "src" / "run" / "script.sh").parent
+    (source / "script.sh").chmod(0o750)
+    os.utime(source / "script.sh", (1_000_000_000, 1_000_000_000))
+    _pathvein_rs.shuffle([source], tmp_path / "kept", preserve_times=True)
+    kept = os.stat(tmp_path / "kept" / "run" / "script.sh")
+    assert kept.st_mtime == 1_000_000_000
+    assert kept.st_mode & 0o777 == 0o750
+
+    _pathvein_rs.shuffle([source], tmp_path / "fresh", preserve_permissions=False)
+    fresh = os.stat(tmp_path / "fresh" / "run" / "script.sh")
+    assert fresh.st_mtime != 1_000_000_000
+    assert fresh.st_mode & 0o111 == 0
+
+
+def test_hardlink(tmp_path):
+    sources = runs(tmp_path / "src")
+    report = _pathvein_rs.shuffle(sources, tmp_path / "dest", mode="hardlink")
+    assert [d.status for d in report.directories] == ["linked", "linked"]
+    linked = tmp_path / "dest" / "run_1" / "raw" / "b.fastq"
+    assert os.path.samefile(linked, sources[0] / "raw" / "b.fastq")
+    assert all(source.exists() for source in sources)
+
+
+def test_hardlink_cannot_verify(tmp_path):
+    with pytest.raises(ValueError):
+        _pathvein_rs.shuffle([], tmp_path, mode="hardlink", verify="blake3")
+
+
+def test_max_bytes_per_second(tmp_path):
+    source = tmp_path / "src" / "run"
+    source.mkdir(parents=True)
+    (source / "data.bin").write_bytes(b"x" * 40_000)
+    started = time.monotonic()
+    report = _pathvein_rs.shuffle([source], tmp_path / "dest", max_bytes_per_second=100_000)
+    assert time.monotonic() - started >= 0.35
+    assert report.bytes == 40_000
+    assert (tmp_path / "dest" / "run" / "data.bin").read_bytes() == b"x" * 40_000
+    with pytest.raises(ValueError):
+        _pathvein_rs.shuffle([source], tmp_path / "dest", max_bytes_per_second=0)
+
+
+def conflicting(tmp_path):
+    source = touch(tmp_path / "src" / "run" / "new.csv", "new")
+    touch(source.parent / "same.csv", "new")
+    touch(tmp_path / "dest" / "run" / "old.csv", "old")
+    touch(tmp_path / "dest" / "run" / "same.csv", "old")
+    return source.parent, tmp_path / "dest"
+
+
+@pytest.mark.parametrize(
+    "on_conflict, status, destination, contents",
+    [
+        ("error", "failed", "run", {"old.csv": "old", "same.csv": "old"}),
+        ("skip", "skipped", "run", {"old.csv": "old", "same.csv": "old"}),
+        ("overwrite", "copied", "run", {"new.csv": "new", "same.csv": "new"}),
+        ("rename", "copied", "run-1", {"new.csv": "new", "same.csv": "new"}),
+        ("merge", "copied", "run", {"old.csv": "old", "new.csv": "new", "same.csv": "new"}),
+    ],
+)
+def test_on_conflict(tmp_path, on_conflict, status, destination, contents):
+    source, dest = conflicting(tmp_path)
+    report = _pathvein_rs.shuffle([source], dest, on_conflict=on_conflict)
+    [shuffled] = report.directories
+    assert shuffled.status == status
+    assert shuffled.destination == str(dest / destination)
+    assert shuffled.conflict == (None if on_conflict == "error" else on_conflict)
+    assert tree(dest / destination) == contents
+    assert tree(source) == {"new.csv": "new", "same.csv": "new"}
+    assert [name for name in os.listdir(dest) if name.startswith(".")] == []
+
+
+def test_rename_takes_the_first_free_name(tmp_path):
+    source, dest = conflicting(tmp_path)
+    (dest / "run-1").mkdir()
+    report = _pathvein_rs.shuffle([source, source], dest, on_conflict="rename")
+    assert [d.destination for d in report.directories] == [
+        str(dest / "run-2"),
+        str(dest / "run-3"),
+    ]
+
+
+def test_plan_reports_conflicts(tmp_path):
+    source, dest = conflicting(tmp_path)
+    plan = _pathvein_rs.plan_shuffle([source], dest, on_conflict="merge")
+    assert plan.conflicts == [(str(dest / "run"), "merge")]
+    assert plan.ok
+
+
+def test_unknown_on_conflict(tmp_path):
+    with pytest.raises(ValueError):
+        _pathvein_rs.shuffle([], tmp_path, on_conflict="ignore")
+
+
+def test_destination_template(tmp_path):
+    touch(tmp_path / "src" / "run_042_2024" / "manifest.json")
+    touch(tmp_path / "src" / "run_007_2023" / "manifest.json")
+    pattern = json.dumps(
+        {
+            "pattern_name": "runs",
+            "directory_name": "run_*_*",
+            "files": ["*.json"],
+            "roles": {"manifest": "*.json"},
+        }
+    )
+    results = _pathvein_rs.scan_parallel(str(tmp_path / "src"), [pattern])
+    template = "{pattern_name}/{capture[1]}/{capture[0]}-{binding[manifest]}-{{x}}"
+    dest = tmp_path / "dest"
+    plan = _pathvein_rs.plan_shuffle(results, dest, destination_template=template)
+    assert sorted(destination for _, destination in plan.directories) == [
+        str(dest / "runs" / "2023" / "007-manifest.json-{x}"),
+        str(dest / "runs" / "2024" / "042-manifest.json-{x}"),
+    ]
+    report = _pathvein_rs.shuffle(results, dest, destination_template=template)
+    assert report.failed == []
+    assert (dest / "runs" / "2024" / "042-manifest.json-{x}" / "manifest.json").exists()
+
+
+def test_template_fields_without_values(tmp_path):
+    source = touch(tmp_path / "src" / "run" / "a.csv").parent
+    report = _pathvein_rs.shuffle([source], tmp_path / "dest", destination_template="{pattern_name}")
+    [failed] = report.failed
+    assert "{pattern_name}" in failed.error
+    plan = _pathvein_rs.plan_shuffle([source], tmp_path / "dest", destination_template="x/{source_name}")
+    assert plan.directories == [(str(source), str(tmp_path / "dest" / "x" / "run"))]
+
+
+@pytest.mark.parametrize("template", ["{nope}", "{source_name", "}", "../{source_name}"])
+def test_invalid_templates(tmp_path, template):
+    source = touch(tmp_path / "src" / "run" / "a.csv").parent
+    if template.startswith(".."):
+        plan = _pathvein_rs.plan_shuffle([source], tmp_path / "dest", destination_template=template)
+        assert len(plan.template_errors) == 1
+    else:
+        with pytest.raises(ValueError):
+            _pathvein_rs.shuffle([source], tmp_path / "dest", destination_template=template)
+
+
+def test_manifest(tmp_path):
+    sources = runs(tmp_path / "src")
+    manifest = tmp_path / "manifest.ndjson"
+    dest = tmp_path / "dest"
+    report = _pathvein_rs.shuffle(sources, dest, verify="sha256", manifest=manifest)
+    assert report.manifest == str(manifest)
+    entries = [json.loads(line) for line in manifest.read_text().splitlines()]
+    assert sorted(entry["destination"] for entry in entries) == [
+        str(dest / "run_1" / "a.csv"),
+        str(dest / "run_1" / "raw" / "b.fastq"),
+        str(dest / "run_2" / "a.csv"),
+    ]
+    assert {entry["status"] for entry in entries} == {"copied"}
+    assert {entry["algorithm"] for entry in entries} == {"sha256"}
+
+    # The manifest catches a copy that was changed afterwards
+    (dest / "run_2" / "a.csv").write_text("corrupted")
+    changed = [
+        entry["destination"]
+        for entry in entries
+        if hashlib.sha256(open(entry["destination"], "rb").read()).hexdigest() != entry["hash"]
+    ]
+    assert changed == [str(dest / "run_2" / "a.csv")]
+
+
+def test_manifest_leaves_out_failed_directories(tmp_path):
+    sources = runs(tmp_path / "src")
+    touch(tmp_path / "dest" / "run_1" / "old.txt")
+    manifest = tmp_path / "manifest.ndjson"
+    _pathvein_rs.shuffle(sources, tmp_path / "dest", manifest=manifest)
+    [entry] = [json.loads(line) for line in manifest.read_text().splitlines()]
+    assert entry["source"] == str(sources[1] / "a.csv")
+    assert entry["hash"] is None
+    assert entry["size"] == 1
+
+
+def test_safe_move_removes_the_source_after_verifying(tmp_path):
+    sources = runs(tmp_path / "src")
+    dest = tmp_path / "dest"
+    report = _pathvein_rs.shuffle(sources, dest, mode="safe_move")
+    assert [d.status for d in report.directories] == ["moved", "moved"]
+    assert report.verify == "blake3"
+    assert [len(d.verified) for d in report.directories] == [2, 1]
+    assert all(f.ok for d in report.directories for f in d.verified)
+    assert not any(source.exists() for source in sources)
+    assert tree(dest / "run_1") == {"a.csv": "1", os.path.join("raw", "b.fastq"): "reads"}
+
+
+@needs_permissions
+def test_safe_move_keeps_the_source_when_a_file_fails(tmp_path):
+    sources = runs(tmp_path / "src")
+    unreadable = sources[0] / "raw" / "b.fastq"
+    unreadable.chmod(0)
+    try:
+        report = _pathvein_rs.shuffle(sources, tmp_path / "dest", mode="safe_move", retries=2)
+    finally:
+        unreadable.chmod(0o644)
+    assert [d.status for d in report.directories] == ["failed", "moved"]
+    assert tree(sources[0]) == {"a.csv": "1", os.path.join("raw", "b.fastq"): "reads"}
+    assert not (tmp_path / "dest" / "run_1").exists()
+    assert not sources[1].exists()
+
+
+def test_trash_dir_quarantines_moved_sources(tmp_path):
+    trash = tmp_path / "trash"
+    touch(trash / "run_1" / "earlier.csv")
+    sources = runs(tmp_path / "src")
+    started = time.time()
+    report = _pathvein_rs.shuffle(
+        sources,
+        tmp_path / "dest",
+        mode="safe_move",
+        delete="trash",
+        trash_dir=trash,
+        trash_days=2,
+    )
+    assert [d.status for d in report.directories] == ["moved", "moved"]
+    assert [d.trashed for d in report.directories] == [
+        str(trash / "run_1-1"),
+        str(trash / "run_2"),
+    ]
+    assert not any(source.exists() for source in sources)
+    assert tree(trash / "run_1-1") == {"a.csv": "1", os.path.join("raw", "b.fastq"): "reads"}
+    assert tree(trash / "run_1") == {"earlier.csv": ""}
+    record = json.loads((trash / "run_1-1.quarantine.json").read_text())
+    assert record["original"] == os.path.realpath(sources[0])
+    assert started <= record["quarantined"] <= time.time()
+    assert record["expires"] == pytest.approx(record["quarantined"] + 2 * 86_400)
+
+
+def test_trash_dir_without_expiry(tmp_path):
+    source = runs(tmp_path / "src")[1]
+    report = _pathvein_rs.shuffle(
+        [source], tmp_path / "dest", mode="move", delete="trash", trash_dir=tmp_path / "trash"
+    )
+    # A move within a filesystem renames, leaving nothing to trash
+    if report.directories[0].trashed is None:
+        assert not (tmp_path / "trash").exists()
+    else:
+        record = json.loads((tmp_path / "trash" / "run_2.quarantine.json").read_text())
+        assert record["expires"] is None
+
+
+@pytest.mark.parametrize(
+    "options",
+    [
+        {"trash_days": 1},
+        {"trash_dir": "trash", "trash_days": 0},
+        {"trash_dir": "trash", "trash_days": -1},
+        {"trash_dir": "trash", "trash_days": float("nan")},
+    ],
+)
+def test_invalid_trash_options(tmp_path, options):
+    with pytest.raises(ValueError):
+        _pathvein_rs.shuffle([], tmp_path, mode="move", delete="trash", **options)
+
+
+def test_trash_options_need_trash(tmp_path):
+    with pytest.raises(ValueError):
+        _pathvein_rs.shuffle([], tmp_path, mode="move", trash_dir=tmp_path / "trash")
+    with pytest.raises(ValueError):
+        _pathvein_rs.shuffle([], tmp_path, mode="copy", delete="trash", trash_dir=tmp_path)
+    with pytest.raises(ValueError):
+        _pathvein_rs.shuffle([], tmp_path, mode="move", delete="shred")
@@ -0,0 +1,140 @@
+import json
+import os
+import sys
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+from tests import touch
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+
+def sample_tree(root):
+    touch(root / "run_1" / "data.csv", "1,2,3")
+    touch(root / "run_1" / "raw" / "a.fastq", "reads")
+    touch(root / "notes.txt")
+    os.utime(root / "notes.txt", (1_000_000_000, 1_000_000_000))
+    return root
+
+
+def test_snapshot_round_trip(tmp_path):
+    root = sample_tree(tmp_path / "tree")
+    out = tmp_path / "tree.snap"
+    taken = _pathvein_rs.snapshot(str(root), str(out))
+    loaded = _pathvein_rs.load_snapshot(str(out))
+    for snap in [taken, loaded]:
+        assert snap.root == str(root)
+        assert (snap.files, snap.directories, snap.size) == (3, 3, 10)
+        assert snap.errors == []
+    entries = [(e.path, e.kind, e.size) for e in loaded.entries()]
+    assert entries[0] == (str(root), "dir", entries[0][2])
+    assert [entry for entry in entries if entry[1] == "file"] == [
+        (str(root / "notes.txt"), "file", 0),
+        (str(root / "run_1" / "data.csv"), "file", 5),
+        (str(root / "run_1" / "raw" / "a.fastq"), "file", 5),
+    ]
+    [notes] = [e for e in loaded.entries() if e.path.endswith("notes.txt")]
+    assert notes.modified == 1_000_000_000
+
+
+def test_snapshot_walk_matches_walk_parallel(tmp_path):
+    root = sample_tree(tmp_path / "tree")
+    out = tmp_path / "tree.snap"
+    _pathvein_rs.snapshot(str(root), str(out))
+
+    def listings(entries):
+        return sorted(
+            (e.path, sorted(e.dirnames), sorted(e.filenames)) for e in entries
+        )
+
+    walked = _pathvein_rs.walk_parallel(str(root))
+    assert listings(_pathvein_rs.load_snapshot(str(out)).walk()) == listings(walked)
+
+
+def test_snapshot_options(tmp_path):
+    root = sample_tree(tmp_path / "tree")
+    out = tmp_path / "tree.snap"
+    snap = _pathvein_rs.snapshot(str(root), str(out), max_depth=1, exclude=["*.txt"])
+    assert [os.path.relpath(e.path, root) for e in snap.entries()] == [".", "run_1"]
+
+
+@pytest.mark.skipif(sys.platform == "win32", reason="needs symlinks")
+def test_snapshot_keeps_unreadable_entries(tmp_path):
+    root = sample_tree(tmp_path / "tree")
+    os.symlink(root / "gone", root / "link")
+    out = tmp_path / "tree.snap"
+    taken = _pathvein_rs.snapshot(str(root), str(out), follow_links=True)
+    assert [error.path for error in taken.errors] == [str(root / "link")]
+    assert _pathvein_rs.load_snapshot(str(out)).errors == taken.errors
+
+
+def test_load_snapshot_rejects_other_files(tmp_path):
+    touch(tmp_path / "not.snap", "hello")
+    with pytest.raises(ValueError):
+        _pathvein_rs.load_snapshot(str(tmp_path / "not.snap"))
+
+
+def test_diff_snapshots(tmp_path):
+    root = sample_tree(tmp_path / "tree")
+    before = _pathvein_rs.snapshot(str(root), str(tmp_path / "before.snap"))
+    (root / "run_1" / "raw" / "a.fastq").unlink()
+    touch(root / "notes.txt", "grown")
+    touch(root / "new.txt")
+    os.rename(root / "run_1", root / "run_one")
+    after = str(tmp_path / "after.snap")
+    _pathvein_rs.snapshot(str(root), after)
+
+    diff = _pathvein_rs.diff_snapshots(before, after)
+    assert [e.path for e in diff.created] == [str(root / "new.txt")]
+    assert [e.path for e in diff.deleted] == [str(root / "run_1" / "raw" / "a.fastq")]
+    [moved] = diff.moved
+    assert (moved.old.path, moved.new.path) == (str(root / "run_1"), str(root / "run_one"))
+    assert moved.fields == ["path"]
+    [modified] = diff.modified
+    assert modified.new.path == str(root / "notes.txt")
+    assert "size" in modified.fields
+    assert (modified.old.size, modified.new.size) == (0, 5)
+
+
+def test_diff_snapshots_of_an_unchanged_tree(tmp_path):
+    root = sample_tree(tmp_path / "tree")
+    a = _pathvein_rs.snapshot(str(root), str(tmp_path / "a.snap"))
+    b = _pathvein_rs.snapshot(str(root), str(tmp_path / "b.snap"))
+    diff = _pathvein_rs.diff_snapshots(a, b)
+    assert diff.created == diff.deleted == diff.modified == diff.moved == []
+
+
+def test_scan_a_snapshot(tmp_path):
+    root = sample_tree(tmp_path / "tree")
+    out = str(tmp_path / "tree.snap")
+    _pathvein_rs.snapshot(str(root), out)
+    patterns = [
+        json.dumps({"directory_name": "run_*", "files": ["*.csv"], "directories": [{"directory_name": "raw"}]}),
+        json.dumps({"files": ["*.csv"], "min_total_size": 10}),
+    ]
+    live = _pathvein_rs.scan_parallel(str(root), patterns)
+    stored = _pathvein_rs.scan_parallel(out, patterns)
+    assert sorted((r.path, r.pattern_index) for r in stored) == [
+        (str(root / "run_1"), 0),
+        (str(root / "run_1"), 1),
+    ]
+    assert sorted((r.path, r.pattern_index) for r in stored) == sorted(
+        (r.path, r.pattern_index) for r in live
+    )
+
+    # The snapshot still answers once the tree is gone
+    (root / "run_1" / "data.csv").unlink()
+    assert len(_pathvein_rs.scan_parallel(out, patterns)) == 2
+    assert len(_pathvein_rs.scan_parallel(str(root), patterns)) == 0
+
+
+def test_content_limits_never_hold_in_snapshots(tmp_path):
+    root = sample_tree(tmp_path / "tree")
+    out = str(tmp_path / "tree.snap")
+    _pathvein_rs.snapshot(str(root), out)
+    pattern = json.dumps(
+        {"files": ["*.csv"], "file_constraints": {"*.csv": {"content_contains": "1"}}}
+    )
+    assert len(_pathvein_rs.scan_parallel(str(root), [pattern])) == 1
+    assert len(_pathvein_rs.scan_parallel(out, [pattern])) == 0
@@ -0,0 +1,97 @@
+import json
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+from pathvein.pattern import FileStructurePattern as PyPattern
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+
+def nested_pattern():
+    Pattern = _pathvein_rs.FileStructurePattern
+    return Pattern(
+        directory_name="experiment_*",
+        files=["config.yaml"],
+        directories=[Pattern(directory_name="data", files=["*.csv"])],
+        optional_directories=[Pattern(directory_name="logs")],
+        any_of=[Pattern(files=["a.txt"]), Pattern(files=["b.txt"])],
+        pattern_name="experiment",
+    )
+
+
+def test_to_json_writes_current_version():
+    spec = json.loads(nested_pattern().to_json())
+    assert spec["version"] == 2
+
+
+def test_to_json_inlines_nested_patterns():
+    spec = json.loads(nested_pattern().to_json())
+    assert spec["directories"] == [
+        {
+            "directory_name": "data",
+            "files": ["*.csv"],
+            "directories": [],
+            "optional_files": [],
+            "optional_directories": [],
+        }
+    ]
+    assert all(isinstance(child, dict) for child in spec["any_of"])
+
+
+def test_to_toml_writes_current_version():
+    assert nested_pattern().to_toml().startswith("version = 2\n")
+
+
+def test_json_round_trip():
+    pattern = nested_pattern()
+    assert _pathvein_rs.FileStructurePattern.from_json(pattern.to_json()) == pattern
+
+
+def test_toml_round_trip():
+    pattern = nested_pattern()
+    assert _pathvein_rs.FileStructurePattern.from_toml(pattern.to_toml()) == pattern
+
+
+def test_yaml_round_trip():
+    # JSON is YAML, so the JSON writer doubles as a YAML one
+    pattern = nested_pattern()
+    assert _pathvein_rs.FileStructurePattern.from_yaml(pattern.to_json()) == pattern
+
+
+def test_version_1_specs_are_upgraded():
+    legacy = json.dumps(
+        {
+            "directory_name": "experiment_*",
+            "files": ["config.yaml"],
+            "directories": [json.dumps({"directory_name": "data", "files": ["*.csv"]})],
+            "anyOf": [json.dumps({"files": ["a.txt"]})],
+        }
+    )
+    pattern = _pathvein_rs.FileStructurePattern.from_json(legacy)
+    assert pattern.directories[0].directory_name == "data"
+    assert pattern.any_of[0].files == ["a.txt"]
+
+
+def test_newer_version_is_rejected():
+    spec = json.dumps({"version": 99, "directory_name": "*"})
+    with pytest.raises(ValueError, match="newer than this pathvein reads"):
+        _pathvein_rs.FileStructurePattern.from_json(spec)
+
+
+def test_python_class_reads_rust_json():
+    pattern = PyPattern.from_json(nested_pattern().to_json())
+    assert pattern.directory_name == "experiment_*"
+    assert [child.directory_name for child in pattern.directories] == ["data"]
+    assert [child.directory_name for child in pattern.optional_directories] == ["logs"]
+
+
+def test_rust_class_reads_python_json():
+    python_json = (
+        PyPattern()
+        .set_directory_name("experiment_*")
+        .add_directory(PyPattern().set_directory_name("data").add_file("*.csv"))
+        .to_json()
+    )
+    pattern = _pathvein_rs.FileStructurePattern.from_json(python_json)
+    assert pattern.directories[0].files == ["*.csv"]
@@ -0,0 +1,50 @@
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+
+def errors(text, **options):
+    return [
+        (error.path, error.line, error.column, error.message)
+        for error in _pathvein_rs.validate_spec(text, **options)
+    ]
+
+
+def test_valid_spec_has_no_errors():
+    assert errors('{"directory_name": "x", "files": ["a"]}') == []
+
+
+def test_unknown_keys_are_reported():
+    assert errors('{"directory_name": "x", "fils": []}') == [
+        ("fils", None, None, "unknown key 'fils'")
+    ]
+
+
+def test_json_type_error_has_path_and_location():
+    assert errors('{"files": "a"}') == [
+        ("files", 1, 13, 'invalid type: string "a", expected a sequence')
+    ]
+
+
+def test_yaml_error_in_nested_pattern():
+    text = "directory_name: x\ndirectories:\n  - files: 3\n"
+    [(path, line, column, message)] = errors(text)
+    assert (path, line, column) == ("directories[0].files", 3, 12)
+    assert "expected a sequence" in message
+
+
+def test_toml_error():
+    [(path, line, _, _)] = errors("files = 3", format="toml")
+    assert (path, line) == ("files", 1)
+
+
+def test_unknown_format():
+    with pytest.raises(ValueError, match="Unknown spec format 'ini'"):
+        _pathvein_rs.validate_spec("x", format="ini")
+
+
+def test_spec_error_repr():
+    [error] = _pathvein_rs.validate_spec('{"fils": []}')
+    assert repr(error) == "SpecError(path='fils', message=\"unknown key 'fils'\")"
@@ -0,0 +1,71 @@
+import threading
+import time
+
+import pytest
+
+from pathvein._backend import HAS_RUST_BACKEND, _pathvein_rs
+from tests import spec, touch
+
+pytestmark = pytest.mark.skipif(not HAS_RUST_BACKEND, reason="requires the Rust backend")
+
+
+class Changes:
+    """Collects callback batches and lets a test wait for one"""
+
+    def __init__(self):
+        self.matched = []
+        self.unmatched = []
+        self.arrived = threading.Event()
+
+    def __call__(self, matched, unmatched):
+        self.matched.extend(result.path for result in matched)
+        self.unmatched.extend(result.path for result in unmatched)
+        self.arrived.set()
+
+    def wait(self):
+        assert self.arrived.wait(10), "no change was reported"
+        self.arrived.clear()
+
+
+def test_initial_matches(tmp_path):
+    touch(tmp_path / "a" / "x.csv")
+    with _pathvein_rs.watch(str(tmp_path), [spec(files=["*.csv"])], Changes()) as watcher:
+        assert watcher.is_running
+        assert [result.path for result in watcher.matches] == [str(tmp_path / "a")]
+    assert not watcher.is_running
+
+
+def test_reports_directories_that_start_and_stop_matching(tmp_path):
+    (tmp_path / "a").mkdir()
+    changes = Changes()
+    pattern = spec(files=["*.csv"])
+    with _pathvein_rs.watch(str(tmp_path), [pattern], changes, debounce=0.05) as watcher:
+        touch(tmp_path / "a" / "x.csv")
+        changes.wait()
+        assert changes.matched == [str(tmp_path / "a")]
+        (tmp_path / "a" / "x.csv").unlink()
+        changes.wait()
+        assert changes.unmatched == [str(tmp_path / "a")]
+        assert watcher.matches == []
+
+
+def test_callback_exception_stops_the_watch(tmp_path):
+    (tmp_path / "a").mkdir()
+
+    def callback(matched, unmatched):
+        raise RuntimeError("stop")
+
+    watcher = _pathvein_rs.watch(
+        str(tmp_path), [spec(files=["*.csv"])], callback, debounce=0.05
+    )
+    touch(tmp_path / "a" / "x.csv")
+    deadline = time.time() + 10
+    while watcher.is_running and time.time() < deadline:
+        time.sleep(0.05)
+    with pytest.raises(RuntimeError, match="stop"):
+        watcher.stop()
+
+
+def test_negative_debounce(tmp_path):
+    with pytest.raises(ValueError):
+        _pathvein_rs.watch(str(tmp_path), [spec()], Changes(), debounce=-1)